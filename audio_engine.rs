@@ -9,8 +9,13 @@ use symphonia::core::meta::MetadataOptions;
 use symphonia::core::probe::Hint;
 use std::collections::HashMap;
 use std::fs::File;
-use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::io::BufWriter;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, SyncSender, TryRecvError};
 use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
 
 use thiserror::Error;
 
@@ -28,6 +33,8 @@ pub enum AudioError {
     ChannelNotFound(u32),
     #[error("Sample {0} not found")]
     SampleNotFound(String),
+    #[error("Recording failed: {0}")]
+    RecordError(String),
 }
 
 pub type Result<T> = std::result::Result<T, AudioError>;
@@ -41,6 +48,25 @@ pub struct DecodedSample {
     pub duration_ms: u32,
 }
 
+// Attack/release fades applied to a voice so one-shot samples don't click where they
+// start and stop. The release ends exactly at the sample's end, so timing is unchanged.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Envelope {
+    pub attack_ms: f32,
+    pub release_ms: f32,
+}
+
+impl Envelope {
+    pub const NONE: Envelope = Envelope { attack_ms: 0.0, release_ms: 0.0 };
+}
+
+impl Default for Envelope {
+    // Just long enough to de-click
+    fn default() -> Self {
+        Self { attack_ms: 2.0, release_ms: 5.0 }
+    }
+}
+
 // Voice represents a single playing instance of a sample
 #[derive(Clone)]
 struct Voice {
@@ -54,6 +80,14 @@ struct Voice {
     end_position: Option<usize>, // Optional end position for segment playback
     start_time: Option<std::time::Instant>,
     start_position_samples: usize,
+    delay_frames: usize, // Output frames of silence before playback starts (latency offset)
+    lowpass_state: (f32, f32), // Last filtered left/right output of the channel's lowpass
+    own_lowpass: Option<f32>, // Coefficient of the voice's own lowpass (the ball's `set filter`)
+    own_lowpass_state: (f32, f32),
+    pan_gains: (f32, f32), // Left/right gain for the voice's stereo position
+    attack_frames: usize, // Envelope lengths in output frames; 0 = no fade
+    release_frames: usize,
+    played_frames: usize, // Frames output since playback started (after any delay)
 }
 
 impl Voice {
@@ -99,10 +133,52 @@ impl Voice {
             end_position: end_sample,
             start_time: Some(std::time::Instant::now()),
             start_position_samples: clamped_position,
+            delay_frames: 0,
+            lowpass_state: (0.0, 0.0),
+            own_lowpass: None,
+            own_lowpass_state: (0.0, 0.0),
+            pan_gains: (1.0, 1.0),
+            attack_frames: 0,
+            release_frames: 0,
+            played_frames: 0,
+        }
+    }
+    
+    // Run a sample through the voice's own lowpass, then its channel's. Filtering each
+    // voice and then summing equals filtering the channel's sum.
+    fn filter(&mut self, left: f32, right: f32, channel_lowpass: Option<f32>) -> (f32, f32) {
+        let (left, right) = match self.own_lowpass {
+            Some(coefficient) => one_pole(&mut self.own_lowpass_state, left, right, coefficient),
+            None => (left, right),
+        };
+        match channel_lowpass {
+            Some(coefficient) => one_pole(&mut self.lowpass_state, left, right, coefficient),
+            None => (left, right),
         }
     }
     
+    // Fade the voice in and out by `envelope`, at an output rate of `sample_rate`
+    fn set_envelope(&mut self, envelope: Envelope, sample_rate: u32) {
+        self.attack_frames = (envelope.attack_ms.max(0.0) * sample_rate as f32 / 1000.0) as usize;
+        self.release_frames = (envelope.release_ms.max(0.0) * sample_rate as f32 / 1000.0) as usize;
+    }
+    
+    // Hold the voice back by the global audio offset plus `extra_offset_ms`. A voice can't
+    // start before it is triggered, so a negative global offset becomes a base delay every
+    // voice shares and the offset is taken off that: the voices keep their timing against
+    // each other and no attack is cut.
+    fn set_offset(&mut self, audio_offset_ms: i32, extra_offset_ms: i32, sample_rate: u32) {
+        let base_delay_ms = (-audio_offset_ms).max(0);
+        let delay_ms = (base_delay_ms + audio_offset_ms + extra_offset_ms).max(0);
+        self.delay_frames = delay_ms as usize * sample_rate as usize / 1000;
+    }
+    
     fn get_next_sample(&mut self) -> (f32, f32) {
+        if self.delay_frames > 0 {
+            self.delay_frames -= 1;
+            return (0.0, 0.0);
+        }
+        
         // Check if we've reached the end position for segment playback
         if let Some(end_pos) = self.end_position {
             if self.position >= end_pos {
@@ -133,6 +209,21 @@ impl Voice {
             base_step
         };
         
+        // Fade in over the attack, and out over the frames left before the end
+        let mut envelope_gain = 1.0;
+        if self.played_frames < self.attack_frames {
+            envelope_gain *= self.played_frames as f32 / self.attack_frames as f32;
+        }
+        if self.release_frames > 0 {
+            let end = self.end_position.unwrap_or(self.sample_data.len());
+            let remaining_frames = end.saturating_sub(self.position) / pitch_step.max(base_step);
+            if remaining_frames < self.release_frames {
+                envelope_gain *= remaining_frames as f32 / self.release_frames as f32;
+            }
+        }
+        self.played_frames += 1;
+        let (left, right) = (left * self.pan_gains.0 * envelope_gain, right * self.pan_gains.1 * envelope_gain);
+        
         // Ensure we don't step beyond the end position for segments
         let next_position = self.position + pitch_step.max(base_step);
         if let Some(end_pos) = self.end_position {
@@ -161,6 +252,9 @@ pub struct AudioChannel {
     pub name: String,
     pub volume: f32,
     pub muted: bool,
+    pub soloed: bool, // While any channel is soloed, only soloed ones are heard
+    pub filter_cutoff: f32, // Lowpass cutoff in Hz; 0 = no filter
+    pub system: bool, // Internal sounds (metronome) that don't count as active samples
 }
 
 impl AudioChannel {
@@ -170,24 +264,377 @@ impl AudioChannel {
             name,
             volume: 1.0,
             muted: false,
+            soloed: false,
+            filter_cutoff: 0.0,
+            system: false,
+        }
+    }
+    
+    fn lowpass_coefficient(&self, sample_rate: u32) -> Option<f32> {
+        lowpass_coefficient(self.filter_cutoff, sample_rate)
+    }
+    
+    // Level this channel's voices are mixed at. Solo never silences system channels,
+    // so the metronome keeps clicking while a part is soloed. `volume` (set with `mix`
+    // or automation) only reaches the output since mute/solo went into the mix.
+    fn gain(&self, any_soloed: bool) -> f32 {
+        if self.muted || (any_soloed && !self.soloed && !self.system) {
+            0.0
+        } else {
+            self.volume
         }
     }
 }
 
+// One-pole lowpass coefficient for `cutoff_hz`, or None to bypass the filter (no
+// cutoff, or one at/above Nyquist where it would do nothing useful)
+fn lowpass_coefficient(cutoff_hz: f32, sample_rate: u32) -> Option<f32> {
+    if cutoff_hz <= 0.0 || cutoff_hz >= sample_rate as f32 / 2.0 {
+        return None;
+    }
+    Some(1.0 - (-std::f32::consts::TAU * cutoff_hz / sample_rate as f32).exp())
+}
+
+// One-pole lowpass step with smoothing `coefficient` (0-1, higher = brighter); `state`
+// holds the last left/right output
+fn one_pole(state: &mut (f32, f32), left: f32, right: f32, coefficient: f32) -> (f32, f32) {
+    state.0 += coefficient * (left - state.0);
+    state.1 += coefficient * (right - state.1);
+    *state
+}
+
 // High-performance audio engine with lock-free mixing
+// Where mixed audio goes. `Null` never opens an output device, so the engine can
+// run headless (simulation harness, machines without a sound card).
+pub enum AudioBackend {
+    Output { _stream: Stream, _claim: OutputClaim },
+    Null,
+}
+
+// Set while an engine owns the output device. The app runs on one engine; a second
+// would mean preloading and playback had split across two of them.
+static OUTPUT_CLAIMED: AtomicBool = AtomicBool::new(false);
+
+// Held by the engine that owns the output stream and released when it's dropped
+pub struct OutputClaim;
+
+impl OutputClaim {
+    fn acquire() -> Result<Self> {
+        if OUTPUT_CLAIMED.swap(true, Ordering::AcqRel) {
+            return Err(AudioError::OutputError("another audio engine already owns the output".to_string()));
+        }
+        Ok(Self)
+    }
+}
+
+impl Drop for OutputClaim {
+    fn drop(&mut self) {
+        OUTPUT_CLAIMED.store(false, Ordering::Release);
+    }
+}
+
+// Buffers of mixed output that can be on their way to the recording writer at once
+const RECORD_BLOCKS: usize = 32;
+
+// The master output being written to a WAV file, exactly as it goes to the device.
+// The file is written on its own thread so the audio callback never touches the disk.
+struct Recording {
+    path: String,
+    stop: Arc<AtomicBool>,
+    dropped: Arc<AtomicUsize>,
+    writer: JoinHandle<std::result::Result<u32, String>>, // Frames written once finished
+}
+
+// The audio callback's end of a recording: it copies each mix into a spare buffer and
+// queues it for the writer thread, which hands the buffer back once it's on disk
+struct RecordTap {
+    filled: SyncSender<Vec<f32>>,
+    spare: Receiver<Vec<f32>>,
+    dropped: Arc<AtomicUsize>, // Samples lost because the writer fell behind
+}
+
+impl RecordTap {
+    // Queue `data` for the writer. False once the writer has gone away.
+    fn send(&self, data: &[f32]) -> bool {
+        match self.spare.try_recv() {
+            Ok(mut block) => {
+                block.clear();
+                block.extend_from_slice(data);
+                self.filled.try_send(block).is_ok()
+            }
+            Err(TryRecvError::Empty) => {
+                self.dropped.fetch_add(data.len(), Ordering::Relaxed);
+                true
+            }
+            Err(TryRecvError::Disconnected) => false,
+        }
+    }
+}
+
+// Open `path` and start the thread that writes whatever the tap sends to it
+fn spawn_recording(path: &str, spec: hound::WavSpec) -> Result<(Recording, RecordTap)> {
+    let writer = hound::WavWriter::create(path, spec)
+        .map_err(|e| AudioError::RecordError(format!("could not create {}: {}", path, e)))?;
+    let (filled, queued) = mpsc::sync_channel(RECORD_BLOCKS);
+    let (give_back, spare) = mpsc::sync_channel(RECORD_BLOCKS);
+    for _ in 0..RECORD_BLOCKS {
+        let _ = give_back.try_send(Vec::with_capacity(MAX_CALLBACK_SAMPLES));
+    }
+    let stop = Arc::new(AtomicBool::new(false));
+    let dropped = Arc::new(AtomicUsize::new(0));
+    let thread_stop = stop.clone();
+    let thread_path = path.to_string();
+    let writer = std::thread::Builder::new()
+        .name("recording".to_string())
+        .spawn(move || write_recording(writer, queued, give_back, thread_stop, thread_path))
+        .map_err(|e| AudioError::RecordError(format!("could not start writing {}: {}", path, e)))?;
+    let recording = Recording { path: path.to_string(), stop, dropped: dropped.clone(), writer };
+    Ok((recording, RecordTap { filled, spare, dropped }))
+}
+
+// Writer thread: write queued blocks until told to stop, then write what's left and finish the file
+fn write_recording(
+    mut writer: hound::WavWriter<BufWriter<File>>,
+    queued: Receiver<Vec<f32>>,
+    give_back: SyncSender<Vec<f32>>,
+    stop: Arc<AtomicBool>,
+    path: String,
+) -> std::result::Result<u32, String> {
+    let write = |block: Vec<f32>, writer: &mut hound::WavWriter<BufWriter<File>>| {
+        let written = block.iter().try_for_each(|sample| writer.write_sample(*sample));
+        let _ = give_back.try_send(block);
+        written.map_err(|e| {
+            // Dropping the writer finalizes what was written so far
+            log::error!("Recording to {} stopped: {}", path, e);
+            e.to_string()
+        })
+    };
+    while !stop.load(Ordering::Acquire) {
+        match queued.recv_timeout(Duration::from_millis(50)) {
+            Ok(block) => write(block, &mut writer)?,
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    while let Ok(block) = queued.try_recv() {
+        write(block, &mut writer)?;
+    }
+    let frames = writer.duration();
+    writer.finalize().map_err(|e| e.to_string())?;
+    Ok(frames)
+}
+
+// Interleaved samples per callback the i16/u16 scratch mix is sized for up front
+const MAX_CALLBACK_SAMPLES: usize = 16384;
+
+// Channel ids the mix keeps levels for; voices on higher ids play at unity, unfiltered
+const MAX_MIX_CHANNELS: usize = 64;
+
+// One channel's level as the audio callback sees it
+struct ChannelLevel {
+    gain: AtomicU32, // f32 bits, with mute/solo already applied
+    lowpass: AtomicU32, // f32 bits of the lowpass coefficient; 0 = no filter
+}
+
+// Master and per-channel levels shared with the audio callback. The engine republishes
+// them whenever a channel changes, so mixing needs neither the channel lock nor a map.
+struct MixLevels {
+    master: AtomicU32, // f32 bits
+    channels: Vec<ChannelLevel>, // Indexed by channel id, allocated once
+}
+
+impl MixLevels {
+    fn new() -> Self {
+        Self {
+            master: AtomicU32::new(1.0f32.to_bits()),
+            channels: (0..MAX_MIX_CHANNELS)
+                .map(|_| ChannelLevel { gain: AtomicU32::new(1.0f32.to_bits()), lowpass: AtomicU32::new(0) })
+                .collect(),
+        }
+    }
+    
+    fn master(&self) -> f32 {
+        f32::from_bits(self.master.load(Ordering::Relaxed))
+    }
+    
+    // Gain and lowpass coefficient for voices on `channel_id`
+    fn channel(&self, channel_id: u32) -> (f32, Option<f32>) {
+        let Some(level) = self.channels.get(channel_id as usize) else {
+            return (1.0, None);
+        };
+        let lowpass = f32::from_bits(level.lowpass.load(Ordering::Relaxed));
+        (f32::from_bits(level.gain.load(Ordering::Relaxed)), (lowpass > 0.0).then_some(lowpass))
+    }
+    
+    // Recompute every channel's level; call with the channel map still locked so
+    // concurrent changes publish in the order they were made
+    fn publish(&self, channels: &HashMap<u32, AudioChannel>, sample_rate: u32) {
+        let any_soloed = channels.values().any(|channel| channel.soloed);
+        for channel in channels.values() {
+            let Some(level) = self.channels.get(channel.id as usize) else {
+                continue;
+            };
+            level.gain.store(channel.gain(any_soloed).to_bits(), Ordering::Relaxed);
+            let lowpass = channel.lowpass_coefficient(sample_rate).unwrap_or(0.0);
+            level.lowpass.store(lowpass.to_bits(), Ordering::Relaxed);
+        }
+    }
+}
+
+// What the audio callback shares with the engine to build each output buffer
+struct Mixer {
+    voices: Arc<Mutex<Vec<Voice>>>,
+    levels: Arc<MixLevels>,
+    limiter: Arc<LimiterSettings>,
+    output_peak: Arc<AtomicU32>,
+    record_taps: Receiver<RecordTap>, // A new tap arrives here when a recording starts
+    record_tap: Option<RecordTap>,
+    scratch: Vec<f32>, // Float mix for i16/u16 devices, sized once so callbacks don't allocate
+}
+
+impl Mixer {
+    // Mix every active voice into `data` (interleaved, `output_channels` wide) at its
+    // channel's level and through its channel's lowpass, then run the master limiter and record the pre-limiter peak
+    // for metering. The finished mix is also written to the WAV recording, if one is running.
+    fn mix(&mut self, data: &mut [f32], output_channels: usize) {
+        // Clear output buffer
+        data.fill(0.0);
+        
+        let master_vol = self.levels.master();
+        
+        if let Ok(mut voices_guard) = self.voices.try_lock() {
+            // Mix all active voices
+            for voice in voices_guard.iter_mut() {
+                if voice.active {
+                    // Silenced voices still advance, so unmuting picks up mid-sample
+                    let (gain, lowpass) = self.levels.channel(voice.channel_id);
+                    let gain = gain * master_vol;
+                    
+                    // Process audio in stereo pairs
+                    for chunk in data.chunks_mut(output_channels) {
+                        let (left, right) = voice.get_next_sample();
+                        let (left, right) = voice.filter(left, right, lowpass);
+                        
+                        if chunk.len() >= 2 {
+                            chunk[0] += left * gain;
+                            chunk[1] += right * gain;
+                        } else if chunk.len() == 1 {
+                            chunk[0] += (left + right) * 0.5 * gain;
+                        }
+                        
+                        if voice.is_finished() {
+                            break;
+                        }
+                    }
+                }
+            }
+            
+            // Remove finished voices
+            voices_guard.retain(|v| v.active && !v.is_finished());
+        }
+        
+        // Peak since the UI last read it; a lost update between load and store only costs one buffer
+        let peak = data.iter().fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+        if peak > f32::from_bits(self.output_peak.load(Ordering::Relaxed)) {
+            self.output_peak.store(peak.to_bits(), Ordering::Relaxed);
+        }
+        
+        self.limiter.load().process(data);
+        
+        if let Ok(tap) = self.record_taps.try_recv() {
+            self.record_tap = Some(tap);
+        }
+        if let Some(tap) = &self.record_tap {
+            if !tap.send(data) {
+                self.record_tap = None;
+            }
+        }
+    }
+}
+
 pub struct AudioEngine {
-    _stream: Stream,
+    backend: AudioBackend,
     sample_cache: Arc<Mutex<HashMap<String, DecodedSample>>>,
     channels: Arc<Mutex<HashMap<u32, AudioChannel>>>,
     voices: Arc<Mutex<Vec<Voice>>>,
     next_channel_id: AtomicU32,
     active_voices: AtomicUsize,
-    master_volume: Arc<Mutex<f32>>,
+    levels: Arc<MixLevels>,
     sample_rate: u32,
+    // Shifts every triggered sample relative to the simulation: positive delays it,
+    // negative is taken off a base delay all voices share (see Voice::set_offset)
+    audio_offset_ms: i32,
+    limiter: Arc<LimiterSettings>,
+    output_peak: Arc<AtomicU32>, // f32 bits of the loudest pre-limiter sample since last read
+    output_channels: u16,
+    recording: Mutex<Option<Recording>>,
+    record_taps: Sender<RecordTap>, // Hands each new recording to the audio callback
+}
+
+// Soft clipper on the master mix: a tanh curve that smoothly approaches `ceiling`
+// instead of clipping hard when many voices stack up. On by default at -1 dBFS;
+// `limiter off` takes it out of the chain.
+#[derive(Clone, Copy, Debug)]
+pub struct Limiter {
+    pub enabled: bool,
+    pub ceiling: f32, // Linear amplitude (1.0 = 0 dBFS)
+}
+
+impl Default for Limiter {
+    fn default() -> Self {
+        Self { enabled: true, ceiling: db_to_linear(-1.0) }
+    }
+}
+
+// Limiter settings shared with the audio callback, which takes a copy per buffer
+// without locking
+struct LimiterSettings {
+    enabled: AtomicBool,
+    ceiling: AtomicU32, // f32 bits
+}
+
+impl LimiterSettings {
+    fn new(limiter: Limiter) -> Self {
+        Self { enabled: AtomicBool::new(limiter.enabled), ceiling: AtomicU32::new(limiter.ceiling.to_bits()) }
+    }
+    
+    fn load(&self) -> Limiter {
+        Limiter {
+            enabled: self.enabled.load(Ordering::Relaxed),
+            ceiling: f32::from_bits(self.ceiling.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+impl Limiter {
+    fn process(&self, buffer: &mut [f32]) {
+        if !self.enabled {
+            return;
+        }
+        for sample in buffer.iter_mut() {
+            *sample = self.ceiling * (*sample / self.ceiling).tanh();
+        }
+    }
+    
+    pub fn ceiling_db(&self) -> f32 {
+        20.0 * self.ceiling.log10()
+    }
+}
+
+fn db_to_linear(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+// Equal-power pan law (-1.0 = left, 1.0 = right), scaled so the center stays at
+// unity gain on both sides and unpanned voices sound exactly as before
+fn pan_gains(pan: f32) -> (f32, f32) {
+    let angle = (pan.clamp(-1.0, 1.0) + 1.0) * std::f32::consts::FRAC_PI_4;
+    (angle.cos() * std::f32::consts::SQRT_2, angle.sin() * std::f32::consts::SQRT_2)
 }
 
 impl AudioEngine {
     pub fn new() -> Result<Self> {
+        let claim = OutputClaim::acquire()?;
         let host = cpal::default_host();
         let device = host.default_output_device()
             .ok_or_else(|| AudioError::OutputError("No output device available".to_string()))?;
@@ -201,12 +648,22 @@ impl AudioEngine {
         let sample_cache = Arc::new(Mutex::new(HashMap::new()));
         let engine_channels = Arc::new(Mutex::new(HashMap::new()));
         let voices = Arc::new(Mutex::new(Vec::new()));
-        let master_volume = Arc::new(Mutex::new(1.0));
+        let levels = Arc::new(MixLevels::new());
         let active_voices = AtomicUsize::new(0);
+        let limiter = Arc::new(LimiterSettings::new(Limiter::default()));
+        let output_peak = Arc::new(AtomicU32::new(0));
+        let (record_taps, tap_receiver) = mpsc::channel();
         
-        // Clone for the audio callback
-        let voices_clone = voices.clone();
-        let master_volume_clone = master_volume.clone();
+        // Shared with the audio callback
+        let mut mixer = Mixer {
+            voices: voices.clone(),
+            levels: levels.clone(),
+            limiter: limiter.clone(),
+            output_peak: output_peak.clone(),
+            record_taps: tap_receiver,
+            record_tap: None,
+            scratch: Vec::with_capacity(MAX_CALLBACK_SAMPLES),
+        };
         
         let stream_config = StreamConfig {
             channels,
@@ -219,7 +676,7 @@ impl AudioEngine {
                 device.build_output_stream(
                     &stream_config,
                     move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
-                        Self::audio_callback_f32(data, &voices_clone, &master_volume_clone, channels as usize);
+                        Self::audio_callback_f32(data, &mut mixer, channels as usize);
                     },
                     |err| log::error!("Audio stream error: {}", err),
                     None,
@@ -229,7 +686,7 @@ impl AudioEngine {
                 device.build_output_stream(
                     &stream_config,
                     move |data: &mut [i16], _: &cpal::OutputCallbackInfo| {
-                        Self::audio_callback_i16(data, &voices_clone, &master_volume_clone, channels as usize);
+                        Self::audio_callback_i16(data, &mut mixer, channels as usize);
                     },
                     |err| log::error!("Audio stream error: {}", err),
                     None,
@@ -239,7 +696,7 @@ impl AudioEngine {
                 device.build_output_stream(
                     &stream_config,
                     move |data: &mut [u16], _: &cpal::OutputCallbackInfo| {
-                        Self::audio_callback_u16(data, &voices_clone, &master_volume_clone, channels as usize);
+                        Self::audio_callback_u16(data, &mut mixer, channels as usize);
                     },
                     |err| log::error!("Audio stream error: {}", err),
                     None,
@@ -253,124 +710,97 @@ impl AudioEngine {
         log::info!("Audio engine initialized: {} Hz, {} channels", sample_rate, channels);
         
         Ok(Self {
-            _stream: stream,
+            backend: AudioBackend::Output { _stream: stream, _claim: claim },
             sample_cache,
             channels: engine_channels,
             voices,
             next_channel_id: AtomicU32::new(0),
             active_voices,
-            master_volume,
+            levels,
             sample_rate,
+            audio_offset_ms: 0,
+            limiter,
+            output_peak,
+            output_channels: channels,
+            recording: Mutex::new(None),
+            record_taps,
         })
     }
     
-    // Lock-free audio callback for f32 samples
-    fn audio_callback_f32(
-        data: &mut [f32],
-        voices: &Arc<Mutex<Vec<Voice>>>,
-        master_volume: &Arc<Mutex<f32>>,
-        output_channels: usize,
-    ) {
-        // Clear output buffer
-        data.fill(0.0);
+    // Engine without an output device: channels and the sample cache work as usual,
+    // but nothing is mixed, so triggered samples never become active voices
+    pub fn new_null() -> Self {
+        log::warn!("Audio disabled: using null audio backend, no sound will be played");
         
-        let master_vol = *master_volume.lock().unwrap();
+        Self {
+            backend: AudioBackend::Null,
+            sample_cache: Arc::new(Mutex::new(HashMap::new())),
+            channels: Arc::new(Mutex::new(HashMap::new())),
+            voices: Arc::new(Mutex::new(Vec::new())),
+            next_channel_id: AtomicU32::new(0),
+            active_voices: AtomicUsize::new(0),
+            levels: Arc::new(MixLevels::new()),
+            sample_rate: 44100,
+            audio_offset_ms: 0,
+            limiter: Arc::new(LimiterSettings::new(Limiter::default())),
+            output_peak: Arc::new(AtomicU32::new(0)),
+            output_channels: 2,
+            recording: Mutex::new(None),
+            record_taps: mpsc::channel().0,
+        }
+    }
+    
+    // Pick the backend at startup: CANTICLE_AUDIO=null forces the null backend,
+    // otherwise the default output device is used, falling back to null if it fails
+    pub fn from_env() -> Self {
+        if std::env::var("CANTICLE_AUDIO").map(|v| v.eq_ignore_ascii_case("null")).unwrap_or(false) {
+            log::info!("CANTICLE_AUDIO=null set");
+            return Self::new_null();
+        }
         
-        if let Ok(mut voices_guard) = voices.try_lock() {
-            // Mix all active voices
-            for voice in voices_guard.iter_mut() {
-                if voice.active {
-                    // Process audio in stereo pairs
-                    for chunk in data.chunks_mut(output_channels) {
-                        let (left, right) = voice.get_next_sample();
-                        
-                        if chunk.len() >= 2 {
-                            chunk[0] += left * master_vol;
-                            chunk[1] += right * master_vol;
-                        } else if chunk.len() == 1 {
-                            chunk[0] += (left + right) * 0.5 * master_vol;
-                        }
-                        
-                        if voice.is_finished() {
-                            break;
-                        }
-                    }
-                }
+        match Self::new() {
+            Ok(engine) => engine,
+            Err(e) => {
+                log::warn!("Could not open audio output ({}), continuing without sound", e);
+                Self::new_null()
             }
-            
-            // Remove finished voices
-            voices_guard.retain(|v| v.active && !v.is_finished());
         }
     }
     
+    pub fn is_null(&self) -> bool {
+        matches!(self.backend, AudioBackend::Null)
+    }
+    
+    // Lock-free audio callback for f32 samples
+    fn audio_callback_f32(data: &mut [f32], mixer: &mut Mixer, output_channels: usize) {
+        mixer.mix(data, output_channels);
+    }
+    
     // Audio callback for i16 samples
-    fn audio_callback_i16(
-        data: &mut [i16],
-        voices: &Arc<Mutex<Vec<Voice>>>,
-        master_volume: &Arc<Mutex<f32>>,
-        output_channels: usize,
-    ) {
-        data.fill(0);
-        
-        let master_vol = *master_volume.lock().unwrap();
-        
-        if let Ok(mut voices_guard) = voices.try_lock() {
-            for voice in voices_guard.iter_mut() {
-                if voice.active {
-                    for chunk in data.chunks_mut(output_channels) {
-                        let (left, right) = voice.get_next_sample();
-                        
-                        if chunk.len() >= 2 {
-                            chunk[0] = (chunk[0] as f32 + left * master_vol * 32767.0) as i16;
-                            chunk[1] = (chunk[1] as f32 + right * master_vol * 32767.0) as i16;
-                        } else if chunk.len() == 1 {
-                            chunk[0] = (chunk[0] as f32 + (left + right) * 0.5 * master_vol * 32767.0) as i16;
-                        }
-                        
-                        if voice.is_finished() {
-                            break;
-                        }
-                    }
-                }
-            }
-            
-            voices_guard.retain(|v| v.active && !v.is_finished());
+    fn audio_callback_i16(data: &mut [i16], mixer: &mut Mixer, output_channels: usize) {
+        let mix = Self::mix_into_scratch(data.len(), mixer, output_channels);
+        for (out, sample) in data.iter_mut().zip(&mix) {
+            *out = (sample.clamp(-1.0, 1.0) * 32767.0) as i16;
         }
+        mixer.scratch = mix;
     }
     
     // Audio callback for u16 samples
-    fn audio_callback_u16(
-        data: &mut [u16],
-        voices: &Arc<Mutex<Vec<Voice>>>,
-        master_volume: &Arc<Mutex<f32>>,
-        output_channels: usize,
-    ) {
-        data.fill(32768);
-        
-        let master_vol = *master_volume.lock().unwrap();
-        
-        if let Ok(mut voices_guard) = voices.try_lock() {
-            for voice in voices_guard.iter_mut() {
-                if voice.active {
-                    for chunk in data.chunks_mut(output_channels) {
-                        let (left, right) = voice.get_next_sample();
-                        
-                        if chunk.len() >= 2 {
-                            chunk[0] = ((chunk[0] as f32 - 32768.0) + left * master_vol * 32767.0 + 32768.0) as u16;
-                            chunk[1] = ((chunk[1] as f32 - 32768.0) + right * master_vol * 32767.0 + 32768.0) as u16;
-                        } else if chunk.len() == 1 {
-                            chunk[0] = ((chunk[0] as f32 - 32768.0) + (left + right) * 0.5 * master_vol * 32767.0 + 32768.0) as u16;
-                        }
-                        
-                        if voice.is_finished() {
-                            break;
-                        }
-                    }
-                }
-            }
-            
-            voices_guard.retain(|v| v.active && !v.is_finished());
+    fn audio_callback_u16(data: &mut [u16], mixer: &mut Mixer, output_channels: usize) {
+        let mix = Self::mix_into_scratch(data.len(), mixer, output_channels);
+        for (out, sample) in data.iter_mut().zip(&mix) {
+            *out = (sample.clamp(-1.0, 1.0) * 32767.0 + 32768.0) as u16;
         }
+        mixer.scratch = mix;
+    }
+    
+    // Mix `len` samples into the mixer's scratch buffer, handed back for conversion.
+    // It only grows past MAX_CALLBACK_SAMPLES if a device asks for larger buffers.
+    fn mix_into_scratch(len: usize, mixer: &mut Mixer, output_channels: usize) -> Vec<f32> {
+        let mut mix = std::mem::take(&mut mixer.scratch);
+        mix.resize(len, 0.0);
+        mixer.mix(&mut mix, output_channels);
+        mix
     }
     
     // Decode audio file using Symphonia
@@ -590,12 +1020,39 @@ impl AudioEngine {
         
         let mut channels = self.channels.lock().unwrap();
         channels.insert(id, channel);
+        self.levels.publish(&channels, self.sample_rate);
+        if id as usize >= MAX_MIX_CHANNELS {
+            log::warn!("Channel {} is past the first {} and ignores volume, mute, solo and filter", id, MAX_MIX_CHANNELS);
+        }
         
         log::info!("Created audio channel {} with ID {}", channels.get(&id).unwrap().name, id);
         id
     }
     
+    // A channel for the app's own sounds; its voices are left out of
+    // get_active_sample_count so they never trip the overload guards
+    pub fn create_system_channel(&mut self, name: String) -> u32 {
+        let id = self.create_channel(name);
+        if let Some(channel) = self.channels.lock().unwrap().get_mut(&id) {
+            channel.system = true;
+        }
+        id
+    }
+    
+    // Cache a sample made in memory so it plays by `name` like a file
+    pub fn insert_sample(&self, name: &str, sample: DecodedSample) {
+        self.sample_cache.lock().unwrap().insert(name.to_string(), sample);
+    }
+    
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+    
     pub fn preload_sample(&self, file_path: &str) -> Result<()> {
+        if self.is_null() {
+            return Ok(());
+        }
+        
         let resolved_path = self.resolve_file_path(file_path);
         
         // Check if already cached
@@ -650,6 +1107,25 @@ impl AudioEngine {
     }
     
     pub fn play_on_channel_with_segment(&self, channel_id: u32, file_path: &str, pitch: f32, volume: f32, start_position: f32, end_position: Option<f32>) -> Result<()> {
+        self.play_on_channel_with_timing(channel_id, file_path, pitch, volume, 0.0, Envelope::NONE, 0.0, start_position, end_position, 0)
+    }
+    
+    // Like play_on_channel_with_segment, placed at `pan` (-1.0 = left, 1.0 = right), faded
+    // by `envelope`, lowpassed at `filter_cutoff` Hz on top of its channel's filter (0 = off),
+    // and with `extra_offset_ms` added on top of the global audio offset (used for
+    // per-trigger timing deviation)
+    pub fn play_on_channel_with_timing(&self, channel_id: u32, file_path: &str, pitch: f32, volume: f32, pan: f32, envelope: Envelope, filter_cutoff: f32, start_position: f32, end_position: Option<f32>, extra_offset_ms: i32) -> Result<()> {
+        // Nothing would ever drain the voice list without an output stream, so
+        // only validate the channel and skip decoding entirely
+        if self.is_null() {
+            let channels = self.channels.lock().unwrap();
+            if !channels.contains_key(&channel_id) {
+                return Err(AudioError::ChannelNotFound(channel_id));
+            }
+            log::debug!("Null backend: skipping playback of {} on channel {}", file_path, channel_id);
+            return Ok(());
+        }
+        
         let resolved_path = self.resolve_file_path(file_path);
         
         // Get sample from cache or load it
@@ -679,7 +1155,13 @@ impl AudioEngine {
         let safe_position = start_position.clamp(0.0, 1.0);
         let safe_end_position = end_position.map(|end_pos| end_pos.clamp(0.0, 1.0));
         
-        let voice = Voice::new_with_segment(&sample, safe_volume, safe_pitch, channel_id, safe_position, safe_end_position);
+        let mut voice = Voice::new_with_segment(&sample, safe_volume, safe_pitch, channel_id, safe_position, safe_end_position);
+        voice.pan_gains = pan_gains(pan);
+        voice.own_lowpass = lowpass_coefficient(filter_cutoff, self.sample_rate);
+        voice.set_envelope(envelope, self.sample_rate);
+        
+        // Latency compensation
+        voice.set_offset(self.audio_offset_ms, extra_offset_ms, self.sample_rate);
         
         {
             let mut voices = self.voices.lock().unwrap();
@@ -705,29 +1187,134 @@ impl AudioEngine {
     }
     
     pub fn set_master_volume(&mut self, volume: f32) {
-        let safe_volume = volume.clamp(0.0, 2.0);
-        *self.master_volume.lock().unwrap() = safe_volume;
+        let safe_volume = volume.clamp(0.0, 1.0);
+        self.levels.master.store(safe_volume.to_bits(), Ordering::Relaxed);
         log::info!("Master volume set to {:.2}", safe_volume);
     }
     
-    pub fn set_channel_volume(&self, channel_id: u32, volume: f32) -> Result<()> {
-        let mut channels = self.channels.lock().unwrap();
-        if let Some(channel) = channels.get_mut(&channel_id) {
-            channel.volume = volume.clamp(0.0, 2.0);
-            Ok(())
-        } else {
-            Err(AudioError::ChannelNotFound(channel_id))
+    pub fn master_volume(&self) -> f32 {
+        self.levels.master()
+    }
+    
+    pub fn limiter(&self) -> Limiter {
+        self.limiter.load()
+    }
+    
+    pub fn set_limiter_enabled(&self, enabled: bool) {
+        self.limiter.enabled.store(enabled, Ordering::Relaxed);
+        log::info!("Limiter {}", if enabled { "on" } else { "off" });
+    }
+    
+    pub fn set_limiter_ceiling_db(&self, ceiling_db: f32) {
+        let ceiling_db = ceiling_db.clamp(-24.0, 0.0);
+        self.limiter.ceiling.store(db_to_linear(ceiling_db).to_bits(), Ordering::Relaxed);
+        log::info!("Limiter ceiling set to {:.1} dBFS", ceiling_db);
+    }
+    
+    /// Loudest output sample (before limiting) since the last call, 1.0 = 0 dBFS
+    pub fn take_output_peak(&self) -> f32 {
+        f32::from_bits(self.output_peak.swap(0, Ordering::Relaxed))
+    }
+    
+    // Tee the master output into a 32-bit float WAV file at `path` until stop_recording
+    pub fn start_recording(&self, path: &str) -> Result<()> {
+        if self.is_null() {
+            return Err(AudioError::RecordError("no audio output to record (null audio backend)".to_string()));
+        }
+        let mut recording = self.recording.lock().unwrap();
+        if let Some(active) = recording.as_ref() {
+            return Err(AudioError::RecordError(format!("already recording to {}", active.path)));
+        }
+        let spec = hound::WavSpec {
+            channels: self.output_channels,
+            sample_rate: self.sample_rate,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        let (active, tap) = spawn_recording(path, spec)?;
+        if self.record_taps.send(tap).is_err() {
+            active.stop.store(true, Ordering::Release);
+            return Err(AudioError::RecordError("audio output has stopped".to_string()));
+        }
+        *recording = Some(active);
+        log::info!("Recording output to {}", path);
+        Ok(())
+    }
+    
+    // Finish the WAV file so it's complete on disk. Returns the path and length in
+    // seconds, or None when nothing was being recorded.
+    pub fn stop_recording(&self) -> Result<Option<(String, f32)>> {
+        let Some(recording) = self.recording.lock().unwrap().take() else {
+            return Ok(None);
+        };
+        recording.stop.store(true, Ordering::Release);
+        let frames = recording.writer.join()
+            .map_err(|_| AudioError::RecordError(format!("writing {} panicked", recording.path)))?
+            .map_err(|e| AudioError::RecordError(format!("could not finish {}: {}", recording.path, e)))?;
+        let dropped = recording.dropped.load(Ordering::Relaxed);
+        if dropped > 0 {
+            log::warn!("Recording to {} skipped {} samples: the disk fell behind", recording.path, dropped);
         }
+        let seconds = frames as f32 / self.sample_rate as f32;
+        log::info!("Recorded {:.1}s to {}", seconds, recording.path);
+        Ok(Some((recording.path, seconds)))
+    }
+    
+    // A recording was started and its file is still being written
+    pub fn is_recording(&self) -> bool {
+        self.recording.lock().unwrap().as_ref().is_some_and(|active| !active.writer.is_finished())
+    }
+    
+    pub fn set_audio_offset_ms(&mut self, offset_ms: i32) {
+        self.audio_offset_ms = offset_ms;
+        log::info!("Audio offset set to {} ms", offset_ms);
+    }
+    
+    pub fn audio_offset_ms(&self) -> i32 {
+        self.audio_offset_ms
+    }
+    
+    // Change one channel and hand the new levels to the audio callback
+    fn update_channel(&self, channel_id: u32, change: impl FnOnce(&mut AudioChannel)) -> Result<()> {
+        let mut channels = self.channels.lock().unwrap();
+        let channel = channels.get_mut(&channel_id).ok_or(AudioError::ChannelNotFound(channel_id))?;
+        change(channel);
+        self.levels.publish(&channels, self.sample_rate);
+        Ok(())
+    }
+    
+    pub fn set_channel_volume(&self, channel_id: u32, volume: f32) -> Result<()> {
+        self.update_channel(channel_id, |channel| channel.volume = volume.clamp(0.0, 2.0))
     }
     
     pub fn mute_channel(&self, channel_id: u32, muted: bool) -> Result<()> {
+        self.update_channel(channel_id, |channel| channel.muted = muted)
+    }
+    
+    pub fn solo_channel(&self, channel_id: u32, soloed: bool) -> Result<()> {
+        self.update_channel(channel_id, |channel| channel.soloed = soloed)
+    }
+    
+    // Lowpass the channel at `cutoff_hz`; 0 (or anything at/above Nyquist) turns it off
+    pub fn set_channel_filter(&self, channel_id: u32, cutoff_hz: f32) -> Result<()> {
+        self.update_channel(channel_id, |channel| channel.filter_cutoff = cutoff_hz.max(0.0))
+    }
+    
+    pub fn clear_solo(&self) {
         let mut channels = self.channels.lock().unwrap();
-        if let Some(channel) = channels.get_mut(&channel_id) {
-            channel.muted = muted;
-            Ok(())
-        } else {
-            Err(AudioError::ChannelNotFound(channel_id))
+        for channel in channels.values_mut() {
+            channel.soloed = false;
         }
+        self.levels.publish(&channels, self.sample_rate);
+    }
+    
+    // Channel by name (case-insensitive) or numeric ID
+    pub fn find_channel(&self, name: &str) -> Option<u32> {
+        let channels = self.channels.lock().unwrap();
+        channels.values()
+            .find(|channel| channel.name.eq_ignore_ascii_case(name))
+            .map(|channel| channel.id)
+            .or_else(|| name.parse::<u32>().ok().filter(|id| channels.contains_key(id)))
     }
     
     pub fn stop_channel(&self, channel_id: u32) -> Result<()> {
@@ -750,8 +1337,14 @@ impl AudioEngine {
     }
     
     pub fn get_active_sample_count(&self) -> u32 {
+        let system_channels: Vec<u32> = self.channels.lock().unwrap().values()
+            .filter(|channel| channel.system)
+            .map(|channel| channel.id)
+            .collect();
         let voices = self.voices.lock().unwrap();
-        let active_count = voices.iter().filter(|v| v.active && !v.is_finished()).count();
+        let active_count = voices.iter()
+            .filter(|v| v.active && !v.is_finished() && !system_channels.contains(&v.channel_id))
+            .count();
         self.active_voices.store(active_count, Ordering::Relaxed);
         active_count as u32
     }
@@ -848,7 +1441,186 @@ impl AudioEngine {
 
 impl Drop for AudioEngine {
     fn drop(&mut self) {
+        if let Err(e) = self.stop_recording() {
+            log::error!("{}", e);
+        }
         self.stop_all();
         log::info!("Audio engine shut down");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Limiter off, so tests see the voices' own levels
+    fn mixer() -> Mixer {
+        Mixer {
+            voices: Arc::new(Mutex::new(Vec::new())),
+            levels: Arc::new(MixLevels::new()),
+            limiter: Arc::new(LimiterSettings::new(Limiter { enabled: false, ..Limiter::default() })),
+            output_peak: Arc::new(AtomicU32::new(0)),
+            record_taps: mpsc::channel().1,
+            record_tap: None,
+            scratch: Vec::with_capacity(MAX_CALLBACK_SAMPLES),
+        }
+    }
+
+    // Mix one buffer of a steady stereo sample on each of `channel_ids`
+    fn mix_voices(mixer: &mut Mixer, channel_ids: &[u32]) -> Vec<f32> {
+        let sample = DecodedSample { data: vec![0.5; 2048], sample_rate: 44100, channels: 2, duration_ms: 23 };
+        *mixer.voices.lock().unwrap() = channel_ids.iter().map(|&id| Voice::new(&sample, 1.0, 1.0, id)).collect();
+        let mut data = vec![0.0; 256];
+        mixer.mix(&mut data, 2);
+        data
+    }
+
+    #[test]
+    fn muted_channel_adds_nothing_to_the_mix() {
+        let mut mixer = mixer();
+        let mut channels = HashMap::new();
+        channels.insert(0, AudioChannel::new(0, "Drums".to_string()));
+        channels.insert(1, AudioChannel { muted: true, ..AudioChannel::new(1, "Bass".to_string()) });
+        mixer.levels.publish(&channels, 44100);
+
+        assert!(mix_voices(&mut mixer, &[1]).iter().all(|sample| *sample == 0.0));
+        let drums = mix_voices(&mut mixer, &[0]);
+        assert!(drums.iter().any(|sample| *sample != 0.0));
+        assert_eq!(mix_voices(&mut mixer, &[0, 1]), drums);
+    }
+
+    // Energy in the sample-to-sample differences: mostly the high end of the spectrum
+    fn high_frequency_energy(data: &[f32]) -> f32 {
+        data.windows(2).map(|pair| (pair[1] - pair[0]).powi(2)).sum()
+    }
+
+    #[test]
+    fn voice_lowpass_takes_the_top_off_white_noise() {
+        let mut seed = 1u32;
+        let noise: Vec<f32> = (0..4096).map(|_| {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            (seed >> 8) as f32 / (1u32 << 23) as f32 - 1.0
+        }).collect();
+        let sample = DecodedSample { data: noise, sample_rate: 44100, channels: 1, duration_ms: 93 };
+        let mut mixer = mixer();
+        let mut mix = |cutoff_hz: f32| {
+            let mut voice = Voice::new(&sample, 1.0, 1.0, 0);
+            voice.own_lowpass = lowpass_coefficient(cutoff_hz, 44100);
+            *mixer.voices.lock().unwrap() = vec![voice];
+            let mut data = vec![0.0; 2048];
+            mixer.mix(&mut data, 1);
+            high_frequency_energy(&data)
+        };
+        let open = mix(0.0);
+        assert!(mix(500.0) < open * 0.05, "{} vs {}", mix(500.0), open);
+        assert_eq!(mix(30000.0), open, "a cutoff at/above Nyquist bypasses the filter");
+    }
+
+    #[test]
+    fn negative_audio_offset_keeps_the_first_frame() {
+        // A ramp, so a skipped frame shows up as a wrong first value
+        let sample = DecodedSample { data: (1..=441).map(|frame| frame as f32 / 441.0).collect(), sample_rate: 44100, channels: 1, duration_ms: 10 };
+        let mut mixer = mixer();
+        let mut render = |audio_offset_ms: i32, extra_offset_ms: i32| {
+            let mut voice = Voice::new(&sample, 1.0, 1.0, 0);
+            voice.pan_gains = (1.0, 1.0);
+            voice.set_offset(audio_offset_ms, extra_offset_ms, 44100);
+            *mixer.voices.lock().unwrap() = vec![voice];
+            let mut data = vec![0.0; 882];
+            mixer.mix(&mut data, 1);
+            data
+        };
+        let on_time = render(0, 0);
+        assert_eq!(render(-5, 0), on_time);
+        // Per-trigger offsets still land after the shared base delay
+        let late = render(-5, 2);
+        assert!(late[..88].iter().all(|sample| *sample == 0.0));
+        assert_eq!(late[88..], on_time[..882 - 88]);
+    }
+
+    #[test]
+    fn envelope_fades_the_rendered_voice_in_and_out() {
+        // 10 ms of a steady level, mono
+        let sample = DecodedSample { data: vec![0.5; 441], sample_rate: 44100, channels: 1, duration_ms: 10 };
+        let mut mixer = mixer();
+        let mut render = |envelope: Envelope| {
+            let mut voice = Voice::new(&sample, 1.0, 1.0, 0);
+            voice.pan_gains = (1.0, 1.0);
+            voice.set_envelope(envelope, 44100);
+            *mixer.voices.lock().unwrap() = vec![voice];
+            let mut data = vec![0.0; 441];
+            mixer.mix(&mut data, 1);
+            data
+        };
+        let dry = render(Envelope::NONE);
+        let wet = render(Envelope::default());
+        let attack = (Envelope::default().attack_ms * 44.1) as usize;
+        let release = (Envelope::default().release_ms * 44.1) as usize;
+
+        assert_eq!((dry[0], dry[440]), (0.5, 0.5));
+        assert_eq!(wet[0], 0.0);
+        assert!(wet[440] < 0.01, "last sample {} should be faded out", wet[440]);
+        assert!(wet[..attack].windows(2).all(|pair| pair[0] < pair[1]), "attack ramps up");
+        assert!(wet[441 - release..].windows(2).all(|pair| pair[0] > pair[1]), "release ramps down");
+        // Between the fades the voice is untouched, so timing and level are unchanged
+        assert_eq!(wet[attack..441 - release], dry[attack..441 - release]);
+    }
+
+    #[test]
+    fn only_one_engine_owns_the_output_at_a_time() {
+        let claim = OutputClaim::acquire().unwrap();
+        assert!(OutputClaim::acquire().is_err());
+        drop(claim);
+        assert!(OutputClaim::acquire().is_ok());
+    }
+
+    #[test]
+    fn limiter_is_on_by_default_and_keeps_the_mix_under_minus_one_dbfs() {
+        let limiter = Limiter::default();
+        assert!(limiter.enabled);
+        let mut buffer = [0.5, -0.9, 1.5];
+        limiter.process(&mut buffer);
+        assert!(buffer.iter().all(|sample| sample.abs() < db_to_linear(-1.0)));
+
+        let off = Limiter { enabled: false, ..limiter };
+        let mut buffer = [0.5, -0.9, 1.5];
+        off.process(&mut buffer);
+        assert_eq!(buffer, [0.5, -0.9, 1.5]);
+    }
+
+    #[test]
+    fn integer_callbacks_reuse_the_scratch_buffer() {
+        let mut mixer = mixer();
+        let scratch = mixer.scratch.as_ptr();
+        let mut data = [1i16; 512];
+        AudioEngine::audio_callback_i16(&mut data, &mut mixer, 2);
+        assert_eq!(data, [0i16; 512]);
+        let mut data = [0u16; 1024];
+        AudioEngine::audio_callback_u16(&mut data, &mut mixer, 2);
+        assert_eq!(data, [32768u16; 1024]);
+        assert_eq!(mixer.scratch.as_ptr(), scratch);
+    }
+
+    #[test]
+    fn recording_is_written_off_the_audio_callback() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap();
+        let spec = hound::WavSpec { channels: 2, sample_rate: 44100, bits_per_sample: 32, sample_format: hound::SampleFormat::Float };
+        let (recording, tap) = spawn_recording(path, spec).unwrap();
+        let (record_taps, tap_receiver) = mpsc::channel();
+        let mut mixer = Mixer { record_taps: tap_receiver, ..mixer() };
+        record_taps.send(tap).unwrap();
+
+        for _ in 0..3 {
+            mixer.mix(&mut [0.0; 512], 2);
+        }
+        recording.stop.store(true, Ordering::Release);
+        assert_eq!(recording.writer.join().unwrap(), Ok(768));
+        assert_eq!(recording.dropped.load(Ordering::Relaxed), 0);
+        assert_eq!(hound::WavReader::open(path).unwrap().duration(), 768);
+
+        // With the writer gone the callback lets go of its end
+        mixer.mix(&mut [0.0; 512], 2);
+        assert!(mixer.record_tap.is_none());
+    }
+}
@@ -24,6 +24,7 @@ pub enum AudioPlayerState {
         scroll_offset: f32, // 0.0 to 1.0
         selection_start: Option<f32>,
         selection_end: Option<f32>,
+        selected_marker: Option<usize>, // Index into `markers` for keyboard nudge/delete
     },
 }
 
@@ -46,6 +47,16 @@ const WAVEFORM_HEIGHT: usize = 200;
 const CONTROLS_HEIGHT: usize = 60;
 const MARKERS_HEIGHT: usize = 40;
 const WAVEFORM_Y_OFFSET: usize = 80;
+const MARKER_NUDGE: f32 = 0.001; // Ctrl+Left/Right step for the selected marker
+const MAX_ZOOM: f32 = 32.0;
+const ZOOM_STEP: f32 = 1.5;
+const SCROLL_STEP: f32 = 0.25; // A/D scroll by this fraction of the visible window
+const WAVEFORM_POINTS: usize = PLAYER_WIDTH * MAX_ZOOM as usize; // About one point per pixel at full zoom
+const CHAR_WIDTH: usize = 8;
+const JUMP_KEYS: [VirtualKeyCode; 10] = [
+    VirtualKeyCode::Key0, VirtualKeyCode::Key1, VirtualKeyCode::Key2, VirtualKeyCode::Key3, VirtualKeyCode::Key4,
+    VirtualKeyCode::Key5, VirtualKeyCode::Key6, VirtualKeyCode::Key7, VirtualKeyCode::Key8, VirtualKeyCode::Key9,
+];
 
 pub struct AudioPlayer {
     pub state: AudioPlayerState,
@@ -110,6 +121,7 @@ impl AudioPlayer {
             scroll_offset: 0.0,
             selection_start: None,
             selection_end: None,
+            selected_marker: None,
         };
 
         Ok(())
@@ -226,33 +238,53 @@ impl AudioPlayer {
             ref mut scroll_offset,
             ref mut selection_start,
             ref mut selection_end,
+            ref mut selected_marker,
             duration_ms,
             ..
         } = &mut self.state {
 
-            // Shift+Space to add marker
-            if input.key_pressed(VirtualKeyCode::Space) && input.held_shift() {
-                // Check if a marker already exists at or very close to the cursor position
-                let tolerance = 0.001; // Small tolerance for floating point comparison
-                let marker_exists = markers.iter().any(|marker| {
-                    (marker.position - *cursor_position).abs() < tolerance
-                });
-                
-                if !marker_exists {
-                    // Add new marker at cursor position
-                    markers.push(AudioMarker {
-                        position: *cursor_position,
-                        name: String::new(), // Temporary name, will be set below
-                    });
-                    
-                    // Sort all markers by position
-                    markers.sort_by(|a, b| a.position.partial_cmp(&b.position).unwrap());
-                    
-                    // Renumber all markers sequentially
-                    for (index, marker) in markers.iter_mut().enumerate() {
-                        marker.name = format!("{}", index + 1);
+            // Shift+Space adds a marker at the cursor, M drops one at the playhead
+            let new_marker_position = if input.key_pressed(VirtualKeyCode::Space) && input.held_shift() {
+                Some(*cursor_position)
+            } else if input.key_pressed(VirtualKeyCode::M) {
+                Some(if *is_playing { *playback_position } else { *cursor_position })
+            } else {
+                None
+            };
+            if let Some(position) = new_marker_position {
+                Self::add_marker(markers, selected_marker, position);
+            }
+
+            // Ctrl+Left/Right nudges the selected marker, Delete removes it
+            if let Some(index) = selected_marker.filter(|index| *index < markers.len()) {
+                if input.held_control() {
+                    let step = if input.key_pressed(VirtualKeyCode::Left) {
+                        -MARKER_NUDGE
+                    } else if input.key_pressed(VirtualKeyCode::Right) {
+                        MARKER_NUDGE
+                    } else {
+                        0.0
+                    };
+                    if step != 0.0 {
+                        let position = (markers[index].position + step).clamp(0.0, 1.0);
+                        markers[index].position = position;
+                        *selected_marker = Some(Self::renumber_markers(markers, position));
+                        *cursor_position = position;
                     }
                 }
+                if input.key_pressed(VirtualKeyCode::Delete) {
+                    markers.remove(index);
+                    Self::renumber_markers(markers, 0.0);
+                    *selected_marker = None;
+                }
+            }
+
+            // Number keys jump the playhead to 0%, 10%, ... 90% of the sample
+            if let Some(digit) = JUMP_KEYS.iter().position(|key| input.key_pressed(*key)) {
+                new_cursor_pos = digit as f32 / 10.0;
+                *cursor_position = new_cursor_pos;
+                *playback_position = new_cursor_pos;
+                need_restart = *is_playing;
             }
 
             // Arrow keys for navigation with smooth acceleration
@@ -272,17 +304,18 @@ impl AudioPlayer {
                     let new_pos = sorted_markers[target_marker].position;
                     *cursor_position = new_pos;
                     *playback_position = new_pos; // Snap playback cursor to marker too
+                    *selected_marker = markers.iter().position(|m| m.position == new_pos);
                     
                     // Reset acceleration timer to prevent continued movement
                     self.left_arrow_held_time = 0.0;
                 }
             }
             // Handle regular left arrow with smooth acceleration (key_held for continuous action)
-            else if input.key_held(VirtualKeyCode::Left) && !input.held_shift() {
+            else if input.key_held(VirtualKeyCode::Left) && !input.held_shift() && !input.held_control() {
                 // Smooth acceleration for left arrow
                 self.left_arrow_held_time += delta_time;
                 let acceleration = (self.left_arrow_held_time * 2.0).min(5.0); // Max 5x speed
-                let move_amount = 0.005 * acceleration; // Base speed 0.005, accelerates up to 0.025
+                let move_amount = 0.005 * acceleration / *zoom_level; // Base speed 0.005, accelerates up to 0.025; finer when zoomed
                 new_cursor_pos = (*cursor_position - move_amount).max(0.0);
                 *cursor_position = new_cursor_pos;
                 if *is_playing {
@@ -307,17 +340,18 @@ impl AudioPlayer {
                     let new_pos = sorted_markers[target_marker].position;
                     *cursor_position = new_pos;
                     *playback_position = new_pos; // Snap playback cursor to marker too
+                    *selected_marker = markers.iter().position(|m| m.position == new_pos);
                     
                     // Reset acceleration timer to prevent continued movement
                     self.right_arrow_held_time = 0.0;
                 }
             }
             // Handle regular right arrow with smooth acceleration (key_held for continuous action)
-            else if input.key_held(VirtualKeyCode::Right) && !input.held_shift() {
+            else if input.key_held(VirtualKeyCode::Right) && !input.held_shift() && !input.held_control() {
                 // Smooth acceleration for right arrow
                 self.right_arrow_held_time += delta_time;
                 let acceleration = (self.right_arrow_held_time * 2.0).min(5.0); // Max 5x speed
-                let move_amount = 0.005 * acceleration; // Base speed 0.005, accelerates up to 0.025
+                let move_amount = 0.005 * acceleration / *zoom_level; // Base speed 0.005, accelerates up to 0.025; finer when zoomed
                 new_cursor_pos = (*cursor_position + move_amount).min(1.0);
                 *cursor_position = new_cursor_pos;
                 if *is_playing {
@@ -328,26 +362,40 @@ impl AudioPlayer {
                 self.right_arrow_held_time = 0.0;
             }
 
-            // Zoom controls
-            if input.key_pressed(VirtualKeyCode::Plus) || input.key_pressed(VirtualKeyCode::Equals) {
-                *zoom_level = (*zoom_level * 1.5).min(10.0);
-            }
-
-            if input.key_pressed(VirtualKeyCode::Minus) {
-                *zoom_level = (*zoom_level / 1.5).max(1.0);
+            // Zoom controls; the cursor stays where it is on screen
+            let new_zoom = if input.key_pressed(VirtualKeyCode::Plus) || input.key_pressed(VirtualKeyCode::Equals) {
+                (*zoom_level * ZOOM_STEP).min(MAX_ZOOM)
+            } else if input.key_pressed(VirtualKeyCode::Minus) {
+                (*zoom_level / ZOOM_STEP).max(1.0)
+            } else {
+                *zoom_level
+            };
+            if new_zoom != *zoom_level {
+                let (start, end) = visible_range(*zoom_level, *scroll_offset);
+                let anchor = if (start..=end).contains(cursor_position) {
+                    (*cursor_position - start) / (end - start)
+                } else {
+                    0.5
+                };
+                *zoom_level = new_zoom;
+                *scroll_offset = clamp_scroll(*cursor_position - anchor / new_zoom, new_zoom);
             }
 
             // Scroll when zoomed
             if *zoom_level > 1.0 {
                 if input.key_pressed(VirtualKeyCode::A) {
-                    *scroll_offset = (*scroll_offset - 0.1).max(0.0);
+                    *scroll_offset = clamp_scroll(*scroll_offset - SCROLL_STEP / *zoom_level, *zoom_level);
                 }
                 if input.key_pressed(VirtualKeyCode::D) {
-                    let max_scroll = 1.0 - (1.0 / *zoom_level);
-                    *scroll_offset = (*scroll_offset + 0.1).min(max_scroll);
+                    *scroll_offset = clamp_scroll(*scroll_offset + SCROLL_STEP / *zoom_level, *zoom_level);
                 }
             }
 
+            // Seeking past either edge scrolls the view along with the cursor
+            if input.key_held(VirtualKeyCode::Left) || input.key_held(VirtualKeyCode::Right) || JUMP_KEYS.iter().any(|key| input.key_pressed(*key)) {
+                *scroll_offset = scroll_to_show(*cursor_position, *zoom_level, *scroll_offset);
+            }
+
             // Selection with mouse (simplified - using keys for now)
             if input.key_pressed(VirtualKeyCode::S) && input.held_shift() {
                 if selection_start.is_none() {
@@ -386,6 +434,7 @@ impl AudioPlayer {
                     let distance = (marker.position - cursor_pos).abs();
                     distance > range_normalized
                 });
+                *selected_marker = None;
             }
 
             // Update playback position if playing
@@ -405,6 +454,61 @@ impl AudioPlayer {
         None
     }
 
+    // Left click in the waveform moves the cursor there; in the marker strip below it
+    // drops a marker. Takes frame pixel coordinates.
+    pub fn handle_click(&mut self, pixel_x: usize, pixel_y: usize, window_width: usize, window_height: usize) {
+        let (player_x, player_y, player_width, _) = player_bounds(window_width, window_height);
+        if let AudioPlayerState::Visible {
+            ref mut cursor_position,
+            ref mut playback_position,
+            is_playing,
+            ref mut markers,
+            ref mut selected_marker,
+            zoom_level,
+            scroll_offset,
+            ..
+        } = &mut self.state {
+            let waveform_width = player_width.saturating_sub(20);
+            let Some(offset) = pixel_x.checked_sub(player_x + 10).filter(|offset| *offset < waveform_width) else {
+                return;
+            };
+            // Markers stay normalized to the whole sample whatever the zoom
+            let position = offset_to_position(offset, visible_range(*zoom_level, *scroll_offset), waveform_width).clamp(0.0, 1.0);
+            let waveform_top = player_y + WAVEFORM_Y_OFFSET;
+            let markers_top = waveform_top + WAVEFORM_HEIGHT + 10;
+            if (waveform_top..waveform_top + WAVEFORM_HEIGHT).contains(&pixel_y) {
+                *cursor_position = position;
+                if !*is_playing {
+                    *playback_position = position;
+                }
+            } else if (markers_top..markers_top + MARKERS_HEIGHT).contains(&pixel_y) {
+                Self::add_marker(markers, selected_marker, position);
+            }
+        }
+    }
+
+    // Add a marker unless one is already at (or within float noise of) the position, and select it
+    fn add_marker(markers: &mut Vec<AudioMarker>, selected_marker: &mut Option<usize>, position: f32) {
+        let tolerance = 0.001; // Small tolerance for floating point comparison
+        if !markers.iter().any(|marker| (marker.position - position).abs() < tolerance) {
+            markers.push(AudioMarker {
+                position,
+                name: String::new(), // Temporary name, set by renumbering
+            });
+            *selected_marker = Some(Self::renumber_markers(markers, position));
+        }
+    }
+
+    // Sort markers by position and name them 1..n; returns the new index of the
+    // marker at `position` so a selection can follow it
+    fn renumber_markers(markers: &mut [AudioMarker], position: f32) -> usize {
+        markers.sort_by(|a, b| a.position.partial_cmp(&b.position).unwrap());
+        for (index, marker) in markers.iter_mut().enumerate() {
+            marker.name = format!("{}", index + 1);
+        }
+        markers.iter().position(|m| m.position == position).unwrap_or(0)
+    }
+
     fn start_playback(&mut self, audio_engine: &mut AudioEngine, sample_path: &str, start_position: f32) {
         if let Some(channel_id) = self.audio_channel_id {
             // Stop any existing playback first
@@ -478,7 +582,7 @@ impl AudioPlayer {
     }
 
     fn generate_waveform_data(&self, sample: &DecodedSample) -> Vec<f32> {
-        let target_samples = 1600; // Target number of waveform points
+        let target_samples = WAVEFORM_POINTS;
         let chunk_size = sample.data.len() / target_samples;
         
         if chunk_size == 0 {
@@ -518,23 +622,11 @@ impl AudioPlayer {
             scroll_offset,
             selection_start,
             selection_end,
+            selected_marker,
             ..
         } = &self.state {
             
-            // Calculate position (center of screen) and constrain to window bounds
-            let actual_player_width = PLAYER_WIDTH.min(window_width);
-            let actual_player_height = PLAYER_HEIGHT.min(window_height);
-            
-            let player_x = if window_width > actual_player_width {
-                (window_width - actual_player_width) / 2
-            } else {
-                0
-            };
-            let player_y = if window_height > actual_player_height {
-                (window_height - actual_player_height) / 2
-            } else {
-                0
-            };
+            let (player_x, player_y, actual_player_width, actual_player_height) = player_bounds(window_width, window_height);
 
             // Draw background
             self.draw_background(frame, player_x, player_y, actual_player_width, actual_player_height, window_width);
@@ -552,6 +644,12 @@ impl AudioPlayer {
             let status_color = if *is_playing { [0, 255, 0] } else { [255, 0, 0] };
             font::draw_text(frame, status_text, player_x + 200, player_y + 30, status_color, false, window_width);
 
+            if *zoom_level > 1.0 {
+                let (start, end) = visible_range(*zoom_level, *scroll_offset);
+                let zoom_text = format!("Zoom {:.1}x  {:.2}s - {:.2}s", zoom_level, start * *duration_ms as f32 / 1000.0, end * *duration_ms as f32 / 1000.0);
+                font::draw_text(frame, &zoom_text, player_x + 300, player_y + 30, [200, 200, 200], false, window_width);
+            }
+
             // Draw waveform
             self.draw_waveform(frame, player_x, player_y + WAVEFORM_Y_OFFSET, waveform_data, 
                              *playback_position, *cursor_position, *zoom_level, *scroll_offset, actual_player_width, window_width);
@@ -564,7 +662,7 @@ impl AudioPlayer {
 
             // Draw markers
             self.draw_markers(frame, player_x, player_y + WAVEFORM_Y_OFFSET + WAVEFORM_HEIGHT + 10, 
-                            markers, *zoom_level, *scroll_offset, actual_player_width, window_width);
+                            markers, *selected_marker, *zoom_level, *scroll_offset, actual_player_width, window_width);
 
             // Draw controls help
             self.draw_controls_help(frame, player_x, player_y + actual_player_height - 60, window_width);
@@ -640,108 +738,38 @@ impl AudioPlayer {
         let waveform_width = player_width.saturating_sub(20);
         let waveform_height = WAVEFORM_HEIGHT;
         let center_y = y + waveform_height / 2;
-
-        // Calculate visible range based on zoom and scroll
-        let visible_start = scroll_offset;
-        let visible_end = (scroll_offset + (1.0 / zoom_level)).min(1.0);
-        
-        let start_sample = (visible_start * waveform_data.len() as f32) as usize;
-        let end_sample = (visible_end * waveform_data.len() as f32) as usize;
-        let visible_samples = &waveform_data[start_sample..end_sample.min(waveform_data.len())];
-
-        // Draw waveform
-        for (i, &amplitude) in visible_samples.iter().enumerate() {
-            let sample_x = x + 10 + (i * waveform_width) / visible_samples.len();
-            let wave_height = (amplitude * (waveform_height as f32 / 2.0)) as usize;
-            
-            // Draw positive part
-            for dy in 0..wave_height {
-                let py = center_y - dy;
-                if sample_x < window_width && py < frame.len() / (window_width * 4) {
-                    let idx = (py * window_width + sample_x) * 4;
-                    if idx + 3 < frame.len() {
-                        frame[idx] = 0;     // R
-                        frame[idx + 1] = 150; // G
-                        frame[idx + 2] = 255; // B
-                    }
-                }
-            }
-            
-            // Draw negative part
-            for dy in 0..wave_height {
-                let py = center_y + dy;
-                if sample_x < window_width && py < frame.len() / (window_width * 4) {
-                    let idx = (py * window_width + sample_x) * 4;
-                    if idx + 3 < frame.len() {
-                        frame[idx] = 0;     // R
-                        frame[idx + 1] = 150; // G
-                        frame[idx + 2] = 255; // B
-                    }
+        let range = visible_range(zoom_level, scroll_offset);
+
+        // Draw waveform, one column per pixel: the loudest point that falls in it
+        if !waveform_data.is_empty() {
+            for column in 0..waveform_width {
+                let first = (offset_to_position(column, range, waveform_width) * waveform_data.len() as f32) as usize;
+                let last = (offset_to_position(column + 1, range, waveform_width) * waveform_data.len() as f32) as usize;
+                let first = first.min(waveform_data.len() - 1);
+                let amplitude = waveform_data[first..last.clamp(first + 1, waveform_data.len())]
+                    .iter()
+                    .fold(0.0f32, |loudest, &a| loudest.max(a));
+                let wave_height = ((amplitude * (waveform_height as f32 / 2.0)) as usize).min(waveform_height / 2);
+                
+                // Positive and negative parts
+                for dy in 0..wave_height {
+                    put_pixel(frame, x + 10 + column, center_y - dy, [0, 150, 255], window_width);
+                    put_pixel(frame, x + 10 + column, center_y + dy, [0, 150, 255], window_width);
                 }
             }
         }
 
         // Draw center line
         for dx in 0..waveform_width {
-            let px = x + 10 + dx;
-            if px < window_width && center_y < frame.len() / (window_width * 4) {
-                let idx = (center_y * window_width + px) * 4;
-                if idx + 3 < frame.len() {
-                    frame[idx] = 80;
-                    frame[idx + 1] = 80;
-                    frame[idx + 2] = 80;
-                }
-            }
+            put_pixel(frame, x + 10 + dx, center_y, [80, 80, 80], window_width);
         }
 
-        // Draw navigation cursor (cursor_position) - white/light gray
-        let nav_cursor_x = x + 10 + ((cursor_position - visible_start) / (visible_end - visible_start) * waveform_width as f32) as usize;
-        
-        let clamped_nav_cursor_x = if cursor_position < visible_start {
-            x + 10 // Show at left edge
-        } else if cursor_position > visible_end {
-            x + 10 + waveform_width - 1 // Show at right edge
-        } else {
-            nav_cursor_x // Show at actual position
-        };
-        
-        // Draw the navigation cursor line (white)
-        if clamped_nav_cursor_x >= x + 10 && clamped_nav_cursor_x < x + 10 + waveform_width {
-            for dy in 0..waveform_height {
-                let py = y + dy;
-                if clamped_nav_cursor_x < window_width && py < frame.len() / (window_width * 4) {
-                    let idx = (py * window_width + clamped_nav_cursor_x) * 4;
-                    if idx + 3 < frame.len() {
-                        frame[idx] = 255;   // R
-                        frame[idx + 1] = 255; // G
-                        frame[idx + 2] = 255; // B (white)
-                    }
-                }
-            }
-        }
-
-        // Draw playback cursor (playback_position) - yellow/orange
-        let playback_cursor_x = x + 10 + ((playback_position - visible_start) / (visible_end - visible_start) * waveform_width as f32) as usize;
-        
-        let clamped_playback_cursor_x = if playback_position < visible_start {
-            x + 10 // Show at left edge
-        } else if playback_position > visible_end {
-            x + 10 + waveform_width - 1 // Show at right edge
-        } else {
-            playback_cursor_x // Show at actual position
-        };
-        
-        // Draw the playback cursor line (yellow/orange)
-        if clamped_playback_cursor_x >= x + 10 && clamped_playback_cursor_x < x + 10 + waveform_width {
-            for dy in 0..waveform_height {
-                let py = y + dy;
-                if clamped_playback_cursor_x < window_width && py < frame.len() / (window_width * 4) {
-                    let idx = (py * window_width + clamped_playback_cursor_x) * 4;
-                    if idx + 3 < frame.len() {
-                        frame[idx] = 255;   // R
-                        frame[idx + 1] = 165; // G (orange)
-                        frame[idx + 2] = 0;   // B
-                    }
+        // Cursors scrolled out of view are pinned to the nearest edge: navigation
+        // cursor in white, playback cursor in orange
+        for (position, color) in [(cursor_position, [255, 255, 255]), (playback_position, [255, 165, 0])] {
+            if let Some(offset) = position_to_offset(position.clamp(range.0, range.1), range, waveform_width) {
+                for dy in 0..waveform_height {
+                    put_pixel(frame, x + 10 + offset, y + dy, color, window_width);
                 }
             }
         }
@@ -750,16 +778,19 @@ impl AudioPlayer {
     fn draw_selection(&self, frame: &mut [u8], x: usize, y: usize, start: f32, end: f32, 
                      zoom_level: f32, scroll_offset: f32, player_width: usize, window_width: usize) {
         let waveform_width = player_width.saturating_sub(20);
-        let visible_start = scroll_offset;
-        let visible_end = (scroll_offset + (1.0 / zoom_level)).min(1.0);
+        let range = visible_range(zoom_level, scroll_offset);
         
-        let selection_start_x = x + 10 + ((start - visible_start) / (visible_end - visible_start) * waveform_width as f32) as usize;
-        let selection_end_x = x + 10 + ((end - visible_start) / (visible_end - visible_start) * waveform_width as f32) as usize;
+        // Only the part of the selection inside the visible window
+        let (start, end) = (start.min(end).max(range.0), start.max(end).min(range.1));
+        let (Some(selection_start_x), Some(selection_end_x)) =
+            (position_to_offset(start, range, waveform_width), position_to_offset(end, range, waveform_width)) else {
+            return;
+        };
         
         // Draw selection overlay
         for dx in selection_start_x..selection_end_x {
             for dy in 0..WAVEFORM_HEIGHT {
-                let px = dx;
+                let px = x + 10 + dx;
                 let py = y + dy;
                 if px < window_width && py < frame.len() / (window_width * 4) {
                     let idx = (py * window_width + px) * 4;
@@ -773,40 +804,40 @@ impl AudioPlayer {
         }
     }
 
-    fn draw_markers(&self, frame: &mut [u8], x: usize, y: usize, markers: &[AudioMarker], 
+    fn draw_markers(&self, frame: &mut [u8], x: usize, y: usize, markers: &[AudioMarker], selected_marker: Option<usize>,
                    zoom_level: f32, scroll_offset: f32, player_width: usize, window_width: usize) {
         let waveform_width = player_width.saturating_sub(20);
-        let visible_start = scroll_offset;
-        let visible_end = (scroll_offset + (1.0 / zoom_level)).min(1.0);
+        let range = visible_range(zoom_level, scroll_offset);
         
-        for marker in markers {
-            if marker.position >= visible_start && marker.position <= visible_end {
-                let marker_x = x + 10 + ((marker.position - visible_start) / (visible_end - visible_start) * waveform_width as f32) as usize;
-                
-                // Draw marker line
-                for dy in 0..MARKERS_HEIGHT {
-                    let py = y + dy;
-                    if marker_x < window_width && py < frame.len() / (window_width * 4) {
-                        let idx = (py * window_width + marker_x) * 4;
-                        if idx + 3 < frame.len() {
-                            frame[idx] = 255;   // R
-                            frame[idx + 1] = 0;   // G
-                            frame[idx + 2] = 255; // B
-                        }
-                    }
-                }
-                
-                // Draw marker name
-                font::draw_text(frame, &marker.name, marker_x + 2, y + 5, [255, 0, 255], false, window_width);
+        for (index, marker) in markers.iter().enumerate() {
+            // The keyboard-selected marker is drawn in yellow
+            let color = if selected_marker == Some(index) { [255, 255, 0] } else { [255, 0, 255] };
+            let Some(offset) = position_to_offset(marker.position, range, waveform_width) else {
+                continue; // Scrolled out of view
+            };
+            let marker_x = x + 10 + offset;
+            
+            // Draw marker line
+            for dy in 0..MARKERS_HEIGHT {
+                put_pixel(frame, marker_x, y + dy, color, window_width);
             }
+            
+            // Names that would run past the waveform's right edge go on the left of the line
+            let name_width = marker.name.len() * CHAR_WIDTH;
+            let name_x = if offset + 2 + name_width <= waveform_width {
+                marker_x + 2
+            } else {
+                marker_x.saturating_sub(name_width + 1).max(x + 10)
+            };
+            font::draw_text(frame, &marker.name, name_x, y + 5, color, false, window_width);
         }
     }
 
     fn draw_controls_help(&self, frame: &mut [u8], x: usize, y: usize, window_width: usize) {
         let help_lines = [
-            "Controls: Space=Play/Pause, Shift+Space=Add Marker, Left/Right=Seek",
-            "Zoom: +/- keys, Scroll: A/D keys, Selection: Shift+S, Export: E",
-            "ESC=Close",
+            "Controls: Space=Play/Pause, Shift+Space=Add Marker, Left/Right=Seek, 0-9=Jump to 0-90%",
+            "Markers: M=Drop at playhead, Shift+Left/Right=Select, Ctrl+Left/Right=Nudge, Del=Remove",
+            "Zoom: +/- keys, Scroll: A/D or seek past an edge, Click: Cursor / Marker, Shift+S=Select, E=Export, ESC",
         ];
         
         for (i, line) in help_lines.iter().enumerate() {
@@ -872,6 +903,55 @@ impl AudioPlayer {
     }
 }
 
+// Player panel (x, y, width, height), centered and constrained to the window
+fn player_bounds(window_width: usize, window_height: usize) -> (usize, usize, usize, usize) {
+    let width = PLAYER_WIDTH.min(window_width);
+    let height = PLAYER_HEIGHT.min(window_height);
+    ((window_width - width) / 2, (window_height - height) / 2, width, height)
+}
+
+// Normalized (start, end) of the sample shown in the waveform
+fn visible_range(zoom_level: f32, scroll_offset: f32) -> (f32, f32) {
+    let start = clamp_scroll(scroll_offset, zoom_level);
+    (start, (start + 1.0 / zoom_level).min(1.0))
+}
+
+// Keep the visible window inside the sample
+fn clamp_scroll(scroll_offset: f32, zoom_level: f32) -> f32 {
+    scroll_offset.clamp(0.0, (1.0 - 1.0 / zoom_level).max(0.0))
+}
+
+// Scroll just far enough that `position` is on screen
+fn scroll_to_show(position: f32, zoom_level: f32, scroll_offset: f32) -> f32 {
+    let (start, end) = visible_range(zoom_level, scroll_offset);
+    if position < start {
+        clamp_scroll(position, zoom_level)
+    } else if position > end {
+        clamp_scroll(position - 1.0 / zoom_level, zoom_level)
+    } else {
+        scroll_offset
+    }
+}
+
+// Pixel offset into the waveform for a normalized position; None when it's out of view
+fn position_to_offset(position: f32, (start, end): (f32, f32), waveform_width: usize) -> Option<usize> {
+    if waveform_width == 0 || position < start || position > end || end <= start {
+        return None;
+    }
+    Some((((position - start) / (end - start) * waveform_width as f32) as usize).min(waveform_width - 1))
+}
+
+fn offset_to_position(offset: usize, (start, end): (f32, f32), waveform_width: usize) -> f32 {
+    start + offset as f32 / waveform_width.max(1) as f32 * (end - start)
+}
+
+fn put_pixel(frame: &mut [u8], px: usize, py: usize, color: [u8; 3], window_width: usize) {
+    if px < window_width && py < frame.len() / (window_width * 4) {
+        let idx = (py * window_width + px) * 4;
+        frame[idx..idx + 3].copy_from_slice(&color);
+    }
+}
+
 impl Default for AudioPlayer {
     fn default() -> Self {
         Self::new()
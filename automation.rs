@@ -0,0 +1,100 @@
+// Records live parameter changes made during a take (console commands) with the
+// time they happened, and replays them at the same times on later takes.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AutomationParam {
+    MasterVolume,
+    Bpm,
+    ChannelVolume(u32),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct AutomationEvent {
+    pub time: f32, // Seconds since the take started
+    pub param: AutomationParam,
+    pub value: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AutomationMode {
+    Off,
+    Record,
+    Play,
+}
+
+pub struct Automation {
+    pub mode: AutomationMode,
+    events: Vec<AutomationEvent>, // Last finished recording, in time order
+    recording: Vec<AutomationEvent>,
+    elapsed: f32,
+    next_event: usize,
+    running: bool,
+}
+
+impl Automation {
+    pub fn new() -> Self {
+        Self {
+            mode: AutomationMode::Off,
+            events: Vec::new(),
+            recording: Vec::new(),
+            elapsed: 0.0,
+            next_event: 0,
+            running: false,
+        }
+    }
+
+    pub fn event_count(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.running && self.mode == AutomationMode::Record
+    }
+
+    pub fn start_take(&mut self) {
+        self.running = true;
+        self.elapsed = 0.0;
+        self.next_event = 0;
+        self.recording.clear();
+    }
+
+    // Finish the take. A non-empty recording replaces the stored automation and
+    // switches to playback; returns how many events were kept.
+    pub fn stop_take(&mut self) -> Option<usize> {
+        self.running = false;
+        if self.mode != AutomationMode::Record || self.recording.is_empty() {
+            return None;
+        }
+        self.events = std::mem::take(&mut self.recording);
+        self.mode = AutomationMode::Play;
+        Some(self.events.len())
+    }
+
+    pub fn record(&mut self, param: AutomationParam, value: f32) {
+        if self.is_recording() {
+            self.recording.push(AutomationEvent { time: self.elapsed, param, value });
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+        self.recording.clear();
+        self.next_event = 0;
+    }
+
+    // Advance the take clock and return the events that became due
+    pub fn advance(&mut self, delta_time: f32) -> Vec<AutomationEvent> {
+        if !self.running {
+            return Vec::new();
+        }
+        self.elapsed += delta_time;
+        if self.mode != AutomationMode::Play {
+            return Vec::new();
+        }
+        let start = self.next_event;
+        while self.next_event < self.events.len() && self.events[self.next_event].time <= self.elapsed {
+            self.next_event += 1;
+        }
+        self.events[start..self.next_event].to_vec()
+    }
+}
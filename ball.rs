@@ -1,6 +1,9 @@
-use crate::sequencer::{GRID_WIDTH, GRID_HEIGHT};
+use crate::audio_engine::Envelope;
+use crate::sequencer::{grid_height, grid_width};
+use crate::square::Program;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
 pub enum Direction {
     Up,
     Down,
@@ -12,6 +15,66 @@ pub enum Direction {
     DownRight,
 }
 
+pub const MAX_TRIM: f32 = 2.0;
+// Ranges programs can set; values outside them are clamped with a console warning
+pub const MIN_SPEED: f32 = 0.1;
+pub const MAX_SPEED: f32 = 50.0;
+pub const MAX_VOLUME: f32 = 2.0;
+pub const MIN_PITCH: f32 = 0.1;
+pub const MAX_PITCH: f32 = 4.0;
+pub const MAX_ENVELOPE_MS: f32 = 2000.0;
+
+// Order balls in a burst fan out in: cardinals first, then diagonals
+pub const BURST_DIRECTIONS: [Direction; 8] = [
+    Direction::Up, Direction::Right, Direction::Down, Direction::Left,
+    Direction::UpRight, Direction::DownRight, Direction::DownLeft, Direction::UpLeft,
+];
+
+// How far outside a square's edge a precise bounce leaves the ball, so it isn't
+// immediately counted as inside the cell again
+pub const EDGE_EPSILON: f32 = 0.001;
+
+// A grid cell the ball moved into, with the point where it crossed the cell's edge
+#[derive(Clone, Copy, Debug)]
+pub struct CellEntry {
+    pub x: usize,
+    pub y: usize,
+    pub entry_x: f32,
+    pub entry_y: f32,
+    pub faces: (bool, bool), // Faces the walk crossed to get in: (left/right, top/bottom)
+    pub across_corner: Option<(i32, i32)>, // Only grazed at a corner: the cell on the other side of it
+}
+
+// Linear ramp of a ball's speed started by `set speed <v> over <ms>`
+#[derive(Clone, Copy, Debug)]
+pub struct SpeedGlide {
+    pub from: f32,
+    pub to: f32,
+    pub duration: f32, // Seconds
+    pub elapsed: f32,
+}
+
+// Everything about a ball's sound and behavior except where it is and who it is;
+// copied from one ball and pasted onto another from the context menu, and saved in projects
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BallConfig {
+    pub direction: Direction,
+    pub sample_path: Option<String>,
+    pub speed: f32,
+    pub color: String,
+    pub pitch: f32,
+    pub volume: f32,
+    pub trim: f32,
+    pub pitch_step: f32,
+    pub humanize: f32,
+    pub pan: Option<f32>,
+    pub envelope: Envelope,
+    #[serde(default)]
+    pub filter_cutoff: f32,
+    pub behavior: Option<Program>,
+    pub glyph: Option<char>,
+}
+
 #[derive(Clone, Debug)]
 pub struct Ball {
     pub x: f32,
@@ -27,7 +90,17 @@ pub struct Ball {
     pub color: String,
     pub pitch: f32, // Pitch multiplier (1.0 = normal, 2.0 = octave up, 0.5 = octave down)
     pub volume: f32, // Volume multiplier (1.0 = normal, 0.0 = silent, 2.0 = double volume)
+    pub trim: f32, // Mixer trim set from the grid; unlike volume, programs and resets leave it alone
+    pub pitch_step: f32, // Semitones added to pitch on every bounce (0.0 = disabled)
+    pub humanize: f32, // Random pitch/timing deviation per triggered sample (0.0 = none, 1.0 = full)
+    pub pan: Option<f32>, // Stereo position set by `set pan` (-1.0 = left, 1.0 = right); None = not set
+    pub envelope: Envelope, // Fade in/out applied to every sample the ball plays
+    pub filter_cutoff: f32, // Lowpass in Hz set by `set filter` for the ball's own hits; 0 = off
     pub id: String, // New unique identifier field
+    pub name: Option<String>, // User-assigned name; stays stable when other balls come and go
+    pub speed_glide: Option<SpeedGlide>,
+    pub behavior: Option<Program>, // Instrument program from a sample template; runs on squares with no program of their own
+    pub glyph: Option<char>, // Symbol drawn on the ball to tell voices apart
 }
 
 impl Ball {
@@ -46,16 +119,33 @@ impl Ball {
             color: "White".to_string(),
             pitch: 1.0,
             volume: 1.0,
+            trim: 1.0,
+            pitch_step: 0.0,
+            humanize: 0.0,
+            pan: None,
+            envelope: Envelope::default(),
+            filter_cutoff: 0.0,
             id, // Set the unique identifier
+            name: None,
+            speed_glide: None,
+            behavior: None,
+            glyph: None,
         }
     }
     
-    pub fn update_position(&mut self, delta_time: f32) -> Vec<(usize, usize)> {
+    // Returns every grid cell the move swept through, in the order crossed and with the
+    // point where it was entered, so a fast or diagonal ball can't skip a square.
+    pub fn update_position(&mut self, delta_time: f32) -> Vec<CellEntry> {
         if !self.active {
             return Vec::new();
         }
         
-        let mut triggered_positions = Vec::new();
+        if let Some(mut glide) = self.speed_glide {
+            glide.elapsed += delta_time;
+            let progress = (glide.elapsed / glide.duration).min(1.0);
+            self.speed = glide.from + (glide.to - glide.from) * progress;
+            self.speed_glide = if progress < 1.0 { Some(glide) } else { None };
+        }
         
         // Calculate movement delta
         let movement_speed = self.speed * delta_time;
@@ -71,12 +161,12 @@ impl Ball {
         
         // Check boundaries and reverse if needed
         let mut _reversed = false;
-        if self.x <= 0.0 || self.x >= GRID_WIDTH as f32 {
+        if self.x <= 0.0 || self.x >= grid_width() as f32 {
             self.x = old_x;
             self.direction = self.reverse_horizontal_direction();
             _reversed = true;
         }
-        if self.y <= 0.0 || self.y >= GRID_HEIGHT as f32 {
+        if self.y <= 0.0 || self.y >= grid_height() as f32 {
               self.y = old_y;
               self.direction = self.reverse_vertical_direction();
               _reversed = true;
@@ -86,9 +176,14 @@ impl Ball {
         let current_grid_x = self.x.floor() as usize;
         let current_grid_y = self.y.floor() as usize;
         
+        let mut triggered_positions = crossed_cells((old_x, old_y), (self.x, self.y));
+        
         if current_grid_x != self.last_grid_x || current_grid_y != self.last_grid_y {
-            if current_grid_x < GRID_WIDTH && current_grid_y < GRID_HEIGHT {
-                triggered_positions.push((current_grid_x, current_grid_y));
+            // Float drift can leave the walk one cell short; the cell the ball ended in always counts
+            let reported = triggered_positions.iter().any(|entry| (entry.x, entry.y) == (current_grid_x, current_grid_y));
+            if current_grid_x < grid_width() && current_grid_y < grid_height() && !reported {
+                let faces = (current_grid_x != self.last_grid_x, current_grid_y != self.last_grid_y);
+                triggered_positions.push(CellEntry { x: current_grid_x, y: current_grid_y, entry_x: self.x, entry_y: self.y, faces, across_corner: None });
             }
             self.last_grid_x = current_grid_x;
             self.last_grid_y = current_grid_y;
@@ -134,6 +229,17 @@ impl Ball {
         }
     }
     
+    // Mirror off a square: flip the horizontal part of the heading when a left/right face
+    // was hit and the vertical part for a top/bottom face. Hitting both (a corner) or
+    // neither sends the ball straight back.
+    pub fn reflect(&mut self, flip_x: bool, flip_y: bool) {
+        match (flip_x, flip_y) {
+            (true, false) => self.direction = self.reverse_horizontal_direction(),
+            (false, true) => self.direction = self.reverse_vertical_direction(),
+            _ => self.reverse_direction(),
+        }
+    }
+    
     pub fn get_grid_position(&self) -> (usize, usize) {
         (self.x.floor() as usize, self.y.floor() as usize)
     }
@@ -159,6 +265,9 @@ impl Ball {
         self.active = false;
         self.pitch = 1.0; // Reset pitch to normal
         self.volume = 1.0; // Reset volume to normal
+        self.pan = None;
+        self.filter_cutoff = 0.0;
+        self.speed_glide = None;
     }
     
     pub fn set_direction(&mut self, direction: Direction) {
@@ -167,6 +276,25 @@ impl Ball {
     
     pub fn set_speed(&mut self, speed: f32) {
         self.speed = speed;
+        self.speed_glide = None;
+    }
+    
+    // Ramp to `target` over `duration_ms`; zero duration sets it immediately
+    pub fn glide_speed(&mut self, target: f32, duration_ms: f32) {
+        if duration_ms <= 0.0 {
+            self.set_speed(target);
+            return;
+        }
+        self.speed_glide = Some(SpeedGlide { from: self.speed, to: target, duration: duration_ms / 1000.0, elapsed: 0.0 });
+    }
+    
+    // Tempo changes scale the current speed and any glide in progress alike
+    pub fn scale_speed(&mut self, ratio: f32) {
+        self.speed *= ratio;
+        if let Some(glide) = &mut self.speed_glide {
+            glide.from *= ratio;
+            glide.to *= ratio;
+        }
     }
     
     pub fn set_sample(&mut self, sample_path: String) {
@@ -190,10 +318,258 @@ impl Ball {
     }
     
     pub fn set_pitch(&mut self, pitch: f32) {
-        self.pitch = pitch.max(0.1).min(4.0); // Clamp pitch between 0.1x and 4.0x
+        self.pitch = pitch.clamp(MIN_PITCH, MAX_PITCH);
     }
     
     pub fn set_volume(&mut self, volume: f32) {
-        self.volume = volume;
+        self.volume = volume.clamp(0.0, MAX_VOLUME);
+    }
+    
+    pub fn set_trim(&mut self, trim: f32) {
+        self.trim = trim.clamp(0.0, MAX_TRIM);
+    }
+    
+    // Volume samples are actually played at
+    pub fn output_volume(&self) -> f32 {
+        self.volume * self.trim
+    }
+    
+    pub fn set_pitch_step(&mut self, semitones: f32) {
+        self.pitch_step = semitones;
+    }
+    
+    pub fn set_humanize(&mut self, amount: f32) {
+        self.humanize = amount.clamp(0.0, 1.0);
+    }
+    
+    pub fn set_pan(&mut self, pan: f32) {
+        self.pan = Some(pan.clamp(-1.0, 1.0));
+    }
+    
+    pub fn set_filter(&mut self, cutoff_hz: f32) {
+        self.filter_cutoff = cutoff_hz.max(0.0);
+    }
+    
+    pub fn set_envelope(&mut self, attack_ms: f32, release_ms: f32) {
+        self.envelope = Envelope {
+            attack_ms: attack_ms.clamp(0.0, MAX_ENVELOPE_MS),
+            release_ms: release_ms.clamp(0.0, MAX_ENVELOPE_MS),
+        };
+    }
+    
+    // Pan a hit plays at: the ball's own pan if one was set, otherwise its column
+    // (left edge = -1.0, right edge = 1.0) when `auto_pan` is on, else centered
+    pub fn output_pan(&self, auto_pan: bool) -> f32 {
+        match self.pan {
+            Some(pan) => pan,
+            None if auto_pan => (self.x / grid_width() as f32 * 2.0 - 1.0).clamp(-1.0, 1.0),
+            None => 0.0,
+        }
+    }
+    
+    pub fn set_name(&mut self, name: Option<String>) {
+        self.name = name;
+    }
+    
+    pub fn config(&self) -> BallConfig {
+        BallConfig {
+            direction: self.direction,
+            sample_path: self.sample_path.clone(),
+            speed: self.speed,
+            color: self.color.clone(),
+            pitch: self.pitch,
+            volume: self.volume,
+            trim: self.trim,
+            pitch_step: self.pitch_step,
+            humanize: self.humanize,
+            pan: self.pan,
+            envelope: self.envelope,
+            filter_cutoff: self.filter_cutoff,
+            behavior: self.behavior.clone(),
+            glyph: self.glyph,
+        }
     }
-}
\ No newline at end of file
+    
+    pub fn apply_config(&mut self, config: &BallConfig) {
+        self.direction = config.direction;
+        self.sample_path = config.sample_path.clone();
+        self.set_speed(config.speed);
+        self.color = config.color.clone();
+        self.pitch = config.pitch;
+        self.volume = config.volume;
+        self.trim = config.trim;
+        self.pitch_step = config.pitch_step;
+        self.humanize = config.humanize;
+        self.pan = config.pan;
+        self.envelope = config.envelope;
+        self.filter_cutoff = config.filter_cutoff;
+        self.behavior = config.behavior.clone();
+        self.glyph = config.glyph;
+    }
+    
+    // Name shown to the user: the assigned name, falling back to the auto-generated id
+    pub fn label(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.id)
+    }
+    
+    // Advance pitch by `pitch_step` semitones, wrapping within one octave either side of normal
+    pub fn apply_pitch_step(&mut self) {
+        if self.pitch_step == 0.0 {
+            return;
+        }
+        let mut pitch = self.pitch * 2f32.powf(self.pitch_step / 12.0);
+        while pitch > 2.0 {
+            pitch /= 2.0;
+        }
+        while pitch < 0.5 {
+            pitch *= 2.0;
+        }
+        self.pitch = pitch;
+    }
+}
+// Walk the grid cells a straight move from `from` to `to` passes through and return
+// each one after the first with the point where the move crossed into it. This is a
+// supercover walk: a move through a cell corner also reports both cells beside it.
+fn crossed_cells(from: (f32, f32), to: (f32, f32)) -> Vec<CellEntry> {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let (mut cell_x, mut cell_y) = (from.0.floor() as i32, from.1.floor() as i32);
+    let (end_x, end_y) = (to.0.floor() as i32, to.1.floor() as i32);
+    
+    // Fraction of the move at which the next vertical/horizontal cell edge is crossed
+    let first_crossing = |start: f32, cell: i32, delta: f32| {
+        if delta > 0.0 {
+            (cell as f32 + 1.0 - start) / delta
+        } else if delta < 0.0 {
+            (cell as f32 - start) / delta
+        } else {
+            f32::INFINITY
+        }
+    };
+    let mut next_x = first_crossing(from.0, cell_x, dx);
+    let mut next_y = first_crossing(from.1, cell_y, dy);
+    let step_x = if dx > 0.0 { 1.0 / dx } else { -1.0 / dx };
+    let step_y = if dy > 0.0 { 1.0 / dy } else { -1.0 / dy };
+    
+    // Crossings this close (in grid units) count as passing through the corner
+    let corner_epsilon = EDGE_EPSILON / dx.abs().max(dy.abs());
+    
+    let mut entries = Vec::new();
+    // A move never spans more cells than the grid has; the cap guards against float drift
+    for _ in 0..(grid_width() + grid_height()) {
+        if (cell_x, cell_y) == (end_x, end_y) {
+            break;
+        }
+        let t = next_x.min(next_y);
+        if t > 1.0 {
+            break;
+        }
+        let (entry_x, entry_y) = (from.0 + dx * t, from.1 + dy * t);
+        let mut push = |x: i32, y: i32, faces: (bool, bool), across_corner: Option<(i32, i32)>| {
+            if x >= 0 && y >= 0 && (x as usize) < grid_width() && (y as usize) < grid_height() {
+                entries.push(CellEntry { x: x as usize, y: y as usize, entry_x, entry_y, faces, across_corner });
+            }
+        };
+        let cross_x = next_x <= t + corner_epsilon;
+        let cross_y = next_y <= t + corner_epsilon;
+        // Through a corner: the cells on either side touch the path too, so a diagonal
+        // ball can't slip between two squares that only meet at that corner. The caller
+        // decides whether they block, since one of them alone leaves a gap.
+        if cross_x && cross_y {
+            let beside_x = (cell_x + dx.signum() as i32, cell_y);
+            let beside_y = (cell_x, cell_y + dy.signum() as i32);
+            push(beside_x.0, beside_x.1, (true, false), Some(beside_y));
+            push(beside_y.0, beside_y.1, (false, true), Some(beside_x));
+        }
+        if cross_x {
+            cell_x += dx.signum() as i32;
+            next_x += step_x;
+        }
+        if cross_y {
+            cell_y += dy.signum() as i32;
+            next_y += step_y;
+        }
+        push(cell_x, cell_y, (cross_x, cross_y), None);
+    }
+    entries
+}
+
+// Order entered cells by how soon the ball reached them from `from`. The sort is stable,
+// so ties keep the walk's order: at a corner the cells beside it come before the one
+// diagonally across.
+pub fn sort_nearest_first(entries: &mut [CellEntry], from: (f32, f32)) {
+    let distance = |entry: &CellEntry| (entry.entry_x - from.0).powi(2) + (entry.entry_y - from.1).powi(2);
+    entries.sort_by(|a, b| distance(a).partial_cmp(&distance(b)).unwrap_or(std::cmp::Ordering::Equal));
+}
+
+// Where a ball that moved from `from` to `to` and hit a square at `entry` should be put
+// back: just outside the edge it crossed, pulled back by however far it overshot so the
+// time lost inside the square is made up on the way out. Never goes back past `from`.
+pub fn rebound_point(from: (f32, f32), entry: (f32, f32), to: (f32, f32)) -> (f32, f32) {
+    let travelled = ((entry.0 - from.0).powi(2) + (entry.1 - from.1).powi(2)).sqrt();
+    if travelled <= EDGE_EPSILON {
+        return from;
+    }
+    let overshoot = ((to.0 - entry.0).powi(2) + (to.1 - entry.1).powi(2)).sqrt();
+    let back = overshoot.clamp(EDGE_EPSILON, travelled);
+    (
+        entry.0 - (entry.0 - from.0) / travelled * back,
+        entry.1 - (entry.1 - from.1) / travelled * back,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(x: usize, y: usize, entry_x: f32, entry_y: f32) -> CellEntry {
+        CellEntry { x, y, entry_x, entry_y, faces: (false, false), across_corner: None }
+    }
+
+    fn cells(entries: &[CellEntry]) -> Vec<(usize, usize)> {
+        entries.iter().map(|entry| (entry.x, entry.y)).collect()
+    }
+
+    #[test]
+    fn entered_cells_sort_nearest_first() {
+        let mut entries = vec![entry(3, 0, 3.0, 0.5), entry(1, 0, 1.0, 0.5), entry(2, 0, 2.0, 0.5)];
+        sort_nearest_first(&mut entries, (0.5, 0.5));
+        assert_eq!(cells(&entries), vec![(1, 0), (2, 0), (3, 0)]);
+    }
+
+    #[test]
+    fn entered_cells_at_the_same_distance_keep_walk_order() {
+        // Both cells beside a corner, then the one diagonally across, all entered at (1, 1)
+        let mut entries = vec![entry(1, 1, 1.0, 1.0), entry(1, 0, 1.0, 1.0), entry(0, 1, 1.0, 1.0), entry(2, 2, 2.0, 2.0)];
+        entries.swap(0, 3);
+        sort_nearest_first(&mut entries, (0.5, 0.5));
+        assert_eq!(cells(&entries), vec![(1, 0), (0, 1), (1, 1), (2, 2)]);
+    }
+
+    fn faces(entries: &[CellEntry]) -> Vec<(bool, bool)> {
+        entries.iter().map(|entry| entry.faces).collect()
+    }
+
+    #[test]
+    fn walked_cells_know_which_face_they_were_entered_through() {
+        assert_eq!(faces(&crossed_cells((0.5, 0.5), (2.5, 0.5))), vec![(true, false), (true, false)]);
+        assert_eq!(faces(&crossed_cells((0.5, 0.5), (0.5, 1.5))), vec![(false, true)]);
+        // Entered through the side face right next to the corner, close enough to the
+        // bottom edge that the entry point alone would look like a corner hit
+        assert_eq!(faces(&crossed_cells((0.5, 0.9986), (1.5, 0.9996))), vec![(true, false)]);
+    }
+
+    #[test]
+    fn walked_corner_reports_each_cell_with_its_own_face() {
+        let entries = crossed_cells((0.5, 0.5), (1.5, 1.5));
+        assert_eq!(cells(&entries), vec![(1, 0), (0, 1), (1, 1)]);
+        assert_eq!(faces(&entries), vec![(true, false), (false, true), (true, true)]);
+    }
+
+    #[test]
+    fn walked_cells_come_out_nearest_first() {
+        let mut entries = crossed_cells((0.5, 0.5), (3.5, 0.5));
+        entries.reverse();
+        sort_nearest_first(&mut entries, (0.5, 0.5));
+        assert_eq!(cells(&entries), vec![(1, 0), (2, 0), (3, 0)]);
+    }
+}
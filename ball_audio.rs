@@ -2,24 +2,172 @@
 //! This module handles all ball audio properties and playback logic in one place,
 //! making it easier to add new audio features without modifying multiple locations.
 
-use crate::audio_engine::AudioEngine;
+use crate::audio_engine::{AudioEngine, Envelope};
 use crate::ball::Ball;
+use crate::sequencer::StepLog;
+use rand::Rng;
 use std::collections::HashMap;
 
+const HUMANIZE_MAX_CENTS: f32 = 30.0; // Pitch deviation at humanize 1.0
+const HUMANIZE_MAX_MS: f32 = 20.0; // Timing deviation at humanize 1.0
+const QUANTIZE_STEPS_PER_BEAT: f32 = 4.0; // Triggers snap to 1/16th notes
+pub const COLLISION_CHANNEL: u32 = 0; // Mixer channel ball hits sound on
+
+const NOTE_NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+
+/// Musical scale that played pitches are snapped to.
+/// A pitch multiplier of 1.0 is taken to be the note C.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scale {
+    pub root: u8, // Semitones above C (0-11)
+    pub kind: String,
+    intervals: &'static [u8],
+}
+
+impl Scale {
+    /// Build a scale from a root note name ("C", "F#", "Bb") and a scale type
+    pub fn parse(root: &str, kind: &str) -> Result<Self, String> {
+        let root = Self::parse_note(root)
+            .ok_or_else(|| format!("Unknown root note '{}'", root))?;
+        let kind = kind.to_lowercase();
+        let intervals: &'static [u8] = match kind.as_str() {
+            "major" => &[0, 2, 4, 5, 7, 9, 11],
+            "minor" => &[0, 2, 3, 5, 7, 8, 10],
+            "harmonic-minor" => &[0, 2, 3, 5, 7, 8, 11],
+            "dorian" => &[0, 2, 3, 5, 7, 9, 10],
+            "pentatonic" => &[0, 2, 4, 7, 9],
+            "minor-pentatonic" => &[0, 3, 5, 7, 10],
+            "blues" => &[0, 3, 5, 6, 7, 10],
+            "chromatic" => &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+            _ => return Err(format!(
+                "Unknown scale '{}' (major, minor, harmonic-minor, dorian, pentatonic, minor-pentatonic, blues, chromatic)",
+                kind
+            )),
+        };
+        Ok(Self { root, kind, intervals })
+    }
+
+    fn parse_note(name: &str) -> Option<u8> {
+        let mut chars = name.chars();
+        let base = match chars.next()?.to_ascii_uppercase() {
+            'C' => 0, 'D' => 2, 'E' => 4, 'F' => 5, 'G' => 7, 'A' => 9, 'B' => 11,
+            _ => return None,
+        };
+        let offset = match chars.as_str() {
+            "" => 0,
+            "#" => 1,
+            "b" => -1,
+            _ => return None,
+        };
+        Some(((base + offset + 12) % 12) as u8)
+    }
+
+    pub fn name(&self) -> String {
+        format!("{} {}", NOTE_NAMES[self.root as usize], self.kind)
+    }
+
+    /// Snap a pitch multiplier to the nearest note of the scale
+    pub fn quantize(&self, pitch: f32) -> f32 {
+        if pitch <= 0.0 {
+            return pitch;
+        }
+        let semitones = 12.0 * pitch.log2();
+        let nearest = semitones.round() as i32;
+        // Scale notes are never more than a few semitones apart, so search outward
+        let snapped = (0..12)
+            .flat_map(|d| [nearest - d, nearest + d])
+            .filter(|note| self.intervals.contains(&((note - self.root as i32).rem_euclid(12) as u8)))
+            .min_by(|a, b| (*a as f32 - semitones).abs().total_cmp(&(*b as f32 - semitones).abs()))
+            .unwrap_or(nearest);
+        2f32.powf(snapped as f32 / 12.0)
+    }
+}
+
+/// Seconds to hold back a trigger at `time` (seconds into the take) so it sounds on
+/// the next 1/16th note boundary at `bpm`. A trigger already on a boundary plays
+/// right away; triggers are only ever delayed, never moved earlier.
+pub fn quantize_delay(time: f32, bpm: f32) -> f32 {
+    let step = 60.0 / bpm / QUANTIZE_STEPS_PER_BEAT;
+    let boundary = (time / step).ceil() * step;
+    (boundary - time).max(0.0)
+}
+
 /// Centralized ball audio playback system
 pub struct BallAudioSystem {
     /// Cache for collision-specific pitch calculations
     collision_pitch_cache: HashMap<String, f32>,
+    /// When set, every pitch is snapped to this scale right before playback
+    pub scale: Option<Scale>,
+    /// Delay every trigger onto the 1/16th note grid of the take
+    pub quantize: bool,
+    /// Pan balls without an explicit `set pan` by their column on the grid
+    pub auto_pan: bool,
+    /// Take clock and tempo the quantize grid is measured against
+    take_time: f32,
+    bpm: f32,
 }
 
 impl BallAudioSystem {
     pub fn new() -> Self {
         Self {
             collision_pitch_cache: HashMap::new(),
+            scale: None,
+            quantize: false,
+            auto_pan: false,
+            take_time: 0.0,
+            bpm: crate::sequencer::DEFAULT_BPM,
+        }
+    }
+
+    /// Restart the quantize grid at the first beat of a new take
+    pub fn start_take(&mut self) {
+        self.take_time = 0.0;
+    }
+
+    /// Advance the take clock; called once per ball update with the current tempo
+    pub fn advance_clock(&mut self, delta_time: f32, bpm: f32) {
+        self.take_time += delta_time;
+        self.bpm = bpm;
+    }
+
+    /// Extra playback delay in ms that puts a trigger happening now on the grid
+    fn quantize_offset_ms(&self) -> i32 {
+        if !self.quantize || self.bpm <= 0.0 {
+            return 0;
         }
+        (quantize_delay(self.take_time, self.bpm) * 1000.0).round() as i32
     }
 
+    /// Playback delay in ms for a trigger happening now that humanize holds back by
+    /// `humanize_delay_ms`
+    fn trigger_offset_ms(&self, humanize_delay_ms: i32) -> i32 {
+        self.quantize_offset_ms() + humanize_delay_ms
+    }
+
+
+
+    /// Final playback rate for a pitch, after scale quantization
+    fn playback_pitch(&self, pitch: f32) -> f32 {
+        match &self.scale {
+            Some(scale) => scale.quantize(pitch),
+            None => pitch,
+        }
+    }
 
+    /// Random pitch and timing deviation for one trigger of `ball`, as
+    /// (pitch multiplier, delay in ms). Drawn from the seeded RNG so takes replay.
+    /// A trigger can't sound before it happens, so every humanized trigger waits the
+    /// largest deviation and the timing deviation swings either way around that.
+    fn humanize(&self, ball: &Ball) -> (f32, i32) {
+        if ball.humanize <= 0.0 {
+            return (1.0, 0);
+        }
+        crate::rng::with_rng(|rng| {
+            let cents = rng.gen_range(-1.0..=1.0) * HUMANIZE_MAX_CENTS * ball.humanize;
+            let ms = rng.gen_range(-1.0..=1.0) * HUMANIZE_MAX_MS * ball.humanize;
+            (2f32.powf(cents / 1200.0), (HUMANIZE_MAX_MS + ms).round() as i32)
+        })
+    }
 
     /// Play ball audio for PlaySample action with specific channel
     pub fn play_sample_action(
@@ -28,50 +176,56 @@ impl BallAudioSystem {
         ball: &Ball,
         collision_pitch: f32,
         sample_index: u32,
-        log_messages: &mut Vec<String>,
+        log_messages: &mut StepLog,
     ) -> Result<(), String> {
-        log_messages.push(format!(
+        let (pitch_jitter, humanize_delay_ms) = self.humanize(ball);
+        let timing_offset_ms = self.trigger_offset_ms(humanize_delay_ms);
+        let collision_pitch = self.playback_pitch(collision_pitch) * pitch_jitter;
+        log_messages.trace(|| format!(
             "  → PlaySample: {} with collision pitch {:.2} and volume {:.2}",
-            sample_index, collision_pitch, ball.volume
+            sample_index, collision_pitch, ball.output_volume()
         ));
 
         if let Some(sample_path) = ball.sample_path.as_ref() {
             let current_active = audio_engine.get_active_sample_count();
             if current_active < 12 { // Conservative limit
-                if let Err(e) = audio_engine.play_on_channel_with_pitch_and_volume(sample_index, sample_path, collision_pitch, ball.volume) {
+                if let Err(e) = audio_engine.play_on_channel_with_timing(sample_index, sample_path, collision_pitch, ball.output_volume(), ball.output_pan(self.auto_pan), ball.envelope, ball.filter_cutoff, 0.0, None, timing_offset_ms) {
                     return Err(format!("Failed to play sample: {}", e));
                 }
             } else {
-                log_messages.push(format!("  → Skipped sample (audio load: {})", current_active));
+                log_messages.trace(|| format!("  → Skipped sample (audio load: {})", current_active));
             }
         }
         
         Ok(())
     }
 
-    /// Play ball audio on collision (uses channel 0)
+    /// Play ball audio on collision (on COLLISION_CHANNEL)
     pub fn play_collision_audio(
         &self,
         audio_engine: &AudioEngine,
         ball: &Ball,
         collision_pitch: f32,
-        log_messages: &mut Vec<String>,
+        log_messages: &mut StepLog,
     ) -> Result<(), String> {
+        let (pitch_jitter, humanize_delay_ms) = self.humanize(ball);
+        let timing_offset_ms = self.trigger_offset_ms(humanize_delay_ms);
+        let collision_pitch = self.playback_pitch(collision_pitch) * pitch_jitter;
         if let Some(ref sample_path) = ball.sample_path {
             let current_active = audio_engine.get_active_sample_count();
             if current_active < 12 { // Conservative limit
-                if let Err(e) = audio_engine.play_on_channel_with_pitch_and_volume(0, sample_path, collision_pitch, ball.volume) {
+                if let Err(e) = audio_engine.play_on_channel_with_timing(COLLISION_CHANNEL, sample_path, collision_pitch, ball.output_volume(), ball.output_pan(self.auto_pan), ball.envelope, ball.filter_cutoff, 0.0, None, timing_offset_ms) {
                     return Err(format!("Failed to play ball audio on collision: {}", e));
                 } else {
-                    log_messages.push(format!(
+                    log_messages.trace(|| format!(
                         "♪ Ball audio played with collision pitch {} and volume {}: {}", 
                         collision_pitch, 
-                        ball.volume, 
+                        ball.output_volume(), 
                         sample_path.split('/').last().unwrap_or(sample_path).split('\\').last().unwrap_or(sample_path)
                     ));
                 }
             } else {
-                log_messages.push(format!("Ball audio skipped (audio load: {})", current_active));
+                log_messages.trace(|| format!("Ball audio skipped (audio load: {})", current_active));
             }
         }
         
@@ -80,6 +234,21 @@ impl BallAudioSystem {
 
 
 
+    /// Play part of a sample (positions 0.0-1.0) for a slice array, held back onto the
+    /// quantize grid like every other trigger
+    pub fn play_slice(
+        &self,
+        audio_engine: &AudioEngine,
+        channel: u32,
+        sample_path: &str,
+        start_position: f32,
+        end_position: Option<f32>,
+    ) -> Result<(), String> {
+        audio_engine
+            .play_on_channel_with_timing(channel, sample_path, 1.0, 1.0, 0.0, Envelope::NONE, 0.0, start_position, end_position, self.quantize_offset_ms())
+            .map_err(|e| e.to_string())
+    }
+
     /// Clear any cached data (useful for performance)
     pub fn clear_cache(&mut self) {
         self.collision_pitch_cache.clear();
@@ -90,4 +259,46 @@ impl Default for BallAudioSystem {
     fn default() -> Self {
         Self::new()
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn near_simultaneous_triggers_share_a_grid_step() {
+        // 1/16th notes at 120 BPM are 125 ms apart
+        let first = 1.01;
+        let second = 1.06;
+        assert_eq!(first + quantize_delay(first, 120.0), 1.125);
+        assert_eq!(second + quantize_delay(second, 120.0), 1.125);
+        assert_eq!(quantize_delay(1.125, 120.0), 0.0);
+    }
+
+    #[test]
+    fn humanize_swings_both_ways_around_a_fixed_delay() {
+        let audio = BallAudioSystem::new();
+        let mut ball = Ball::new(0, 0, "ball1".to_string());
+        assert_eq!(audio.humanize(&ball).1, 0);
+
+        ball.set_humanize(1.0);
+        crate::rng::reseed(7);
+        let delays: Vec<i32> = (0..200).map(|_| audio.humanize(&ball).1).collect();
+        let max_ms = HUMANIZE_MAX_MS as i32;
+        assert!(delays.iter().all(|delay| (0..=2 * max_ms).contains(delay)));
+        let early = delays.iter().filter(|&&delay| delay < max_ms).count();
+        let late = delays.iter().filter(|&&delay| delay > max_ms).count();
+        assert!(early > 60 && late > 60, "{} early, {} late", early, late);
+    }
+
+    #[test]
+    fn humanize_delay_adds_to_the_quantize_delay() {
+        let mut audio = BallAudioSystem::new();
+        audio.quantize = true;
+        audio.advance_clock(1.085, 120.0); // 40 ms before the next step
+        assert_eq!(audio.trigger_offset_ms(5), 45);
+        assert_eq!(audio.trigger_offset_ms(35), 75);
+
+        audio.quantize = false;
+        assert_eq!(audio.trigger_offset_ms(5), 5);
+    }
+}
@@ -10,6 +10,7 @@ pub enum ContextMenuState {
     BallRelativeSpeed { ball_index: usize, selected_ball: usize, speed_ratio: f32, category: RatioCategory },
     BallCustomRatio { ball_index: usize, selected_ball: usize, numerator: u32, denominator: u32 },
     BallColor { ball_index: usize, selected_option: usize },
+    BallName { ball_index: usize }, // Text is held in ContextMenu::name_input
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -25,13 +26,25 @@ pub struct ContextMenu {
     left_key_held_time: f32,
     right_key_held_time: f32,
     last_update: Option<Instant>,
+    name_input: String,
 }
 
-const BALL_MENU_OPTIONS: &[&str] = &["Direction", "Speed", "Relative Speed", "Sample", "Color"];
+const BALL_MENU_OPTIONS: &[&str] = &["Direction", "Speed", "Relative Speed", "Sample", "Color", "Volume", "Pitch", "Name", "Copy Config", "Paste Config"];
+const VOLUME_OPTION: usize = 5;
+const PITCH_OPTION: usize = 6;
+const NAME_OPTION: usize = 7;
+const MAX_NAME_LENGTH: usize = 12;
 const DIRECTION_OPTIONS: &[&str] = &["Up", "Down", "Left", "Right", "Up-Left", "Up-Right", "Down-Left", "Down-Right"];
 const MIN_SPEED: f32 = 0.5;
 const MAX_SPEED: f32 = 10.0;
 const SPEED_STEP: f32 = 0.1;
+// Ranges the Volume/Pitch rows step through with Left/Right
+const MENU_MIN_VOLUME: f32 = 0.0;
+const MENU_MAX_VOLUME: f32 = 1.0;
+const VOLUME_STEP: f32 = 0.05;
+const MENU_MIN_PITCH: f32 = 0.5;
+const MENU_MAX_PITCH: f32 = 2.0;
+const PITCH_STEP: f32 = 0.05;
 
 const COLOR_OPTIONS: &[&str] = &["Red", "Green", "Blue", "Yellow", "Cyan", "Magenta", "White", "Orange"];
 
@@ -94,6 +107,7 @@ impl ContextMenu {
             left_key_held_time: 0.0,
             right_key_held_time: 0.0,
             last_update: None,
+            name_input: String::new(),
         }
     }
 
@@ -117,6 +131,15 @@ impl ContextMenu {
         !matches!(self.state, ContextMenuState::None)
     }
 
+    // Typed characters arrive as ReceivedCharacter events, routed here while naming a ball
+    pub fn insert_name_character(&mut self, ch: char) {
+        if let ContextMenuState::BallName { .. } = self.state {
+            if (ch.is_ascii_alphanumeric() || ch == '_') && self.name_input.len() < MAX_NAME_LENGTH {
+                self.name_input.push(ch);
+            }
+        }
+    }
+
     pub fn update(&mut self, delta_time: f32) {
         self.last_update = Some(Instant::now());
     }
@@ -144,6 +167,28 @@ impl ContextMenu {
                     self.state = ContextMenuState::BallMenu { ball_index, selected_option: new_option };
                     return None;
                 }
+                // Volume and Pitch change in place so the ball can be auditioned while tuning
+                let step = match (input.key_pressed(VirtualKeyCode::Left), input.key_pressed(VirtualKeyCode::Right)) {
+                    (true, false) => -1.0,
+                    (false, true) => 1.0,
+                    _ => 0.0,
+                };
+                if step != 0.0 {
+                    if let Some(ball) = balls.get(ball_index) {
+                        match selected_option {
+                            VOLUME_OPTION => {
+                                let volume = (ball.volume + step * VOLUME_STEP).clamp(MENU_MIN_VOLUME, MENU_MAX_VOLUME);
+                                return Some(ContextMenuAction::SetVolume { ball_index, volume });
+                            }
+                            PITCH_OPTION => {
+                                let pitch = (ball.pitch + step * PITCH_STEP).clamp(MENU_MIN_PITCH, MENU_MAX_PITCH);
+                                return Some(ContextMenuAction::SetPitch { ball_index, pitch });
+                            }
+                            _ => {}
+                        }
+                    }
+                    return None;
+                }
                 if input.key_pressed(VirtualKeyCode::Return) {
                     // Handle Enter key for Sample option
                     if selected_option == 3 {
@@ -187,6 +232,18 @@ impl ContextMenu {
                             return Some(ContextMenuAction::OpenFileDialog { ball_index });
                         },
                         4 => self.state = ContextMenuState::BallColor { ball_index, selected_option: 0 },
+                        NAME_OPTION => {
+                            self.name_input = balls.get(ball_index).and_then(|b| b.name.clone()).unwrap_or_default();
+                            self.state = ContextMenuState::BallName { ball_index };
+                        }
+                        8 => {
+                            self.close();
+                            return Some(ContextMenuAction::CopyBallConfig { ball_index });
+                        }
+                        9 => {
+                            self.close();
+                            return Some(ContextMenuAction::PasteBallConfig { ball_index });
+                        }
                         _ => {}
                     }
                     return None;
@@ -528,6 +585,23 @@ impl ContextMenu {
                 }
                 None
             }
+            ContextMenuState::BallName { ball_index } => {
+                if input.key_pressed(VirtualKeyCode::Escape) {
+                    self.state = ContextMenuState::BallMenu { ball_index, selected_option: NAME_OPTION };
+                    return None;
+                }
+                if input.key_pressed(VirtualKeyCode::Back) {
+                    self.name_input.pop();
+                    return None;
+                }
+                if input.key_pressed(VirtualKeyCode::Return) {
+                    // An empty name clears it, falling back to the ball's id
+                    let name = if self.name_input.is_empty() { None } else { Some(self.name_input.clone()) };
+                    self.state = ContextMenuState::BallMenu { ball_index, selected_option: NAME_OPTION };
+                    return Some(ContextMenuAction::SetName { ball_index, name });
+                }
+                None
+            }
             ContextMenuState::None => None,
         }
     }
@@ -570,6 +644,12 @@ impl ContextMenu {
                     draw_color_menu(frame, ball_x, ball_y, selected_option);
                 }
             }
+            ContextMenuState::BallName { ball_index } => {
+                if let Some(ball) = balls.get(ball_index) {
+                    let (ball_x, ball_y) = ball.get_grid_position();
+                    draw_name_menu(frame, ball_x, ball_y, &self.name_input, ball);
+                }
+            }
             ContextMenuState::None => {}
         }
     }
@@ -581,27 +661,28 @@ pub enum ContextMenuAction {
     SetSpeed { ball_index: usize, speed: f32 },
     SetSample { ball_index: usize, sample: String },
     SetColor { ball_index: usize, color: String },
+    SetName { ball_index: usize, name: Option<String> },
+    SetVolume { ball_index: usize, volume: f32 },
+    SetPitch { ball_index: usize, pitch: f32 },
     OpenFileDialog { ball_index: usize },
     AddSampleToLibrary { ball_index: usize },
     OpenAudioPlayer { ball_index: usize },
+    CopyBallConfig { ball_index: usize },
+    PasteBallConfig { ball_index: usize },
 }
 
 // Import types from modules
 use crate::ball::{Ball, Direction};
 use crate::font;
-
-// Constants for drawing
-const CELL_SIZE: usize = 40;
-const WINDOW_WIDTH: usize = 640;
-const WINDOW_HEIGHT: usize = 480;
+use crate::sequencer::{CELL_SIZE, grid_area_height, window_width};
 
 fn draw_menu_background(frame: &mut [u8], x: usize, y: usize, width: usize, height: usize) {
     for dy in 0..height {
         for dx in 0..width {
             let px = x + dx;
             let py = y + dy;
-            if px < WINDOW_WIDTH && py < WINDOW_HEIGHT {
-                let idx = (py * WINDOW_WIDTH + px) * 4;
+            if px < window_width() && py < grid_area_height() {
+                let idx = (py * window_width() + px) * 4;
                 frame[idx] = 40;     // R
                 frame[idx + 1] = 40; // G
                 frame[idx + 2] = 40; // B
@@ -616,9 +697,9 @@ fn draw_menu_border(frame: &mut [u8], x: usize, y: usize, width: usize, height:
         for dx in 0..width {
             let px = x + dx;
             let py = y + dy;
-            if px < WINDOW_WIDTH && py < WINDOW_HEIGHT {
+            if px < window_width() && py < grid_area_height() {
                 if dx == 0 || dx == width - 1 || dy == 0 || dy == height - 1 {
-                    let idx = (py * WINDOW_WIDTH + px) * 4;
+                    let idx = (py * window_width() + px) * 4;
                     frame[idx] = 255;     // R
                     frame[idx + 1] = 255; // G
                     frame[idx + 2] = 255; // B
@@ -630,43 +711,43 @@ fn draw_menu_border(frame: &mut [u8], x: usize, y: usize, width: usize, height:
 }
 
 fn draw_text(frame: &mut [u8], text: &str, x: usize, y: usize, color: [u8; 3], selected: bool) {
-    font::draw_text(frame, text, x, y, color, selected, WINDOW_WIDTH);
+    font::draw_text(frame, text, x, y, color, selected, window_width());
 }
 
 
 
 fn draw_ball_menu(frame: &mut [u8], ball_x: usize, ball_y: usize, selected_option: usize, ball: &Ball, ball_index: usize) {
     let menu_width = CELL_SIZE * 6; // Increased width to accommodate sample names
-    let menu_height = CELL_SIZE * 4; // Increased height to accommodate ball info
+    let menu_height = CELL_SIZE * 6 + 10; // Increased height to accommodate ball info
     
     // Position menu to the right of the ball, but keep it on screen
     let mut menu_x = ball_x * CELL_SIZE + CELL_SIZE;
     let mut menu_y = ball_y * CELL_SIZE;
     
     // Adjust if menu would go off screen
-    if menu_x + menu_width > WINDOW_WIDTH {
+    if menu_x + menu_width > window_width() {
         if ball_x * CELL_SIZE >= menu_width {
             menu_x = (ball_x * CELL_SIZE).saturating_sub(menu_width);
         } else {
             menu_x = 0;
         }
     }
-    if menu_y + menu_height > WINDOW_HEIGHT {
-        menu_y = WINDOW_HEIGHT - menu_height;
+    if menu_y + menu_height > grid_area_height() {
+        menu_y = grid_area_height() - menu_height;
     }
     
     draw_menu_background(frame, menu_x, menu_y, menu_width, menu_height);
     draw_menu_border(frame, menu_x, menu_y, menu_width, menu_height);
     
     // Draw ball name and color at the top
-    let ball_info = format!("{} ({})", ball.id, ball.color);
+    let ball_info = format!("{} ({})", ball.label(), ball.color);
     draw_text(frame, &ball_info, menu_x + 5, menu_y + 5, [255, 255, 255], false);
     
     // Draw separator line
     let separator_y = menu_y + 25;
     for x in (menu_x + 5)..(menu_x + menu_width - 5) {
-        if x < WINDOW_WIDTH && separator_y < WINDOW_HEIGHT {
-            let idx = (separator_y * WINDOW_WIDTH + x) * 4;
+        if x < window_width() && separator_y < grid_area_height() {
+            let idx = (separator_y * window_width() + x) * 4;
             if idx + 3 < frame.len() {
                 frame[idx] = 150;     // R
                 frame[idx + 1] = 150; // G
@@ -695,6 +776,10 @@ fn draw_ball_menu(frame: &mut [u8], ball_x: usize, ball_y: usize, selected_optio
                 "Sample".to_string()
             };
             draw_text(frame, &display_text, text_x, text_y, [200, 200, 200], is_selected);
+        } else if i == VOLUME_OPTION {
+            draw_text(frame, &format!("Volume < {:.2} >", ball.volume), text_x, text_y, [200, 200, 200], is_selected);
+        } else if i == PITCH_OPTION {
+            draw_text(frame, &format!("Pitch < {:.2} >", ball.pitch), text_x, text_y, [200, 200, 200], is_selected);
         } else {
             draw_text(frame, option, text_x, text_y, [200, 200, 200], is_selected);
         }
@@ -710,15 +795,15 @@ fn draw_direction_menu(frame: &mut [u8], ball_x: usize, ball_y: usize, selected_
     let mut menu_y = ball_y * CELL_SIZE;
     
     // Adjust if menu would go off screen
-    if menu_x + menu_width > WINDOW_WIDTH {
+    if menu_x + menu_width > window_width() {
         if ball_x * CELL_SIZE >= menu_width {
             menu_x = (ball_x * CELL_SIZE).saturating_sub(menu_width);
         } else {
             menu_x = 0;
         }
     }
-    if menu_y + menu_height > WINDOW_HEIGHT {
-        menu_y = WINDOW_HEIGHT - menu_height;
+    if menu_y + menu_height > grid_area_height() {
+        menu_y = grid_area_height() - menu_height;
     }
     
     draw_menu_background(frame, menu_x, menu_y, menu_width, menu_height);
@@ -742,11 +827,11 @@ fn draw_enhanced_speed_menu_with_reference(frame: &mut [u8], ball_x: usize, ball
     let mut menu_y = ball_y * CELL_SIZE;
     
     // Adjust if menu would go off screen
-    if menu_x + menu_width > WINDOW_WIDTH {
+    if menu_x + menu_width > window_width() {
         menu_x = (ball_x * CELL_SIZE).saturating_sub(menu_width);
     }
-    if menu_y + menu_height > WINDOW_HEIGHT {
-        menu_y = WINDOW_HEIGHT - menu_height;
+    if menu_y + menu_height > grid_area_height() {
+        menu_y = grid_area_height() - menu_height;
     }
     
     draw_menu_background(frame, menu_x, menu_y, menu_width, menu_height);
@@ -764,11 +849,11 @@ fn draw_enhanced_speed_menu_with_reference(frame: &mut [u8], ball_x: usize, ball
     let ball_radius = 8;
     for y in ball_display_y.saturating_sub(ball_radius)..ball_display_y + ball_radius {
         for x in ball_display_x.saturating_sub(ball_radius)..ball_display_x + ball_radius {
-            if x < WINDOW_WIDTH && y < WINDOW_HEIGHT {
+            if x < window_width() && y < grid_area_height() {
                 let dx = x as i32 - ball_display_x as i32;
                 let dy = y as i32 - ball_display_y as i32;
                 if dx * dx + dy * dy <= (ball_radius as i32) * (ball_radius as i32) {
-                    let index = (y * WINDOW_WIDTH + x) * 4;
+                    let index = (y * window_width() + x) * 4;
                     if index + 2 < frame.len() {
                         frame[index] = ball_color[0];
                         frame[index + 1] = ball_color[1];
@@ -799,11 +884,11 @@ fn draw_enhanced_speed_menu_with_reference(frame: &mut [u8], ball_x: usize, ball
             
             for y in ref_ball_y.saturating_sub(6)..ref_ball_y + 6 {
                 for x in ref_ball_x.saturating_sub(6)..ref_ball_x + 6 {
-                    if x < WINDOW_WIDTH && y < WINDOW_HEIGHT {
+                    if x < window_width() && y < grid_area_height() {
                         let dx = x as i32 - ref_ball_x as i32;
                         let dy = y as i32 - ref_ball_y as i32;
                         if dx * dx + dy * dy <= 36 {
-                            let index = (y * WINDOW_WIDTH + x) * 4;
+                            let index = (y * window_width() + x) * 4;
                             if index + 2 < frame.len() {
                                 frame[index] = ref_ball_color[0];
                                 frame[index + 1] = ref_ball_color[1];
@@ -843,8 +928,8 @@ fn draw_enhanced_speed_menu_with_reference(frame: &mut [u8], ball_x: usize, ball
     // Draw slider track
     for y in slider_y..slider_y + slider_height {
         for x in slider_x..slider_x + slider_width {
-            if x < WINDOW_WIDTH && y < WINDOW_HEIGHT {
-                let index = (y * WINDOW_WIDTH + x) * 4;
+            if x < window_width() && y < grid_area_height() {
+                let index = (y * window_width() + x) * 4;
                 if index + 2 < frame.len() {
                     frame[index] = 60;
                     frame[index + 1] = 60;
@@ -865,11 +950,11 @@ fn draw_enhanced_speed_menu_with_reference(frame: &mut [u8], ball_x: usize, ball
     // Draw handle with ball color
     for y in handle_center_y.saturating_sub(handle_radius)..handle_center_y + handle_radius {
         for x in handle_center_x.saturating_sub(handle_radius)..handle_center_x + handle_radius {
-            if x < WINDOW_WIDTH && y < WINDOW_HEIGHT {
+            if x < window_width() && y < grid_area_height() {
                 let dx = x as i32 - handle_center_x as i32;
                 let dy = y as i32 - handle_center_y as i32;
                 if dx * dx + dy * dy <= (handle_radius as i32) * (handle_radius as i32) {
-                    let index = (y * WINDOW_WIDTH + x) * 4;
+                    let index = (y * window_width() + x) * 4;
                     if index + 2 < frame.len() {
                         if dx * dx + dy * dy <= 25 {
                             frame[index] = ball_color[0];
@@ -901,11 +986,11 @@ fn draw_color_menu(frame: &mut [u8], ball_x: usize, ball_y: usize, selected_opti
     let mut menu_y = ball_y * CELL_SIZE;
     
     // Adjust if menu would go off screen
-    if menu_x + menu_width > WINDOW_WIDTH {
+    if menu_x + menu_width > window_width() {
         menu_x = (ball_x * CELL_SIZE).saturating_sub(menu_width);
     }
-    if menu_y + menu_height > WINDOW_HEIGHT {
-        menu_y = WINDOW_HEIGHT - menu_height;
+    if menu_y + menu_height > grid_area_height() {
+        menu_y = grid_area_height() - menu_height;
     }
     
     draw_menu_background(frame, menu_x, menu_y, menu_width, menu_height);
@@ -927,8 +1012,8 @@ fn draw_color_menu(frame: &mut [u8], ball_x: usize, ball_y: usize, selected_opti
             for dx in 0..preview_size {
                 let px = preview_x + dx;
                 let py = preview_y + dy;
-                if px < WINDOW_WIDTH && py < WINDOW_HEIGHT {
-                    let idx = (py * WINDOW_WIDTH + px) * 4;
+                if px < window_width() && py < grid_area_height() {
+                    let idx = (py * window_width() + px) * 4;
                     frame[idx] = color_preview[0];     // R
                     frame[idx + 1] = color_preview[1]; // G
                     frame[idx + 2] = color_preview[2]; // B
@@ -941,6 +1026,30 @@ fn draw_color_menu(frame: &mut [u8], ball_x: usize, ball_y: usize, selected_opti
     }
 }
 
+fn draw_name_menu(frame: &mut [u8], ball_x: usize, ball_y: usize, name_input: &str, ball: &Ball) {
+    let menu_width = CELL_SIZE * 5;
+    let menu_height = 75;
+    
+    // Position menu to the right of the ball, but keep it on screen
+    let mut menu_x = ball_x * CELL_SIZE + CELL_SIZE;
+    let mut menu_y = ball_y * CELL_SIZE;
+    
+    // Adjust if menu would go off screen
+    if menu_x + menu_width > window_width() {
+        menu_x = (ball_x * CELL_SIZE).saturating_sub(menu_width);
+    }
+    if menu_y + menu_height > grid_area_height() {
+        menu_y = grid_area_height() - menu_height;
+    }
+    
+    draw_menu_background(frame, menu_x, menu_y, menu_width, menu_height);
+    draw_menu_border(frame, menu_x, menu_y, menu_width, menu_height);
+    
+    draw_text(frame, &format!("Name for {}:", ball.id), menu_x + 5, menu_y + 5, [255, 255, 255], false);
+    draw_text(frame, &format!("{}_", name_input), menu_x + 5, menu_y + 28, [255, 255, 100], false);
+    draw_text(frame, "Enter: save  Esc: back", menu_x + 5, menu_y + 51, [150, 150, 150], false);
+}
+
 fn draw_relative_speed_menu(frame: &mut [u8], ball_x: usize, ball_y: usize, selected_ball: usize, speed_ratio: f32, balls: &[Ball]) {
     let menu_width = 250;
     let menu_height = 120;
@@ -950,11 +1059,11 @@ fn draw_relative_speed_menu(frame: &mut [u8], ball_x: usize, ball_y: usize, sele
     let mut menu_y = ball_y * CELL_SIZE;
     
     // Adjust if menu would go off screen
-    if menu_x + menu_width > WINDOW_WIDTH {
+    if menu_x + menu_width > window_width() {
         menu_x = (ball_x * CELL_SIZE).saturating_sub(menu_width);
     }
-    if menu_y + menu_height > WINDOW_HEIGHT {
-        menu_y = WINDOW_HEIGHT - menu_height;
+    if menu_y + menu_height > grid_area_height() {
+        menu_y = grid_area_height() - menu_height;
     }
     
     draw_menu_background(frame, menu_x, menu_y, menu_width, menu_height);
@@ -1002,13 +1111,13 @@ fn draw_small_ball(frame: &mut [u8], x: usize, y: usize, color: [u8; 3]) {
             let px = x + dx;
             let py = y + dy;
             
-            if px < WINDOW_WIDTH && py < WINDOW_HEIGHT {
+            if px < window_width() && py < grid_area_height() {
                 let dist_x = px as f32 - center_x;
                 let dist_y = py as f32 - center_y;
                 let distance_sq = dist_x * dist_x + dist_y * dist_y;
                 
                 if distance_sq <= (radius as f32) * (radius as f32) {
-                    let idx = (py * WINDOW_WIDTH + px) * 4;
+                    let idx = (py * window_width() + px) * 4;
                     if idx + 3 < frame.len() {
                         frame[idx] = color[0];     // R
                         frame[idx + 1] = color[1]; // G
@@ -1030,11 +1139,11 @@ fn draw_enhanced_relative_speed_menu(frame: &mut [u8], ball_x: usize, ball_y: us
     let mut menu_y = ball_y * CELL_SIZE;
     
     // Adjust if menu would go off screen
-    if menu_x + menu_width > WINDOW_WIDTH {
+    if menu_x + menu_width > window_width() {
         menu_x = (ball_x * CELL_SIZE).saturating_sub(menu_width);
     }
-    if menu_y + menu_height > WINDOW_HEIGHT {
-        menu_y = WINDOW_HEIGHT - menu_height;
+    if menu_y + menu_height > grid_area_height() {
+        menu_y = grid_area_height() - menu_height;
     }
     
     draw_menu_background(frame, menu_x, menu_y, menu_width, menu_height);
@@ -1109,11 +1218,11 @@ fn draw_custom_ratio_menu(frame: &mut [u8], ball_x: usize, ball_y: usize, select
     let mut menu_y = ball_y * CELL_SIZE;
     
     // Adjust if menu would go off screen
-    if menu_x + menu_width > WINDOW_WIDTH {
+    if menu_x + menu_width > window_width() {
         menu_x = (ball_x * CELL_SIZE).saturating_sub(menu_width);
     }
-    if menu_y + menu_height > WINDOW_HEIGHT {
-        menu_y = WINDOW_HEIGHT - menu_height;
+    if menu_y + menu_height > grid_area_height() {
+        menu_y = grid_area_height() - menu_height;
     }
     
     draw_menu_background(frame, menu_x, menu_y, menu_width, menu_height);
@@ -1169,6 +1278,9 @@ fn draw_custom_ratio_menu(frame: &mut [u8], ball_x: usize, ball_y: usize, select
 }
 
 fn get_color_rgb(color_name: &str) -> [u8; 3] {
+    if let Some(rgb) = crate::renderer::Renderer::parse_hex_color(color_name) {
+        return rgb;
+    }
     match color_name {
         "Red" => [255, 0, 0],
         "Green" => [0, 255, 0],
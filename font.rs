@@ -534,6 +534,10 @@ impl Font {
     }
     
     /// Draw a single character at the specified position
+    pub fn has_char(&self, ch: char) -> bool {
+        self.patterns.contains_key(&ch)
+    }
+    
     pub fn draw_char(&self, frame: &mut [u8], ch: char, x: usize, y: usize, color: [u8; 3], window_width: usize) {
         let pattern = self.patterns.get(&ch).unwrap_or(&[
             0b00000000, 0b00000000, 0b00000000, 0b00000000,
@@ -590,7 +594,7 @@ impl Font {
     
     /// Draw text with syntax highlighting (for program editor)
     pub fn draw_syntax_highlighted_text(&self, frame: &mut [u8], text: &str, x: usize, y: usize, window_width: usize) {
-        let keywords = ["def", "if", "then", "and", "set", "create", "with", "end", "hits", "times"];
+        let keywords = ["def", "if", "then", "else", "and", "set", "create", "destroy", "with", "end", "return", "var", "hits", "times", "loop"];
         let colors = [
             "red", "green", "blue", "yellow", "cyan", "magenta", "white", "gray", "orange", "purple"
         ];
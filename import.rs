@@ -0,0 +1,132 @@
+// Importer for simple step-sequencer patterns (tracks × steps) in JSON:
+//
+// {
+//   "bpm": 120,
+//   "tracks": [
+//     { "name": "kick", "sample": "samples/kick.wav", "steps": [1, 0, 0, 0, 1, 0, 0, 0] },
+//     { "name": "hat", "sample": "samples/hat.wav", "steps": "..x...x...x...x." }
+//   ]
+// }
+//
+// Each track becomes a row: a ball starts in column 0 moving right at one cell per
+// 16th note, carrying the track's sample, and every active step is a square that
+// lets the ball pass while sounding it. Steps are given as an array of 0/1/booleans
+// or a string where 'x' (or '1') marks an active step.
+
+use crate::ball::{Ball, Direction};
+use crate::sequencer::{beats_to_speed, empty_cells, grid_height, grid_width, DEFAULT_BPM, MAX_BPM, MIN_BPM};
+use crate::square::{Cell, Instruction, Program};
+use serde_json::Value as JsonValue;
+
+const TRACK_COLORS: &[&str] = &["Red", "Blue", "Green", "Yellow", "Cyan", "Magenta", "Orange", "White"];
+const SQUARE_COLORS: &[[u8; 3]] = &[
+    [255, 100, 100], [100, 100, 255], [100, 255, 100], [255, 255, 100],
+    [100, 255, 255], [255, 100, 255], [255, 180, 80], [220, 220, 220],
+];
+
+pub struct StepImport {
+    pub cells: Vec<Vec<Cell>>,
+    pub balls: Vec<Ball>,
+    pub warnings: Vec<String>,
+    pub bpm: f32,
+}
+
+pub fn import_steps(json: &str) -> Result<StepImport, String> {
+    let root: JsonValue = serde_json::from_str(json).map_err(|e| format!("Invalid JSON: {}", e))?;
+
+    let bpm = match root.get("bpm") {
+        Some(bpm) => bpm.as_f64().filter(|bpm| *bpm > 0.0).ok_or("'bpm' must be a positive number")? as f32,
+        None => DEFAULT_BPM,
+    }.clamp(MIN_BPM, MAX_BPM);
+    let tracks = root.get("tracks")
+        .and_then(|tracks| tracks.as_array())
+        .ok_or("Missing 'tracks' array")?;
+    if tracks.is_empty() {
+        return Err("Pattern has no tracks".to_string());
+    }
+
+    let mut cells = empty_cells();
+    let max_steps = grid_width() - 1; // Column 0 holds the ball
+    let mut balls = Vec::new();
+    let mut warnings = Vec::new();
+
+    if tracks.len() > grid_height() {
+        warnings.push(format!("Only the first {} of {} tracks fit on the grid", grid_height(), tracks.len()));
+    }
+
+    // One cell per 16th note
+    let speed = beats_to_speed(0.25, bpm);
+
+    for (row, track) in tracks.iter().take(grid_height()).enumerate() {
+        let name = track.get("name").and_then(|n| n.as_str()).unwrap_or("track").to_string();
+        let steps = parse_steps(track.get("steps"))
+            .map_err(|e| format!("Track '{}': {}", name, e))?;
+        if steps.len() > max_steps {
+            warnings.push(format!("Track '{}': only the first {} of {} steps fit on the grid", name, max_steps, steps.len()));
+        }
+
+        for (step, _) in steps.iter().take(max_steps).enumerate().filter(|(_, active)| **active) {
+            let cell = &mut cells[row][step + 1];
+            cell.place_square(Some(SQUARE_COLORS[row % SQUARE_COLORS.len()]));
+            cell.program.load_programs(vec![Program {
+                instructions: vec![Instruction::Pass],
+                name: format!("{}_step", name),
+                source_text: Some(vec![format!("def {}_step", name), "pass".to_string()]),
+                parameters: Vec::new(),
+            }]);
+            cell.display_text = Some((step + 1).to_string());
+        }
+
+        let mut ball = Ball::new(0, row, format!("ball{}", row + 1));
+        ball.set_direction(Direction::Right);
+        ball.set_speed(speed);
+        ball.set_color(TRACK_COLORS[row % TRACK_COLORS.len()].to_string());
+        match track.get("sample").and_then(|s| s.as_str()) {
+            Some(sample) => ball.set_sample(sample.to_string()),
+            None => warnings.push(format!("Track '{}' has no sample", name)),
+        }
+        balls.push(ball);
+    }
+
+    Ok(StepImport { cells, balls, warnings, bpm })
+}
+
+fn parse_steps(steps: Option<&JsonValue>) -> Result<Vec<bool>, String> {
+    match steps {
+        Some(JsonValue::String(pattern)) => Ok(pattern.chars().map(|c| matches!(c, 'x' | 'X' | '1')).collect()),
+        Some(JsonValue::Array(values)) => values.iter()
+            .map(|value| match value {
+                JsonValue::Bool(active) => Ok(*active),
+                JsonValue::Number(n) => Ok(n.as_f64().unwrap_or(0.0) != 0.0),
+                other => Err(format!("invalid step value {}", other)),
+            })
+            .collect(),
+        Some(_) => Err("'steps' must be an array or a string".to_string()),
+        None => Err("missing 'steps'".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn active_steps_run_the_pass_program() {
+        let import = import_steps(r#"{ "tracks": [{ "name": "kick", "sample": "kick.wav", "steps": "x.x" }] }"#).unwrap();
+        for step in [1, 3] {
+            let program = &import.cells[0][step].program;
+            assert_eq!(program.programs.len(), 1);
+            let active = &program.programs[program.active_program.unwrap()];
+            assert_eq!(active.name, "kick_step");
+            assert_eq!(active.instructions, vec![Instruction::Pass]);
+        }
+        assert!(!import.cells[0][2].is_square());
+    }
+
+    #[test]
+    fn tempo_outside_the_supported_range_is_clamped_before_timing_the_balls() {
+        let import = import_steps(r#"{ "bpm": 1000, "tracks": [{ "name": "kick", "sample": "kick.wav", "steps": "x" }] }"#).unwrap();
+        assert_eq!(import.bpm, MAX_BPM);
+        assert_eq!(import.balls[0].speed, beats_to_speed(0.25, MAX_BPM));
+    }
+}
@@ -0,0 +1,44 @@
+// Auto-repeat for held navigation keys. WinitInputHelper::key_pressed only fires
+// on the initial press, so holding an arrow key would otherwise move just once.
+
+use std::time::{Duration, Instant};
+use winit::event::VirtualKeyCode;
+use winit_input_helper::WinitInputHelper;
+
+#[derive(Clone, Debug)]
+pub struct KeyRepeat {
+    delay: Duration, // Hold time before repeating starts
+    rate: Duration,  // Interval between repeats
+    // Only the most recently pressed key repeats: (key, pressed at, last fired)
+    active: Option<(VirtualKeyCode, Instant, Instant)>,
+}
+
+impl KeyRepeat {
+    pub fn new(delay: Duration, rate: Duration) -> Self {
+        Self { delay, rate, active: None }
+    }
+
+    /// True on the initial press, then every `rate` once `key` has been held for `delay`
+    pub fn fired(&mut self, input: &WinitInputHelper, key: VirtualKeyCode) -> bool {
+        let now = Instant::now();
+        if input.key_pressed(key) {
+            self.active = Some((key, now, now));
+            return true;
+        }
+
+        match &mut self.active {
+            Some((active_key, pressed_at, last_fired)) if *active_key == key => {
+                if !input.key_held(key) {
+                    self.active = None;
+                    return false;
+                }
+                if now.duration_since(*pressed_at) >= self.delay && now.duration_since(*last_fired) >= self.rate {
+                    *last_fired = now;
+                    return true;
+                }
+                false
+            }
+            _ => false,
+        }
+    }
+}
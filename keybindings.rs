@@ -0,0 +1,76 @@
+// Central registry of keyboard controls and console commands.
+// The startup printout and the in-app help overlay both read from here,
+// so new controls only need to be listed once.
+
+pub struct KeyBinding {
+    pub keys: &'static str,
+    pub description: &'static str,
+}
+
+pub const KEYBINDINGS: &[KeyBinding] = &[
+    KeyBinding { keys: "Arrow keys", description: "Move cursor (hold to repeat)" },
+    KeyBinding { keys: "Left click", description: "Move cursor to a cell; click again to place a square" },
+    KeyBinding { keys: "Right click", description: "Ball/square menu for the cell under the pointer" },
+    KeyBinding { keys: "S", description: "Place square / edit label of existing square" },
+    KeyBinding { keys: "Shift+S", description: "Start a rectangle; S fills it with squares, Shift+Delete clears it" },
+    KeyBinding { keys: "C", description: "Place ball (starts inactive)" },
+    KeyBinding { keys: "P", description: "Start balls (saves state) / reset to saved" },
+    KeyBinding { keys: "Delete/Backspace", description: "Clear cell under cursor" },
+    KeyBinding { keys: "Space", description: "Context menu for ball/square, else library" },
+    KeyBinding { keys: "R", description: "Square programming menu" },
+    KeyBinding { keys: "G", description: "Open library browser" },
+    KeyBinding { keys: "L", description: "List libraries in console" },
+    KeyBinding { keys: "F1", description: "List library functions" },
+    KeyBinding { keys: "F2", description: "List library samples" },
+    KeyBinding { keys: "F3", description: "Clear auto-generated library" },
+    KeyBinding { keys: "`", description: "Console prompt: type a command, Enter runs it, Up/Down recall, ESC closes" },
+    KeyBinding { keys: "F4", description: "Toggle antialiased ball/square rendering" },
+    KeyBinding { keys: "A", description: "Audition the sample of the ball under the cursor" },
+    KeyBinding { keys: "PageUp / PageDown", description: "Trim the volume of the ball under the cursor, else scroll the console" },
+    KeyBinding { keys: "F5", description: "Toggle grid mini-map" },
+    KeyBinding { keys: "F6", description: "Toggle trigger timeline in the console area" },
+    KeyBinding { keys: "F7", description: "Toggle stats panel (balls, voices, hits/s, frame time)" },
+    KeyBinding { keys: "Ctrl+Z / Ctrl+Y", description: "Undo/redo in the program editor" },
+    KeyBinding { keys: "Ctrl+S / Ctrl+O", description: "Save/load the whole project (grid, balls, libraries)" },
+    KeyBinding { keys: "Ctrl+C / Ctrl+V", description: "Copy the square under the cursor (with its programs) / paste it" },
+    KeyBinding { keys: "? / F12", description: "Toggle this help overlay" },
+    KeyBinding { keys: "ESC", description: "Close/go back in menus" },
+];
+
+pub const CONSOLE_COMMANDS: &[KeyBinding] = &[
+    KeyBinding { keys: "lib list", description: "List all libraries" },
+    KeyBinding { keys: "lib functions [library]", description: "List functions" },
+    KeyBinding { keys: "lib samples [library]", description: "List samples" },
+    KeyBinding { keys: "lib clear auto", description: "Clear auto-generated library" },
+    KeyBinding { keys: "lib enable|disable <name>", description: "Hide a library from listings and searches, or bring it back" },
+    KeyBinding { keys: "lib import-folder [path]", description: "Import a folder as a sample library" },
+    KeyBinding { keys: "lib mutate <lib> <fn> [seed]", description: "Save a randomly tweaked copy of a function" },
+    KeyBinding { keys: "glyph <char> | glyph off", description: "Mark the ball or square under the cursor with a symbol" },
+    KeyBinding { keys: "maxvoices <n>", description: "Active samples before triggers are skipped" },
+    KeyBinding { keys: "import steps [file]", description: "Load a JSON step pattern as rows of balls/squares" },
+    KeyBinding { keys: "import scene [file]", description: "Place the squares and programs of a .cant scene" },
+    KeyBinding { keys: "offset <ms>", description: "Shift sample triggers for latency (negative = earlier)" },
+    KeyBinding { keys: "bpm <value>", description: "Set the tempo (scales every ball's speed)" },
+    KeyBinding { keys: "volume <0.0-1.0>", description: "Set the master volume" },
+    KeyBinding { keys: "mix <channel> <0-2>", description: "Set a channel's volume" },
+    KeyBinding { keys: "autostart on [seconds]|off", description: "Start the balls by themselves after loading a scene or pattern" },
+    KeyBinding { keys: "edges precise|cell", description: "Hit squares at the exact edge crossing (steadier timing for fast balls)" },
+    KeyBinding { keys: "limiter on|off", description: "Soft-clip the master output (on by default)" },
+    KeyBinding { keys: "limiter ceiling <dB>", description: "Set the limiter ceiling (-24 to 0 dBFS, default -1)" },
+    KeyBinding { keys: "automation record|play|off", description: "Record live volume/mix/bpm changes and replay them" },
+    KeyBinding { keys: "automation clear", description: "Forget recorded automation" },
+    KeyBinding { keys: "seed <n> | seed random", description: "Seed used for every take's randomness" },
+    KeyBinding { keys: "theme <name> | theme reload", description: "Switch to dark/light/high-contrast, or reload theme.toml" },
+    KeyBinding { keys: "scale <root> <type>", description: "Snap played pitches to a scale (e.g. C major)" },
+    KeyBinding { keys: "scale off", description: "Play pitches unquantized" },
+    KeyBinding { keys: "mute|unmute <channel>", description: "Silence a mixer channel (Drums, Bass, Melody) or bring it back" },
+    KeyBinding { keys: "solo <channel>", description: "Hear only soloed channels; 'unsolo <channel>|all' to undo" },
+    KeyBinding { keys: "filter <channel> <hz>", description: "Lowpass a mixer channel (0 = off)" },
+    KeyBinding { keys: "record start [file.wav]", description: "Record the master output to a WAV file" },
+    KeyBinding { keys: "record stop", description: "Stop recording and finish the WAV file" },
+    KeyBinding { keys: "metronome on|off", description: "Click on every beat at the current BPM (accented downbeat)" },
+    KeyBinding { keys: "autopan on|off", description: "Pan balls without 'set pan' by their column (left to right)" },
+    KeyBinding { keys: "quantize on|off", description: "Delay ball triggers to the next 1/16th note at the current BPM" },
+    KeyBinding { keys: "samples normalize on <db>", description: "Peak-normalize imported samples" },
+    KeyBinding { keys: "samples normalize off", description: "Import samples unchanged" },
+];
@@ -1,6 +1,15 @@
 use std::collections::HashMap;
-use crate::square::{Program, Instruction, Expression, Value, FunctionLibrary, SampleLibrary, SampleTemplate, LibraryManager};
-use crate::ball::Direction;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use crate::square::{Program, Instruction, Expression, Value, DestroyTarget, FunctionLibrary, SampleLibrary, SampleTemplate, LibraryManager};
+use crate::ball::{Direction, BURST_DIRECTIONS};
+
+const MUTATION_RATE: f64 = 0.35; // Chance that any one literal changes
+const MUTATION_ATTEMPTS: usize = 8; // Re-rolls before giving up on a program that never changed
+// Rebuilt from the grid as squares and samples are used, so edits there wouldn't stick
+const READ_ONLY_LIBRARIES: &[&str] = &["auto"];
+// Built-in containers that stay around even once all their items are deleted
+const PERMANENT_LIBRARIES: &[&str] = &["auto", "lib"];
 
 /// Library builder for creating function libraries programmatically
 pub struct LibraryBuilder {
@@ -29,6 +38,7 @@ impl LibraryBuilder {
             name: name.to_string(),
             instructions,
             source_text: None, // Library functions don't preserve original text
+            parameters: Vec::new(),
         };
         self.functions.insert(name.to_string(), program);
         self
@@ -81,6 +91,7 @@ impl LibraryBuilder {
             name: self.name,
             functions: self.functions,
             description: self.description,
+            enabled: true,
         }
     }
 }
@@ -147,6 +158,7 @@ impl SampleLibraryBuilder {
             name: self.name,
             samples: self.samples,
             description: self.description,
+            enabled: true,
         }
     }
 }
@@ -204,6 +216,268 @@ impl LibraryManagerExt for LibraryManager {
     }
 }
 
+/// Generative variants of library functions
+impl LibraryManager {
+    /// Clone a library function and randomly perturb its literals: numbers drift by up to
+    /// ±20% (whole numbers by ±1), pitches move by up to two semitones and directions may
+    /// turn. The same seed always produces the same variant.
+    pub fn mutate_function(&self, library_name: &str, function_name: &str, seed: u64) -> Result<Program, String> {
+        let original = self.get_function(library_name, function_name)
+            .ok_or_else(|| format!("Function {}.{} not found", library_name, function_name))?;
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        for _ in 0..MUTATION_ATTEMPTS {
+            let mut instructions = original.instructions.clone();
+            let mut mutations = 0;
+            mutate_instructions(&mut instructions, &mut rng, &mut mutations);
+            if mutations > 0 {
+                return Ok(Program {
+                    instructions,
+                    name: original.name.clone(),
+                    source_text: None, // The original text no longer matches
+                    parameters: original.parameters.clone(),
+                });
+            }
+        }
+        Err(format!("{}.{} has no numbers or directions to mutate", library_name, function_name))
+    }
+
+    pub fn add_function_to_library(&mut self, library_name: &str, program: Program) -> Result<(), String> {
+        let library = self.function_libraries.get_mut(library_name)
+            .ok_or_else(|| format!("Library '{}' not found", library_name))?;
+        library.functions.insert(program.name.clone(), program);
+        Ok(())
+    }
+
+    /// Move a function to a new name within its library. The `def` line of its source
+    /// follows so the editor shows the new name. Refuses to overwrite an existing function.
+    pub fn rename_function(&mut self, library_name: &str, old_name: &str, new_name: &str) -> Result<(), String> {
+        let library = self.editable_function_library(library_name)?;
+        check_new_name(old_name, new_name, |name| library.functions.contains_key(name), library_name)?;
+        let mut program = library.functions.remove(old_name)
+            .ok_or_else(|| format!("Function {}.{} not found", library_name, old_name))?;
+        program.name = new_name.to_string();
+        if let Some(def_line) = program.source_text.as_mut().and_then(|lines| lines.first_mut()) {
+            if let Some(rest) = def_line.trim_start().strip_prefix("def ").and_then(|rest| rest.strip_prefix(old_name)) {
+                *def_line = format!("def {}{}", new_name, rest);
+            }
+        }
+        library.functions.insert(new_name.to_string(), program);
+        Ok(())
+    }
+
+    pub fn rename_sample(&mut self, library_name: &str, old_name: &str, new_name: &str) -> Result<(), String> {
+        let library = self.editable_sample_library(library_name)?;
+        check_new_name(old_name, new_name, |name| library.samples.contains_key(name), library_name)?;
+        let mut template = library.samples.remove(old_name)
+            .ok_or_else(|| format!("Sample {}.{} not found", library_name, old_name))?;
+        template.name = new_name.to_string();
+        library.samples.insert(new_name.to_string(), template);
+        Ok(())
+    }
+
+    /// Remove a function; a user library left empty is removed with it
+    pub fn delete_function(&mut self, library_name: &str, function_name: &str) -> Result<(), String> {
+        let library = self.function_libraries.get_mut(library_name)
+            .ok_or_else(|| format!("Library '{}' not found", library_name))?;
+        library.functions.remove(function_name)
+            .ok_or_else(|| format!("Function {}.{} not found", library_name, function_name))?;
+        if library.functions.is_empty() && !PERMANENT_LIBRARIES.contains(&library_name) {
+            self.function_libraries.remove(library_name);
+        }
+        Ok(())
+    }
+
+    pub fn delete_sample(&mut self, library_name: &str, sample_name: &str) -> Result<(), String> {
+        let library = self.sample_libraries.get_mut(library_name)
+            .ok_or_else(|| format!("Library '{}' not found", library_name))?;
+        library.samples.remove(sample_name)
+            .ok_or_else(|| format!("Sample {}.{} not found", library_name, sample_name))?;
+        if library.samples.is_empty() && !PERMANENT_LIBRARIES.contains(&library_name) {
+            self.sample_libraries.remove(library_name);
+        }
+        Ok(())
+    }
+
+    fn editable_function_library(&mut self, library_name: &str) -> Result<&mut FunctionLibrary, String> {
+        if READ_ONLY_LIBRARIES.contains(&library_name) {
+            return Err(format!("Library '{}' is generated automatically and can't be edited", library_name));
+        }
+        self.function_libraries.get_mut(library_name)
+            .ok_or_else(|| format!("Library '{}' not found", library_name))
+    }
+
+    fn editable_sample_library(&mut self, library_name: &str) -> Result<&mut SampleLibrary, String> {
+        if READ_ONLY_LIBRARIES.contains(&library_name) {
+            return Err(format!("Library '{}' is generated automatically and can't be edited", library_name));
+        }
+        self.sample_libraries.get_mut(library_name)
+            .ok_or_else(|| format!("Library '{}' not found", library_name))
+    }
+
+    /// Warn about `lib.fn` calls to functions no library defines and `return <name>` targets
+    /// that aren't among `square_functions`. Both are still resolved when the program runs,
+    /// so these are only hints for typos.
+    pub fn unresolved_reference_warnings(&self, programs: &[Program], square_functions: &[String]) -> Vec<String> {
+        let mut warnings = Vec::new();
+        for program in programs {
+            self.collect_unresolved(&program.name, &program.instructions, square_functions, &mut warnings);
+        }
+        warnings
+    }
+
+    fn collect_unresolved(&self, function: &str, instructions: &[Instruction], square_functions: &[String], warnings: &mut Vec<String>) {
+        for instruction in instructions {
+            match instruction {
+                Instruction::ExecuteLibraryFunction { library_function, .. } => {
+                    let found = library_function.split_once('.')
+                        .and_then(|(library, name)| self.get_function(library, name))
+                        .is_some();
+                    if !found {
+                        warnings.push(format!("Warning: {}: no library function '{}' (yet)", function, library_function));
+                    }
+                }
+                Instruction::Return(Some(target)) if !square_functions.contains(target) => {
+                    warnings.push(format!("Warning: {}: 'return {}' names no function on this square", function, target));
+                }
+                Instruction::If { then_block, else_block, .. } => {
+                    self.collect_unresolved(function, then_block, square_functions, warnings);
+                    if let Some(else_block) = else_block {
+                        self.collect_unresolved(function, else_block, square_functions, warnings);
+                    }
+                }
+                Instruction::Loop { body, .. } | Instruction::RepeatAnd { body, .. } | Instruction::RepeatThen { body, .. } => {
+                    self.collect_unresolved(function, body, square_functions, warnings);
+                }
+                Instruction::ExecuteProgram(program) => {
+                    self.collect_unresolved(function, &program.instructions, square_functions, warnings);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+// A rename target must be a usable name that isn't taken in the library
+fn check_new_name(old_name: &str, new_name: &str, exists: impl Fn(&str) -> bool, library_name: &str) -> Result<(), String> {
+    if new_name.is_empty() || new_name.contains(char::is_whitespace) || new_name.contains('.') {
+        return Err(format!("'{}' is not a valid name", new_name));
+    }
+    if new_name != old_name && exists(new_name) {
+        return Err(format!("'{}' already exists in library '{}'", new_name, library_name));
+    }
+    Ok(())
+}
+
+fn mutate_instructions(instructions: &mut [Instruction], rng: &mut StdRng, mutations: &mut usize) {
+    for instruction in instructions.iter_mut() {
+        match instruction {
+            // Pitches are multipliers, so move them musically rather than by percent
+            Instruction::SetPitch(Expression::Literal(Value::Number(pitch))) => {
+                if rng.gen_bool(MUTATION_RATE) {
+                    let semitones = [-2.0, -1.0, 1.0, 2.0][rng.gen_range(0..4)];
+                    *pitch *= 2f32.powf(semitones / 12.0);
+                    *mutations += 1;
+                }
+            }
+            Instruction::SetSpeed(value) | Instruction::SetDirection(value) | Instruction::SetPitch(value)
+            | Instruction::SetSpeedBeats(value) | Instruction::SetPitchStep(value) | Instruction::SetHumanize(value) | Instruction::SetVolume(value)
+            | Instruction::SetColor(value) | Instruction::SetSquareColor(value) | Instruction::PlaySample(value) | Instruction::SetActiveProgram(value)
+            | Instruction::Print(value) | Instruction::SetBpm { value, .. } | Instruction::SetVariable { value, .. }
+            | Instruction::SetGlobalVariable { value, .. } | Instruction::SetReverse { speed: value, .. }
+            | Instruction::SpawnBurst { count: value } => mutate_expression(value, rng, mutations),
+            Instruction::If { condition, then_block, else_block } => {
+                mutate_expression(condition, rng, mutations);
+                mutate_instructions(then_block, rng, mutations);
+                if let Some(else_block) = else_block {
+                    mutate_instructions(else_block, rng, mutations);
+                }
+            }
+            Instruction::Loop { count, body } | Instruction::RepeatAnd { count, body } | Instruction::RepeatThen { count, body } => {
+                mutate_expression(count, rng, mutations);
+                mutate_instructions(body, rng, mutations);
+            }
+            Instruction::ExecuteProgram(program) => mutate_instructions(&mut program.instructions, rng, mutations),
+            Instruction::ExecuteLibraryFunction { args, .. } => {
+                for arg in args.iter_mut() {
+                    mutate_expression(arg, rng, mutations);
+                }
+            }
+            Instruction::SpawnBall { x, y, speed, direction } | Instruction::CreateBall { x, y, speed, direction } => {
+                for value in [x, y, speed, direction] {
+                    mutate_expression(value, rng, mutations);
+                }
+            }
+            Instruction::SetSpeedGlide { target, duration_ms } => {
+                mutate_expression(target, rng, mutations);
+                mutate_expression(duration_ms, rng, mutations);
+            }
+            Instruction::CreateBallRandom { speed, direction } => {
+                mutate_expression(speed, rng, mutations);
+                mutate_expression(direction, rng, mutations);
+            }
+            Instruction::CreateSquareWithProgram { x, y, program } => {
+                mutate_expression(x, rng, mutations);
+                mutate_expression(y, rng, mutations);
+                mutate_instructions(&mut program.instructions, rng, mutations);
+            }
+            Instruction::ArmSquare { x, y, program } => {
+                for value in [x, y, program] {
+                    mutate_expression(value, rng, mutations);
+                }
+            }
+            Instruction::CreateSquare { x, y } | Instruction::ResetSquare { x, y }
+            | Instruction::CreateBallFromSample { x, y, .. } | Instruction::CreateSquareFromSample { x, y, .. }
+            | Instruction::CreateBallWithLibrary { x, y, .. } | Instruction::CreateSquareWithLibrary { x, y, .. }
+            | Instruction::DestroyBall { target: DestroyTarget::Coordinates { x, y } }
+            | Instruction::DestroySquare { target: DestroyTarget::Coordinates { x, y } } => {
+                mutate_expression(x, rng, mutations);
+                mutate_expression(y, rng, mutations);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn mutate_expression(expression: &mut Expression, rng: &mut StdRng, mutations: &mut usize) {
+    match expression {
+        Expression::Literal(Value::Number(number)) => {
+            if rng.gen_bool(MUTATION_RATE) {
+                *number = mutate_number(*number, rng);
+                *mutations += 1;
+            }
+        }
+        Expression::Literal(Value::Direction(direction)) => {
+            if rng.gen_bool(MUTATION_RATE) {
+                let others: Vec<Direction> = BURST_DIRECTIONS.iter().copied().filter(|d| d != direction).collect();
+                *direction = others[rng.gen_range(0..others.len())];
+                *mutations += 1;
+            }
+        }
+        Expression::BinaryOp { left, right, .. } => {
+            mutate_expression(left, rng, mutations);
+            mutate_expression(right, rng, mutations);
+        }
+        Expression::Call { args, .. } => {
+            for arg in args {
+                mutate_expression(arg, rng, mutations);
+            }
+        }
+        _ => {}
+    }
+}
+
+// Whole numbers (counts, coordinates, indices) step by one without turning negative;
+// everything else scales by up to ±20%
+fn mutate_number(number: f32, rng: &mut StdRng) -> f32 {
+    if number.fract() == 0.0 {
+        let stepped = if rng.gen_bool(0.5) { number + 1.0 } else { number - 1.0 };
+        if number >= 0.0 && stepped < 0.0 { number + 1.0 } else { stepped }
+    } else {
+        number * (1.0 + rng.gen_range(-0.2..=0.2))
+    }
+}
+
 /// Example usage and helper functions
 pub mod examples {
     use super::*;
@@ -245,4 +519,69 @@ pub mod examples {
             .add_sample("crash", 4.0, Direction::Left, "Orange", Some("speed_boost"))
             .build()
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manager() -> LibraryManager {
+        let mut manager = LibraryManager::new();
+        manager.create_default_programmatic_libraries();
+        manager
+    }
+
+    #[test]
+    fn renamed_function_is_found_under_its_new_name_only() {
+        let mut manager = manager();
+        manager.rename_function("lib", "speed_boost", "turbo").unwrap();
+
+        assert!(manager.get_function("lib", "speed_boost").is_none());
+        let program = manager.get_function("lib", "turbo").unwrap();
+        assert_eq!(program.name, "turbo");
+        assert!(matches!(program.instructions.as_slice(), [Instruction::SetSpeed(_)]));
+    }
+
+    #[test]
+    fn rename_onto_an_existing_function_is_refused() {
+        let mut manager = manager();
+        assert!(manager.rename_function("lib", "speed_boost", "bounce").is_err());
+        assert!(manager.get_function("lib", "speed_boost").is_some());
+        assert!(matches!(manager.get_function("lib", "bounce").unwrap().instructions.as_slice(), [Instruction::Bounce]));
+    }
+
+    #[test]
+    fn deleting_the_last_function_removes_a_user_library() {
+        let mut manager = manager();
+        manager.add_function_library(LibraryBuilder::new("mine").add_bounce_function("a").add_bounce_function("b").build());
+
+        manager.delete_function("mine", "a").unwrap();
+        assert!(manager.get_function("mine", "a").is_none());
+        assert!(manager.get_function("mine", "b").is_some());
+
+        manager.delete_function("mine", "b").unwrap();
+        assert!(!manager.function_libraries.contains_key("mine"));
+        assert!(manager.delete_function("mine", "b").is_err());
+    }
+
+    #[test]
+    fn deleting_every_function_keeps_the_builtin_library() {
+        let mut manager = manager();
+        let names: Vec<String> = manager.function_libraries["lib"].functions.keys().cloned().collect();
+        for name in names {
+            manager.delete_function("lib", &name).unwrap();
+        }
+        assert!(manager.function_libraries["lib"].functions.is_empty());
+    }
+
+    #[test]
+    fn deleting_the_last_sample_removes_a_user_library() {
+        let mut manager = manager();
+        manager.delete_sample("default", "red_bouncer").unwrap();
+        assert!(manager.get_sample_template("default", "red_bouncer").is_none());
+        assert!(manager.get_sample_template("default", "blue_speedster").is_some());
+
+        manager.delete_sample("default", "blue_speedster").unwrap();
+        manager.delete_sample("default", "green_cycler").unwrap();
+        assert!(!manager.sample_libraries.contains_key("default"));
+    }
+}
@@ -3,7 +3,8 @@ use winit_input_helper::WinitInputHelper;
 use crate::square::{LibraryManager, Program, Cell};
 use crate::program_editor::{ProgramEditor, ProgramEditorAction};
 use crate::font;
-use std::time::{Duration, Instant};
+use crate::key_repeat::KeyRepeat;
+use std::time::Duration;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum ProgramSource {
@@ -51,7 +52,7 @@ pub enum LibraryColumn {
 
 #[derive(Debug, Clone)]
 pub enum EditingMode {
-    RenameItem { original_name: String, new_name: String },
+    RenameItem { library_name: String, original_name: String, new_name: String, is_sample: bool },
     CreateProgram { name: String, editor: ProgramEditor },
     EditProgram { name: String, source: ProgramSource, editor: ProgramEditor },
 }
@@ -60,8 +61,8 @@ pub enum EditingMode {
 impl PartialEq for EditingMode {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
-            (EditingMode::RenameItem { original_name: a1, new_name: b1 }, 
-             EditingMode::RenameItem { original_name: a2, new_name: b2 }) => a1 == a2 && b1 == b2,
+            (EditingMode::RenameItem { library_name: l1, original_name: a1, new_name: b1, .. }, 
+             EditingMode::RenameItem { library_name: l2, original_name: a2, new_name: b2, .. }) => l1 == l2 && a1 == a2 && b1 == b2,
             (EditingMode::CreateProgram { name: n1, .. }, 
              EditingMode::CreateProgram { name: n2, .. }) => n1 == n2,
             (EditingMode::EditProgram { name: n1, source: s1, .. }, 
@@ -84,6 +85,7 @@ pub enum LibraryGuiAction {
     LoadProgramFromFile,
     OpenAudioPlayer { library_name: String, sample_name: String },
     LoadProgramToSquare { program: Program, square_x: usize, square_y: usize },
+    ToggleLibrary { library_name: String },
 }
 
 const LIBRARY_GUI_WIDTH: usize = 580;
@@ -92,25 +94,26 @@ const COLUMN_WIDTH: usize = 280;
 const ITEM_HEIGHT: usize = 22;
 const HEADER_HEIGHT: usize = 40;
 const MAX_VISIBLE_ITEMS: usize = 16;
+const MAX_ITEM_NAME_LENGTH: usize = 24;
 
 pub struct LibraryGui {
     pub state: LibraryGuiState,
-    last_key_repeat: Option<Instant>,
-    key_repeat_delay: Duration,
-    key_repeat_rate: Duration,
+    key_repeat: KeyRepeat,
+    // Library, name and kind of the item a first Delete press armed; a second press removes it
+    pending_delete: Option<(String, String, bool)>,
 }
 
 impl LibraryGui {
     pub fn new() -> Self {
         Self {
             state: LibraryGuiState::Hidden,
-            last_key_repeat: None,
-            key_repeat_delay: Duration::from_millis(500),
-            key_repeat_rate: Duration::from_millis(100), // Slower repeat rate to prevent double deletion
+            key_repeat: KeyRepeat::new(Duration::from_millis(400), Duration::from_millis(80)),
+            pending_delete: None,
         }
     }
 
     pub fn toggle(&mut self) {
+        self.pending_delete = None;
         self.state = match self.state {
             LibraryGuiState::Hidden => LibraryGuiState::Visible {
                 selected_column: LibraryColumn::Samples,
@@ -150,6 +153,15 @@ impl LibraryGui {
         }
     }
 
+    // Typed characters arrive as ReceivedCharacter events, routed here while renaming an item
+    pub fn insert_name_character(&mut self, ch: char) {
+        if let LibraryGuiState::Visible { editing_mode: Some(EditingMode::RenameItem { new_name, .. }), .. } = &mut self.state {
+            if (ch.is_ascii_alphanumeric() || ch == '_' || ch == '-') && new_name.len() < MAX_ITEM_NAME_LENGTH {
+                new_name.push(ch);
+            }
+        }
+    }
+
     pub fn get_current_editor_mut(&mut self) -> Option<&mut ProgramEditor> {
         if let LibraryGuiState::Visible { editing_mode: Some(ref mut edit_mode), .. } = &mut self.state {
             match edit_mode {
@@ -162,7 +174,7 @@ impl LibraryGui {
         }
     }
 
-    pub fn handle_input(&mut self, input: &WinitInputHelper, library_manager: &LibraryManager, grid: &[[Cell; crate::sequencer::GRID_WIDTH]; crate::sequencer::GRID_HEIGHT]) -> Option<LibraryGuiAction> {
+    pub fn handle_input(&mut self, input: &WinitInputHelper, library_manager: &LibraryManager, grid: &[Vec<Cell>]) -> Option<LibraryGuiAction> {
         // Extract state to avoid borrowing conflicts
         let (mut selected_column, mut selected_library, mut selected_item, mut scroll_offset, mut editing_mode, mut target_square) = 
             if let LibraryGuiState::Visible { 
@@ -185,7 +197,7 @@ impl LibraryGui {
             
         // Handle editing mode input ONLY if we're actually in editing mode
         if let Some(ref mut edit_mode) = editing_mode {
-            let result = self.handle_editing_input(input, edit_mode, &selected_library);
+            let result = self.handle_editing_input(input, edit_mode);
             // Update state - but don't overwrite editing_mode if it was set to None by handle_editing_input
             if let LibraryGuiState::Visible { editing_mode: ref current_editing_mode, .. } = &self.state {
                 if current_editing_mode.is_none() {
@@ -241,8 +253,8 @@ impl LibraryGui {
             }
         }
 
-        // Navigation within column
-        if input.key_pressed(VirtualKeyCode::Up) {
+        // Navigation within column (repeats while held, for long lists)
+        if self.should_handle_key_repeat(input, VirtualKeyCode::Up) {
             if selected_item > 0 {
                 selected_item -= 1;
                 if selected_item < scroll_offset {
@@ -251,7 +263,7 @@ impl LibraryGui {
             }
         }
 
-        if input.key_pressed(VirtualKeyCode::Down) {
+        if self.should_handle_key_repeat(input, VirtualKeyCode::Down) {
             let max_items = self.get_item_count(library_manager, &selected_column, &selected_library, grid);
             if selected_item + 1 < max_items {
                 selected_item += 1;
@@ -263,22 +275,34 @@ impl LibraryGui {
 
         // Actions
         if input.key_pressed(VirtualKeyCode::F2) { // Rename
-            if let Some(item_name) = self.get_selected_item_name(library_manager, &selected_column, &selected_library, selected_item, grid) {
+            if let Some((library_name, item_name, is_sample)) = self.get_selected_library_item(library_manager, &selected_column, &selected_library, selected_item, grid) {
                 editing_mode = Some(EditingMode::RenameItem {
+                    library_name,
                     original_name: item_name.clone(),
                     new_name: item_name,
+                    is_sample,
                 });
             }
         }
 
         let mut result = None;
-        if input.key_pressed(VirtualKeyCode::Delete) { // Delete
-            if let Some(item_name) = self.get_selected_item_name(library_manager, &selected_column, &selected_library, selected_item, grid) {
-                result = Some(LibraryGuiAction::DeleteItem {
-                    library_name: selected_library.clone(),
-                    item_name,
-                    is_sample: matches!(selected_column, LibraryColumn::Samples),
-                });
+        if input.key_pressed(VirtualKeyCode::F3) { // Enable/disable the selected library
+            result = Some(LibraryGuiAction::ToggleLibrary { library_name: selected_library.clone() });
+        }
+        // Delete asks for a second press on the same item; moving the selection disarms it
+        let delete_pressed = input.key_pressed(VirtualKeyCode::Delete);
+        if self.pending_delete.is_some() || delete_pressed {
+            let selected_entry = self.get_selected_library_item(library_manager, &selected_column, &selected_library, selected_item, grid);
+            if self.pending_delete.is_some() && self.pending_delete != selected_entry {
+                self.pending_delete = None;
+            }
+            if delete_pressed {
+                match self.pending_delete.take() {
+                    Some((library_name, item_name, is_sample)) => {
+                        result = Some(LibraryGuiAction::DeleteItem { library_name, item_name, is_sample });
+                    }
+                    None => self.pending_delete = selected_entry,
+                }
             }
         }
 
@@ -406,16 +430,19 @@ impl LibraryGui {
         result
     }
 
-    fn handle_editing_input(&mut self, input: &WinitInputHelper, edit_mode: &mut EditingMode, selected_library: &str) -> Option<LibraryGuiAction> {
+    fn handle_editing_input(&mut self, input: &WinitInputHelper, edit_mode: &mut EditingMode) -> Option<LibraryGuiAction> {
         match edit_mode {
-            EditingMode::RenameItem { original_name, new_name } => {
-                // Handle text input for renaming
+            EditingMode::RenameItem { library_name, original_name, new_name, is_sample } => {
+                // Typed characters arrive through insert_name_character
+                if input.key_pressed(VirtualKeyCode::Back) {
+                    new_name.pop();
+                }
                 if input.key_pressed(VirtualKeyCode::Return) {
                     let action = Some(LibraryGuiAction::RenameItem {
-                        library_name: selected_library.to_string(),
+                        library_name: library_name.clone(),
                         old_name: original_name.clone(),
                         new_name: new_name.clone(),
-                        is_sample: true, // This would need to be determined based on context
+                        is_sample: *is_sample,
                     });
                     if let LibraryGuiState::Visible { editing_mode, .. } = &mut self.state {
                         *editing_mode = None;
@@ -427,7 +454,6 @@ impl LibraryGui {
                         *editing_mode = None;
                     }
                 }
-                // TODO: Handle character input for editing the name
             },
             EditingMode::CreateProgram { name, editor } => {
                 match editor.handle_input_with_context(input, true) {
@@ -536,37 +562,10 @@ impl LibraryGui {
 
     
     fn should_handle_key_repeat(&mut self, input: &WinitInputHelper, key: VirtualKeyCode) -> bool {
-        let now = Instant::now();
-        
-        // Check if key is currently pressed
-        if !input.key_held(key) {
-            // Key not held, reset timing
-            if input.key_pressed(key) {
-                self.last_key_repeat = Some(now);
-                return true; // Handle initial press
-            }
-            return false;
-        }
-        
-        // Key is held, check timing
-        match self.last_key_repeat {
-            Some(last_time) => {
-                let elapsed = now.duration_since(last_time);
-                if elapsed >= self.key_repeat_rate {
-                    self.last_key_repeat = Some(now);
-                    return true;
-                }
-                false
-            }
-            None => {
-                // First time holding, set initial delay
-                self.last_key_repeat = Some(now);
-                false
-            }
-        }
+        self.key_repeat.fired(input, key)
     }
 
-    fn get_item_count(&self, library_manager: &LibraryManager, column: &LibraryColumn, library_name: &str, grid: &[[Cell; crate::sequencer::GRID_WIDTH]; crate::sequencer::GRID_HEIGHT]) -> usize {
+    fn get_item_count(&self, library_manager: &LibraryManager, column: &LibraryColumn, library_name: &str, grid: &[Vec<Cell>]) -> usize {
         match column {
             LibraryColumn::Samples => {
                 self.collect_all_samples(library_manager, library_name).len()
@@ -577,24 +576,44 @@ impl LibraryGui {
         }
     }
 
-    fn get_selected_item_name(&self, library_manager: &LibraryManager, column: &LibraryColumn, library_name: &str, index: usize, grid: &[[Cell; crate::sequencer::GRID_WIDTH]; crate::sequencer::GRID_HEIGHT]) -> Option<String> {
+    // Keep the highlight on an existing row after items or whole libraries were removed
+    pub fn clamp_selection(&mut self, library_manager: &LibraryManager, grid: &[Vec<Cell>]) {
+        let (column, library) = match &self.state {
+            LibraryGuiState::Visible { selected_column, selected_library, .. } => (selected_column.clone(), selected_library.clone()),
+            LibraryGuiState::Hidden => return,
+        };
+        let library = if library_manager.sample_libraries.contains_key(&library) || library_manager.function_libraries.contains_key(&library) {
+            library
+        } else {
+            "lib".to_string()
+        };
+        let item_count = self.get_item_count(library_manager, &column, &library, grid);
+        if let LibraryGuiState::Visible { selected_library, selected_item, scroll_offset, .. } = &mut self.state {
+            *selected_library = library;
+            *selected_item = (*selected_item).min(item_count.saturating_sub(1));
+            *scroll_offset = (*scroll_offset).min(*selected_item);
+        }
+    }
+
+    // The library, bare name and kind (true for samples) of the selected entry, for
+    // actions that change the library itself. Square programs aren't library items.
+    fn get_selected_library_item(&self, library_manager: &LibraryManager, column: &LibraryColumn, library_name: &str, index: usize, grid: &[Vec<Cell>]) -> Option<(String, String, bool)> {
         match column {
             LibraryColumn::Samples => {
-                let all_samples = self.collect_all_samples(library_manager, library_name);
-                all_samples.get(index).map(|entry| entry.name.clone())
+                let entry = self.collect_all_samples(library_manager, library_name).into_iter().nth(index)?;
+                let source_library = match entry.source {
+                    SampleSource::Auto => "auto".to_string(),
+                    SampleSource::Library { library_name } => library_name,
+                };
+                let name = entry.name.strip_suffix(&format!(" ({})", source_library))?.to_string();
+                Some((source_library, name, true))
             },
             LibraryColumn::Programs => {
-                let all_programs = self.collect_all_programs(library_manager, grid);
-                all_programs.get(index).map(|entry| {
-                    match &entry.source {
-                        ProgramSource::Library { library_name } => {
-                            format!("{} ({})", entry.name, library_name)
-                        },
-                        ProgramSource::Square { x, y, program_index: _ } => {
-                            format!("{} @({},{})", entry.name, x, y)
-                        }
-                    }
-                })
+                let entry = self.collect_all_programs(library_manager, grid).into_iter().nth(index)?;
+                match entry.source {
+                    ProgramSource::Library { library_name } => Some((library_name, entry.name, false)),
+                    ProgramSource::Square { .. } => None,
+                }
             },
         }
     }
@@ -627,7 +646,7 @@ impl LibraryGui {
         all_samples
     }
 
-    fn collect_all_programs(&self, library_manager: &LibraryManager, grid: &[[Cell; crate::sequencer::GRID_WIDTH]; crate::sequencer::GRID_HEIGHT]) -> Vec<ProgramEntry> {
+    fn collect_all_programs(&self, library_manager: &LibraryManager, grid: &[Vec<Cell>]) -> Vec<ProgramEntry> {
         let mut all_programs = Vec::new();
         let mut seen_names = std::collections::HashSet::new();
         
@@ -657,7 +676,7 @@ impl LibraryGui {
         }
         
         // Then, include library programs that don't conflict with square programs
-        for (lib_name, lib) in &library_manager.function_libraries {
+        for (lib_name, lib) in library_manager.function_libraries.iter().filter(|(_, lib)| lib.enabled) {
             for (prog_name, program) in &lib.functions {
                 // Skip predefined functions and auto-generated copies of square programs
                 if lib_name == "lib" && self.is_predefined_function(prog_name) {
@@ -726,6 +745,9 @@ impl LibraryGui {
             Instruction::Bounce => {
                 lines.push("bounce".to_string());
             },
+            Instruction::Reflect => {
+                lines.push("reflect".to_string());
+            },
             Instruction::Stop => {
                 lines.push("stop".to_string());
             },
@@ -745,6 +767,8 @@ impl LibraryGui {
                     crate::square::DestroyTarget::BallReference(ball_ref) => {
                         lines.push(format!("destroy square({})", ball_ref));
                     },
+                    crate::square::DestroyTarget::All => lines.push("destroy square(all)".to_string()),
+                    crate::square::DestroyTarget::Color(color) => lines.push(format!("destroy square({})", color)),
                     crate::square::DestroyTarget::Coordinates { x, y } => {
                         let x_val = self.expression_to_number(x).unwrap_or(0.0);
                         let y_val = self.expression_to_number(y).unwrap_or(0.0);
@@ -752,12 +776,27 @@ impl LibraryGui {
                     }
                 }
             },
-            Instruction::If { condition, then_block, else_block: _ } => {
+            Instruction::If { condition, then_block, else_block } => {
                 if let Some(condition_line) = self.condition_to_source(condition) {
                     lines.push(format!("if {}", condition_line));
                     for then_instruction in then_block {
                         self.instruction_to_source_lines(then_instruction, lines, 0);
                     }
+                    if let Some(else_block) = else_block {
+                        lines.push("else".to_string());
+                        for else_instruction in else_block {
+                            self.instruction_to_source_lines(else_instruction, lines, 0);
+                        }
+                    }
+                }
+            },
+            Instruction::Loop { count, body } => {
+                if let Some(count) = self.expression_to_number(count) {
+                    lines.push(format!("loop {}", count));
+                    for body_instruction in body {
+                        self.instruction_to_source_lines(body_instruction, lines, 0);
+                    }
+                    lines.push("end".to_string());
                 }
             },
             _ => {
@@ -818,7 +857,7 @@ impl LibraryGui {
         }
     }
 
-    pub fn render(&self, frame: &mut [u8], library_manager: &LibraryManager, grid: &[[Cell; crate::sequencer::GRID_WIDTH]; crate::sequencer::GRID_HEIGHT], window_width: usize, window_height: usize) {
+    pub fn render(&self, frame: &mut [u8], library_manager: &LibraryManager, grid: &[Vec<Cell>], window_width: usize, window_height: usize) {
         if let LibraryGuiState::Visible { 
             selected_column, 
             selected_library, 
@@ -836,7 +875,12 @@ impl LibraryGui {
             self.draw_background(frame, gui_x, gui_y, window_width);
 
             // Draw headers
-            self.draw_headers(frame, gui_x, gui_y, selected_column, window_width);
+            let state = if library_manager.is_library_enabled(selected_library) { "" } else { " [disabled]" };
+            let title = match &self.pending_delete {
+                Some((library_name, item_name, _)) => format!("Delete {}.{}? Press Delete again", library_name, item_name),
+                None => format!("Library Manager - {}{}", selected_library, state),
+            };
+            self.draw_headers(frame, gui_x, gui_y, &title, selected_column, window_width);
 
             // Draw sample column
             self.draw_sample_column(frame, gui_x, gui_y, library_manager, selected_library, 
@@ -946,7 +990,7 @@ impl LibraryGui {
         }
     }
 
-    fn draw_headers(&self, frame: &mut [u8], x: usize, y: usize, selected_column: &LibraryColumn, window_width: usize) {
+    fn draw_headers(&self, frame: &mut [u8], x: usize, y: usize, title: &str, selected_column: &LibraryColumn, window_width: usize) {
         // Draw header background with subtle gradient
         for dy in 0..HEADER_HEIGHT {
             for dx in 0..LIBRARY_GUI_WIDTH {
@@ -968,7 +1012,7 @@ impl LibraryGui {
         }
 
         // Draw title
-        font::draw_text(frame, "Library Manager", x + 15, y + 8, [200, 200, 255], false, window_width);
+        font::draw_text(frame, title, x + 15, y + 8, [200, 200, 255], false, window_width);
         
         // Sample header
         let sample_selected = matches!(selected_column, LibraryColumn::Samples);
@@ -1044,7 +1088,7 @@ impl LibraryGui {
     }
 
     fn draw_program_column(&self, frame: &mut [u8], x: usize, y: usize, library_manager: &LibraryManager, 
-                          grid: &[[Cell; crate::sequencer::GRID_WIDTH]; crate::sequencer::GRID_HEIGHT],
+                          grid: &[Vec<Cell>],
                           selected_library: &str, selected_column: &LibraryColumn, 
                           selected_item: usize, scroll_offset: usize, window_width: usize) {
         let start_y = y + HEADER_HEIGHT + 5;
@@ -1090,7 +1134,7 @@ impl LibraryGui {
 
     fn draw_editing_overlay(&self, frame: &mut [u8], x: usize, y: usize, edit_mode: &EditingMode, window_width: usize) {
         match edit_mode {
-            EditingMode::RenameItem { original_name: _, new_name } => {
+            EditingMode::RenameItem { new_name, .. } => {
                 let overlay_width = 350;
                 let overlay_height = 150;
                 let overlay_x = x + (LIBRARY_GUI_WIDTH - overlay_width) / 2;
@@ -1162,4 +1206,48 @@ impl LibraryGui {
     fn draw_syntax_highlighted_text(&self, frame: &mut [u8], text: &str, x: usize, y: usize, window_width: usize) {
         font::draw_syntax_highlighted_text(frame, text, x, y, window_width);
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::library::SampleLibraryBuilder;
+    use crate::ball::Direction;
+
+    fn showing_samples(library: &str, item: usize) -> LibraryGui {
+        let mut gui = LibraryGui::new();
+        gui.state = LibraryGuiState::Visible {
+            selected_column: LibraryColumn::Samples,
+            selected_library: library.to_string(),
+            selected_item: item,
+            scroll_offset: item,
+            editing_mode: None,
+            target_square: None,
+        };
+        gui
+    }
+
+    fn selection(gui: &LibraryGui) -> (String, usize, usize) {
+        match &gui.state {
+            LibraryGuiState::Visible { selected_library, selected_item, scroll_offset, .. } => (selected_library.clone(), *selected_item, *scroll_offset),
+            LibraryGuiState::Hidden => panic!("library GUI is hidden"),
+        }
+    }
+
+    #[test]
+    fn selection_follows_deleted_samples() {
+        let mut manager = LibraryManager::new();
+        manager.add_sample_library(SampleLibraryBuilder::new("kit")
+            .add_sample("kick", 1.0, Direction::Right, "Red", None)
+            .add_sample("snare", 1.0, Direction::Right, "Blue", None)
+            .build());
+        let mut gui = showing_samples("kit", 1);
+
+        manager.delete_sample("kit", "snare").unwrap();
+        gui.clamp_selection(&manager, &[]);
+        assert_eq!(selection(&gui), ("kit".to_string(), 0, 0));
+
+        manager.delete_sample("kit", "kick").unwrap();
+        gui.clamp_selection(&manager, &[]);
+        assert_eq!(selection(&gui), ("lib".to_string(), 0, 0));
+    }
+}
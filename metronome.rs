@@ -0,0 +1,74 @@
+// Click track for timing reference: a short synthesized click on every beat at the
+// grid's BPM, with beat 1 of each 4/4 bar accented an octave higher. It plays on its
+// own system channel, so it never counts toward the voice limits.
+
+use crate::audio_engine::{AudioEngine, DecodedSample};
+
+const CLICK_SAMPLE: &str = "<metronome click>"; // Cache key; never a real file
+const CLICK_MS: u32 = 25;
+const CLICK_FREQUENCY: f32 = 1500.0;
+const CLICK_VOLUME: f32 = 0.5;
+const ACCENT_PITCH: f32 = 2.0; // Voices step through samples in whole frames, so an octave is exact
+const BEATS_PER_BAR: u32 = 4;
+
+pub struct Metronome {
+    pub enabled: bool,
+    channel_id: u32,
+    next_click: f32, // Seconds until the next beat
+    beat: u32, // Beat within the bar, 0 = downbeat
+}
+
+impl Metronome {
+    pub fn new(audio_engine: &mut AudioEngine) -> Self {
+        let channel_id = audio_engine.create_system_channel("Metronome".to_string());
+        audio_engine.insert_sample(CLICK_SAMPLE, click_sample(audio_engine.sample_rate()));
+        Self {
+            enabled: false,
+            channel_id,
+            next_click: 0.0,
+            beat: 0,
+        }
+    }
+
+    // Start counting again from a downbeat, played on the next tick
+    pub fn restart(&mut self) {
+        self.next_click = 0.0;
+        self.beat = 0;
+    }
+
+    pub fn tick(&mut self, delta_time: f32, bpm: f32, audio_engine: &AudioEngine) -> Result<(), String> {
+        if !self.enabled || bpm <= 0.0 {
+            return Ok(());
+        }
+        self.next_click -= delta_time;
+        while self.next_click <= 0.0 {
+            let result = if self.beat == 0 {
+                audio_engine.play_on_channel_with_pitch(self.channel_id, CLICK_SAMPLE, ACCENT_PITCH)
+            } else {
+                audio_engine.play_on_channel(self.channel_id, CLICK_SAMPLE)
+            };
+            self.beat = (self.beat + 1) % BEATS_PER_BAR;
+            self.next_click += 60.0 / bpm;
+            result.map_err(|e| format!("Metronome click failed: {}", e))?;
+        }
+        Ok(())
+    }
+}
+
+// Exponentially decaying sine burst
+fn click_sample(sample_rate: u32) -> DecodedSample {
+    let length = (sample_rate * CLICK_MS / 1000) as usize;
+    let decay = CLICK_MS as f32 / 1000.0 / 5.0; // Seconds to fall to 1/e; silent by the end
+    let data = (0..length)
+        .map(|i| {
+            let t = i as f32 / sample_rate as f32;
+            (t * CLICK_FREQUENCY * std::f32::consts::TAU).sin() * (-t / decay).exp() * CLICK_VOLUME
+        })
+        .collect();
+    DecodedSample {
+        data,
+        sample_rate,
+        channels: 1,
+        duration_ms: CLICK_MS,
+    }
+}
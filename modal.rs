@@ -0,0 +1,86 @@
+// Tracks which overlay (menu, dialog, editor, prompt or panel) is open so input and
+// rendering are routed from one place instead of every caller checking each overlay.
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Modal {
+    ContextMenu,
+    SquareMenu,
+    LibraryGui,
+    AudioPlayer,
+    Help,
+    ConsolePrompt,
+    // Read-only panels: drawn while open but never take input
+    Stats,
+    Timeline,
+    Minimap,
+}
+
+impl Modal {
+    pub fn is_panel(self) -> bool {
+        matches!(self, Modal::Stats | Modal::Timeline | Modal::Minimap)
+    }
+
+    /// Whether the grid (and its cursor) stays visible and meaningful under this overlay
+    pub fn shows_grid(self) -> bool {
+        matches!(self, Modal::ContextMenu | Modal::ConsolePrompt)
+    }
+}
+
+#[derive(Default)]
+pub struct ModalStack {
+    stack: Vec<Modal>, // Bottom to top; only the top overlay receives input
+}
+
+impl ModalStack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The overlay that currently owns input, if any; panels never do
+    pub fn top(&self) -> Option<Modal> {
+        self.stack.iter().rev().copied().find(|modal| !modal.is_panel())
+    }
+
+    pub fn is_open(&self, modal: Modal) -> bool {
+        self.stack.contains(&modal)
+    }
+
+    /// Put an overlay on top; one that is already open stays where it is
+    pub fn open(&mut self, modal: Modal) {
+        if !self.is_open(modal) {
+            self.stack.push(modal);
+        }
+    }
+
+    pub fn close(&mut self, modal: Modal) {
+        self.stack.retain(|open| *open != modal);
+    }
+
+    /// Open or close an overlay, returning whether it is now open
+    pub fn toggle(&mut self, modal: Modal) -> bool {
+        if self.is_open(modal) {
+            self.close(modal);
+            false
+        } else {
+            self.open(modal);
+            true
+        }
+    }
+
+    /// Open overlays in draw order (bottom first)
+    pub fn iter(&self) -> impl Iterator<Item = Modal> + '_ {
+        self.stack.iter().copied()
+    }
+
+    /// Reconcile with the open state of overlays that track it themselves: those that
+    /// closed are dropped, and newly opened ones are placed on top. Overlays not listed
+    /// are opened and closed through the stack only and are left alone.
+    pub fn sync(&mut self, open_states: &[(Modal, bool)]) {
+        self.stack.retain(|modal| open_states.iter().all(|(m, open)| m != modal || *open));
+        for &(modal, open) in open_states {
+            if open && !self.stack.contains(&modal) {
+                self.stack.push(modal);
+            }
+        }
+    }
+}
@@ -0,0 +1,64 @@
+// Samples waiting to be decoded. A large import is worked through a few samples per
+// frame, within a time budget, so the window keeps drawing a progress bar instead of
+// freezing until every file is loaded.
+
+use crate::audio_engine::AudioEngine;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+pub struct PreloadQueue {
+    pending: VecDeque<String>,
+    total: usize, // Samples queued since the queue was last empty
+    started: Option<Instant>,
+}
+
+impl PreloadQueue {
+    pub fn new() -> Self {
+        Self {
+            pending: VecDeque::new(),
+            total: 0,
+            started: None,
+        }
+    }
+
+    pub fn push(&mut self, path: String) {
+        if self.pending.is_empty() {
+            self.total = 0;
+            self.started = Some(Instant::now());
+        }
+        self.pending.push_back(path);
+        self.total += 1;
+    }
+
+    // (loaded, total) while samples are still pending
+    pub fn progress(&self) -> Option<(usize, usize)> {
+        if self.pending.is_empty() {
+            None
+        } else {
+            Some((self.total - self.pending.len(), self.total))
+        }
+    }
+
+    // Preload samples until `budget` is used up, always at least one so progress is
+    // made on slow files. Returns messages for the console.
+    pub fn process(&mut self, audio_engine: &AudioEngine, budget: Duration) -> Vec<String> {
+        let mut messages = Vec::new();
+        if self.pending.is_empty() {
+            return messages;
+        }
+        let frame_start = Instant::now();
+        while let Some(path) = self.pending.pop_front() {
+            if let Err(e) = audio_engine.preload_sample(&path) {
+                messages.push(format!("Warning: Failed to preload sample {}: {}", path, e));
+            }
+            if frame_start.elapsed() >= budget {
+                break;
+            }
+        }
+        if self.pending.is_empty() {
+            let elapsed = self.started.take().map(|started| started.elapsed()).unwrap_or_default();
+            messages.push(format!("Preloaded {} samples in {} ms", self.total, elapsed.as_millis()));
+        }
+        messages
+    }
+}
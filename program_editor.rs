@@ -1,9 +1,11 @@
 use winit::event::VirtualKeyCode;
 use crate::square::Program;
 use crate::programmer::SimpleProgramParser;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 use clipboard::{ClipboardProvider, ClipboardContext};
 use crate::font;
+use crate::sequencer::{grid_area_height, window_width};
+use crate::key_repeat::KeyRepeat;
 
 #[derive(Clone, Debug)]
 pub struct ProgramEditor {
@@ -13,9 +15,33 @@ pub struct ProgramEditor {
     pub parser: SimpleProgramParser,
     pub scroll_offset: usize,
     // Key repeat timing
-    last_key_repeat: Option<Instant>,
-    key_repeat_delay: Duration,
-    key_repeat_rate: Duration,
+    key_repeat: KeyRepeat,
+    // Edit history for Ctrl+Z / Ctrl+Y
+    undo_stack: Vec<EditSnapshot>,
+    redo_stack: Vec<EditSnapshot>,
+    last_edit: Option<EditKind>,
+    // Result of parsing the text after its last change: the failing line (0-based,
+    // when the error names one) and the message; drawing reads these every frame
+    error_line: Option<usize>,
+    error_message: Option<String>,
+    diagnostics_stale: bool,
+}
+
+const MAX_UNDO_HISTORY: usize = 100;
+
+#[derive(Clone, Debug)]
+struct EditSnapshot {
+    program_text: Vec<String>,
+    cursor_line: usize,
+    cursor_col: usize,
+}
+
+// Consecutive edits of the same kind share one undo step, so undo removes a
+// typed word rather than a single character
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum EditKind {
+    Typing,
+    Deleting,
 }
 
 #[derive(Debug)]
@@ -41,9 +67,13 @@ impl ProgramEditor {
             cursor_col: "def my_program".len(), // Position cursor at end of first line
             parser: SimpleProgramParser::new(),
             scroll_offset: 0,
-            last_key_repeat: None,
-            key_repeat_delay: Duration::from_millis(500),
-            key_repeat_rate: Duration::from_millis(100), // Slower to prevent double deletions
+            key_repeat: KeyRepeat::new(Duration::from_millis(500), Duration::from_millis(100)), // Slower to prevent double deletions
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            last_edit: None,
+            error_line: None,
+            error_message: None,
+            diagnostics_stale: true,
         }
     }
 
@@ -54,6 +84,7 @@ impl ProgramEditor {
         if !text.is_empty() {
             editor.cursor_col = text[0].len();
         }
+        editor.refresh_diagnostics();
         editor
     }
 
@@ -67,9 +98,13 @@ impl ProgramEditor {
             cursor_col: "def my_program".len(), // Position cursor at end of first line
             parser: SimpleProgramParser::new(),
             scroll_offset: 0,
-            last_key_repeat: None,
-            key_repeat_delay: Duration::from_millis(500),
-            key_repeat_rate: Duration::from_millis(100),
+            key_repeat: KeyRepeat::new(Duration::from_millis(500), Duration::from_millis(100)),
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            last_edit: None,
+            error_line: None,
+            error_message: None,
+            diagnostics_stale: true,
         }
     }
 
@@ -80,9 +115,13 @@ impl ProgramEditor {
             cursor_col: 0,
             parser: SimpleProgramParser::new(),
             scroll_offset: 0,
-            last_key_repeat: None,
-            key_repeat_delay: Duration::from_millis(500),
-            key_repeat_rate: Duration::from_millis(100),
+            key_repeat: KeyRepeat::new(Duration::from_millis(500), Duration::from_millis(100)),
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            last_edit: None,
+            error_line: None,
+            error_message: None,
+            diagnostics_stale: true,
         }
     }
 
@@ -98,7 +137,7 @@ impl ProgramEditor {
                 // This allows users to save work-in-progress code
                 let program_name = if let Some(first_line) = self.program_text.first() {
                     if first_line.starts_with("def ") {
-                        first_line.strip_prefix("def ").unwrap_or("my_program").trim().to_string()
+                        first_line.strip_prefix("def ").unwrap_or("my_program").split('(').next().unwrap_or("my_program").trim().to_string()
                     } else {
                         "my_program".to_string()
                     }
@@ -110,6 +149,7 @@ impl ProgramEditor {
                     name: program_name,
                     instructions: vec![], // Empty instructions but name is preserved
                     source_text: Some(self.program_text.clone()), // Preserve source text
+                    parameters: Vec::new(),
                 }
             }
         }
@@ -137,7 +177,7 @@ impl ProgramEditor {
                 
                 // Extract program name from the first def line if possible
                 let program_name = if let Some(def_line) = self.program_text.iter().find(|line| line.starts_with("def ")) {
-                    def_line.strip_prefix("def ").unwrap_or("my_program").trim().to_string()
+                    def_line.strip_prefix("def ").unwrap_or("my_program").split('(').next().unwrap_or("my_program").trim().to_string()
                 } else {
                     "my_program".to_string()
                 };
@@ -147,6 +187,7 @@ impl ProgramEditor {
                     name: program_name,
                     instructions: vec![], // Empty instructions due to syntax error
                     source_text: Some(commented_text), // Preserve source with error comments
+                    parameters: Vec::new(),
                 }]
             }
         }
@@ -161,7 +202,8 @@ impl ProgramEditor {
         for line in &self.program_text {
             let trimmed = line.trim();
             if trimmed.starts_with("def ") {
-                let name = trimmed.strip_prefix("def ").unwrap_or("my_program").trim();
+                // Drop any parameter list, e.g. "def spawner(a, b)"
+                let name = trimmed.strip_prefix("def ").unwrap_or("my_program").split('(').next().unwrap_or("my_program").trim();
                 // Remove any invalid filename characters
                 return name.chars()
                     .filter(|c| c.is_alphanumeric() || *c == '_' || *c == '-')
@@ -198,7 +240,17 @@ impl ProgramEditor {
 
         // Handle clipboard operations
         if input.held_control() {
-            if input.key_pressed(VirtualKeyCode::A) {
+            if input.key_pressed(VirtualKeyCode::Z) {
+                if input.held_shift() {
+                    self.redo();
+                } else {
+                    self.undo();
+                }
+                return ProgramEditorAction::Continue;
+            } else if input.key_pressed(VirtualKeyCode::Y) {
+                self.redo();
+                return ProgramEditorAction::Continue;
+            } else if input.key_pressed(VirtualKeyCode::A) {
                 // Select all - move cursor to end
                 self.cursor_line = self.program_text.len().saturating_sub(1);
                 self.cursor_col = self.program_text[self.cursor_line].len();
@@ -212,6 +264,7 @@ impl ProgramEditor {
                 // Paste from clipboard
                 if let Ok(mut ctx) = ClipboardContext::new() {
                     if let Ok(clipboard_text) = ctx.get_contents() {
+                        self.record_edit(None);
                         // Clear current content and replace with clipboard content
                         self.program_text = clipboard_text.lines().map(|s| s.to_string()).collect();
                         if self.program_text.is_empty() {
@@ -225,6 +278,14 @@ impl ProgramEditor {
             }
         }
 
+        // Moving the cursor ends the current typing/deleting run
+        if [VirtualKeyCode::Up, VirtualKeyCode::Down, VirtualKeyCode::Left, VirtualKeyCode::Right]
+            .iter()
+            .any(|key| input.key_pressed(*key))
+        {
+            self.last_edit = None;
+        }
+
         // Cursor movement with key repeat support
         if self.should_handle_key_repeat(input, VirtualKeyCode::Up) {
             if self.cursor_line > 0 {
@@ -260,6 +321,7 @@ impl ProgramEditor {
 
         // Text editing
         if input.key_pressed(VirtualKeyCode::Return) {
+            self.record_edit(None);
             // Split current line at cursor position
             let current_line = self.program_text[self.cursor_line].clone();
             let (left, right) = current_line.split_at(self.cursor_col);
@@ -273,6 +335,7 @@ impl ProgramEditor {
         // Handle backspace with proper key repeat
         if self.should_handle_key_repeat(input, VirtualKeyCode::Back) {
             if input.held_shift() {
+                self.record_edit(None);
                 // Shift+Backspace: Delete entire line
                 if self.program_text.len() > 1 {
                     self.program_text.remove(self.cursor_line);
@@ -288,6 +351,9 @@ impl ProgramEditor {
                 }
             } else {
                 // Normal backspace behavior
+                if self.cursor_col > 0 || self.cursor_line > 0 {
+                    self.record_edit(Some(EditKind::Deleting));
+                }
                 if self.cursor_col > 0 {
                     // Remove character before cursor
                     self.program_text[self.cursor_line].remove(self.cursor_col - 1);
@@ -303,6 +369,9 @@ impl ProgramEditor {
         }
 
         if self.should_handle_key_repeat(input, VirtualKeyCode::Delete) {
+            if self.cursor_col < self.program_text[self.cursor_line].len() || self.cursor_line < self.program_text.len() - 1 {
+                self.record_edit(Some(EditKind::Deleting));
+            }
             if self.cursor_col < self.program_text[self.cursor_line].len() {
                 // Remove character at cursor
                 self.program_text[self.cursor_line].remove(self.cursor_col);
@@ -316,6 +385,7 @@ impl ProgramEditor {
         // Character input
         self.handle_character_input(input, from_library);
 
+        self.refresh_diagnostics();
         ProgramEditorAction::Continue
     }
 
@@ -431,13 +501,79 @@ impl ProgramEditor {
     }
     
     pub fn insert_character(&mut self, ch: char) {
+        self.record_edit(Some(EditKind::Typing));
         self.program_text[self.cursor_line].insert(self.cursor_col, ch);
         self.cursor_col += 1;
+        self.refresh_diagnostics();
+    }
+    
+    // Re-parse after the text changed, so the error line and message stay current
+    // without parsing on every frame
+    fn refresh_diagnostics(&mut self) {
+        if !self.diagnostics_stale {
+            return;
+        }
+        self.diagnostics_stale = false;
+        match self.parser.parse_multiple_programs(&self.program_text.join("\n")) {
+            Ok(_) => {
+                self.error_line = None;
+                self.error_message = None;
+            }
+            Err(error) => {
+                self.error_line = crate::programmer::error_line(&error).map(|line| line - 1);
+                self.error_message = Some(error);
+            }
+        }
+    }
+
+    fn snapshot(&self) -> EditSnapshot {
+        EditSnapshot {
+            program_text: self.program_text.clone(),
+            cursor_line: self.cursor_line,
+            cursor_col: self.cursor_col,
+        }
+    }
+
+    fn restore(&mut self, snapshot: EditSnapshot) {
+        self.program_text = snapshot.program_text;
+        self.diagnostics_stale = true;
+        self.cursor_line = snapshot.cursor_line.min(self.program_text.len().saturating_sub(1));
+        self.cursor_col = snapshot.cursor_col.min(self.program_text[self.cursor_line].len());
+        self.update_scroll_offset();
+    }
+
+    // Call before changing the text. Edits of the same kind as the previous one
+    // are merged into its undo step; `None` always starts a new step.
+    fn record_edit(&mut self, kind: Option<EditKind>) {
+        if kind.is_none() || kind != self.last_edit {
+            self.undo_stack.push(self.snapshot());
+            if self.undo_stack.len() > MAX_UNDO_HISTORY {
+                self.undo_stack.remove(0);
+            }
+        }
+        self.redo_stack.clear();
+        self.last_edit = kind;
+        self.diagnostics_stale = true;
+    }
+
+    pub fn undo(&mut self) {
+        if let Some(snapshot) = self.undo_stack.pop() {
+            self.redo_stack.push(self.snapshot());
+            self.restore(snapshot);
+        }
+        self.last_edit = None;
+    }
+
+    pub fn redo(&mut self) {
+        if let Some(snapshot) = self.redo_stack.pop() {
+            self.undo_stack.push(self.snapshot());
+            self.restore(snapshot);
+        }
+        self.last_edit = None;
     }
 
     fn should_handle_key_repeat(&mut self, input: &winit_input_helper::WinitInputHelper, key: VirtualKeyCode) -> bool {
-        // Simple approach: just use winit's built-in key repeat
-        input.key_pressed(key)
+        self.key_repeat.fired(input, key)
     }
 
     fn update_scroll_offset(&mut self) {
@@ -455,7 +591,7 @@ impl ProgramEditor {
     }
 
     pub fn draw_syntax_highlighted_text(&self, frame: &mut [u8], text: &str, x: usize, y: usize) {
-        font::draw_syntax_highlighted_text(frame, text, x, y, 640);
+        font::draw_syntax_highlighted_text(frame, text, x, y, window_width());
     }
 
     pub fn draw_program_editor(&self, frame: &mut [u8], title: &str, instructions: &str) {
@@ -469,15 +605,15 @@ impl ProgramEditor {
         draw_menu_border(frame, menu_x, menu_y, menu_width, menu_height);
 
         // Draw title bar
-        font::draw_text(frame, title, menu_x + 10, menu_y + 5, [255, 255, 255], false, 640);
-        font::draw_text(frame, instructions, menu_x + 10, menu_y + 25, [180, 180, 180], false, 640);
+        font::draw_text(frame, title, menu_x + 10, menu_y + 5, [255, 255, 255], false, window_width());
+        font::draw_text(frame, instructions, menu_x + 10, menu_y + 25, [180, 180, 180], false, window_width());
 
         // Draw line number background
         let line_num_width = 40;
         for y in (menu_y + 45)..(menu_y + menu_height - 10) {
             for x in (menu_x + 5)..(menu_x + line_num_width) {
-                if x < 640 && y < 480 {
-                    let pixel_index = (y * 640 + x) * 4;
+                if x < window_width() && y < grid_area_height() {
+                    let pixel_index = (y * window_width() + x) * 4;
                     if pixel_index + 3 < frame.len() {
                         frame[pixel_index] = 40;     // R
                         frame[pixel_index + 1] = 40; // G
@@ -505,14 +641,30 @@ impl ProgramEditor {
             // Draw line number
             let line_num = format!("{:2}", actual_line + 1);
             let line_num_color = if is_cursor_line { [255, 255, 100] } else { [120, 120, 120] };
-            font::draw_text(frame, &line_num, menu_x + 8, y_pos, line_num_color, false, 640);
+            font::draw_text(frame, &line_num, menu_x + 8, y_pos, line_num_color, false, window_width());
+            
+            // Lines that failed the last parse get a red background
+            if self.error_line == Some(actual_line) {
+                for x in text_start_x..(menu_x + menu_width - 10) {
+                    for dy in 0..16 {
+                        if x < window_width() && y_pos + dy < grid_area_height() {
+                            let pixel_index = ((y_pos + dy) * window_width() + x) * 4;
+                            if pixel_index + 3 < frame.len() {
+                                frame[pixel_index] = 110;     // R
+                                frame[pixel_index + 1] = 30;  // G
+                                frame[pixel_index + 2] = 30;  // B
+                            }
+                        }
+                    }
+                }
+            }
             
             // Highlight current line background
             if is_cursor_line {
                 for x in text_start_x..(menu_x + menu_width - 10) {
                     for dy in 0..16 {
-                        if x < 640 && y_pos + dy < 480 {
-                            let pixel_index = ((y_pos + dy) * 640 + x) * 4;
+                        if x < window_width() && y_pos + dy < grid_area_height() {
+                            let pixel_index = ((y_pos + dy) * window_width() + x) * 4;
                             if pixel_index + 3 < frame.len() {
                                 frame[pixel_index] = frame[pixel_index].saturating_add(15);     // R
                                 frame[pixel_index + 1] = frame[pixel_index + 1].saturating_add(15); // G
@@ -540,8 +692,8 @@ impl ProgramEditor {
                 // Draw cursor
                 for dx in 0..2 {
                     for dy in 0..16 {
-                        if cursor_x + dx < 640 && y_pos + dy < 480 {
-                            let pixel_index = ((y_pos + dy) * 640 + cursor_x + dx) * 4;
+                        if cursor_x + dx < window_width() && y_pos + dy < grid_area_height() {
+                            let pixel_index = ((y_pos + dy) * window_width() + cursor_x + dx) * 4;
                             if pixel_index + 3 < frame.len() {
                                 frame[pixel_index] = 255;     // R
                                 frame[pixel_index + 1] = 255; // G
@@ -567,14 +719,22 @@ impl ProgramEditor {
          
          // Status info
          let status_text = format!("Line: {} | Column: {} | Lines: {}", self.cursor_line + 1, self.cursor_col + 1, self.program_text.len());
-         font::draw_text(frame, &status_text, menu_x + 10, menu_y + menu_height - 20, [180, 180, 180], false, 640);
+         font::draw_text(frame, &status_text, menu_x + 10, menu_y + menu_height - 20, [180, 180, 180], false, window_width());
+         
+         // The last parse error, cut to the space left on the status row
+         if let Some(error) = &self.error_message {
+             let error_x = menu_x + 10 + (status_text.len() + 3) * 8;
+             let max_chars = (menu_x + menu_width - 10).saturating_sub(error_x) / 8;
+             let error_text: String = error.chars().take(max_chars).collect();
+             font::draw_text(frame, &error_text, error_x, menu_y + menu_height - 20, [255, 110, 110], false, window_width());
+         }
     }
 }
 
 // Helper functions for drawing
 fn draw_menu_background(frame: &mut [u8], x: usize, y: usize, width: usize, height: usize) {
-    let window_width = 640;
-    let window_height = 480;
+    let window_width = window_width();
+    let window_height = grid_area_height();
     
     for py in y..y + height {
         for px in x..x + width {
@@ -592,8 +752,8 @@ fn draw_menu_background(frame: &mut [u8], x: usize, y: usize, width: usize, heig
 }
 
 fn draw_menu_border(frame: &mut [u8], x: usize, y: usize, width: usize, height: usize) {
-    let window_width = 640;
-    let window_height = 480;
+    let window_width = window_width();
+    let window_height = grid_area_height();
     
     // Top and bottom borders
     for px in x..x + width {
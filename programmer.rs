@@ -1,29 +1,37 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
 use crate::ball::{Ball, Direction};
-use crate::square::{Value, Expression, Instruction, BinaryOperator, BallProperty, Program, ExecutionContext, ProgramAction, DestroyTarget};
+use crate::square::{Value, Expression, Instruction, BinaryOperator, BallProperty, Program, ExecutionContext, ProgramAction, DestroyTarget, MAX_LOOP_ITERATIONS, EXPRESSION_FUNCTIONS, call_function};
+use crate::sequencer::{count_collisions_within, CollisionEvent};
 // Grid dimensions are available from the sequencer module if needed
 
 #[derive(Clone, Debug)]
 pub struct ProgrammerState {
-    pub variables: HashMap<String, Value>,
+    pub variables: HashMap<String, Value>, // Globals, set with `$var`
+    pub square_variables: HashMap<(usize, usize), HashMap<String, Value>>, // `var`s, kept per square between hits
     pub ball_hit_counts: HashMap<String, u32>, // Track hits per ball color (global)
     pub square_hit_counts: HashMap<(usize, usize), u32>, // Track hits per square position
-    pub ball_color_square_hits: HashMap<(String, usize, usize), u32>, // Track hits per ball color per square
+    pub ball_color_square_hits: HashMap<(String, usize, usize), u32>, // Track hits per ball color (or ball id/name) per square
     pub slice_arrays: HashMap<(usize, usize), Vec<u32>>, // Track slice arrays per square position
     pub slice_hit_indices: HashMap<(usize, usize), usize>, // Track current index in slice array per square
     pub ball_object_hit_counts: HashMap<String, u32>, // Track hits per ball object (ball1, ball2, etc.)
+    pub active_voices: u32, // Samples playing when this update started, read by `voices`
+    pub elapsed: f32, // Seconds of ball updates since the take started, read by `time`
 }
 
 impl Default for ProgrammerState {
     fn default() -> Self {
         Self {
             variables: HashMap::new(),
+            square_variables: HashMap::new(),
             ball_hit_counts: HashMap::new(),
             square_hit_counts: HashMap::new(),
             ball_color_square_hits: HashMap::new(),
             slice_arrays: HashMap::new(),
             slice_hit_indices: HashMap::new(),
             ball_object_hit_counts: HashMap::new(),
+            active_voices: 0,
+            elapsed: 0.0,
         }
     }
 }
@@ -31,6 +39,11 @@ impl Default for ProgrammerState {
 #[derive(Clone, Debug)]
 pub struct SimpleProgramParser;
 
+// The 1-based source line a parse error starts with ("line 4: ..."), if it names one
+pub fn error_line(error: &str) -> Option<usize> {
+    error.strip_prefix("line ")?.split(':').next()?.parse().ok()
+}
+
 impl SimpleProgramParser {
     pub fn new() -> Self {
         Self
@@ -55,6 +68,18 @@ impl SimpleProgramParser {
         }
     }
     
+    // Hex colors (#rrggbb) are kept as-is; named colors go through validation
+    fn parse_color_literal(&self, color_str: &str) -> Result<String, String> {
+        if let Some(hex) = color_str.strip_prefix('#') {
+            if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(format!("Invalid hex color '{}'. Expected: #rrggbb", color_str));
+            }
+            Ok(color_str.to_lowercase())
+        } else {
+            self.validate_color(color_str)
+        }
+    }
+    
     fn validate_color(&self, color: &str) -> Result<String, String> {
         // Handle both "Red" and "c_red" formats
         let normalized_color = if color.starts_with("c_") {
@@ -99,9 +124,10 @@ impl SimpleProgramParser {
     
     /// Parse multiple function definitions from the same source text
     pub fn parse_multiple_programs(&self, source: &str) -> Result<Vec<Program>, String> {
-        let lines: Vec<&str> = source.lines().map(|l| l.trim()).filter(|l| !l.is_empty()).collect();
+        // Blank lines stay in so an index into `lines` is the source line number minus one
+        let lines: Vec<&str> = source.lines().map(|l| l.trim()).collect();
         
-        if lines.is_empty() {
+        if lines.iter().all(|l| l.is_empty()) {
             return Err("Empty program".to_string());
         }
         
@@ -111,19 +137,24 @@ impl SimpleProgramParser {
         while i < lines.len() {
             let line = lines[i];
             
-            if line.starts_with("def ") {
-                let function_name = line[4..].trim().to_string();
+            if line.is_empty() {
+                i += 1;
+            } else if line.starts_with("def ") {
+                let (function_name, parameters) = self.parse_def_header(&line[4..]).map_err(|e| Self::locate(i, e))?;
                 let (instructions, next_i) = self.parse_block(&lines, i + 1)?;
                 
                 programs.push(Program {
                     name: function_name,
                     instructions,
                     source_text: None, // Parser doesn't preserve original text
+                    parameters,
                 });
                 
                 i = next_i;
+            } else if let Some(previous) = programs.last() {
+                return Err(format!("line {}: '{}' comes after the end of function '{}' and would never run - move it above the 'return' or start a new 'def'", i + 1, line, previous.name));
             } else {
-                return Err(format!("Expected 'def function_name', found: {}", line));
+                return Err(format!("line {}: Expected 'def function_name', found: {}", i + 1, line));
             }
         }
         
@@ -134,6 +165,79 @@ impl SimpleProgramParser {
         Ok(programs)
     }
     
+    /// Warnings for lines that follow the `return`/`end` closing a function and so never run.
+    /// Parsing stops a function at that line, so nothing after it reaches the program.
+    pub fn unreachable_code_warnings(&self, source: &str) -> Vec<String> {
+        let mut warnings = Vec::new();
+        let mut open_blocks: usize = 0; // Function bodies (including nested/embedded defs) not yet closed
+        let mut closed_at = None; // Line of the return that closed the last function
+        
+        for (index, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+            if line.starts_with("def ") || (line.starts_with("create square(") && line.contains("with def ")) {
+                open_blocks += 1;
+                closed_at = None;
+                continue;
+            }
+            if let Some(return_line) = closed_at {
+                warnings.push(format!("Warning: line {}: '{}' comes after the return on line {} and will never run",
+                    index + 1, line, return_line));
+                continue;
+            }
+            if line.starts_with("loop ") {
+                open_blocks += 1; // Closed by its own 'end'
+                continue;
+            }
+            if line == "return" || line.starts_with("return ") || line == "end" {
+                open_blocks = open_blocks.saturating_sub(1);
+                if open_blocks == 0 {
+                    closed_at = Some(index + 1);
+                }
+            }
+        }
+        warnings
+    }
+    
+    /// Split a def header like "spawner(x, y)" into the function name and its parameter names
+    pub fn parse_def_header(&self, header: &str) -> Result<(String, Vec<String>), String> {
+        let header = header.trim();
+        let open_paren = match header.find('(') {
+            Some(pos) => pos,
+            None => return Ok((header.to_string(), Vec::new())),
+        };
+        if !header.ends_with(')') {
+            return Err(format!("Missing ')' in function definition: {}", header));
+        }
+        
+        let name = header[..open_paren].trim().to_string();
+        let parameters: Vec<String> = header[open_paren + 1..header.len() - 1]
+            .split(',')
+            .map(|p| p.trim().to_string())
+            .filter(|p| !p.is_empty())
+            .collect();
+        
+        if let Some(bad) = parameters.iter().find(|p| !p.chars().all(|c| c.is_alphanumeric() || c == '_')) {
+            return Err(format!("Invalid parameter name '{}' in function {}", bad, name));
+        }
+        // x, y and speed always refer to the ball, so they can't be rebound as parameters
+        if let Some(reserved) = parameters.iter().find(|p| matches!(p.as_str(), "x" | "y" | "speed" | "time")) {
+            return Err(format!("Parameter name '{}' in function {} is reserved for ball properties", reserved, name));
+        }
+        Ok((name, parameters))
+    }
+    
+    // Prefix an error with the source line it came from, unless a nested block already did
+    fn locate(index: usize, error: String) -> String {
+        if error.starts_with("line ") {
+            error
+        } else {
+            format!("line {}: {}", index + 1, error)
+        }
+    }
+    
     fn parse_block(&self, lines: &[&str], start_index: usize) -> Result<(Vec<Instruction>, usize), String> {
         let mut instructions = Vec::new();
         let mut i = start_index;
@@ -141,6 +245,11 @@ impl SimpleProgramParser {
         while i < lines.len() {
             let line = lines[i];
             
+            if line.is_empty() {
+                i += 1;
+                continue;
+            }
+            
             if line == "return" {
                 instructions.push(Instruction::Return(None));
                 i += 1;
@@ -162,7 +271,7 @@ impl SimpleProgramParser {
             
             // Handle if statements with potential then blocks
             if line.starts_with("if ") {
-                let (if_instruction, next_i) = self.parse_if_with_then(lines, i)?;
+                let (if_instruction, next_i) = self.parse_if_with_then(lines, i).map_err(|e| Self::locate(i, e))?;
                 instructions.push(if_instruction);
                 i = next_i;
                 continue;
@@ -176,7 +285,9 @@ impl SimpleProgramParser {
                 i += 1;
                 while i < lines.len() && depth > 0 {
                     let current_line = lines[i];
-                    if current_line == "end" || current_line.starts_with("return") {
+                    if current_line.starts_with("loop ") {
+                        depth += 1;
+                    } else if current_line == "end" || current_line.starts_with("return") {
                         depth -= 1;
                     }
                     i += 1;
@@ -184,9 +295,16 @@ impl SimpleProgramParser {
                 continue;
             }
             
+            if line.starts_with("loop ") {
+                let (loop_instruction, next_i) = self.parse_loop_block(lines, i).map_err(|e| Self::locate(i, e))?;
+                instructions.push(loop_instruction);
+                i = next_i;
+                continue;
+            }
+            
             // Handle create square with embedded program
             if line.starts_with("create square(") && line.contains("with") {
-                let (create_instruction, next_i) = self.parse_create_square_with_program(lines, i)?;
+                let (create_instruction, next_i) = self.parse_create_square_with_program(lines, i).map_err(|e| Self::locate(i, e))?;
                 instructions.push(create_instruction);
                 i = next_i;
                 continue;
@@ -205,10 +323,9 @@ impl SimpleProgramParser {
                 }
             }
             
-            if let Ok(instruction) = self.parse_line(line) {
-                instructions.push(instruction);
-            } else {
-                return Err(format!("Failed to parse line: {}", line));
+            match self.parse_line(line) {
+                Ok(instruction) => instructions.push(instruction),
+                Err(e) => return Err(format!("line {}: Failed to parse '{}': {}", i + 1, line, e)),
             }
             
             i += 1;
@@ -217,19 +334,55 @@ impl SimpleProgramParser {
         Ok((instructions, i))
     }
     
+    // "loop 4" repeats the lines up to its matching "end" inline; loops can nest
+    fn parse_loop_block(&self, lines: &[&str], start_index: usize) -> Result<(Instruction, usize), String> {
+        let count_str = lines[start_index]["loop ".len()..].trim();
+        let count = self.parse_coordinate_expression(count_str)?;
+        if let Expression::Literal(Value::Number(n)) = count {
+            if n < 0.0 || n > MAX_LOOP_ITERATIONS as f32 {
+                return Err(format!("Loop count {} is outside 0-{}", n, MAX_LOOP_ITERATIONS));
+            }
+        }
+        
+        let mut depth = 1;
+        let mut end_index = start_index + 1;
+        while end_index < lines.len() {
+            let current_line = lines[end_index];
+            if current_line.starts_with("loop ") {
+                depth += 1;
+            } else if current_line == "end" {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+            } else if current_line.starts_with("def ") {
+                break;
+            }
+            end_index += 1;
+        }
+        if depth > 0 {
+            return Err(format!("Missing 'end' after 'loop {}'", count_str));
+        }
+        
+        // Cut the lines off at the loop's own end; indices stay those of the whole source
+        let (body, _) = self.parse_block(&lines[..end_index], start_index + 1)?;
+        Ok((Instruction::Loop { count, body }, end_index + 1))
+    }
+    
     fn parse_nested_function(&self, lines: &[&str], start_index: usize) -> Result<(Program, usize), String> {
         let line = lines[start_index];
         if !line.starts_with("def ") {
             return Err("Expected function definition".to_string());
         }
         
-        let function_name = line[4..].trim().to_string();
+        let (function_name, parameters) = self.parse_def_header(&line[4..])?;
         let (instructions, next_i) = self.parse_block(lines, start_index + 1)?;
         
         Ok((Program {
             name: function_name,
             instructions,
             source_text: None, // Parser doesn't preserve original text
+            parameters,
         }, next_i))
     }
     
@@ -259,13 +412,14 @@ impl SimpleProgramParser {
                                 return Err("Expected 'def function_name' after 'with'".to_string());
                             }
                             
-                            let function_name = def_part[4..].trim().to_string();
+                            let (function_name, parameters) = self.parse_def_header(&def_part[4..])?;
                             let (instructions, end_index) = self.parse_block(lines, start_index + 1)?;
                             
                             let embedded_program = Program {
                 name: function_name,
                 instructions,
                 source_text: None, // Parser doesn't preserve original text
+                parameters,
             };
                             
                             return Ok((Instruction::CreateSquareWithProgram {
@@ -293,7 +447,9 @@ impl SimpleProgramParser {
         while i < lines.len() {
             let current_line = lines[i];
             
-            if current_line == "then" {
+            if current_line.is_empty() {
+                i += 1;
+            } else if current_line == "then" {
                 // 'then' means continue to next function in sequence
                 then_block.push(Instruction::ContinueToNext);
                 i += 1;
@@ -311,7 +467,7 @@ impl SimpleProgramParser {
                         });
                     }
                 } else {
-                    return Err(format!("Invalid number in 'then {}'", count_str));
+                    return Err(format!("line {}: Invalid number in 'then {}'", i + 1, count_str));
                 }
                 i += 1;
                 break;
@@ -332,16 +488,20 @@ impl SimpleProgramParser {
                         });
                     }
                 } else {
-                    return Err(format!("Invalid number in 'and {}'", count_str));
+                    return Err(format!("line {}: Invalid number in 'and {}'", i + 1, count_str));
                 }
                 i += 1;
                 continue;
-            } else if current_line.starts_with("if ") || current_line.starts_with("def ") || current_line == "end" {
+            } else if current_line.starts_with("if ") || current_line.starts_with("def ") || current_line == "end" || current_line == "else" {
                 // End of if block without explicit then
                 break;
             } else if current_line == "return" || current_line.starts_with("return ") {
                 // Return statement should not be part of the if block - stop parsing if block
                 break;
+            } else if current_line.starts_with("loop ") {
+                let (loop_instruction, next_i) = self.parse_loop_block(lines, i).map_err(|e| Self::locate(i, e))?;
+                then_block.push(loop_instruction);
+                i = next_i;
             } else {
                 // Handle create ball/square with library reference on next line (same as parse_block)
                 if (current_line.starts_with("create ball(") || current_line.starts_with("create square(")) && i + 1 < lines.len() {
@@ -357,23 +517,70 @@ impl SimpleProgramParser {
                 }
                 
                 // Parse instruction as part of the if block
-                if let Ok(instruction) = self.parse_line(current_line) {
-                    then_block.push(instruction);
-                    i += 1;
-                    // Continue parsing all instructions as part of the if block
-                } else {
-                    return Err(format!("Failed to parse instruction in if block: {}", current_line));
+                match self.parse_line(current_line) {
+                    Ok(instruction) => {
+                        then_block.push(instruction);
+                        i += 1;
+                        // Continue parsing all instructions as part of the if block
+                    }
+                    Err(e) => return Err(format!("line {}: Failed to parse '{}' in if block: {}", i + 1, current_line, e)),
                 }
             }
         }
         
+        let mut else_block = None;
+        if i < lines.len() && lines[i] == "else" {
+            let (block, next_i) = self.parse_else_block(lines, i).map_err(|e| Self::locate(i, e))?;
+            else_block = Some(block);
+            i = next_i;
+        }
+        
         Ok((Instruction::If {
             condition,
             then_block,
-            else_block: None,
+            else_block,
         }, i))
     }
     
+    // Instructions after `else`, up to the `end` (left for the enclosing block, like an
+    // if block's), a `then`, a `return` or the next `if`. Running into another `def` or
+    // the end of the text means the `end` is missing.
+    fn parse_else_block(&self, lines: &[&str], else_index: usize) -> Result<(Vec<Instruction>, usize), String> {
+        let mut else_block = Vec::new();
+        let mut i = else_index + 1;
+        
+        while i < lines.len() {
+            let current_line = lines[i];
+            
+            if current_line.is_empty() {
+                i += 1;
+            } else if current_line == "end" || current_line == "return" || current_line.starts_with("return ") || current_line.starts_with("if ") {
+                return Ok((else_block, i));
+            } else if current_line == "then" {
+                else_block.push(Instruction::ContinueToNext);
+                return Ok((else_block, i + 1));
+            } else if current_line == "else" {
+                return Err(format!("line {}: 'else' appears twice in the same if block", i + 1));
+            } else if current_line.starts_with("def ") {
+                return Err(format!("line {}: Missing 'end' after the else block before '{}'", i + 1, current_line));
+            } else if current_line == "and" {
+                i += 1;
+            } else if current_line.starts_with("loop ") {
+                let (loop_instruction, next_i) = self.parse_loop_block(lines, i).map_err(|e| Self::locate(i, e))?;
+                else_block.push(loop_instruction);
+                i = next_i;
+            } else {
+                match self.parse_line(current_line) {
+                    Ok(instruction) => else_block.push(instruction),
+                    Err(e) => return Err(format!("line {}: Failed to parse '{}' in else block: {}", i + 1, current_line, e)),
+                }
+                i += 1;
+            }
+        }
+        
+        Err("Missing 'end' after the else block".to_string())
+    }
+    
     fn parse_if_condition(&self, line: &str) -> Result<Expression, String> {
         // Parse "if c_red hits self 10 times" or "if ball1 hits ball2 4 times" or general expressions
         let condition_part = &line[3..].trim(); // Remove "if "
@@ -411,10 +618,44 @@ impl SimpleProgramParser {
             }
         }
         
+        // "direction is up", "direction is not left", "direction == down-right", "direction != up"
+        if parts.len() >= 3 && parts[0] == "direction" {
+            let (negated, target) = match (parts[1], parts.get(2).copied(), parts.len()) {
+                ("is", Some("not"), 4) => (true, parts[3]),
+                ("is", _, 3) | ("==", _, 3) => (false, parts[2]),
+                ("!=", _, 3) => (true, parts[2]),
+                _ => return Err(format!("Invalid direction condition '{}'. Expected: if direction is [not] <direction>", condition_part)),
+            };
+            let direction = Self::parse_heading(target)
+                .ok_or_else(|| format!("Invalid direction '{}'. Use up, down, left, right, up-left, up-right, down-left or down-right", target))?;
+            return Ok(Expression::BinaryOp {
+                left: Box::new(Expression::BallProperty(BallProperty::Direction)),
+                op: if negated { BinaryOperator::NotEqual } else { BinaryOperator::Equal },
+                right: Box::new(Expression::Literal(Value::Direction(direction))),
+            });
+        }
+        
         // If traditional format fails, try to parse as a general expression
         self.parse_coordinate_expression(condition_part)
     }
     
+    // The direction a ball is actually travelling in. Unlike "set direction", which is
+    // inverted to account for the bounce that follows, conditions test the heading the
+    // ball had when it arrived at the square.
+    fn parse_heading(word: &str) -> Option<Direction> {
+        match word {
+            "up" => Some(Direction::Up),
+            "down" => Some(Direction::Down),
+            "left" => Some(Direction::Left),
+            "right" => Some(Direction::Right),
+            "up-left" => Some(Direction::UpLeft),
+            "up-right" => Some(Direction::UpRight),
+            "down-left" => Some(Direction::DownLeft),
+            "down-right" => Some(Direction::DownRight),
+            _ => None,
+        }
+    }
+    
     fn parse_line(&self, line: &str) -> Result<Instruction, String> {
         let line = line.trim();
         
@@ -438,6 +679,16 @@ impl SimpleProgramParser {
             return self.parse_print_statement(line);
         }
         
+        // Handle "log" statements
+        if line.starts_with("log ") {
+            return self.parse_log_statement(line);
+        }
+        
+        // "wait 120": apply the rest of the program 120ms later
+        if let Some(delay) = line.strip_prefix("wait ") {
+            return Ok(Instruction::Wait(self.parse_coordinate_expression(delay)?));
+        }
+        
         // Note: 'reverse sample of' syntax has been removed
         // Use 'set reverse ball_reference speed' instead
         
@@ -456,10 +707,72 @@ impl SimpleProgramParser {
             return self.parse_slice_statement(line);
         }
         
+        // Handle "spawn burst <count>" to fan balls out from this square
+        if let Some(count_str) = line.strip_prefix("spawn burst ") {
+            let count_str = count_str.trim();
+            let count_expr = match count_str.parse::<f32>() {
+                Ok(count) if count >= 1.0 => Expression::Literal(Value::Number(count)),
+                Ok(_) => return Err("Burst count must be at least 1".to_string()),
+                Err(_) => self.parse_coordinate_expression(count_str)?,
+            };
+            return Ok(Instruction::SpawnBurst { count: count_expr });
+        }
+        
+        // Handle "pass" to let the ball continue straight through this square
+        if line == "pass" {
+            return Ok(Instruction::Pass);
+        }
+        
+        // Handle "reflect" to bounce off the face that was hit instead of straight back
+        if line == "reflect" {
+            return Ok(Instruction::Reflect);
+        }
+        
+        // Handle "reset self" to re-arm this square's hit counters
+        if line == "reset self" {
+            return Ok(Instruction::ResetHits);
+        }
+        
+        // Handle "reset square(x, y)" to re-arm another square's hit counters
+        if let Some(coords) = line.strip_prefix("reset square") {
+            let (x, y) = self.parse_square_target(coords, "reset square(x, y)")?;
+            return Ok(Instruction::ResetSquare { x, y });
+        }
+        
+        // Handle "teleport(x, y)" to move the ball to another cell
+        if let Some(coords) = line.strip_prefix("teleport").filter(|rest| rest.trim_start().starts_with('(')) {
+            let (x, y) = self.parse_square_target(coords, "teleport(x, y)")?;
+            return Ok(Instruction::Teleport { x, y });
+        }
+        
+        // Handle "arm square(x, y) program <index|\"name\">" to switch another square's program
+        if let Some(rest) = line.strip_prefix("arm square") {
+            let usage = "arm square(x, y) program <index|\"name\">";
+            let close_paren = rest.find(')').ok_or_else(|| format!("Invalid syntax. Expected: {}", usage))?;
+            let (x, y) = self.parse_square_target(&rest[..=close_paren], usage)?;
+            let program_ref = rest[close_paren + 1..].trim().strip_prefix("program ")
+                .ok_or_else(|| format!("Invalid syntax. Expected: {}", usage))?;
+            let program = self.parse_coordinate_expression(program_ref.trim())?;
+            return Ok(Instruction::ArmSquare { x, y, program });
+        }
+        
+        // Handle "use program <index>" / "use program \"name\"" to switch the active program
+        if let Some(program_ref) = line.strip_prefix("use program ") {
+            let program_expr = self.parse_coordinate_expression(program_ref)?;
+            return Ok(Instruction::SetActiveProgram(program_expr));
+        }
+        
         // Handle library function calls
         if line.starts_with("lib.") {
+            // Optional argument list: lib.spawner(3, 4), lib.f(max(1, 2), 3)
+            if let Some((name, args)) = Self::split_call(&line["lib.".len()..]) {
+                let args = args.into_iter()
+                    .map(|arg| self.parse_coordinate_expression(arg))
+                    .collect::<Result<Vec<_>, _>>()?;
+                return Ok(Instruction::ExecuteLibraryFunction { library_function: format!("lib.{}", name), args });
+            }
             let library_function = line.to_string();
-            return Ok(Instruction::ExecuteLibraryFunction { library_function });
+            return Ok(Instruction::ExecuteLibraryFunction { library_function, args: Vec::new() });
         }
         
         Err(format!("Unknown instruction: {}", line))
@@ -467,27 +780,96 @@ impl SimpleProgramParser {
     
     fn validate_object_reference(&self, object_ref: &str) -> Result<String, String> {
         // Check if it's a ball object reference (ball1, ball2, etc.)
-        if object_ref.starts_with("ball") && object_ref[4..].chars().all(|c| c.is_ascii_digit()) {
+        if Self::is_ball_id(object_ref) {
             return Ok(object_ref.to_string());
         }
         
         // Check if it's a color reference (c_red, Red, etc.)
-        if object_ref.starts_with("c_") {
+        if Self::is_color_reference(object_ref) {
             return self.validate_color(object_ref);
         }
         
-        // Normalize the color name and check if it's valid
-        let normalized_color = if !object_ref.is_empty() {
-            format!("{}{}", object_ref.chars().next().unwrap().to_uppercase(), &object_ref[1..].to_lowercase())
-        } else {
-            object_ref.to_string()
-        };
+        // Anything else that looks like an identifier is a ball name (set from the ball menu);
+        // unknown_ball_references flags the ones no ball has when the program is saved
+        if Self::is_identifier(object_ref) && object_ref != "self" {
+            return Ok(object_ref.to_string());
+        }
         
-        if Self::VALID_COLORS.contains(&normalized_color.as_str()) {
-            return self.validate_color(object_ref);
+        Err(format!("Invalid object reference '{}'. Use ball objects (ball1, ball2, etc.), ball names, or colors (c_red, Red, etc.)", object_ref))
+    }
+    
+    fn is_ball_id(object_ref: &str) -> bool {
+        object_ref.len() > 4 && object_ref.starts_with("ball") && object_ref[4..].chars().all(|c| c.is_ascii_digit())
+    }
+    
+    fn is_color_reference(object_ref: &str) -> bool {
+        if object_ref.starts_with("c_") {
+            return true;
+        }
+        let mut chars = object_ref.chars();
+        match chars.next() {
+            Some(first) => {
+                let normalized = format!("{}{}", first.to_uppercase(), chars.as_str().to_lowercase());
+                Self::VALID_COLORS.contains(&normalized.as_str())
+            }
+            None => false,
+        }
+    }
+    
+    fn is_identifier(text: &str) -> bool {
+        let mut chars = text.chars();
+        matches!(chars.next(), Some(c) if c.is_ascii_alphabetic()) && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+    }
+    
+    /// Check that `name` can be given to a ball and referenced from programs
+    /// without being mistaken for an id, a color, or `self`
+    pub fn check_ball_name(name: &str) -> Result<(), String> {
+        if !Self::is_identifier(name) {
+            return Err(format!("Invalid ball name '{}': use letters, digits and '_', starting with a letter", name));
+        }
+        if Self::is_ball_id(name) || Self::is_color_reference(name) || name == "self" {
+            return Err(format!("Invalid ball name '{}': reserved for ball ids, colors or 'self'", name));
+        }
+        Ok(())
+    }
+    
+    /// Ball ids and names used in hit conditions ("if kick hits self", "hits(ball3)")
+    /// that aren't in `known`. Any identifier parses as a ball name, since balls can be
+    /// named after the program is written, so these are only hints for typos.
+    pub fn unknown_ball_references(instructions: &[Instruction], known: &[String]) -> Vec<String> {
+        let mut variables = Vec::new();
+        for instruction in instructions {
+            instruction.for_each_variable(&mut |name| variables.push(name));
         }
         
-        Err(format!("Invalid object reference '{}'. Use ball objects (ball1, ball2, etc.) or colors (c_red, Red, etc.)", object_ref))
+        let is_known = |object: &str| Self::is_color_reference(object) || known.iter().any(|name| name == object);
+        let mut unknown: Vec<String> = Vec::new();
+        for variable in variables {
+            let object = if let Some(object) = variable.strip_prefix("__ball_color_square_hits_") {
+                (!is_known(object)).then_some(object)
+            } else if let Some(rest) = variable.strip_prefix("__ball_hits_") {
+                // "<object>" from hits(), or "<object>_<target>" from "if <object> hits <target>"
+                let is_target = |target: &str| target == "self" || target.starts_with("square(") || is_known(target);
+                if is_known(rest) {
+                    None
+                } else {
+                    // Name whichever side is missing: split after the object where the rest is a valid target
+                    let object = rest.match_indices('_')
+                        .map(|(at, _)| (&rest[..at], &rest[at + 1..]))
+                        .find(|(_, target)| is_target(target))
+                        .map_or(rest, |(object, _)| object);
+                    (!is_known(object)).then_some(object)
+                }
+            } else {
+                None
+            };
+            if let Some(object) = object {
+                if !unknown.iter().any(|name| name == object) {
+                    unknown.push(object.to_string());
+                }
+            }
+        }
+        unknown
     }
     
     fn create_hit_condition(&self, object_ref: &str, target: &str, count: u32) -> Result<Expression, String> {
@@ -497,16 +879,16 @@ impl SimpleProgramParser {
         // Validate the target as well
         let validated_target = if target == "self" {
             "self".to_string()
-        } else if target.starts_with("ball") && target[4..].chars().all(|c| c.is_ascii_digit()) {
-            target.to_string()
         } else if target.starts_with("square(") && target.ends_with(")") {
             target.to_string()
+        } else if Self::is_ball_id(target) || (Self::is_identifier(target) && !Self::is_color_reference(target)) {
+            target.to_string()
         } else {
-            return Err(format!("Invalid target '{}'. Use 'self', ball objects (ball1, ball2, etc.), or square coordinates square(x, y)", target));
+            return Err(format!("Invalid target '{}'. Use 'self', ball objects (ball1, ball2, etc.), ball names, or square coordinates square(x, y)", target));
         };
         
         // Create a condition that checks hit count for the specific object and target combination
-        let hit_variable = if object_ref.starts_with("ball") {
+        let hit_variable = if !Self::is_color_reference(object_ref) {
             if target == "self" {
                 // For ball hitting 'self' (the square), use ball_color_square_hits
                 format!("__ball_color_square_hits_{}", validated_ref)
@@ -582,32 +964,143 @@ impl SimpleProgramParser {
         }
     }
     
+    // The first comparison operator outside parentheses and quotes: its byte offset, operator and length
+    fn find_comparison(expr: &str) -> Option<(usize, BinaryOperator, usize)> {
+        let bytes = expr.as_bytes();
+        let mut depth = 0;
+        let mut in_quotes = false;
+        for (index, &byte) in bytes.iter().enumerate() {
+            let next = bytes.get(index + 1).copied();
+            match byte {
+                b'"' | b'\'' => in_quotes = !in_quotes,
+                b'(' if !in_quotes => depth += 1,
+                b')' if !in_quotes => depth -= 1,
+                _ if in_quotes || depth != 0 => {}
+                b'<' if next == Some(b'=') => return Some((index, BinaryOperator::LessEqual, 2)),
+                b'>' if next == Some(b'=') => return Some((index, BinaryOperator::GreaterEqual, 2)),
+                b'=' if next == Some(b'=') => return Some((index, BinaryOperator::Equal, 2)),
+                b'!' if next == Some(b'=') => return Some((index, BinaryOperator::NotEqual, 2)),
+                b'<' => return Some((index, BinaryOperator::Less, 1)),
+                b'>' => return Some((index, BinaryOperator::Greater, 1)),
+                _ => {}
+            }
+        }
+        None
+    }
+    
+    // Position of `op` outside any parentheses or quotes
+    fn find_top_level(expr: &str, op: char) -> Option<usize> {
+        let mut depth = 0;
+        let mut in_quotes = false;
+        for (index, c) in expr.char_indices() {
+            match c {
+                '"' | '\'' => in_quotes = !in_quotes,
+                '(' if !in_quotes => depth += 1,
+                ')' if !in_quotes => depth -= 1,
+                c if c == op && depth == 0 && !in_quotes => return Some(index),
+                _ => {}
+            }
+        }
+        None
+    }
+    
+    // "name(a, b(c, d))" -> ("name", ["a", "b(c, d)"]), when the whole text is one call
+    fn split_call(expr: &str) -> Option<(&str, Vec<&str>)> {
+        let open = expr.find('(')?;
+        let name = expr[..open].trim();
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') || !expr.ends_with(')') {
+            return None;
+        }
+        let inner = &expr[open + 1..expr.len() - 1];
+        let mut args = Vec::new();
+        let mut depth = 0;
+        let mut start = 0;
+        for (index, c) in inner.char_indices() {
+            match c {
+                '(' => depth += 1,
+                ')' if depth == 0 => return None, // The first call closes before the end, as in "f(a) + g(b)"
+                ')' => depth -= 1,
+                ',' if depth == 0 => {
+                    args.push(inner[start..index].trim());
+                    start = index + 1;
+                }
+                _ => {}
+            }
+        }
+        if !inner.trim().is_empty() || !args.is_empty() {
+            args.push(inner[start..].trim());
+        }
+        Some((name, args))
+    }
+    
+    // Whitespace-separated words, keeping anything in parentheses together so
+    // "set speed random(0.5, 3)" has "random(0.5, 3)" as its third word
+    fn split_words(line: &str) -> Vec<&str> {
+        let mut words = Vec::new();
+        let mut depth = 0;
+        let mut start = None;
+        for (index, c) in line.char_indices() {
+            match c {
+                '(' => depth += 1,
+                ')' => depth = (depth - 1).max(0),
+                c if c.is_whitespace() && depth == 0 => {
+                    if let Some(word_start) = start.take() {
+                        words.push(&line[word_start..index]);
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+            start.get_or_insert(index);
+        }
+        if let Some(word_start) = start {
+            words.push(&line[word_start..]);
+        }
+        words
+    }
+    
     fn parse_set_statement(&self, line: &str) -> Result<Instruction, String> {
         // Parse "set speed +0.1", "set speed 2.0", or "set speed variable_name"
-        let parts: Vec<&str> = line.split_whitespace().collect();
+        let parts = Self::split_words(line);
         
         if parts.len() >= 3 && parts[0] == "set" {
             let property = parts[1];
             
             match property {
                 "speed" => {
+                    // "set speed beats 1/4" moves one cell per sixteenth note at any tempo
+                    if parts[2] == "beats" {
+                        return match &parts[3..] {
+                            [beats] => Ok(Instruction::SetSpeedBeats(self.parse_coordinate_expression(beats)?)),
+                            _ => Err("Invalid speed statement format. Expected: set speed beats <beats per cell>".to_string()),
+                        };
+                    }
                     let speed_str = parts[2];
                     
                     // Check if it starts with + or - for relative change
-                    if speed_str.starts_with('+') || speed_str.starts_with('-') {
+                    let speed_expr = if speed_str.starts_with('+') || speed_str.starts_with('-') {
                         // Relative speed change
-                        if let Ok(change) = speed_str.parse::<f32>() {
-                            return Ok(Instruction::SetSpeed(Expression::BinaryOp {
+                        match speed_str.parse::<f32>() {
+                            Ok(change) => Expression::BinaryOp {
                                 left: Box::new(Expression::BallProperty(BallProperty::Speed)),
                                 op: BinaryOperator::Add,
                                 right: Box::new(Expression::Literal(Value::Number(change))),
-                            }));
+                            },
+                            Err(_) => return Err(format!("Invalid relative speed: {}", speed_str)),
                         }
                     } else {
                         // Absolute speed change - use coordinate expression parser to handle variables
-                        let speed_expr = self.parse_coordinate_expression(speed_str)?;
-                        return Ok(Instruction::SetSpeed(speed_expr));
+                        self.parse_coordinate_expression(speed_str)?
+                    };
+                    
+                    // "set speed 3 over 500" glides to the new speed over 500ms
+                    if let ["over", duration] = &parts[3..] {
+                        return Ok(Instruction::SetSpeedGlide {
+                            target: speed_expr,
+                            duration_ms: self.parse_coordinate_expression(duration)?,
+                        });
                     }
+                    return Ok(Instruction::SetSpeed(speed_expr));
                 }
                 "direction" => {
                     if parts.len() >= 3 {
@@ -633,16 +1126,16 @@ impl SimpleProgramParser {
                 }
                 "color" => {
                     if parts.len() >= 3 {
-                        let color_str = parts[2];
-                        
-                        // Validate the color using existing validation method
-                        let validated_color = self.validate_color(color_str)?;
-                        
+                        let validated_color = self.parse_color_literal(parts[2])?;
                         return Ok(Instruction::SetColor(Expression::Literal(Value::String(validated_color))));
                     } else {
-                        return Err("Invalid color statement format. Expected: set color <color_name>".to_string());
+                        return Err("Invalid color statement format. Expected: set color <color_name|#rrggbb>".to_string());
                     }
                 }
+                "square-color" => {
+                    let validated_color = self.parse_color_literal(parts[2])?;
+                    return Ok(Instruction::SetSquareColor(Expression::Literal(Value::String(validated_color))));
+                }
                 "reverse" => {
                     // Parse "set reverse ball_reference speed"
                     if parts.len() >= 4 {
@@ -690,8 +1183,9 @@ impl SimpleProgramParser {
                                         return Err(format!("Invalid pitch change value: {}", pitch_str));
                                     }
                                 } else {
-                                    // Absolute pitch change - use coordinate expression parser to handle variables
-                                    self.parse_coordinate_expression(pitch_str)?
+                                    // Absolute pitch change - use coordinate expression parser to handle variables;
+                                    // the rest of the line is one expression, as in "set pitch 1 + 0.1*sin(time*6)"
+                                    self.parse_coordinate_expression(&parts[2..].join(" "))?
                                 }
                             }
                         };
@@ -700,6 +1194,99 @@ impl SimpleProgramParser {
                         return Err("Invalid pitch statement format. Expected: set pitch <value|note>".to_string());
                     }
                 }
+                "sample" => {
+                    // Parse "set sample <library>.<sample_name>", e.g. "set sample lib.kick.wav"
+                    if parts.len() >= 3 {
+                        let reference = parts[2..].join(" ");
+                        if let Some(dot_pos) = reference.find('.') {
+                            let library = reference[..dot_pos].to_string();
+                            let name = reference[dot_pos + 1..].to_string();
+                            if !library.is_empty() && !name.is_empty() {
+                                return Ok(Instruction::SetSample { library, name });
+                            }
+                        }
+                    }
+                    return Err("Invalid sample statement format. Expected: set sample <library>.<sample_name>".to_string());
+                }
+                "pitch-step" => {
+                    if parts.len() >= 3 {
+                        let step_str = parts[2];
+                        // Plain numbers (including negatives) are literal semitone counts
+                        let step_expr = match step_str.parse::<f32>() {
+                            Ok(semitones) => Expression::Literal(Value::Number(semitones)),
+                            Err(_) => self.parse_coordinate_expression(step_str)?,
+                        };
+                        return Ok(Instruction::SetPitchStep(step_expr));
+                    } else {
+                        return Err("Invalid pitch-step statement format. Expected: set pitch-step <semitones>".to_string());
+                    }
+                }
+                "bpm" => {
+                    if parts.len() >= 3 {
+                        // "+5" / "-5" nudge the current tempo, anything else sets it
+                        let bpm_str = parts[2];
+                        let relative = bpm_str.starts_with('+') || bpm_str.starts_with('-');
+                        let value_expr = match bpm_str.parse::<f32>() {
+                            Ok(bpm) => Expression::Literal(Value::Number(bpm)),
+                            Err(_) if !relative => self.parse_coordinate_expression(bpm_str)?,
+                            Err(_) => return Err(format!("Invalid bpm change '{}'. Expected: set bpm +<n> or set bpm -<n>", bpm_str)),
+                        };
+                        return Ok(Instruction::SetBpm { value: value_expr, relative });
+                    } else {
+                        return Err("Invalid bpm statement format. Expected: set bpm <value>".to_string());
+                    }
+                }
+                "solid" => {
+                    return match parts[2] {
+                        "on" => Ok(Instruction::SetSolid(true)),
+                        "off" => Ok(Instruction::SetSolid(false)),
+                        other => Err(format!("Invalid solid value '{}'. Expected: set solid on|off", other)),
+                    };
+                }
+                "humanize" => {
+                    if parts.len() >= 3 {
+                        let amount_expr = match parts[2].parse::<f32>() {
+                            Ok(amount) => Expression::Literal(Value::Number(amount)),
+                            Err(_) => self.parse_coordinate_expression(parts[2])?,
+                        };
+                        return Ok(Instruction::SetHumanize(amount_expr));
+                    } else {
+                        return Err("Invalid humanize statement format. Expected: set humanize <amount>".to_string());
+                    }
+                }
+                "envelope" => {
+                    if parts.len() >= 4 {
+                        let parse_ms = |text: &str| match text.parse::<f32>() {
+                            Ok(ms) => Ok(Expression::Literal(Value::Number(ms))),
+                            Err(_) => self.parse_coordinate_expression(text),
+                        };
+                        return Ok(Instruction::SetEnvelope { attack_ms: parse_ms(parts[2])?, release_ms: parse_ms(parts[3])? });
+                    } else {
+                        return Err("Invalid envelope statement format. Expected: set envelope <attack ms> <release ms>".to_string());
+                    }
+                }
+                "pan" => {
+                    if parts.len() >= 3 {
+                        let pan_expr = match parts[2].parse::<f32>() {
+                            Ok(pan) => Expression::Literal(Value::Number(pan)),
+                            Err(_) => self.parse_coordinate_expression(parts[2])?,
+                        };
+                        return Ok(Instruction::SetPan(pan_expr));
+                    } else {
+                        return Err("Invalid pan statement format. Expected: set pan <-1.0..1.0>".to_string());
+                    }
+                }
+                "filter" => {
+                    if parts.len() >= 3 {
+                        let cutoff_expr = match parts[2].parse::<f32>() {
+                            Ok(cutoff) => Expression::Literal(Value::Number(cutoff)),
+                            Err(_) => self.parse_coordinate_expression(parts[2])?,
+                        };
+                        return Ok(Instruction::SetFilter(cutoff_expr));
+                    } else {
+                        return Err("Invalid filter statement format. Expected: set filter <hz>".to_string());
+                    }
+                }
                 "volume" => {
                     if parts.len() >= 3 {
                         let volume_str = parts[2];
@@ -738,6 +1325,13 @@ impl SimpleProgramParser {
     fn parse_coordinate_expression(&self, coord_str: &str) -> Result<Expression, String> {
         let coord_str = coord_str.trim();
         
+        // Comparisons bind loosest, so "hits(self) + 1 > 8" compares the whole sum
+        if let Some((position, op, len)) = Self::find_comparison(coord_str) {
+            let left = self.parse_coordinate_expression(&coord_str[..position])?;
+            let right = self.parse_coordinate_expression(&coord_str[position + len..])?;
+            return Ok(Expression::BinaryOp { left: Box::new(left), op, right: Box::new(right) });
+        }
+        
         // Check for coordinate syntax like (0, 3)
         if coord_str.starts_with('(') && coord_str.ends_with(')') {
             let inner = &coord_str[1..coord_str.len()-1];
@@ -760,6 +1354,43 @@ impl SimpleProgramParser {
             return Ok(Expression::Literal(Value::String(string_content.to_string())));
         }
         
+        // "hits(self)", "hits(c_red)", "hits(square(3, 5))", ...
+        if let Some(target) = coord_str.strip_prefix("hits(").and_then(|rest| rest.strip_suffix(')')) {
+            return self.parse_hits_function(target.trim());
+        }
+        
+        // Check for "recent_hits(c_red, 2000)": hits by a color (or "any") in the last N ms
+        if coord_str.starts_with("recent_hits(") && coord_str.ends_with(')') && coord_str.matches(')').count() == 1 {
+            let args: Vec<&str> = coord_str[12..coord_str.len() - 1].split(',').map(|s| s.trim()).collect();
+            if args.len() != 2 {
+                return Err("Invalid recent_hits format. Expected: recent_hits(<color|any>, <milliseconds>)".to_string());
+            }
+            let color = if args[0] == "any" { "any".to_string() } else { self.validate_color(args[0])? };
+            let window_ms = args[1].parse::<u64>()
+                .map_err(|_| format!("Invalid time window in recent_hits: {}", args[1]))?;
+            return Ok(Expression::Variable(format!("__recent_hits_{}_{}", color, window_ms)));
+        }
+        
+        // "random(0.5, 3)": a uniform number in [min, max), drawn from the take's seeded RNG on every evaluation
+        if coord_str.starts_with("random(") && coord_str.ends_with(')') && coord_str.matches(')').count() == 1 {
+            let args: Vec<&str> = coord_str[7..coord_str.len() - 1].split(',').map(|s| s.trim()).collect();
+            let bounds: Vec<f32> = args.iter().filter_map(|arg| arg.parse::<f32>().ok()).collect();
+            if args.len() != 2 || bounds.len() != 2 {
+                return Err(format!("Invalid random format '{}'. Expected: random(<min>, <max>) with numbers", coord_str));
+            }
+            return Ok(Expression::Random { min: bounds[0].min(bounds[1]), max: bounds[0].max(bounds[1]) });
+        }
+        
+        // "voices": how many samples the audio engine is playing, so patches can throttle themselves
+        if coord_str == "voices" {
+            return Ok(Expression::Variable("__active_voices".to_string()));
+        }
+        
+        // "time": seconds since the take started, e.g. "1 + 0.1*sin(time*6)" for vibrato
+        if coord_str == "time" {
+            return Ok(Expression::Time);
+        }
+        
         // Check for ball properties
         if coord_str == "x" {
             return Ok(Expression::BallProperty(BallProperty::X));
@@ -771,9 +1402,10 @@ impl SimpleProgramParser {
             return Ok(Expression::BallProperty(BallProperty::Speed));
         }
         
-        // Check for arithmetic expressions like "x+1", "y-2", etc.
+        // Check for arithmetic expressions like "x+1", "y-2", etc.; operators inside a
+        // function call's parentheses belong to its arguments
         for op_char in ['+', '-', '*', '/', '%'] {
-            if let Some(op_pos) = coord_str.find(op_char) {
+            if let Some(op_pos) = Self::find_top_level(coord_str, op_char) {
                 let left_str = coord_str[..op_pos].trim();
                 let right_str = coord_str[op_pos + 1..].trim();
                 
@@ -804,6 +1436,24 @@ impl SimpleProgramParser {
             }
         }
         
+        // Built-in calls such as "clamp(v, lo, hi)" or "abs(x - 4)"; arguments can be calls too
+        if let Some((name, args)) = Self::split_call(coord_str) {
+            let Some(&(_, arity)) = EXPRESSION_FUNCTIONS.iter().find(|(function, _)| *function == name) else {
+                let supported: Vec<&str> = EXPRESSION_FUNCTIONS.iter().map(|(function, _)| *function).collect();
+                return Err(format!("Unknown function '{}'. Supported functions: {}", name, supported.join(", ")));
+            };
+            if args.len() != arity {
+                return Err(format!("{}() takes {} argument{}, got {}", name, arity, if arity == 1 { "" } else { "s" }, args.len()));
+            }
+            let args = args.iter()
+                .map(|arg| match arg.parse::<f32>() {
+                    Ok(number) => Ok(Expression::Literal(Value::Number(number))),
+                    Err(_) => self.parse_coordinate_expression(arg),
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            return Ok(Expression::Call { name: name.to_string(), args });
+        }
+        
         // Try to parse as a literal number
         if let Ok(num) = coord_str.parse::<f32>() {
             return Ok(Expression::Literal(Value::Number(num)));
@@ -822,6 +1472,19 @@ impl SimpleProgramParser {
         Ok(Expression::Variable(coord_str.to_string()))
     }
     
+    // Parse the "(x, y)" target of reset square / arm square
+    fn parse_square_target(&self, coords: &str, usage: &str) -> Result<(Expression, Expression), String> {
+        let coords = coords.trim();
+        if !coords.starts_with('(') || !coords.ends_with(')') {
+            return Err(format!("Invalid syntax. Expected: {}", usage));
+        }
+        let parts: Vec<&str> = coords[1..coords.len() - 1].split(',').map(|s| s.trim()).collect();
+        if parts.len() != 2 {
+            return Err(format!("Invalid coordinates. Expected: {}", usage));
+        }
+        Ok((self.parse_coordinate_expression(parts[0])?, self.parse_coordinate_expression(parts[1])?))
+    }
+    
     fn parse_create_statement(&self, line: &str) -> Result<Instruction, String> {
         // Parse "create ball(3,14)(self,self)", "create square(3, 17)", or "create ball from sample library.sample_name(3,4)"
         let content = &line[7..].trim(); // Remove "create "
@@ -831,6 +1494,43 @@ impl SimpleProgramParser {
             return self.parse_create_from_sample(content);
         }
         
+        // "create wall (x1, y1) to (x2, y2)" outlines a rectangle (a line on one row or
+        // column); a trailing "filled" fills it
+        if let Some(rest) = content.strip_prefix("wall") {
+            let usage = "create wall (x1, y1) to (x2, y2) [filled]";
+            let (rest, filled) = match rest.trim().strip_suffix("filled") {
+                Some(corners) => (corners.trim(), true),
+                None => (rest.trim(), false),
+            };
+            let (start, end) = rest.split_once(" to ").ok_or_else(|| format!("Invalid wall syntax. Expected: {}", usage))?;
+            return Ok(Instruction::CreateWall {
+                start: self.parse_square_target(start, usage)?,
+                end: self.parse_square_target(end, usage)?,
+                filled,
+            });
+        }
+        
+        // "create ball at random" or "create ball at random(speed,direction)"
+        if let Some(rest) = content.strip_prefix("ball at random") {
+            let rest = rest.trim();
+            if rest.is_empty() {
+                return Ok(Instruction::CreateBallRandom {
+                    speed: Expression::Literal(Value::Number(1.0)),
+                    direction: Expression::Literal(Value::Direction(Direction::Right)),
+                });
+            }
+            if rest.starts_with('(') && rest.ends_with(')') {
+                let params: Vec<&str> = rest[1..rest.len() - 1].split(',').map(|s| s.trim()).collect();
+                if params.len() == 2 {
+                    return Ok(Instruction::CreateBallRandom {
+                        speed: self.parse_speed_expression(params[0])?,
+                        direction: self.parse_direction_expression(params[1])?,
+                    });
+                }
+            }
+            return Err("Invalid random ball syntax. Expected: create ball at random(speed,direction)".to_string());
+        }
+        
         if let Some(paren_pos) = content.find('(') {
             if let Some(close_paren) = content.find(')') {
                 let object_type = content[..paren_pos].trim();
@@ -1099,7 +1799,7 @@ impl SimpleProgramParser {
     }
     
     fn parse_destroy_statement(&self, line: &str) -> Result<Instruction, String> {
-        // Parse "destroy ball(3,14)", "destroy ball(self)", or "destroy square(3, 17)"
+        // Parse "destroy ball(3,14)", "destroy ball(self)", "destroy ball(all)", "destroy ball(c_red)" or "destroy square(3, 17)"
         let content = &line[8..].trim(); // Remove "destroy "
         
         if let Some(paren_pos) = content.find('(') {
@@ -1107,6 +1807,20 @@ impl SimpleProgramParser {
                 let object_type = content[..paren_pos].trim();
                 let target_str = &content[paren_pos + 1..close_paren].trim();
                 
+                // Everything, or everything of one color
+                if *target_str == "all" || target_str.starts_with("c_") || target_str.starts_with('#') {
+                    let target = if *target_str == "all" {
+                        DestroyTarget::All
+                    } else {
+                        DestroyTarget::Color(self.parse_color_literal(target_str)?)
+                    };
+                    return match object_type {
+                        "ball" => Ok(Instruction::DestroyBall { target }),
+                        "square" => Ok(Instruction::DestroySquare { target }),
+                        _ => Err(format!("Unknown object type: {}", object_type)),
+                    };
+                }
+                
                 // Check if it's a ball reference (contains no comma or is "self")
                 if *target_str == "self" || (target_str.contains("last.") && !target_str.contains(",")) {
                     // Ball reference syntax
@@ -1144,23 +1858,48 @@ impl SimpleProgramParser {
             }
         }
         
-        Err("Invalid destroy statement format. Expected: destroy ball(x,y), destroy ball(self), or destroy square(x,y)".to_string())
+        Err("Invalid destroy statement format. Expected: destroy ball(x,y), destroy ball(self), destroy ball(all), destroy ball(c_red), or destroy square(x,y)".to_string())
     }
     
     fn parse_print_statement(&self, line: &str) -> Result<Instruction, String> {
         // Parse "print expression" or "print hits(target)"
         let content = &line[6..].trim(); // Remove "print "
-        println!("DEBUG: Parsing print statement with content: '{}'", content);
+        log::debug!("Parsing print statement with content: '{}'", content);
         
         if content.is_empty() {
             return Err("Print statement requires an expression".to_string());
         }
         
         let expr = self.parse_print_expression(content)?;
-        println!("DEBUG: Parsed print expression: {:?}", expr);
+        log::debug!("Parsed print expression: {:?}", expr);
         Ok(Instruction::Print(expr))
     }
     
+    fn parse_log_statement(&self, line: &str) -> Result<Instruction, String> {
+        // Parse 'log "speed is " speed ", hits " hits(self)': quoted text is kept as-is,
+        // the text between quotes is one or more comma-separated expressions
+        let mut parts = Vec::new();
+        let mut rest = line[4..].trim();
+        while !rest.is_empty() {
+            if let Some(quoted) = rest.strip_prefix('"') {
+                let end = quoted.find('"').ok_or_else(|| format!("Unterminated string in log statement: {}", line))?;
+                parts.push(Expression::Literal(Value::String(quoted[..end].to_string())));
+                rest = quoted[end + 1..].trim_start();
+            } else {
+                let end = rest.find('"').unwrap_or(rest.len());
+                for expr_str in rest[..end].split(',').map(|s| s.trim()).filter(|s| !s.is_empty()) {
+                    parts.push(self.parse_print_expression(expr_str)?);
+                }
+                rest = &rest[end..];
+            }
+        }
+        
+        if parts.is_empty() {
+            return Err("Log statement requires a message".to_string());
+        }
+        Ok(Instruction::Log(parts))
+    }
+    
     fn parse_print_expression(&self, expr_str: &str) -> Result<Expression, String> {
         // Check if it's a hits() function call
         if expr_str.starts_with("hits(") && expr_str.ends_with(")") {
@@ -1173,7 +1912,18 @@ impl SimpleProgramParser {
     }
     
     fn parse_hits_function(&self, target: &str) -> Result<Expression, String> {
-        // Parse hits(self), hits(c_red), hits(ball1), hits(square(3, 5)), etc.
+        // Parse hits(self), hits(c_red), hits(c_red, self), hits(ball1), hits(square(3, 5)), etc.
+        if !target.starts_with("square(") {
+            if let Some((color, scope)) = target.split_once(',') {
+                if scope.trim() != "self" {
+                    return Err(format!("Invalid hits() scope '{}'. Expected: hits(<color>, self)", scope.trim()));
+                }
+                // Hits by that color on this square only, rather than on every square
+                let color = self.validate_color(color.trim())?;
+                return Ok(Expression::Variable(format!("__ball_color_square_hits_{}", color)));
+            }
+        }
+        
         if target == "self" {
             // Return hits for current square
             return Ok(Expression::Variable("__square_hits".to_string()));
@@ -1202,7 +1952,7 @@ impl SimpleProgramParser {
             }
         }
         
-        Err(format!("Invalid hits() target: {}", target))
+        Err(format!("Invalid hits() target: {}. Use hits(self) for this square, hits(c_red) or hits(ball1) for hits on any square, hits(c_red, self) for one color's hits on this square, or hits(square(x, y))", target))
     }
     
     fn parse_slice_statement(&self, line: &str) -> Result<Instruction, String> {
@@ -1231,6 +1981,15 @@ impl SimpleProgramParser {
     }
 }
 
+// A ball color as programs name it: "Red" and "c_red" are both "c_red"
+fn ball_color_key(color: &str) -> String {
+    if color.starts_with("c_") {
+        color.to_string()
+    } else {
+        format!("c_{}", color.to_lowercase())
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct ProgramExecutor {
     pub state: ProgrammerState,
@@ -1252,13 +2011,64 @@ impl ProgramExecutor {
         self.state.slice_hit_indices.clear();
     }
     
+    pub fn reset_square_hit_counts(&mut self, square_x: usize, square_y: usize) {
+        self.state.square_hit_counts.remove(&(square_x, square_y));
+        self.state.ball_color_square_hits.retain(|(_, x, y), _| !(*x == square_x && *y == square_y));
+    }
+    
+    // Forget the hits and variables of the square at (square_x, square_y), as if it had never been hit
+    pub fn reset_square_state(&mut self, square_x: usize, square_y: usize) {
+        self.reset_square_hit_counts(square_x, square_y);
+        self.state.square_variables.remove(&(square_x, square_y));
+    }
+    
     pub fn reset_variables(&mut self) {
         self.state.variables.clear();
+        self.state.square_variables.clear();
+    }
+    
+    // Values for the hit-count, recent-hit and voice variables `instructions` read, as seen
+    // from the square at (square_x, square_y), so they also resolve in interpreters that only
+    // see the context; `recent_hits` counts over `history`. Variables the program never
+    // reads are left out.
+    pub fn hit_count_variables(&self, square_x: usize, square_y: usize, instructions: &[Instruction], history: &VecDeque<CollisionEvent>) -> HashMap<String, Value> {
+        let mut variables = HashMap::new();
+        for instruction in instructions {
+            instruction.for_each_variable(&mut |name| {
+                if variables.contains_key(name) {
+                    return;
+                }
+                let count = if name == "__square_hits" {
+                    self.state.square_hit_counts.get(&(square_x, square_y)).copied().unwrap_or(0)
+                } else if name == "__active_voices" {
+                    self.state.active_voices
+                } else if let Some(color) = name.strip_prefix("__ball_color_square_hits_") {
+                    self.state.ball_color_square_hits.get(&(color.to_string(), square_x, square_y)).copied().unwrap_or(0)
+                } else if let Some((x, y)) = name.strip_prefix("__square_hits_").and_then(|coords| coords.split_once('_')) {
+                    match (x.parse::<usize>(), y.parse::<usize>()) {
+                        (Ok(x), Ok(y)) => self.state.square_hit_counts.get(&(x, y)).copied().unwrap_or(0),
+                        _ => return,
+                    }
+                } else if let Some((color, window_ms)) = name.strip_prefix("__recent_hits_").and_then(|query| query.rsplit_once('_')) {
+                    // "<color>_<ms>": hits by that color (or any) within the window, this one included
+                    let Ok(window_ms) = window_ms.parse() else {
+                        return;
+                    };
+                    let matches = |event: &CollisionEvent| color == "any" || ball_color_key(&event.ball_color) == color;
+                    count_collisions_within(history, self.state.elapsed, Duration::from_millis(window_ms), matches) as u32
+                } else {
+                    return;
+                };
+                variables.insert(name.to_string(), Value::Number(count as f32));
+            });
+        }
+        variables
     }
     
     pub fn reset_all_state(&mut self) {
         self.reset_all_hit_counts();
         self.reset_variables();
+        self.state.elapsed = 0.0;
     }
     
     pub fn execute_on_collision(
@@ -1267,6 +2077,7 @@ impl ProgramExecutor {
         ball: &Ball,
         square_x: usize,
         square_y: usize,
+        history: &VecDeque<CollisionEvent>,
     ) -> Vec<ProgramAction> {
         // Get current hit counts WITHOUT incrementing them yet
         let ball_color = self.get_ball_color(ball);
@@ -1275,11 +2086,20 @@ impl ProgramExecutor {
         let ball_color_square_key = (ball_color.clone(), square_x, square_y);
         let current_ball_color_square_hits = *self.state.ball_color_square_hits.get(&ball_color_square_key).unwrap_or(&0);
         let ball_self_key = format!("__ball_hits_{}_self", ball.id);
+        // Per-square hits are also kept under the ball's id and name so
+        // "if ball1 hits self" / "if kick hits self" can look them up
+        let ball_object_square_keys: Vec<(String, usize, usize)> = std::iter::once(ball.id.clone())
+            .chain(ball.name.clone())
+            .map(|object| (object, square_x, square_y))
+            .collect();
         let current_ball_self_hits = *self.state.ball_object_hit_counts.get(&ball_self_key).unwrap_or(&0);
         
-        // Create execution context with CURRENT (not incremented) hit counts
+        // Create execution context with CURRENT (not incremented) hit counts and this
+        // square's own variables from its earlier hits
+        let mut variables = self.state.square_variables.get(&(square_x, square_y)).cloned().unwrap_or_default();
+        variables.extend(self.hit_count_variables(square_x, square_y, &program.instructions, history));
         let mut context = ExecutionContext {
-            variables: self.state.variables.clone(),
+            variables,
             ball_hit_count: current_ball_color_square_hits,
             square_hit_count: current_square_hits,
             ball_x: ball.x,
@@ -1290,6 +2110,7 @@ impl ProgramExecutor {
             ball_volume: ball.volume,
             square_x,
             square_y,
+            time: self.state.elapsed,
         };
         
         // Execute the program FIRST
@@ -1300,16 +2121,24 @@ impl ProgramExecutor {
         *self.state.square_hit_counts.entry((square_x, square_y)).or_insert(0) += 1;
         *self.state.ball_color_square_hits.entry(ball_color_square_key.clone()).or_insert(0) += 1;
         *self.state.ball_object_hit_counts.entry(ball_self_key.clone()).or_insert(0) += 1;
+        for key in ball_object_square_keys {
+            *self.state.ball_color_square_hits.entry(key).or_insert(0) += 1;
+        }
+        if let Some(name) = &ball.name {
+            *self.state.ball_object_hit_counts.entry(format!("__ball_hits_{}_self", name)).or_insert(0) += 1;
+        }
         
         // Debug logging with the NEW incremented counts
         let ball_hits = *self.state.ball_hit_counts.get(&ball_color).unwrap();
         let square_hits = *self.state.square_hit_counts.get(&(square_x, square_y)).unwrap();
         let ball_self_hits = *self.state.ball_object_hit_counts.get(&ball_self_key).unwrap_or(&0);
-        println!("DEBUG: Ball {} (color {:?}) hits: {}, Square ({},{}) hits: {}, Ball self hits: {}", 
+        log::debug!("Ball {} (color {:?}) hits: {}, Square ({},{}) hits: {}, Ball self hits: {}", 
             ball.id, ball_color, ball_hits, square_x, square_y, square_hits, ball_self_hits);
         
-        // Update state with any variable changes
-        self.state.variables = context.variables;
+        // Keep this square's variables for its next hit (hit counts and other
+        // built-ins are recomputed on every run)
+        context.variables.retain(|name, _| !name.starts_with("__"));
+        self.state.square_variables.insert((square_x, square_y), context.variables);
         
         // Process SetGlobalVariable actions and remove them from the action list
         let mut filtered_actions = Vec::new();
@@ -1330,13 +2159,7 @@ impl ProgramExecutor {
     }
     
     fn get_ball_color(&self, ball: &Ball) -> String {
-        // Convert ball color to c_ prefix format for consistency with parser
-        let color = &ball.color;
-        if color.starts_with("c_") {
-            color.clone()
-        } else {
-            format!("c_{}", color.to_lowercase())
-        }
+        ball_color_key(&ball.color)
     }
     
     fn execute_instructions(&self, instructions: &[Instruction], context: &mut ExecutionContext) -> Vec<ProgramAction> {
@@ -1349,6 +2172,17 @@ impl ProgramExecutor {
                         actions.push(ProgramAction::SetSpeed(speed));
                     }
                 }
+                Instruction::SetSpeedGlide { target, duration_ms } => {
+                    if let (Value::Number(target), Value::Number(duration_ms)) =
+                        (self.evaluate_expression(target, context), self.evaluate_expression(duration_ms, context)) {
+                        actions.push(ProgramAction::SetSpeedGlide { target, duration_ms });
+                    }
+                }
+                Instruction::SetSpeedBeats(expr) => {
+                    if let Value::Number(beats) = self.evaluate_expression(expr, context) {
+                        actions.push(ProgramAction::SetSpeedBeats(beats));
+                    }
+                }
                 Instruction::SetDirection(expr) => {
                     if let Value::Direction(dir) = self.evaluate_expression(expr, context) {
                         actions.push(ProgramAction::SetDirection(dir));
@@ -1359,6 +2193,36 @@ impl ProgramExecutor {
                         actions.push(ProgramAction::SetPitch(pitch));
                     }
                 }
+                Instruction::SetPitchStep(expr) => {
+                    if let Value::Number(semitones) = self.evaluate_expression(expr, context) {
+                        actions.push(ProgramAction::SetPitchStep(semitones));
+                    }
+                }
+                Instruction::SetHumanize(expr) => {
+                    if let Value::Number(amount) = self.evaluate_expression(expr, context) {
+                        actions.push(ProgramAction::SetHumanize(amount));
+                    }
+                }
+                Instruction::SetEnvelope { attack_ms, release_ms } => {
+                    if let (Value::Number(attack_ms), Value::Number(release_ms)) = (self.evaluate_expression(attack_ms, context), self.evaluate_expression(release_ms, context)) {
+                        actions.push(ProgramAction::SetEnvelope { attack_ms, release_ms });
+                    }
+                }
+                Instruction::SetPan(expr) => {
+                    if let Value::Number(pan) = self.evaluate_expression(expr, context) {
+                        actions.push(ProgramAction::SetPan(pan));
+                    }
+                }
+                Instruction::SetFilter(expr) => {
+                    if let Value::Number(cutoff) = self.evaluate_expression(expr, context) {
+                        actions.push(ProgramAction::SetFilter(cutoff));
+                    }
+                }
+                Instruction::SetBpm { value, relative } => {
+                    if let Value::Number(bpm) = self.evaluate_expression(value, context) {
+                        actions.push(ProgramAction::SetBpm { value: bpm, relative: *relative });
+                    }
+                }
                 Instruction::SetVolume(expr) => {
                     if let Value::Number(volume) = self.evaluate_expression(expr, context) {
                         actions.push(ProgramAction::SetVolume(volume));
@@ -1369,9 +2233,23 @@ impl ProgramExecutor {
                         actions.push(ProgramAction::SetColor(color));
                     }
                 }
+                Instruction::SetSquareColor(expr) => {
+                    if let Value::String(color) = self.evaluate_expression(expr, context) {
+                        actions.push(ProgramAction::SetSquareColor { x: context.square_x, y: context.square_y, color });
+                    }
+                }
+                Instruction::SetSample { library, name } => {
+                    actions.push(ProgramAction::SetSample { library: library.clone(), name: name.clone() });
+                }
                 Instruction::Bounce => {
                     actions.push(ProgramAction::Bounce);
                 }
+                Instruction::Reflect => {
+                    actions.push(ProgramAction::Reflect);
+                }
+                Instruction::Pass => {
+                    actions.push(ProgramAction::Pass);
+                }
                 Instruction::Stop => {
                     actions.push(ProgramAction::Stop);
                 }
@@ -1395,7 +2273,7 @@ impl ProgramExecutor {
                 }
                 Instruction::Loop { count, body } => {
                     if let Value::Number(n) = self.evaluate_expression(count, context) {
-                        for _ in 0..(n as i32).max(0) {
+                        for _ in 0..(n.max(0.0) as usize).min(MAX_LOOP_ITERATIONS) {
                             actions.extend(self.execute_instructions(body, context));
                         }
                     }
@@ -1425,6 +2303,24 @@ impl ProgramExecutor {
                         actions.push(ProgramAction::CreateBall { x, y, speed: s, direction: d });
                     }
                 }
+                Instruction::CreateBallRandom { speed, direction } => {
+                    let speed_val = self.evaluate_expression(speed, context);
+                    let dir_val = self.evaluate_expression(direction, context);
+                    
+                    if let (Value::Number(s), Value::Direction(d)) = (speed_val, dir_val) {
+                        actions.push(ProgramAction::CreateBallRandom { speed: s, direction: d });
+                    }
+                }
+                Instruction::SpawnBurst { count } => {
+                    if let Value::Number(n) = self.evaluate_expression(count, context) {
+                        actions.push(ProgramAction::SpawnBurst {
+                            x: context.square_x as f32,
+                            y: context.square_y as f32,
+                            count: n.max(0.0) as usize,
+                            speed: context.ball_speed,
+                        });
+                    }
+                }
                 Instruction::CreateSquare { x, y } => {
                     let x_val = self.evaluate_expression(x, context);
                     let y_val = self.evaluate_expression(y, context);
@@ -1433,6 +2329,17 @@ impl ProgramExecutor {
                         actions.push(ProgramAction::CreateSquare { x: x as i32, y: y as i32 });
                     }
                 }
+                Instruction::CreateWall { start, end, filled } => {
+                    let corners = (
+                        self.evaluate_expression(&start.0, context), self.evaluate_expression(&start.1, context),
+                        self.evaluate_expression(&end.0, context), self.evaluate_expression(&end.1, context),
+                    );
+                    if let (Value::Number(x1), Value::Number(y1), Value::Number(x2), Value::Number(y2)) = corners {
+                        for (x, y) in crate::square::wall_cells((x1 as i32, y1 as i32), (x2 as i32, y2 as i32), *filled) {
+                            actions.push(ProgramAction::CreateSquare { x, y });
+                        }
+                    }
+                }
                 Instruction::CreateSquareWithProgram { x, y, program } => {
                     let x_val = self.evaluate_expression(x, context);
                     let y_val = self.evaluate_expression(y, context);
@@ -1505,6 +2412,8 @@ impl ProgramExecutor {
                     DestroyTarget::BallReference(ball_ref) => {
                         actions.push(ProgramAction::DestroyBall { x: 0.0, y: 0.0, ball_reference: Some(ball_ref.clone()) });
                     }
+                    DestroyTarget::All => actions.push(ProgramAction::DestroyBalls { color: None }),
+                    DestroyTarget::Color(color) => actions.push(ProgramAction::DestroyBalls { color: Some(color.clone()) }),
                 }
             }
             Instruction::DestroySquare { target } => {
@@ -1519,29 +2428,31 @@ impl ProgramExecutor {
                     DestroyTarget::BallReference(ball_ref) => {
                         actions.push(ProgramAction::DestroySquare { x: 0.0, y: 0.0, ball_reference: Some(ball_ref.clone()) });
                     }
+                    DestroyTarget::All => actions.push(ProgramAction::DestroySquares { color: None }),
+                    DestroyTarget::Color(color) => actions.push(ProgramAction::DestroySquares { color: Some(color.clone()) }),
                 }
             }
-                Instruction::ExecuteLibraryFunction { library_function } => {
+                Instruction::ExecuteLibraryFunction { library_function, args } => {
+                    let args = args.iter().map(|arg| self.evaluate_expression(arg, context)).collect();
                     actions.push(ProgramAction::ExecuteLibraryFunction {
                         library_function: library_function.clone(),
+                        args,
                     });
                 }
                 Instruction::Return(function_name) => {
                     actions.push(ProgramAction::Return(function_name.clone()));
                     break; // Exit the instruction loop immediately
                 }
+                Instruction::Log(parts) => {
+                    let text: String = parts.iter().map(|part| self.evaluate_expression(part, context).display_text()).collect();
+                    actions.push(ProgramAction::Log(text));
+                }
                 Instruction::Print(expr) => {
-                    println!("DEBUG: Print instruction with expression: {:?}", expr);
+                    log::debug!("Print instruction with expression: {:?}", expr);
                     let val = self.evaluate_expression(expr, context);
-                    println!("DEBUG: Evaluated expression to value: {:?}", val);
-                    let display_text = match val {
-                        Value::Number(n) => n.to_string(),
-                        Value::Boolean(b) => b.to_string(),
-                        Value::Direction(d) => format!("{:?}", d),
-                        Value::String(s) => s,
-                        Value::Coordinate(x, y) => format!("({}, {})", x, y),
-                    };
-                    println!("DEBUG: Final display text: {}", display_text);
+                    log::debug!("Evaluated expression to value: {:?}", val);
+                    let display_text = val.display_text();
+                    log::debug!("Final display text: {}", display_text);
                     actions.push(ProgramAction::Print(display_text));
                 }
                 Instruction::SetSliceArray { markers } => {
@@ -1551,6 +2462,52 @@ impl ProgramExecutor {
                         markers: markers.clone(),
                     });
                 }
+                Instruction::ResetHits => {
+                    actions.push(ProgramAction::ResetHits {
+                        x: context.square_x,
+                        y: context.square_y,
+                    });
+                }
+                Instruction::SetSolid(solid) => {
+                    actions.push(ProgramAction::SetSolid {
+                        x: context.square_x,
+                        y: context.square_y,
+                        solid: *solid,
+                    });
+                }
+                Instruction::Wait(expr) => {
+                    if let Value::Number(ms) = self.evaluate_expression(expr, context) {
+                        actions.push(ProgramAction::Wait(ms.max(0.0)));
+                    }
+                }
+                Instruction::SetActiveProgram(expr) => {
+                    let program = self.evaluate_expression(expr, context);
+                    actions.push(ProgramAction::SetActiveProgram {
+                        x: context.square_x,
+                        y: context.square_y,
+                        program,
+                    });
+                }
+                Instruction::Teleport { x, y } => {
+                    if let (Value::Number(x), Value::Number(y)) = (self.evaluate_expression(x, context), self.evaluate_expression(y, context)) {
+                        actions.push(ProgramAction::Teleport { x: x.round() as i32, y: y.round() as i32 });
+                    }
+                }
+                Instruction::ResetSquare { x, y } => {
+                    if let (Value::Number(x), Value::Number(y)) = (self.evaluate_expression(x, context), self.evaluate_expression(y, context)) {
+                        if x >= 0.0 && y >= 0.0 {
+                            actions.push(ProgramAction::ResetHits { x: x.round() as usize, y: y.round() as usize });
+                        }
+                    }
+                }
+                Instruction::ArmSquare { x, y, program } => {
+                    if let (Value::Number(x), Value::Number(y)) = (self.evaluate_expression(x, context), self.evaluate_expression(y, context)) {
+                        if x >= 0.0 && y >= 0.0 {
+                            let program = self.evaluate_expression(program, context);
+                            actions.push(ProgramAction::SetActiveProgram { x: x.round() as usize, y: y.round() as usize, program });
+                        }
+                    }
+                }
                 Instruction::End => {
                     actions.push(ProgramAction::End);
                     break; // Exit the instruction loop immediately
@@ -1566,11 +2523,11 @@ impl ProgramExecutor {
         match expr {
             Expression::Literal(value) => value.clone(),
             Expression::Variable(name) => {
-                // Handle special hit count variables
+                // Handle special hit count variables (recent_hits comes in through the context)
                 if name == "__square_hits" {
                     // Return hits for current square
                     let hits = self.state.square_hit_counts.get(&(context.square_x, context.square_y)).unwrap_or(&0);
-                    println!("DEBUG: __square_hits for ({},{}) = {}", context.square_x, context.square_y, hits);
+                    log::debug!("__square_hits for ({},{}) = {}", context.square_x, context.square_y, hits);
                     return Value::Number(*hits as f32);
                 }
                 
@@ -1579,7 +2536,7 @@ impl ProgramExecutor {
                 let ball_color = &name[25..]; // Remove "__ball_color_square_hits_" prefix to get the color
                 let key = (ball_color.to_string(), context.square_x, context.square_y);
                 let hits = self.state.ball_color_square_hits.get(&key).unwrap_or(&0);
-                println!("DEBUG: Ball color square hits for {} = {}", name, hits);
+                log::debug!("Ball color square hits for {} = {}", name, hits);
                 return Value::Number(*hits as f32);
             }
             
@@ -1588,20 +2545,20 @@ impl ProgramExecutor {
                 let color_part = &name[14..]; // Remove "__ball_hits_c_" prefix
                 let full_color_key = format!("c_{}", color_part); // Add "c_" prefix to match storage format
                 let hits = self.state.ball_hit_counts.get(&full_color_key).unwrap_or(&0);
-                println!("DEBUG: Color hit count for {} (key: {}) = {}", name, full_color_key, hits);
+                log::debug!("Color hit count for {} (key: {}) = {}", name, full_color_key, hits);
                 return Value::Number(*hits as f32);
             }
             
-            if name.starts_with("__ball_hits_ball") {
-                // Return hits for specific ball object (ball1, ball2, etc.)
+            if name.starts_with("__ball_hits_") {
+                // Return hits for specific ball object (ball1, ball2, etc., or a ball name)
                 let hits = self.state.ball_object_hit_counts.get(name).unwrap_or(&0);
-                println!("DEBUG: Ball object hit count for {} = {} (available keys: {:?})", name, hits, self.state.ball_object_hit_counts.keys().collect::<Vec<_>>());
+                log::debug!("Ball object hit count for {} = {} (available keys: {:?})", name, hits, self.state.ball_object_hit_counts.keys().collect::<Vec<_>>());
                 return Value::Number(*hits as f32);
             }
             
             if name.starts_with("__square_hits_") {
                     // Return hits for specific square coordinates
-                    let coords_str = &name[15..]; // Remove "__square_hits_" prefix
+                    let coords_str = &name[14..]; // Remove "__square_hits_" prefix
                     if let Some(underscore_pos) = coords_str.find('_') {
                         let x_str = &coords_str[..underscore_pos];
                         let y_str = &coords_str[underscore_pos + 1..];
@@ -1612,7 +2569,8 @@ impl ProgramExecutor {
                     }
                 }
                 
-                context.variables.get(name).cloned().unwrap_or(Value::Number(0.0))
+                // A square's own variable shadows a global of the same name
+                context.variables.get(name).or_else(|| self.state.variables.get(name)).cloned().unwrap_or(Value::Number(0.0))
             }
             Expression::GlobalVariable(name) => {
                 // Look up global variable in the ProgrammerState
@@ -1636,9 +2594,17 @@ impl ProgramExecutor {
             }
             Expression::Random { min, max } => {
                 use rand::Rng;
-                let mut rng = rand::thread_rng();
-                Value::Number(rng.gen_range(*min..*max))
+                if min < max {
+                    Value::Number(crate::rng::with_rng(|rng| rng.gen_range(*min..*max)))
+                } else {
+                    Value::Number(*min) // random(3, 3) is just 3; an empty range would panic
+                }
+            }
+            Expression::Call { name, args } => {
+                let args: Vec<Value> = args.iter().map(|arg| self.evaluate_expression(arg, context)).collect();
+                call_function(name, &args)
             }
+            Expression::Time => Value::Number(context.time),
         }
     }
     
@@ -1669,7 +2635,230 @@ impl ProgramExecutor {
                     _ => Value::Boolean(false),
                 }
             }
+            (Value::Direction(a), Value::Direction(b)) => {
+                match op {
+                    BinaryOperator::Equal => Value::Boolean(a == b),
+                    BinaryOperator::NotEqual => Value::Boolean(a != b),
+                    _ => Value::Boolean(false),
+                }
+            }
             _ => Value::Boolean(false),
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Parse the body of a single `def test` function
+    fn parse(body: &str) -> Result<Program, String> {
+        SimpleProgramParser::new().parse_program(&format!("def test\n{}", body))
+    }
+
+    // Whether `if <condition>` fires on a square that has already been hit `hits` times
+    fn fires(condition: &str, hits: u32) -> bool {
+        let program = parse(&format!("if {}\nset speed 2", condition)).unwrap();
+        let mut executor = ProgramExecutor::new();
+        executor.state.square_hit_counts.insert((2, 2), hits);
+        let ball = Ball::new(1, 1, "ball1".to_string());
+        executor.execute_on_collision(&program, &ball, 2, 2, &VecDeque::new()).contains(&ProgramAction::SetSpeed(2.0))
+    }
+
+    #[test]
+    fn comparisons_against_square_hits() {
+        let cases = [
+            ("<", [true, false, false]),
+            ("<=", [true, true, false]),
+            (">", [false, false, true]),
+            (">=", [false, true, true]),
+            ("==", [false, true, false]),
+            ("!=", [true, false, true]),
+        ];
+        for (op, expected) in cases {
+            for (hits, expected) in [7, 8, 9].into_iter().zip(expected) {
+                assert_eq!(fires(&format!("hits(self) {} 8", op), hits), expected, "hits(self) {} 8 with {} hits", op, hits);
+            }
+        }
+    }
+
+    #[test]
+    fn arithmetic_binds_tighter_than_comparison() {
+        assert!(!fires("hits(self) + 1 > 8", 7));
+        assert!(fires("hits(self) + 1 > 8", 8));
+        assert!(fires("hits(self) * 2 >= 16", 8));
+        assert!(!fires("hits(self) * 2 >= 16", 7));
+        assert!(fires("9 > hits(self) + 1", 7));
+    }
+
+    // How many balls `body` creates on a square that has already been hit `hits` times
+    fn balls_created(body: &str, hits: u32) -> usize {
+        let program = parse(body).unwrap();
+        let mut executor = ProgramExecutor::new();
+        executor.state.square_hit_counts.insert((2, 2), hits);
+        let ball = Ball::new(1, 1, "ball1".to_string());
+        executor.execute_on_collision(&program, &ball, 2, 2, &VecDeque::new()).iter()
+            .filter(|action| matches!(action, ProgramAction::CreateBall { .. }))
+            .count()
+    }
+
+    #[test]
+    fn loops_inside_if_and_else_repeat_their_body() {
+        assert_eq!(balls_created("loop 3\ncreate ball(3,4)(self,self)\nend", 0), 3);
+
+        let branches = "if hits(self) > 1\nloop 3\ncreate ball(3,4)(self,self)\nend\nelse\nloop 2\ncreate ball(3,4)(self,self)\nend\nend";
+        assert_eq!(balls_created(branches, 2), 3);
+        assert_eq!(balls_created(branches, 1), 2);
+    }
+
+    #[test]
+    fn square_variables_carry_over_between_hits() {
+        let program = parse("var count = count + 1\nset speed count").unwrap();
+        let mut executor = ProgramExecutor::new();
+        let ball = Ball::new(1, 1, "ball1".to_string());
+        for expected in [1.0, 2.0, 3.0] {
+            assert!(executor.execute_on_collision(&program, &ball, 2, 2, &VecDeque::new()).contains(&ProgramAction::SetSpeed(expected)));
+        }
+        // Another square counts on its own
+        assert!(executor.execute_on_collision(&program, &ball, 3, 2, &VecDeque::new()).contains(&ProgramAction::SetSpeed(1.0)));
+    }
+
+    // `expression` evaluated on a hit by a ball at (1.5, 1.5) moving at speed 2
+    fn evaluate(expression: &str) -> f32 {
+        let program = parse(&format!("var result = {}", expression)).unwrap();
+        let mut executor = ProgramExecutor::new();
+        executor.execute_on_collision(&program, &Ball::new(1, 1, "ball1".to_string()), 2, 2, &VecDeque::new());
+        match executor.state.square_variables[&(2, 2)]["result"] {
+            Value::Number(n) => n,
+            ref other => panic!("{} gave {:?}", expression, other),
+        }
+    }
+
+    #[test]
+    fn numeric_functions_on_literals_and_ball_properties() {
+        let cases = [
+            ("abs(2.5)", 2.5), ("abs(x - 5)", 3.5),
+            ("floor(2.7)", 2.0), ("floor(x)", 1.0),
+            ("ceil(2.1)", 3.0), ("ceil(x)", 2.0),
+            ("round(2.4)", 2.0), ("round(2.5)", 3.0), ("round(y)", 2.0),
+            ("sqrt(16)", 4.0), ("sqrt(speed * 8)", 4.0),
+        ];
+        for (expression, expected) in cases {
+            assert_eq!(evaluate(expression), expected, "{}", expression);
+        }
+    }
+
+    #[test]
+    fn sqrt_of_a_negative_number_is_zero() {
+        assert_eq!(evaluate("sqrt(0 - 4)"), 0.0);
+        assert_eq!(evaluate("sqrt(x - 5)"), 0.0);
+    }
+
+    // Source line a parse error is reported on
+    fn error_line(source: &str) -> usize {
+        let error = SimpleProgramParser::new().parse_multiple_programs(source).unwrap_err();
+        error.strip_prefix("line ")
+            .and_then(|rest| rest.split(':').next())
+            .and_then(|number| number.parse().ok())
+            .unwrap_or_else(|| panic!("no line number in '{}'", error))
+    }
+
+    #[test]
+    fn parse_errors_name_the_offending_line() {
+        assert_eq!(error_line("def a\nset speed 2\nfrobnicate"), 3);
+        assert_eq!(error_line("frobnicate\ndef a\nset speed 2"), 1);
+        assert_eq!(error_line("def a\nif hits(self) > 1\nset speed 2\nfrobnicate\nend"), 4);
+        assert_eq!(error_line("def a\nif hits(self) > 1\nset speed 2\nelse\nfrobnicate\nend"), 5);
+        assert_eq!(error_line("def a\nloop 2\nset speed 2\nfrobnicate\nend"), 4);
+        assert_eq!(error_line("def a\nloop 2\nset speed 2"), 2);
+    }
+
+    #[test]
+    fn blank_lines_count_towards_the_reported_line() {
+        assert_eq!(error_line("\n\ndef a\n\nset speed 2\n\n\nfrobnicate"), 8);
+        assert_eq!(error_line("def a\n\nloop 2\n\nfrobnicate\nend"), 5);
+    }
+
+    #[test]
+    fn lib_call_arguments_split_at_top_level_commas() {
+        let program = parse("lib.f(max(1, 2), 3)").unwrap();
+        match &program.instructions[..] {
+            [Instruction::ExecuteLibraryFunction { library_function, args }] => {
+                assert_eq!(library_function, "lib.f");
+                assert_eq!(args.len(), 2);
+                assert!(matches!(&args[0], Expression::Call { name, args } if name == "max" && args.len() == 2));
+            }
+            other => panic!("unexpected instructions: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unknown_ball_references_flag_only_missing_balls() {
+        let program = parse("var n = hits(ball9)\nvar m = hits(c_red)\nif kick hits self 2 times\nset speed 2\nif kik hits ball1 1 times\nset speed 3").unwrap();
+        let known = vec!["ball1".to_string(), "kick".to_string()];
+        assert_eq!(SimpleProgramParser::unknown_ball_references(&program.instructions, &known), vec!["ball9", "kik"]);
+    }
+
+    #[test]
+    fn hit_count_variables_cover_only_what_the_program_reads() {
+        let mut executor = ProgramExecutor::new();
+        for (x, y) in [(2, 2), (3, 5), (7, 1)] {
+            executor.state.square_hit_counts.insert((x, y), (x + y) as u32);
+        }
+        let program = parse("var a = hits(self)\nvar b = hits(square(3, 5))").unwrap();
+
+        let variables = executor.hit_count_variables(2, 2, &program.instructions, &VecDeque::new());
+        let mut names: Vec<&str> = variables.keys().map(String::as_str).collect();
+        names.sort();
+        assert_eq!(names, vec!["__square_hits", "__square_hits_3_5"]);
+        assert_eq!(variables["__square_hits"], Value::Number(4.0));
+        assert_eq!(variables["__square_hits_3_5"], Value::Number(8.0));
+    }
+
+    // Hits at `times` seconds of the take, alternating red and blue balls
+    fn history(times: &[f32]) -> VecDeque<CollisionEvent> {
+        times.iter().enumerate().map(|(index, &time)| CollisionEvent {
+            ball_index: index % 2,
+            ball_color: if index % 2 == 0 { "Red" } else { "Blue" }.to_string(),
+            square_x: 2,
+            square_y: 2,
+            timestamp: std::time::Instant::now(),
+            time,
+        }).collect()
+    }
+
+    #[test]
+    fn recent_hits_count_within_the_window_on_the_take_clock() {
+        let program = parse("var red = recent_hits(c_red, 1000)\nvar any = recent_hits(any, 1500)").unwrap();
+        let mut executor = ProgramExecutor::new();
+        executor.state.elapsed = 3.0;
+        // Red at 0.5, 2.5 and 3.0, blue at 1.5 and 2.8
+        let variables = executor.hit_count_variables(2, 2, &program.instructions, &history(&[0.5, 1.5, 2.5, 2.8, 3.0]));
+        assert_eq!(variables["__recent_hits_c_red_1000"], Value::Number(2.0));
+        assert_eq!(variables["__recent_hits_any_1500"], Value::Number(4.0));
+    }
+
+    #[test]
+    fn recent_hits_work_in_called_functions() {
+        // A returned function runs in the square interpreter, which only sees the context
+        let program = parse("set speed recent_hits(any, 1000)").unwrap();
+        let mut executor = ProgramExecutor::new();
+        executor.state.elapsed = 10.0;
+        let mut context = ExecutionContext {
+            variables: executor.hit_count_variables(2, 2, &program.instructions, &history(&[9.2, 9.9])),
+            ball_hit_count: 0,
+            square_hit_count: 0,
+            ball_x: 1.5,
+            ball_y: 1.5,
+            ball_speed: 2.0,
+            ball_direction: Direction::Up,
+            ball_pitch: 1.0,
+            ball_volume: 1.0,
+            square_x: 2,
+            square_y: 2,
+            time: 10.0,
+        };
+        let actions = crate::square::SquareProgram::new().execute_instructions(&program.instructions, &mut context);
+        assert_eq!(actions, vec![ProgramAction::SetSpeed(2.0)]);
+    }
+}
@@ -0,0 +1,189 @@
+// Project files: a whole composition (squares with their programs, balls, tempo and
+// library contents) saved as one JSON document. Every file carries a version so older
+// projects can be migrated when the format changes.
+
+use crate::ball::{Ball, BallConfig};
+use crate::sequencer::{empty_cells, grid_height, grid_width};
+use crate::square::{Cell, LibraryManager};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+pub const PROJECT_VERSION: u32 = 2;
+
+#[derive(Serialize, Deserialize)]
+pub struct ProjectSquare {
+    pub x: usize,
+    pub y: usize,
+    pub cell: Cell,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ProjectBall {
+    pub id: String,
+    pub name: Option<String>,
+    pub x: f32, // Starting position in grid units
+    pub y: f32,
+    #[serde(flatten)]
+    pub config: BallConfig,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Project {
+    pub version: u32,
+    pub bpm: f32,
+    pub auto_start: bool,
+    pub auto_start_delay: f32,
+    pub squares: Vec<ProjectSquare>, // Only cells that hold a square
+    pub balls: Vec<ProjectBall>,
+    pub library_manager: LibraryManager,
+}
+
+impl Project {
+    pub fn new(cells: &[Vec<Cell>], balls: &[Ball], library_manager: &LibraryManager,
+               bpm: f32, auto_start: bool, auto_start_delay: f32) -> Self {
+        let squares = cells.iter().enumerate()
+            .flat_map(|(y, row)| row.iter().enumerate().map(move |(x, cell)| (x, y, cell)))
+            .filter(|(_, _, cell)| cell.is_square())
+            .map(|(x, y, cell)| ProjectSquare { x, y, cell: cell.clone() })
+            .collect();
+        let balls = balls.iter()
+            .map(|ball| ProjectBall {
+                id: ball.id.clone(),
+                name: ball.name.clone(),
+                x: ball.original_x,
+                y: ball.original_y,
+                config: ball.config(),
+            })
+            .collect();
+        Self {
+            version: PROJECT_VERSION,
+            bpm,
+            auto_start,
+            auto_start_delay,
+            squares,
+            balls,
+            library_manager: library_manager.clone(),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| format!("Could not serialize project: {}", e))
+    }
+
+    // The grid the project describes, with every other cell empty
+    pub fn cells(&self) -> Vec<Vec<Cell>> {
+        let mut cells = empty_cells();
+        for square in &self.squares {
+            cells[square.y][square.x] = square.cell.clone();
+        }
+        cells
+    }
+
+    // Balls at their starting positions, stopped; samples are attached by the caller
+    pub fn balls(&self) -> Vec<Ball> {
+        self.balls.iter()
+            .map(|saved| {
+                let mut ball = Ball::new(saved.x as usize, saved.y as usize, saved.id.clone());
+                ball.x = saved.x;
+                ball.y = saved.y;
+                ball.original_x = saved.x;
+                ball.original_y = saved.y;
+                ball.name = saved.name.clone();
+                ball.apply_config(&saved.config);
+                ball
+            })
+            .collect()
+    }
+}
+
+pub fn parse_project(json: &str) -> Result<Project, String> {
+    let mut root: JsonValue = serde_json::from_str(json).map_err(|e| format!("Invalid JSON: {}", e))?;
+    let version = root.get("version")
+        .and_then(|version| version.as_u64())
+        .ok_or("Missing project 'version'")? as u32;
+    if version > PROJECT_VERSION {
+        return Err(format!("Project version {} is newer than this build supports ({})", version, PROJECT_VERSION));
+    }
+    migrate(&mut root, version)?;
+
+    let project: Project = serde_json::from_value(root).map_err(|e| format!("Invalid project: {}", e))?;
+    if let Some(square) = project.squares.iter().find(|square| square.x >= grid_width() || square.y >= grid_height()) {
+        return Err(format!("Square ({}, {}) is outside the {}x{} grid", square.x, square.y, grid_width(), grid_height()));
+    }
+    if let Some(ball) = project.balls.iter().find(|ball| ball.x < 0.0 || ball.y < 0.0 || ball.x >= grid_width() as f32 || ball.y >= grid_height() as f32) {
+        return Err(format!("Ball {} at ({}, {}) is outside the {}x{} grid", ball.id, ball.x, ball.y, grid_width(), grid_height()));
+    }
+    Ok(project)
+}
+
+// Bring an older project up to PROJECT_VERSION, one version step at a time
+fn migrate(root: &mut JsonValue, version: u32) -> Result<(), String> {
+    if version == 0 {
+        return Err("Project version 0 is not a valid project".to_string());
+    }
+    // Version 1 kept only direction, speed, color and sample of each ball; the settings
+    // added in version 2 start from a new ball's defaults
+    if version < 2 {
+        let defaults = serde_json::to_value(Ball::new(0, 0, String::new()).config())
+            .map_err(|e| format!("Could not build ball defaults: {}", e))?;
+        let defaults = defaults.as_object().ok_or("Ball defaults are not an object")?;
+        for ball in root["balls"].as_array_mut().into_iter().flatten() {
+            if let Some(ball) = ball.as_object_mut() {
+                for (key, value) in defaults {
+                    ball.entry(key.clone()).or_insert_with(|| value.clone());
+                }
+            }
+        }
+    }
+    root["version"] = JsonValue::from(PROJECT_VERSION);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ball::Direction;
+    use crate::square::{Instruction, Program};
+
+    #[test]
+    fn balls_keep_every_setting_across_save_and_load() {
+        let mut ball = Ball::new(2, 3, "ball1".to_string());
+        ball.name = Some("kick".to_string());
+        ball.set_direction(Direction::Left);
+        ball.set_speed(3.0);
+        ball.set_color("Red".to_string());
+        ball.set_sample("kick.wav".to_string());
+        ball.set_volume(0.5);
+        ball.set_pitch(1.5);
+        ball.set_trim(0.8);
+        ball.set_pitch_step(2.0);
+        ball.set_humanize(0.25);
+        ball.set_pan(-0.5);
+        ball.set_envelope(10.0, 40.0);
+        ball.behavior = Some(Program { name: "pass".to_string(), instructions: vec![Instruction::Pass], source_text: None, parameters: Vec::new() });
+        ball.glyph = Some('K');
+
+        let json = Project::new(&empty_cells(), &[ball.clone()], &LibraryManager::new(), 120.0, false, 0.0).to_json().unwrap();
+        let loaded = parse_project(&json).unwrap().balls().remove(0);
+
+        assert_eq!((loaded.id.as_str(), loaded.name.as_deref()), ("ball1", Some("kick")));
+        assert_eq!((loaded.x, loaded.y), (ball.original_x, ball.original_y));
+        assert_eq!(format!("{:?}", loaded.config()), format!("{:?}", ball.config()));
+    }
+
+    #[test]
+    fn version_1_balls_load_with_default_settings() {
+        let json = r#"{
+            "version": 1, "bpm": 120.0, "auto_start": false, "auto_start_delay": 0.0, "squares": [],
+            "balls": [{"id": "ball1", "name": null, "x": 1.5, "y": 1.5, "direction": "Right", "speed": 3.0, "color": "Blue", "sample_path": "hat.wav"}],
+            "library_manager": {"function_libraries": {}, "sample_libraries": {}}
+        }"#;
+        let ball = parse_project(json).unwrap().balls().remove(0);
+        assert_eq!(ball.direction, Direction::Right);
+        assert_eq!(ball.speed, 3.0);
+        assert_eq!(ball.color, "Blue");
+        assert_eq!(ball.sample_path.as_deref(), Some("hat.wav"));
+        assert_eq!((ball.volume, ball.pitch, ball.trim, ball.pan), (1.0, 1.0, 1.0, None));
+        assert!(ball.behavior.is_none());
+    }
+}
@@ -1,21 +1,24 @@
 use std::collections::VecDeque;
 use crate::ball::Ball;
 use crate::square::Cell;
+use crate::sequencer::{CollisionEvent, PatchStats, CELL_SIZE, grid_area_height, grid_height, grid_width, window_height, window_width};
+use crate::theme::Theme;
+use crate::audio_engine::Limiter;
 use crate::font;
+use crate::keybindings::{KeyBinding, KEYBINDINGS, CONSOLE_COMMANDS};
 
-// Rendering constants moved from sequencer.rs
-pub const GRID_WIDTH: usize = 16;
-pub const GRID_HEIGHT: usize = 12;
-pub const CELL_SIZE: usize = 40;
-pub const CONSOLE_HEIGHT: usize = 150;
-pub const WINDOW_WIDTH: usize = GRID_WIDTH * CELL_SIZE;
-pub const WINDOW_HEIGHT: usize = GRID_HEIGHT * CELL_SIZE + CONSOLE_HEIGHT;
-pub const GRID_AREA_HEIGHT: usize = GRID_HEIGHT * CELL_SIZE;
+const CONSOLE_TEXT_TOP: usize = 10; // Gap between the console border and the first message
+const CONSOLE_LINE_HEIGHT: usize = 14;
 
 pub struct Renderer;
 
 impl Renderer {
     pub fn get_color_rgb(color_name: &str) -> [u8; 3] {
+        // Hex colors like #ff8800
+        if let Some(rgb) = Self::parse_hex_color(color_name) {
+            return rgb;
+        }
+        
         // Normalize the color name to handle different formats
         let normalized_color = if color_name.starts_with("c_") {
             // Handle c_blue -> Blue format
@@ -51,15 +54,24 @@ impl Renderer {
         }
     }
 
-    pub fn draw_grid_lines(frame: &mut [u8]) {
-        let grid_color = [60, 60, 60];
+    pub fn parse_hex_color(color: &str) -> Option<[u8; 3]> {
+        let hex = color.strip_prefix('#')?;
+        if hex.len() != 6 {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        Some([channel(0)?, channel(2)?, channel(4)?])
+    }
+
+    pub fn draw_grid_lines(frame: &mut [u8], theme: &Theme) {
+        let grid_color = theme.grid_lines;
         
         // Vertical lines
-        for x in 0..=GRID_WIDTH {
+        for x in 0..=grid_width() {
             let pixel_x = x * CELL_SIZE;
-            if pixel_x < WINDOW_WIDTH {
-                for y in 0..WINDOW_HEIGHT {
-                    let index = (y * WINDOW_WIDTH + pixel_x) * 4;
+            if pixel_x < window_width() {
+                for y in 0..window_height() {
+                    let index = (y * window_width() + pixel_x) * 4;
                     if index + 2 < frame.len() {
                         frame[index] = grid_color[0];
                         frame[index + 1] = grid_color[1];
@@ -70,11 +82,11 @@ impl Renderer {
         }
         
         // Horizontal lines
-        for y in 0..=GRID_HEIGHT {
+        for y in 0..=grid_height() {
             let pixel_y = y * CELL_SIZE;
-            if pixel_y < WINDOW_HEIGHT {
-                for x in 0..WINDOW_WIDTH {
-                    let index = (pixel_y * WINDOW_WIDTH + x) * 4;
+            if pixel_y < window_height() {
+                for x in 0..window_width() {
+                    let index = (pixel_y * window_width() + x) * 4;
                     if index + 2 < frame.len() {
                         frame[index] = grid_color[0];
                         frame[index + 1] = grid_color[1];
@@ -85,7 +97,23 @@ impl Renderer {
         }
     }
 
-    pub fn draw_square(frame: &mut [u8], grid_x: usize, grid_y: usize, color: [u8; 3], display_text: &Option<String>) {
+    // Blend a color into the frame with the given coverage (0.0 = untouched, 1.0 = solid)
+    fn blend_pixel(frame: &mut [u8], x: usize, y: usize, color: [u8; 3], coverage: f32) {
+        if x >= window_width() || y >= window_height() || coverage <= 0.0 {
+            return;
+        }
+        let index = (y * window_width() + x) * 4;
+        if index + 3 < frame.len() {
+            let alpha = coverage.min(1.0);
+            for channel in 0..3 {
+                let background = frame[index + channel] as f32;
+                frame[index + channel] = (background + (color[channel] as f32 - background) * alpha).round() as u8;
+            }
+            frame[index + 3] = 0xff;
+        }
+    }
+
+    pub fn draw_square(frame: &mut [u8], grid_x: usize, grid_y: usize, color: [u8; 3], display_text: &Option<String>, glyph: Option<char>, antialias: bool) {
         let start_x = grid_x * CELL_SIZE + 2;
         let start_y = grid_y * CELL_SIZE + 2;
         let end_x = (grid_x + 1) * CELL_SIZE - 2;
@@ -93,8 +121,14 @@ impl Renderer {
         
         for y in start_y..end_y {
             for x in start_x..end_x {
-                if x < WINDOW_WIDTH && y < WINDOW_HEIGHT {
-                    let index = (y * WINDOW_WIDTH + x) * 4;
+                if x < window_width() && y < window_height() {
+                    // Soften the outermost ring of pixels when antialiasing is enabled
+                    let on_border = x == start_x || x == end_x - 1 || y == start_y || y == end_y - 1;
+                    if antialias && on_border {
+                        Self::blend_pixel(frame, x, y, color, 0.5);
+                        continue;
+                    }
+                    let index = (y * window_width() + x) * 4;
                     if index + 2 < frame.len() {
                         frame[index] = color[0];
                         frame[index + 1] = color[1];
@@ -115,13 +149,27 @@ impl Renderer {
                 let line_y = text_y + (line_index * 12); // 12 pixels per line (font height)
                 // Only draw if the line fits within the cell
                 if line_y + 12 <= end_y {
-                    font::draw_text(frame, line, text_x, line_y, [255, 255, 255], false, WINDOW_WIDTH);
+                    font::draw_text(frame, line, text_x, line_y, [255, 255, 255], false, window_width());
                 }
             }
         }
+        
+        if let Some(glyph) = glyph {
+            Self::draw_glyph(frame, (start_x + end_x) as f32 / 2.0, (start_y + end_y) as f32 / 2.0, glyph, color);
+        }
+    }
+    
+    // Draw a font character centered on a point, dark or light to contrast with the fill
+    pub fn draw_glyph(frame: &mut [u8], center_x: f32, center_y: f32, glyph: char, fill: [u8; 3]) {
+        let luminance = 0.299 * fill[0] as f32 + 0.587 * fill[1] as f32 + 0.114 * fill[2] as f32;
+        let color = if luminance > 140.0 { [0, 0, 0] } else { [255, 255, 255] };
+        // Glyph patterns occupy the top-left 5x8 pixels of the 8x12 cell
+        let x = (center_x - 2.5).max(0.0) as usize;
+        let y = (center_y - 4.0).max(0.0) as usize;
+        font::draw_char(frame, glyph, x, y, color, window_width());
     }
 
-    pub fn draw_circle(frame: &mut [u8], grid_x: usize, grid_y: usize, color: [u8; 3]) {
+    pub fn draw_circle(frame: &mut [u8], grid_x: usize, grid_y: usize, color: [u8; 3], antialias: bool) {
         let center_x = grid_x * CELL_SIZE + CELL_SIZE / 2;
         let center_y = grid_y * CELL_SIZE + CELL_SIZE / 2;
         let radius = (CELL_SIZE / 2 - 2) as f32;
@@ -137,8 +185,14 @@ impl Renderer {
                 let dy = y as f32 - center_y as f32;
                 let distance = (dx * dx + dy * dy).sqrt();
                 
-                if distance <= radius && x < WINDOW_WIDTH && y < WINDOW_HEIGHT {
-                    let index = (y * WINDOW_WIDTH + x) * 4;
+                if antialias {
+                    // Fade pixels within ~1px of the edge into the background
+                    Self::blend_pixel(frame, x, y, color, radius + 0.5 - distance);
+                    continue;
+                }
+                
+                if distance <= radius && x < window_width() && y < window_height() {
+                    let index = (y * window_width() + x) * 4;
                     if index + 2 < frame.len() {
                         frame[index] = color[0];
                         frame[index + 1] = color[1];
@@ -149,16 +203,16 @@ impl Renderer {
         }
     }
 
-    pub fn draw_cursor(frame: &mut [u8], cursor_x: usize, cursor_y: usize) {
-        let cursor_color = [255, 255, 0]; // Yellow cursor
+    pub fn draw_cursor(frame: &mut [u8], cursor_x: usize, cursor_y: usize, theme: &Theme) {
+        let cursor_color = theme.cursor;
         let x = cursor_x * CELL_SIZE;
         let y = cursor_y * CELL_SIZE;
         
         // Draw cursor border
         for i in 0..CELL_SIZE {
             // Top border
-            if x + i < WINDOW_WIDTH && y < WINDOW_HEIGHT {
-                let index = (y * WINDOW_WIDTH + x + i) * 4;
+            if x + i < window_width() && y < window_height() {
+                let index = (y * window_width() + x + i) * 4;
                 if index + 2 < frame.len() {
                     frame[index] = cursor_color[0];
                     frame[index + 1] = cursor_color[1];
@@ -167,8 +221,8 @@ impl Renderer {
             }
             
             // Bottom border
-            if x + i < WINDOW_WIDTH && y + CELL_SIZE - 1 < WINDOW_HEIGHT {
-                let index = ((y + CELL_SIZE - 1) * WINDOW_WIDTH + x + i) * 4;
+            if x + i < window_width() && y + CELL_SIZE - 1 < window_height() {
+                let index = ((y + CELL_SIZE - 1) * window_width() + x + i) * 4;
                 if index + 2 < frame.len() {
                     frame[index] = cursor_color[0];
                     frame[index + 1] = cursor_color[1];
@@ -177,8 +231,8 @@ impl Renderer {
             }
             
             // Left border
-            if x < WINDOW_WIDTH && y + i < WINDOW_HEIGHT {
-                let index = ((y + i) * WINDOW_WIDTH + x) * 4;
+            if x < window_width() && y + i < window_height() {
+                let index = ((y + i) * window_width() + x) * 4;
                 if index + 2 < frame.len() {
                     frame[index] = cursor_color[0];
                     frame[index + 1] = cursor_color[1];
@@ -187,8 +241,8 @@ impl Renderer {
             }
             
             // Right border
-            if x + CELL_SIZE - 1 < WINDOW_WIDTH && y + i < WINDOW_HEIGHT {
-                let index = ((y + i) * WINDOW_WIDTH + x + CELL_SIZE - 1) * 4;
+            if x + CELL_SIZE - 1 < window_width() && y + i < window_height() {
+                let index = ((y + i) * window_width() + x + CELL_SIZE - 1) * 4;
                 if index + 2 < frame.len() {
                     frame[index] = cursor_color[0];
                     frame[index + 1] = cursor_color[1];
@@ -198,7 +252,32 @@ impl Renderer {
         }
     }
 
-    pub fn draw_ball(frame: &mut [u8], ball_x: f32, ball_y: f32, color: [u8; 3]) {
+    // Outline of the rectangle of cells between two corners, in the cursor color
+    pub fn draw_selection(frame: &mut [u8], anchor: (usize, usize), cursor: (usize, usize), theme: &Theme) {
+        let left = anchor.0.min(cursor.0) * CELL_SIZE;
+        let top = anchor.1.min(cursor.1) * CELL_SIZE;
+        let right = (anchor.0.max(cursor.0) + 1) * CELL_SIZE - 1;
+        let bottom = (anchor.1.max(cursor.1) + 1) * CELL_SIZE - 1;
+        let mut plot = |x: usize, y: usize| {
+            if x < window_width() && y < grid_area_height() {
+                let index = (y * window_width() + x) * 4;
+                if index + 2 < frame.len() {
+                    frame[index..index + 3].copy_from_slice(&theme.cursor);
+                }
+            }
+        };
+        // Every other pixel, so the outline reads apart from the solid cursor
+        for x in (left..=right).step_by(2) {
+            plot(x, top);
+            plot(x, bottom);
+        }
+        for y in (top..=bottom).step_by(2) {
+            plot(left, y);
+            plot(right, y);
+        }
+    }
+
+    pub fn draw_ball(frame: &mut [u8], ball_x: f32, ball_y: f32, color: [u8; 3], glyph: Option<char>, antialias: bool) {
         let pixel_x = ball_x * CELL_SIZE as f32;
         let pixel_y = ball_y * CELL_SIZE as f32;
         let center_x = pixel_x;
@@ -207,17 +286,23 @@ impl Renderer {
         
         let start_x = (pixel_x as usize).saturating_sub(CELL_SIZE / 2);
         let start_y = (pixel_y as usize).saturating_sub(CELL_SIZE / 2);
-        let end_x = ((pixel_x + CELL_SIZE as f32) as usize).min(WINDOW_WIDTH);
-        let end_y = ((pixel_y + CELL_SIZE as f32) as usize).min(WINDOW_HEIGHT);
+        let end_x = ((pixel_x + CELL_SIZE as f32) as usize).min(window_width());
+        let end_y = ((pixel_y + CELL_SIZE as f32) as usize).min(window_height());
         
         // Draw ball with specified color
         for y in start_y..end_y {
             for x in start_x..end_x {
-                if x < WINDOW_WIDTH && y < WINDOW_HEIGHT {
+                if x < window_width() && y < window_height() {
                     let dx = x as f32 - center_x;
                     let dy = y as f32 - center_y;
+                    if antialias {
+                        // Fade pixels within ~1px of the edge into the background
+                        let distance = (dx * dx + dy * dy).sqrt();
+                        Self::blend_pixel(frame, x, y, color, radius + 0.5 - distance);
+                        continue;
+                    }
                     if dx * dx + dy * dy <= radius * radius {
-                        let index = (y * WINDOW_WIDTH + x) * 4;
+                        let index = (y * window_width() + x) * 4;
                         if index + 3 < frame.len() {
                             frame[index] = color[0];     // R
                             frame[index + 1] = color[1]; // G
@@ -228,51 +313,401 @@ impl Renderer {
                 }
             }
         }
+        
+        if let Some(glyph) = glyph {
+            Self::draw_glyph(frame, center_x, center_y, glyph, color);
+        }
     }
 
-    pub fn draw_console(frame: &mut [u8], console_messages: &VecDeque<String>) {
+    // Number of message rows that fit in the console area
+    pub fn console_visible_lines() -> usize {
+        (window_height() - grid_area_height() - CONSOLE_TEXT_TOP - 13) / CONSOLE_LINE_HEIGHT + 1
+    }
+    
+    // `scroll` is how many lines the view is scrolled up from the newest message;
+    // `input` is the open command prompt's text, drawn on the bottom row
+    pub fn draw_console(frame: &mut [u8], console_messages: &VecDeque<String>, scroll: usize, input: Option<&str>, theme: &Theme) {
         // Draw console background
-        let console_y_start = GRID_AREA_HEIGHT;
-        for y in console_y_start..WINDOW_HEIGHT {
-            for x in 0..WINDOW_WIDTH {
-                let idx = (y * WINDOW_WIDTH + x) * 4;
+        let console_y_start = grid_area_height();
+        for y in console_y_start..window_height() {
+            for x in 0..window_width() {
+                let idx = (y * window_width() + x) * 4;
                 if idx + 3 < frame.len() {
-                    frame[idx] = 30;     // R - darker background
-                    frame[idx + 1] = 30; // G
-                    frame[idx + 2] = 30; // B
+                    frame[idx] = theme.console_background[0];
+                    frame[idx + 1] = theme.console_background[1];
+                    frame[idx + 2] = theme.console_background[2];
                     frame[idx + 3] = 255; // A
                 }
             }
         }
         
         // Draw console border
-        for x in 0..WINDOW_WIDTH {
-            let idx = (console_y_start * WINDOW_WIDTH + x) * 4;
+        for x in 0..window_width() {
+            let idx = (console_y_start * window_width() + x) * 4;
             if idx + 3 < frame.len() {
-                frame[idx] = 100;     // R - border color
-                frame[idx + 1] = 100; // G
-                frame[idx + 2] = 100; // B
+                frame[idx] = theme.console_border[0];
+                frame[idx + 1] = theme.console_border[1];
+                frame[idx + 2] = theme.console_border[2];
                 frame[idx + 3] = 255; // A
             }
         }
         
-        // Draw console messages
-        for (i, message) in console_messages.iter().enumerate() {
-            let text_y = console_y_start + 10 + i * 14;
-            if text_y + 12 < WINDOW_HEIGHT {
-                Self::draw_menu_text(frame, message, 5, text_y, [200, 200, 200], false);
-            }
+        // Draw the window of messages ending `scroll` lines above the newest; when scrolled
+        // up, the bottom row says how much newer output is waiting below
+        let visible_lines = Self::console_visible_lines();
+        let scroll = scroll.min(console_messages.len());
+        let rows = visible_lines - (scroll > 0) as usize - input.is_some() as usize;
+        let end = console_messages.len() - scroll;
+        let start = end.saturating_sub(rows);
+        for (i, message) in console_messages.range(start..end).enumerate() {
+            let text_y = console_y_start + CONSOLE_TEXT_TOP + i * CONSOLE_LINE_HEIGHT;
+            Self::draw_menu_text(frame, message, 5, text_y, theme.console_text, false);
+        }
+        if scroll > 0 {
+            let text_y = console_y_start + CONSOLE_TEXT_TOP + rows * CONSOLE_LINE_HEIGHT;
+            let more = format!("-- {} newer line{} (PageDown) --", scroll, if scroll == 1 { "" } else { "s" });
+            Self::draw_menu_text(frame, &more, 5, text_y, theme.console_border, false);
+        }
+        if let Some(input) = input {
+            let text_y = console_y_start + CONSOLE_TEXT_TOP + (visible_lines - 1) * CONSOLE_LINE_HEIGHT;
+            Self::draw_menu_text(frame, &format!("> {}_", input), 5, text_y, theme.console_text, false);
         }
     }
 
     pub fn draw_menu_text(frame: &mut [u8], text: &str, x: usize, y: usize, color: [u8; 3], selected: bool) {
-        font::draw_text(frame, text, x, y, color, selected, WINDOW_WIDTH);
+        font::draw_text(frame, text, x, y, color, selected, window_width());
+    }
+
+    // Status bar along the bottom of the grid with a ball's live values
+    pub fn draw_ball_readout(frame: &mut [u8], ball: &Ball, theme: &Theme) {
+        let text = format!("{} ({}) speed {:.2} dir {:?} pitch {:.2} vol {:.2} trim {:.0}% pos ({:.2}, {:.2})",
+            ball.id, ball.color, ball.speed, ball.direction, ball.pitch, ball.volume, ball.trim * 100.0, ball.x, ball.y);
+        let start_y = grid_area_height() - 18;
+        for y in start_y..grid_area_height() {
+            for x in 0..window_width() {
+                let idx = (y * window_width() + x) * 4;
+                if idx + 3 < frame.len() {
+                    frame[idx] = theme.console_background[0];
+                    frame[idx + 1] = theme.console_background[1];
+                    frame[idx + 2] = theme.console_background[2];
+                    frame[idx + 3] = 255;
+                }
+            }
+        }
+        Self::draw_menu_text(frame, &text, 5, start_y + 3, theme.readout_text, false);
+    }
+
+    // Semi-transparent panel in the top-left corner of the grid with live patch stats
+    pub fn draw_stats_overlay(frame: &mut [u8], stats: &PatchStats, theme: &Theme) {
+        let lines = [
+            format!("Balls    {} active / {}", stats.active_balls, stats.total_balls),
+            format!("Squares  {}", stats.squares),
+            format!("Voices   {} / {}", stats.voices, stats.max_voices),
+            format!("Hits/s   {:.0}", stats.collisions_per_second),
+            format!("Tempo    {:.1} BPM", stats.bpm),
+            format!("Frame    {:.1} ms ({:.0} fps)", stats.frame_ms, 1000.0 / stats.frame_ms.max(0.001)),
+            format!("Update   {:.2} ms{}", stats.update_ms, if stats.lean { " (lean)" } else { "" }),
+        ];
+        let width = lines.iter().map(|line| line.len()).max().unwrap_or(0) * 8 + 12;
+        let height = lines.len() * 14 + 8;
+        let (start_x, start_y) = (5, 5);
+
+        for y in start_y..start_y + height {
+            for x in start_x..start_x + width {
+                let idx = (y * window_width() + x) * 4;
+                if idx + 3 < frame.len() {
+                    // 75% dark panel over whatever is underneath
+                    for channel in 0..3 {
+                        frame[idx + channel] = (frame[idx + channel] as u16 / 4 + 10) as u8;
+                    }
+                }
+            }
+        }
+        for (i, line) in lines.iter().enumerate() {
+            Self::draw_menu_text(frame, line, start_x + 6, start_y + 5 + i * 14, theme.panel_text, false);
+        }
+    }
+
+    // Progress panel in the middle of the grid while imported samples are preloading
+    pub fn draw_loading_bar(frame: &mut [u8], loaded: usize, total: usize, theme: &Theme) {
+        let (width, height) = (320, 44);
+        let start_x = (window_width() - width) / 2;
+        let start_y = (grid_area_height() - height) / 2;
+        let bar_y = start_y + 24;
+        let bar_width = width - 16;
+        let filled = bar_width * loaded / total.max(1);
+
+        for y in start_y..start_y + height {
+            for x in start_x..start_x + width {
+                let idx = (y * window_width() + x) * 4;
+                if idx + 3 < frame.len() {
+                    let in_bar = y >= bar_y && y < bar_y + 12 && x >= start_x + 8 && x < start_x + 8 + bar_width;
+                    let color = if in_bar && x < start_x + 8 + filled {
+                        [80, 200, 120]
+                    } else if in_bar {
+                        theme.grid_lines
+                    } else {
+                        theme.panel_background
+                    };
+                    frame[idx..idx + 3].copy_from_slice(&color);
+                    frame[idx + 3] = 255;
+                }
+            }
+        }
+        let text = format!("Loading samples {}/{}", loaded, total);
+        Self::draw_menu_text(frame, &text, start_x + 8, start_y + 6, theme.panel_text, false);
+    }
+
+    // Red "OVERLOAD" badge in the top-right corner of the grid
+    pub fn draw_overload_indicator(frame: &mut [u8]) {
+        let text = "OVERLOAD";
+        let width = text.len() * 8 + 8;
+        let start_x = window_width() - width - 5;
+        let start_y = 5;
+        for y in start_y..start_y + 18 {
+            for x in start_x..start_x + width {
+                let idx = (y * window_width() + x) * 4;
+                if idx + 3 < frame.len() {
+                    frame[idx] = 200;
+                    frame[idx + 1] = 20;
+                    frame[idx + 2] = 20;
+                    frame[idx + 3] = 255;
+                }
+            }
+        }
+        Self::draw_menu_text(frame, text, start_x + 4, start_y + 3, [255, 255, 255], false);
     }
 
-    pub fn draw_cursor_coordinates(frame: &mut [u8], cursor_x: usize, cursor_y: usize) {
+    // Current tempo and RNG seed, shown in the top-right corner of the console
+    pub fn draw_transport_indicator(frame: &mut [u8], bpm: f32, seed: u64, theme: &Theme) {
+        let text = format!("{:.0} BPM  seed {}", bpm, seed);
+        let width = text.len() * 8 + 8;
+        let start_x = window_width() - width - 5;
+        let start_y = grid_area_height() + 4;
+        for y in start_y..start_y + 16 {
+            for x in start_x..start_x + width {
+                let idx = (y * window_width() + x) * 4;
+                if idx + 3 < frame.len() {
+                    frame[idx..idx + 3].copy_from_slice(&theme.transport_background);
+                    frame[idx + 3] = 255;
+                }
+            }
+        }
+        Self::draw_menu_text(frame, &text, start_x + 4, start_y + 2, theme.transport_text, false);
+    }
+
+    // Master output level under the transport badge. The bar turns red once the
+    // mix goes past the limiter ceiling (or past 0 dBFS with the limiter off).
+    pub fn draw_peak_meter(frame: &mut [u8], level: f32, limiter: Limiter) {
+        const WIDTH: usize = 100;
+        const HEIGHT: usize = 4;
+        let start_x = window_width() - WIDTH - 5;
+        let start_y = grid_area_height() + 22;
+        let threshold = if limiter.enabled { limiter.ceiling } else { 1.0 };
+        let filled = ((level.min(1.0) * WIDTH as f32) as usize).min(WIDTH);
+        let ceiling_x = (threshold * WIDTH as f32) as usize;
+        let bar_color = if level > threshold { [230, 60, 60] } else { [90, 200, 110] };
+
+        for y in start_y..start_y + HEIGHT {
+            for x in 0..WIDTH {
+                let color = if x == ceiling_x.min(WIDTH - 1) && limiter.enabled {
+                    [220, 220, 120]
+                } else if x < filled {
+                    bar_color
+                } else {
+                    [45, 45, 60]
+                };
+                let idx = (y * window_width() + start_x + x) * 4;
+                if idx + 3 < frame.len() {
+                    frame[idx] = color[0];
+                    frame[idx + 1] = color[1];
+                    frame[idx + 2] = color[2];
+                    frame[idx + 3] = 255;
+                }
+            }
+        }
+    }
+
+    // Scaled-down overview of the whole grid in the bottom-right of the console:
+    // one block per cell, with a rectangle around the cells currently in view.
+    // `viewport` is (x, y, width, height) in cells.
+    pub fn draw_minimap(frame: &mut [u8], cells: &[Vec<Cell>], balls: &[Ball], viewport: (usize, usize, usize, usize), theme: &Theme) {
+        const SCALE: usize = 3;
+        let rows = cells.len();
+        let map_width = grid_width() * SCALE;
+        let map_height = rows * SCALE;
+        let start_x = window_width() - map_width - 6;
+        let start_y = window_height() - map_height - 6;
+
+        let fill = |frame: &mut [u8], x: usize, y: usize, color: [u8; 3]| {
+            let idx = (y * window_width() + x) * 4;
+            if idx + 3 < frame.len() {
+                frame[idx] = color[0];
+                frame[idx + 1] = color[1];
+                frame[idx + 2] = color[2];
+                frame[idx + 3] = 255;
+            }
+        };
+
+        for (row, row_cells) in cells.iter().enumerate() {
+            for (col, cell) in row_cells.iter().enumerate() {
+                let color = if cell.is_square() { cell.color } else { theme.background };
+                for dy in 0..SCALE {
+                    for dx in 0..SCALE {
+                        fill(frame, start_x + col * SCALE + dx, start_y + row * SCALE + dy, color);
+                    }
+                }
+            }
+        }
+
+        for ball in balls {
+            let (col, row) = ball.get_grid_position();
+            if col < grid_width() && row < rows {
+                let color = Self::get_color_rgb(&ball.color);
+                fill(frame, start_x + col * SCALE + 1, start_y + row * SCALE + 1, color);
+            }
+        }
+
+        // Viewport outline, one pixel outside the cells it covers
+        let (view_x, view_y, view_width, view_height) = viewport;
+        let left = start_x + view_x * SCALE - 1;
+        let top = start_y + view_y * SCALE - 1;
+        let right = start_x + (view_x + view_width).min(grid_width()) * SCALE;
+        let bottom = start_y + (view_y + view_height).min(rows) * SCALE;
+        for x in left..=right {
+            fill(frame, x, top, theme.panel_text);
+            fill(frame, x, bottom, theme.panel_text);
+        }
+        for y in top..=bottom {
+            fill(frame, left, y, theme.panel_text);
+            fill(frame, right, y, theme.panel_text);
+        }
+    }
+
+    // Piano-roll view of recent triggers across the console area: one lane per
+    // ball color, newest hits at the right edge, scrolling left as they age.
+    pub fn draw_timeline(frame: &mut [u8], history: &VecDeque<CollisionEvent>, theme: &Theme) {
+        const SECONDS: f32 = 4.0;
+        const LABEL_WIDTH: usize = 70;
+        let top = grid_area_height() + 22;
+        let bottom = window_height() - 4;
+        let plot_left = LABEL_WIDTH;
+        let plot_right = window_width() - 6;
+        let pixels_per_second = (plot_right - plot_left) as f32 / SECONDS;
+
+        let fill = |frame: &mut [u8], x: usize, y: usize, color: [u8; 3]| {
+            let idx = (y * window_width() + x) * 4;
+            if idx + 3 < frame.len() {
+                frame[idx] = color[0];
+                frame[idx + 1] = color[1];
+                frame[idx + 2] = color[2];
+                frame[idx + 3] = 255;
+            }
+        };
+
+        for y in grid_area_height()..window_height() {
+            for x in 0..window_width() {
+                fill(frame, x, y, theme.console_background);
+            }
+        }
+        for x in 0..window_width() {
+            fill(frame, x, grid_area_height(), theme.console_border);
+        }
+        Self::draw_menu_text(frame, &format!("Timeline (last {}s)", SECONDS), 5, grid_area_height() + 5, theme.console_text, false);
+
+        // Lanes in a stable (alphabetical) order so they don't jump around
+        let mut lanes: Vec<&str> = history.iter().map(|event| event.ball_color.as_str()).collect();
+        lanes.sort();
+        lanes.dedup();
+        if lanes.is_empty() {
+            Self::draw_menu_text(frame, "No triggers yet", plot_left, top + 10, theme.muted_text, false);
+            return;
+        }
+        let lane_height = ((bottom - top) / lanes.len()).max(6);
+
+        // One tick per second, the right edge being "now"
+        for second in 0..=SECONDS as usize {
+            let x = plot_right - (second as f32 * pixels_per_second) as usize;
+            for y in top..bottom {
+                fill(frame, x, y, theme.grid_lines);
+            }
+        }
+
+        for (lane, color_name) in lanes.iter().enumerate() {
+            let lane_top = top + lane * lane_height;
+            if lane_top + lane_height > bottom {
+                break; // More colors than fit; the rest are dropped
+            }
+            let color = Self::get_color_rgb(color_name);
+            for x in plot_left..=plot_right {
+                fill(frame, x, lane_top + lane_height - 1, theme.grid_lines);
+            }
+            if lane_height >= 12 {
+                Self::draw_menu_text(frame, color_name, 5, lane_top + (lane_height - 12) / 2, color, false);
+            }
+
+            let dot_size = (lane_height / 2).clamp(2, 6);
+            let dot_y = lane_top + (lane_height - dot_size) / 2;
+            for event in history.iter().filter(|event| event.ball_color == *color_name) {
+                let age = event.timestamp.elapsed().as_secs_f32();
+                if age > SECONDS {
+                    continue;
+                }
+                let x = plot_right - (age * pixels_per_second) as usize;
+                for dy in 0..dot_size {
+                    for dx in 0..dot_size {
+                        let px = x.saturating_sub(dot_size / 2) + dx;
+                        if px >= plot_left && px <= plot_right {
+                            fill(frame, px, dot_y + dy, color);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn draw_cursor_coordinates(frame: &mut [u8], cursor_x: usize, cursor_y: usize, theme: &Theme) {
         let coord_text = format!("({}, {})", cursor_x, cursor_y);
         // Position coordinates in the black area above grid (0,0)
         // Grid (0,0) starts at pixel (0,0), so we position the text just above it
-        Self::draw_menu_text(frame, &coord_text, 5, 25, [255, 255, 255], false); // White text above grid (0,0)
+        Self::draw_menu_text(frame, &coord_text, 5, 25, theme.panel_text, false);
+    }
+
+    // Draw the help overlay listing keybindings and console commands over the grid
+    pub fn draw_help_overlay(frame: &mut [u8], theme: &Theme) {
+        let margin = 10;
+        let line_height = 14;
+        let description_x = margin + 10 + 27 * 8; // Leave room for the longest command
+        
+        // Panel background and border
+        for y in margin..window_height() - margin {
+            for x in margin..window_width() - margin {
+                let idx = (y * window_width() + x) * 4;
+                if idx + 3 < frame.len() {
+                    let on_border = x == margin || x == window_width() - margin - 1 || y == margin || y == window_height() - margin - 1;
+                    let color = if on_border { theme.panel_border } else { theme.panel_background };
+                    frame[idx..idx + 3].copy_from_slice(&color);
+                    frame[idx + 3] = 255;
+                }
+            }
+        }
+        
+        let mut text_y = margin + 10;
+        let sections: [(&str, &[KeyBinding]); 2] = [("Keybindings", KEYBINDINGS), ("Console Commands", CONSOLE_COMMANDS)];
+        for (title, bindings) in sections {
+            Self::draw_menu_text(frame, title, margin + 10, text_y, theme.heading_text, false);
+            text_y += line_height + 4;
+            for binding in bindings {
+                if text_y + 12 >= window_height() - margin {
+                    return;
+                }
+                Self::draw_menu_text(frame, binding.keys, margin + 10, text_y, theme.key_text, false);
+                Self::draw_menu_text(frame, binding.description, description_x, text_y, theme.console_text, false);
+                text_y += line_height;
+            }
+            text_y += line_height;
+        }
+        
+        Self::draw_menu_text(frame, "Press ? / F12 / ESC to close", margin + 10, text_y, theme.muted_text, false);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,19 @@
+// Shared random number generator for everything random in the simulation
+// (random(), random spawn cells, ...). It is reseeded from SequencerGrid::rng_seed
+// whenever balls start or reset, so a take played from the same seed is identical.
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::cell::RefCell;
+
+thread_local! {
+    static RNG: RefCell<StdRng> = RefCell::new(StdRng::seed_from_u64(0));
+}
+
+pub fn reseed(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
+pub fn with_rng<T>(f: impl FnOnce(&mut StdRng) -> T) -> T {
+    RNG.with(|rng| f(&mut rng.borrow_mut()))
+}
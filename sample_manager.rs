@@ -5,6 +5,7 @@ use std::io;
 /// Manages local copying and caching of audio samples
 pub struct SampleManager {
     samples_dir: PathBuf,
+    normalize_target_db: Option<f32>, // Peak level (dBFS) for imported copies, None = leave as-is
 }
 
 impl SampleManager {
@@ -17,7 +18,22 @@ impl SampleManager {
             println!("Created samples directory: {:?}", samples_dir);
         }
         
-        Ok(Self { samples_dir })
+        Ok(Self::at(samples_dir))
+    }
+    
+    /// Use `samples_dir` as-is, without creating it (imports fail until it exists)
+    pub fn at(samples_dir: PathBuf) -> Self {
+        Self { samples_dir, normalize_target_db: None }
+    }
+    
+    /// Enable or disable peak normalization of imported samples
+    pub fn set_normalization(&mut self, target_db: Option<f32>) {
+        self.normalize_target_db = target_db;
+    }
+    
+    /// Get the current normalization target, if enabled
+    pub fn normalization(&self) -> Option<f32> {
+        self.normalize_target_db
     }
     
     /// Copy an audio file to the local samples folder and return the local path
@@ -33,17 +49,63 @@ impl SampleManager {
         let dest_path = self.samples_dir.join(&*filename);
         
         // Copy the file if it doesn't already exist or if source is newer
-        if !dest_path.exists() || self.should_update_file(source, &dest_path)? {
+        let copied = !dest_path.exists() || self.should_update_file(source, &dest_path)?;
+        if copied {
             fs::copy(source, &dest_path)?;
             println!("Copied sample {} to local samples folder", filename);
         } else {
             println!("Sample {} already exists in local samples folder", filename);
         }
         
+        // Normalize a fresh local copy only; the source file is never touched, and an
+        // existing copy was already normalized when it was imported
+        if let Some(target_db) = self.normalize_target_db.filter(|_| copied) {
+            match normalize_wav_peak(&dest_path, target_db) {
+                Ok(gain_db) => println!("Normalized {} to {:.1} dBFS (gain {:+.1} dB)", filename, target_db, gain_db),
+                Err(e) => println!("Skipped normalizing {}: {}", filename, e),
+            }
+        }
+        
         // Return the local path as a string
         Ok(dest_path.to_string_lossy().to_string())
     }
     
+    /// Import every supported audio file in a directory, returning the local paths
+    /// of imported samples and the names of files that were skipped
+    pub fn import_folder(&self, folder_path: &str) -> io::Result<(Vec<String>, Vec<String>)> {
+        let mut imported = Vec::new();
+        let mut skipped = Vec::new();
+        
+        let mut entries: Vec<PathBuf> = fs::read_dir(folder_path)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.is_file())
+            .collect();
+        entries.sort();
+        
+        for path in entries {
+            let filename = path.file_name().map(|f| f.to_string_lossy().to_string()).unwrap_or_default();
+            let supported = path.extension()
+                .and_then(|ext| ext.to_str())
+                .map(|ext| matches!(ext.to_lowercase().as_str(), "wav" | "mp3"))
+                .unwrap_or(false);
+            
+            if !supported {
+                skipped.push(filename);
+                continue;
+            }
+            
+            match self.import_sample(&path.to_string_lossy()) {
+                Ok(local_path) => imported.push(local_path),
+                Err(e) => {
+                    println!("Failed to import {}: {}", filename, e);
+                    skipped.push(filename);
+                }
+            }
+        }
+        
+        Ok((imported, skipped))
+    }
+    
     /// Check if source file is newer than destination
     fn should_update_file(&self, source: &Path, dest: &Path) -> io::Result<bool> {
         let source_modified = source.metadata()?.modified()?;
@@ -102,6 +164,82 @@ impl SampleManager {
     }
 }
 
+/// Peak-normalize a PCM or float WAV file in place, returning the applied gain in dB
+fn normalize_wav_peak(path: &Path, target_db: f32) -> io::Result<f32> {
+    let mut bytes = fs::read(path)?;
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "only WAV files can be normalized"));
+    }
+    
+    // Walk the RIFF chunks to find the sample format and the audio data
+    let mut format = None;
+    let mut data_range = None;
+    let mut pos = 12;
+    while pos + 8 <= bytes.len() {
+        let size = u32::from_le_bytes([bytes[pos + 4], bytes[pos + 5], bytes[pos + 6], bytes[pos + 7]]) as usize;
+        let body = pos + 8;
+        let end = (body + size).min(bytes.len());
+        match &bytes[pos..pos + 4] {
+            b"fmt " if end - body >= 16 => {
+                let mut tag = u16::from_le_bytes([bytes[body], bytes[body + 1]]);
+                let bits = u16::from_le_bytes([bytes[body + 14], bytes[body + 15]]);
+                // WAVE_FORMAT_EXTENSIBLE stores the real format at the start of the sub-format GUID
+                if tag == 0xFFFE && end - body >= 26 {
+                    tag = u16::from_le_bytes([bytes[body + 24], bytes[body + 25]]);
+                }
+                format = Some((tag, bits));
+            }
+            b"data" => data_range = Some((body, end)),
+            _ => {}
+        }
+        pos = body + size + (size & 1);
+    }
+    
+    let (tag, bits) = format.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing fmt chunk"))?;
+    let (start, end) = data_range.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing data chunk"))?;
+    if !matches!((tag, bits), (1, 16) | (1, 24) | (1, 32) | (3, 32)) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("unsupported WAV format {} ({} bit)", tag, bits)));
+    }
+    
+    let width = bits as usize / 8;
+    let data = &mut bytes[start..end - (end - start) % width];
+    let is_float = tag == 3;
+    
+    let peak = data.chunks_exact(width)
+        .map(|sample| read_wav_sample(sample, is_float).abs())
+        .fold(0.0f32, f32::max);
+    if peak <= 0.0 {
+        return Ok(0.0); // Silent file, nothing to scale
+    }
+    
+    let gain = 10f32.powf(target_db / 20.0) / peak;
+    for sample in data.chunks_exact_mut(width) {
+        let value = (read_wav_sample(sample, is_float) * gain).clamp(-1.0, 1.0);
+        write_wav_sample(sample, value, is_float);
+    }
+    
+    fs::write(path, &bytes)?;
+    Ok(20.0 * gain.log10())
+}
+
+fn read_wav_sample(sample: &[u8], is_float: bool) -> f32 {
+    match sample.len() {
+        2 => i16::from_le_bytes([sample[0], sample[1]]) as f32 / 32768.0,
+        3 => (i32::from_le_bytes([0, sample[0], sample[1], sample[2]]) >> 8) as f32 / 8388608.0,
+        _ if is_float => f32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]]),
+        _ => i32::from_le_bytes([sample[0], sample[1], sample[2], sample[3]]) as f32 / 2147483648.0,
+    }
+}
+
+fn write_wav_sample(sample: &mut [u8], value: f32, is_float: bool) {
+    match sample.len() {
+        2 => sample.copy_from_slice(&((value * 32767.0) as i16).to_le_bytes()),
+        3 => sample.copy_from_slice(&((value * 8388607.0) as i32).to_le_bytes()[..3]),
+        _ if is_float => sample.copy_from_slice(&value.to_le_bytes()),
+        _ => sample.copy_from_slice(&((value as f64 * 2147483647.0) as i32).to_le_bytes()),
+    }
+}
+
 impl Default for SampleManager {
     fn default() -> Self {
         Self::new().expect("Failed to create SampleManager")
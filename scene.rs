@@ -0,0 +1,123 @@
+// Scene files: a .cant file that places squares as well as defining their programs,
+// so a reusable sub-patch can be shared as one text file:
+//
+//   // kick and a switch
+//   square (3, 4):
+//   def kick
+//   pass
+//
+//   square (5, 4): def switch
+//   set direction up
+//   end
+//   def back
+//   set direction down
+//
+// Everything from a `square (x, y):` line up to the next one is that square's
+// source, in the same format as a program file. The first function is active.
+// An `autostart [seconds]` line before the first square starts the balls once the
+// scene has loaded, after an optional countdown.
+
+use crate::programmer::SimpleProgramParser;
+use crate::sequencer::{grid_height, grid_width};
+use crate::square::Program;
+
+pub struct SceneSquare {
+    pub x: usize,
+    pub y: usize,
+    pub programs: Vec<Program>,
+}
+
+pub struct Scene {
+    pub squares: Vec<SceneSquare>,
+    pub autostart: Option<f32>, // Countdown in seconds when the scene asks to start itself
+}
+
+pub fn parse_scene(source: &str) -> Result<Scene, String> {
+    // (line number of the header, x, y, source lines)
+    let mut blocks: Vec<(usize, usize, usize, Vec<String>)> = Vec::new();
+    let mut autostart = None;
+
+    for (index, raw_line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let line = raw_line.trim();
+        if line.is_empty() || line.starts_with("//") {
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix("square").filter(|h| h.trim_start().starts_with('(')) {
+            let (x, y, rest) = parse_square_header(header).map_err(|e| format!("line {}: {}", line_number, e))?;
+            if blocks.iter().any(|(_, bx, by, _)| (*bx, *by) == (x, y)) {
+                return Err(format!("line {}: square ({}, {}) is defined twice", line_number, x, y));
+            }
+            let mut lines = Vec::new();
+            if !rest.is_empty() {
+                lines.push(rest.to_string());
+            }
+            blocks.push((line_number, x, y, lines));
+        } else if let Some(delay) = line.strip_prefix("autostart")
+            .filter(|rest| blocks.is_empty() && (rest.is_empty() || rest.starts_with(char::is_whitespace))) {
+            let delay = delay.trim();
+            autostart = Some(if delay.is_empty() {
+                0.0
+            } else {
+                delay.parse::<f32>().ok().filter(|d| *d >= 0.0)
+                    .ok_or_else(|| format!("line {}: invalid autostart countdown '{}'", line_number, delay))?
+            });
+        } else {
+            match blocks.last_mut() {
+                Some((_, _, _, lines)) => lines.push(raw_line.to_string()),
+                None => return Err(format!("line {}: expected 'square (x, y):' before any program text", line_number)),
+            }
+        }
+    }
+
+    if blocks.is_empty() {
+        return Err("Scene has no squares".to_string());
+    }
+
+    let parser = SimpleProgramParser::new();
+    let mut squares = Vec::new();
+    for (line_number, x, y, lines) in blocks {
+        let mut programs = parser.parse_multiple_programs(&lines.join("\n"))
+            .map_err(|e| format!("square ({}, {}) at line {}: {}", x, y, line_number, e))?;
+        // Keep each function's own text so it opens in the editor as written
+        for (program, source_text) in programs.iter_mut().zip(split_functions(&lines)) {
+            program.source_text = Some(source_text);
+        }
+        squares.push(SceneSquare { x, y, programs });
+    }
+    Ok(Scene { squares, autostart })
+}
+
+// Parse " (x, y): rest" after the `square` keyword
+fn parse_square_header(header: &str) -> Result<(usize, usize, &str), String> {
+    let (inner, rest) = header.trim_start().strip_prefix('(')
+        .and_then(|inner| inner.split_once(')'))
+        .ok_or("expected 'square (x, y):'")?;
+    let coordinates: Vec<&str> = inner.split(',').map(|c| c.trim()).collect();
+    let rest = rest.trim_start().strip_prefix(':').ok_or("missing ':' after square coordinates")?;
+
+    let (x, y) = match coordinates.as_slice() {
+        [x, y] => (
+            x.parse::<usize>().map_err(|_| format!("invalid x coordinate '{}'", x))?,
+            y.parse::<usize>().map_err(|_| format!("invalid y coordinate '{}'", y))?,
+        ),
+        _ => return Err("expected two coordinates, e.g. square (3, 4):".to_string()),
+    };
+    if x >= grid_width() || y >= grid_height() {
+        return Err(format!("square ({}, {}) is outside the {}x{} grid", x, y, grid_width(), grid_height()));
+    }
+    Ok((x, y, rest.trim()))
+}
+
+// Group a square's source lines into one chunk per `def`
+fn split_functions(lines: &[String]) -> Vec<Vec<String>> {
+    let mut functions: Vec<Vec<String>> = Vec::new();
+    for line in lines.iter().filter(|l| !l.trim().is_empty()) {
+        if line.trim().starts_with("def ") || functions.is_empty() {
+            functions.push(Vec::new());
+        }
+        functions.last_mut().unwrap().push(line.clone());
+    }
+    functions
+}
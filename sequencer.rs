@@ -6,11 +6,11 @@ use winit::window::WindowBuilder;
 use winit_input_helper::WinitInputHelper;
 use rfd::FileDialog;
 
-use crate::ball::{Ball, Direction};
-use crate::square::{Cell, CellContent, ProgramAction, DestroyTarget, LibraryManager};
+use crate::ball::{rebound_point, sort_nearest_first, Ball, BallConfig, Direction, BURST_DIRECTIONS, MAX_ENVELOPE_MS, MAX_PITCH, MAX_SPEED, MAX_VOLUME, MIN_PITCH, MIN_SPEED};
+use crate::square::{Cell, CellContent, Program, ProgramAction, DestroyTarget, LibraryManager, Value};
 use crate::context_menu::{ContextMenu, ContextMenuAction};
 use crate::square_menu::{SquareContextMenu, SquareMenuAction};
-use crate::programmer::ProgramExecutor;
+use crate::programmer::{ProgramExecutor, SimpleProgramParser};
 use crate::audio_engine::AudioEngine;
 use crate::library_gui::{LibraryGui, LibraryGuiAction};
 use crate::sample_manager::SampleManager;
@@ -18,6 +18,12 @@ use crate::ball_audio::BallAudioSystem;
 use crate::audio_player::{AudioPlayer, AudioPlayerAction};
 use crate::font;
 use crate::renderer::Renderer;
+use crate::modal::{Modal, ModalStack};
+use crate::key_repeat::KeyRepeat;
+use crate::theme::{Theme, BUILTIN_THEMES, THEME_FILE};
+use crate::automation::{Automation, AutomationMode, AutomationParam};
+use crate::preload::PreloadQueue;
+use crate::metronome::Metronome;
 use std::collections::VecDeque;
 use std::fs::OpenOptions;
 use std::io::Write;
@@ -30,6 +36,157 @@ pub struct CollisionEvent {
     pub square_x: usize,
     pub square_y: usize,
     pub timestamp: std::time::Instant,
+    pub time: f32, // Simulated seconds since the take started, the clock programs run on
+}
+
+// Collisions in `history` at most `window` of simulated time before `now` that match
+// `filter`. Best-effort: the history only keeps the most recent collisions.
+pub fn count_collisions_within(history: &VecDeque<CollisionEvent>, now: f32, window: std::time::Duration, filter: impl Fn(&CollisionEvent) -> bool) -> usize {
+    let window = window.as_secs_f32();
+    history.iter()
+        .rev()
+        .take_while(|event| now - event.time <= window)
+        .filter(|event| filter(event))
+        .count()
+}
+
+// Snapshot of the running patch for the stats overlay (F7)
+#[derive(Clone, Debug)]
+pub struct PatchStats {
+    pub active_balls: usize,
+    pub total_balls: usize,
+    pub squares: usize,
+    pub voices: u32,
+    pub max_voices: u32,
+    pub collisions_per_second: f32,
+    pub bpm: f32,
+    pub frame_ms: f32,
+    pub update_ms: f32,
+    pub lean: bool,
+}
+
+// Program actions held back by `wait`, applied once `remaining` runs out
+#[derive(Clone, Debug)]
+pub struct DelayedActions {
+    pub remaining: f32, // Seconds
+    pub ball_id: String, // Not an index: destroying balls shifts those before this is due
+    pub square_x: usize,
+    pub square_y: usize,
+    pub actions: Vec<ProgramAction>,
+}
+
+// The square whose program is running and the faces the ball came in through, for `reflect`
+#[derive(Clone, Copy, Debug)]
+struct ActionSite {
+    square_x: usize,
+    square_y: usize,
+    flip_x: bool,
+    flip_y: bool,
+}
+
+// What a hit's actions did to the ball, for the bounce and placement that follow
+#[derive(Clone, Copy, Debug)]
+struct HitOutcome {
+    collision_pitch: f32, // Pitch for this hit only
+    should_reset_position: bool,
+    should_snap_to_grid_center: bool,
+    explicit_bounce: bool,
+    should_pass_through: bool, // Set by `pass`: skip the default bounce
+    teleported: bool, // Already placed in another cell: no bounce or reset
+}
+
+impl HitOutcome {
+    fn new(collision_pitch: f32) -> Self {
+        Self {
+            collision_pitch,
+            should_reset_position: false,
+            should_snap_to_grid_center: false,
+            explicit_bounce: false,
+            should_pass_through: false,
+            teleported: false,
+        }
+    }
+}
+
+// Grid changes programs ask for during a step, applied once every ball has moved
+#[derive(Default)]
+struct StepEffects {
+    ball_positions: Vec<(f32, f32)>,
+    reverse_sample_actions: Vec<(String, f32, usize, usize)>,
+    create_ball_actions: Vec<(f32, f32, f32, Direction)>,
+    create_random_ball_actions: Vec<(f32, Direction)>,
+    burst_actions: Vec<(f32, f32, usize, f32)>,
+    bpm_changes: Vec<(f32, bool)>,
+    create_ball_with_library_actions: Vec<(f32, f32, String, Option<String>)>,
+    destroy_ball_actions: Vec<(f32, f32)>,
+    create_square_actions: Vec<(i32, i32)>,
+    create_square_with_program_actions: Vec<(i32, i32, Program)>,
+    create_ball_from_sample_actions: Vec<(i32, i32, String, String)>,
+    create_square_from_sample_actions: Vec<(i32, i32, String, String)>,
+    destroy_square_actions: Vec<(f32, f32)>,
+    // `destroy ball(all)` / `destroy ball(c_red)`: None matches everything
+    destroy_ball_colors: Vec<Option<String>>,
+    destroy_square_colors: Vec<Option<String>>,
+    error_comments: Vec<(usize, usize, String)>, // Added to the programs after ball iteration
+    delayed_actions: Vec<DelayedActions>,
+}
+
+// Adaptive guard on the cost of stepping the balls: when updates stay over budget the
+// grid goes lean (no per-collision trace logging, shorter collision history) until
+// they are comfortably back under it
+pub struct UpdateBudget {
+    pub update_ms: f32, // Smoothed duration of update_balls
+    pub lean: bool,
+    frames_over: u32,
+}
+
+impl UpdateBudget {
+    fn new() -> Self {
+        Self { update_ms: 0.0, lean: false, frames_over: 0 }
+    }
+
+    // Returns the new state when the grid switches in or out of lean mode
+    fn record(&mut self, elapsed: std::time::Duration) -> Option<bool> {
+        self.update_ms = self.update_ms * 0.9 + elapsed.as_secs_f32() * 1000.0 * 0.1;
+        if self.update_ms > UPDATE_BUDGET_MS {
+            self.frames_over += 1;
+        } else {
+            self.frames_over = 0;
+        }
+        if !self.lean && self.frames_over >= LEAN_AFTER_FRAMES {
+            self.lean = true;
+            return Some(true);
+        }
+        if self.lean && self.update_ms < UPDATE_BUDGET_MS / 2.0 {
+            self.lean = false;
+            return Some(false);
+        }
+        None
+    }
+}
+
+// Console lines collected while the balls step, written out once the step is done.
+// Trace lines follow every action as it runs and are not even formatted in lean mode;
+// notices (program `log` output, clamp warnings) always get through.
+pub struct StepLog {
+    trace: bool,
+    lines: Vec<String>,
+}
+
+impl StepLog {
+    fn new(trace: bool) -> Self {
+        Self { trace, lines: Vec::new() }
+    }
+
+    pub fn trace(&mut self, line: impl FnOnce() -> String) {
+        if self.trace {
+            self.lines.push(line());
+        }
+    }
+
+    pub fn notice(&mut self, line: String) {
+        self.lines.push(line);
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -37,17 +194,75 @@ pub struct CollisionCooldown {
     pub ball_index: usize,
     pub square_x: usize,
     pub square_y: usize,
-    pub last_collision: std::time::Instant,
+    pub last_collision: f32, // Simulated seconds (program_executor.state.elapsed)
 }
 
 
-pub const GRID_WIDTH: usize = 16;
-pub const GRID_HEIGHT: usize = 12;
-const CELL_SIZE: usize = 40;
+pub const DEFAULT_GRID_WIDTH: usize = 16;
+pub const DEFAULT_GRID_HEIGHT: usize = 12;
+// Menus and panels are laid out for the default window, so grids can only grow
+pub const MAX_GRID_WIDTH: usize = 64;
+pub const MAX_GRID_HEIGHT: usize = 48;
+pub const CELL_SIZE: usize = 40;
 const CONSOLE_HEIGHT: usize = 150;
-const WINDOW_WIDTH: usize = GRID_WIDTH * CELL_SIZE;
-const WINDOW_HEIGHT: usize = GRID_HEIGHT * CELL_SIZE + CONSOLE_HEIGHT;
-const GRID_AREA_HEIGHT: usize = GRID_HEIGHT * CELL_SIZE;
+const CONSOLE_MAX_LINES: usize = 500; // Scrollback kept in memory; parser_log.txt has everything
+const CONSOLE_HISTORY_LIMIT: usize = 50; // Commands Up/Down can recall at the console prompt
+const COLLISION_HISTORY_LIMIT: usize = 400; // Enough for several seconds of dense patterns on the timeline
+const LEAN_COLLISION_HISTORY_LIMIT: usize = 100;
+const UPDATE_BUDGET_MS: f32 = 4.0; // Ball updates consistently slower than this switch the grid to lean mode
+const LEAN_AFTER_FRAMES: u32 = 30;
+const MAX_BURST_SIZE: usize = 16;
+pub const DEFAULT_BPM: f32 = 120.0;
+pub const MIN_BPM: f32 = 20.0;
+pub const MAX_BPM: f32 = 300.0;
+
+// Speed in cells per second for a ball that crosses one cell every `beats` beats:
+// bpm / 60 beats per second over beats per cell. At 120 BPM, "beats 1/4" (one cell
+// per sixteenth note) is 8 cells/s. set_bpm rescales speeds by the tempo ratio, so a
+// speed set this way stays locked to the beat when the tempo changes.
+pub fn beats_to_speed(beats: f32, bpm: f32) -> f32 {
+    bpm / 60.0 / beats
+}
+const BALL_TRIM_STEP: f32 = 0.05; // PageUp/PageDown trim step (5%)
+const PRELOAD_FRAME_BUDGET: std::time::Duration = std::time::Duration::from_millis(12); // Sample decoding per frame during bulk imports
+const MAX_SPAWNED_BALLS: usize = 64; // Bursts stop adding balls once the grid holds this many
+const MAX_FUNCTION_CALLS: usize = 16; // Per hit, so a function that returns to itself can't spin forever
+
+// Grid size in cells, chosen once at startup (see main.rs) before anything reads it
+static GRID_SIZE: std::sync::OnceLock<(usize, usize)> = std::sync::OnceLock::new();
+
+pub fn set_grid_size(width: usize, height: usize) -> Result<(), String> {
+    if !(DEFAULT_GRID_WIDTH..=MAX_GRID_WIDTH).contains(&width) || !(DEFAULT_GRID_HEIGHT..=MAX_GRID_HEIGHT).contains(&height) {
+        return Err(format!("Grid size {}x{} is outside {}x{} to {}x{}", width, height,
+            DEFAULT_GRID_WIDTH, DEFAULT_GRID_HEIGHT, MAX_GRID_WIDTH, MAX_GRID_HEIGHT));
+    }
+    GRID_SIZE.set((width, height)).map_err(|_| "Grid size is already set".to_string())
+}
+
+pub fn grid_width() -> usize {
+    GRID_SIZE.get().map_or(DEFAULT_GRID_WIDTH, |size| size.0)
+}
+
+pub fn grid_height() -> usize {
+    GRID_SIZE.get().map_or(DEFAULT_GRID_HEIGHT, |size| size.1)
+}
+
+pub fn grid_area_height() -> usize {
+    grid_height() * CELL_SIZE
+}
+
+pub fn window_width() -> usize {
+    grid_width() * CELL_SIZE
+}
+
+pub fn window_height() -> usize {
+    grid_area_height() + CONSOLE_HEIGHT
+}
+
+// A grid_width() x grid_height() grid of empty cells, indexed [y][x]
+pub fn empty_cells() -> Vec<Vec<Cell>> {
+    vec![vec![Cell::default(); grid_width()]; grid_height()]
+}
 
 pub struct Cursor {
     pub x: usize,
@@ -66,7 +281,7 @@ impl Cursor {
     }
     
     pub fn move_down(&mut self) {
-        if self.y < GRID_HEIGHT - 1 {
+        if self.y < grid_height() - 1 {
             self.y += 1;
         }
     }
@@ -78,14 +293,14 @@ impl Cursor {
     }
     
     pub fn move_right(&mut self) {
-        if self.x < GRID_WIDTH - 1 {
+        if self.x < grid_width() - 1 {
             self.x += 1;
         }
     }
 }
 
 pub struct SequencerGrid {
-    pub cells: [[Cell; GRID_WIDTH]; GRID_HEIGHT],
+    pub cells: Vec<Vec<Cell>>,
     pub cursor: Cursor,
     pub balls: Vec<Ball>,
     pub context_menu: ContextMenu,
@@ -96,21 +311,60 @@ pub struct SequencerGrid {
     pub audio_engine: AudioEngine,
     pub ball_audio_system: BallAudioSystem,
     pub console_messages: VecDeque<String>,
+    pub console_logged: usize, // Messages logged since startup, including ones dropped from the scrollback
     pub collision_cooldowns: Vec<CollisionCooldown>,
     pub library_manager: LibraryManager,
     pub library_gui: LibraryGui,
     pub sample_manager: SampleManager,
     pub audio_player: AudioPlayer,
+    pub modals: ModalStack,
     // State tracking for reset functionality
-    pub original_cells: [[Cell; GRID_WIDTH]; GRID_HEIGHT],
+    pub original_cells: Vec<Vec<Cell>>,
     pub original_balls: Vec<Ball>,
     pub ball_counter: u32,
+    // Audio overload guard: triggers are skipped above this many active samples
+    pub max_voices: u32,
+    pub last_overload: Option<std::time::Instant>,
+    // Seed for the shared RNG, reapplied on every start/reset so takes are reproducible
+    pub rng_seed: u64,
+    pub theme: Theme,
+    // Global tempo; changing it scales every ball's speed so the pattern keeps its shape
+    pub bpm: f32,
+    original_bpm: f32,
+    // Live console changes recorded during a take and replayed on later takes
+    pub automation: Automation,
+    // Ball settings copied from the context menu, waiting to be pasted onto another ball
+    pub copied_ball_config: Option<BallConfig>,
+    // Square (programs, color, label) copied with Ctrl+C, pasted with Ctrl+V
+    pub copied_cell: Option<Cell>,
+    // Corner set with Shift+S; S fills and Shift+Delete clears the rectangle to the cursor
+    pub selection_anchor: Option<(usize, usize)>,
+    pub update_budget: UpdateBudget,
+    // Samples from bulk imports, decoded a few per frame behind a progress bar
+    pub preload_queue: PreloadQueue,
+    // Detect square hits at the exact point a ball crosses a cell edge instead of per cell
+    pub precise_edges: bool,
+    // Actions from programs that hit a `wait`, due on a later update
+    pub delayed_actions: Vec<DelayedActions>,
+    // Start the balls by themselves after a load, for installations that run unattended
+    pub auto_start: bool,
+    pub auto_start_delay: f32, // Seconds
+    auto_start_countdown: Option<f32>,
+    // Click on every beat at the current BPM ('metronome on|off')
+    pub metronome: Metronome,
 }
 
 impl SequencerGrid {
-    pub fn new(audio_engine: AudioEngine) -> Self {
-        let initial_cells = std::array::from_fn(|_| std::array::from_fn(|_| Cell::default()));
-        let sample_manager = SampleManager::new().expect("Failed to create SampleManager");
+    pub fn new(mut audio_engine: AudioEngine) -> Self {
+        let initial_cells = empty_cells();
+        let metronome = Metronome::new(&mut audio_engine);
+        // A read-only working directory shouldn't stop the app, only sample imports
+        let sample_manager = SampleManager::new().unwrap_or_else(|e| {
+            eprintln!("Warning: could not create the samples directory ({}); importing samples is disabled", e);
+            SampleManager::at(std::path::PathBuf::from("samples"))
+        });
+        let rng_seed = rand::random::<u32>() as u64; // Kept short so it is easy to note down
+        crate::rng::reseed(rng_seed);
         Self {
             cells: initial_cells.clone(),
             cursor: Cursor::new(),
@@ -123,15 +377,35 @@ impl SequencerGrid {
             audio_engine,
             ball_audio_system: BallAudioSystem::new(),
             console_messages: VecDeque::new(),
+            console_logged: 0,
             collision_cooldowns: Vec::new(),
             library_manager: LibraryManager::new(),
             library_gui: LibraryGui::new(),
             sample_manager,
             audio_player: AudioPlayer::new(),
+            modals: ModalStack::new(),
             // Initialize original state
             original_cells: initial_cells,
             original_balls: Vec::new(),
             ball_counter: 0,
+            max_voices: 15,
+            last_overload: None,
+            rng_seed,
+            theme: Theme::default(),
+            bpm: DEFAULT_BPM,
+            original_bpm: DEFAULT_BPM,
+            automation: Automation::new(),
+            copied_ball_config: None,
+            copied_cell: None,
+            selection_anchor: None,
+            update_budget: UpdateBudget::new(),
+            preload_queue: PreloadQueue::new(),
+            precise_edges: false,
+            delayed_actions: Vec::new(),
+            auto_start: false,
+            auto_start_delay: 0.0,
+            auto_start_countdown: None,
+            metronome,
         }
     }
     
@@ -143,11 +417,12 @@ impl SequencerGrid {
             .as_millis();
         let formatted_message = format!("[{}] {}", timestamp, message);
         
-        // Add to console (keep only last 10 messages)
+        // Add to console scrollback
         self.console_messages.push_back(formatted_message.clone());
-        if self.console_messages.len() > 10 {
+        if self.console_messages.len() > CONSOLE_MAX_LINES {
             self.console_messages.pop_front();
         }
+        self.console_logged += 1;
         
         // Write to file
         if let Ok(mut file) = OpenOptions::new()
@@ -161,13 +436,13 @@ impl SequencerGrid {
 
     
     pub fn place_square(&mut self, x: usize, y: usize) {
-        if x < GRID_WIDTH && y < GRID_HEIGHT {
+        if x < grid_width() && y < grid_height() {
             self.cells[y][x].place_square(Some([255, 100, 100])); // Red square
         }
     }
     
     pub fn place_ball(&mut self, x: usize, y: usize) {
-        if x < GRID_WIDTH && y < GRID_HEIGHT {
+        if x < grid_width() && y < grid_height() {
             // Create a ball at this position but don't start it moving
             self.ball_counter += 1;
             let ball_id = format!("ball{}", self.ball_counter);
@@ -177,7 +452,7 @@ impl SequencerGrid {
     }
     
     pub fn clear_cell(&mut self, x: usize, y: usize) {
-        if x < GRID_WIDTH && y < GRID_HEIGHT {
+        if x < grid_width() && y < grid_height() {
             self.cells[y][x].clear();
             
             // Remove any ball at this position (check both original and current positions)
@@ -201,7 +476,7 @@ impl SequencerGrid {
         if let Some(ball_index) = self.get_ball_at(x, y) {
             self.context_menu.open_ball_menu(ball_index);
             self.selected_ball = Some(ball_index);
-        } else if x < GRID_WIDTH && y < GRID_HEIGHT && self.cells[y][x].is_square() {
+        } else if x < grid_width() && y < grid_height() && self.cells[y][x].is_square() {
             // Open square programming menu
             self.square_menu.open_square_menu(x, y);
         }
@@ -254,12 +529,303 @@ impl SequencerGrid {
         }
     }
     
+    pub fn copy_ball_config(&mut self, ball_index: usize) {
+        if let Some(ball) = self.balls.get(ball_index) {
+            let label = ball.label().to_string();
+            self.copied_ball_config = Some(ball.config());
+            self.log_to_console(format!("Copied {}'s settings", label));
+        }
+    }
+    
+    // Apply the copied settings to a ball, leaving its position, id and name as they are
+    pub fn paste_ball_config(&mut self, ball_index: usize) {
+        let Some(config) = self.copied_ball_config.clone() else {
+            self.log_to_console("No ball settings copied yet".to_string());
+            return;
+        };
+        if let Some(ball) = self.balls.get_mut(ball_index) {
+            ball.apply_config(&config);
+            let label = ball.label().to_string();
+            self.log_to_console(format!("Pasted settings onto {}", label));
+        }
+    }
+    
+    pub fn set_selection_anchor(&mut self, x: usize, y: usize) {
+        self.selection_anchor = Some((x, y));
+        self.log_to_console(format!("Selection from ({}, {}) - move the cursor, then S fills or Shift+Delete clears", x, y));
+    }
+    
+    // Cells of the rectangle between the anchor and (x, y), whichever corner is which
+    fn selection_cells(&self, x: usize, y: usize) -> Vec<(usize, usize)> {
+        let Some(anchor) = self.selection_anchor else {
+            return Vec::new();
+        };
+        crate::square::wall_cells((anchor.0 as i32, anchor.1 as i32), (x as i32, y as i32), true)
+            .into_iter()
+            .map(|(cell_x, cell_y)| (cell_x as usize, cell_y as usize))
+            .collect()
+    }
+    
+    // Place squares on the selection's empty cells; squares already there keep their programs
+    pub fn fill_selection(&mut self, x: usize, y: usize) {
+        let cells = self.selection_cells(x, y);
+        let mut placed = 0;
+        for &(cell_x, cell_y) in &cells {
+            if !self.cells[cell_y][cell_x].is_square() {
+                self.place_square(cell_x, cell_y);
+                placed += 1;
+            }
+        }
+        self.selection_anchor = None;
+        self.log_to_console(format!("Filled {} of {} cells with squares", placed, cells.len()));
+    }
+    
+    // Empty every cell of the selection, balls included, as Delete does for one cell
+    pub fn clear_selection(&mut self, x: usize, y: usize) {
+        let cells = self.selection_cells(x, y);
+        for &(cell_x, cell_y) in &cells {
+            self.clear_cell(cell_x, cell_y);
+        }
+        self.selection_anchor = None;
+        self.log_to_console(format!("Cleared {} cells", cells.len()));
+    }
+    
+    pub fn copy_cell(&mut self, x: usize, y: usize) {
+        if !self.cells[y][x].is_square() {
+            self.log_to_console("Nothing to copy - move the cursor onto a square".to_string());
+            return;
+        }
+        let cell = self.cells[y][x].clone();
+        self.log_to_console(format!("Copied square ({}, {}) with {} program(s)", x, y, cell.program.programs.len()));
+        self.copied_cell = Some(cell);
+    }
+    
+    // Put a copy of the copied square here; each paste owns its programs, so editing one
+    // square never changes another. The pasted square starts with no hits or variables
+    // of its own, whatever was counted at this cell before.
+    pub fn paste_cell(&mut self, x: usize, y: usize) {
+        let Some(cell) = self.copied_cell.clone() else {
+            self.log_to_console("No square copied yet".to_string());
+            return;
+        };
+        if self.get_ball_at(x, y).is_some() {
+            self.log_to_console(format!("Can't paste onto ({}, {}) - a ball is there", x, y));
+            return;
+        }
+        for program in &cell.program.programs {
+            self.auto_add_program_to_library(program);
+        }
+        self.cells[y][x] = cell;
+        self.program_executor.reset_square_state(x, y);
+        self.log_to_console(format!("Pasted square at ({}, {})", x, y));
+        self.warn_unresolved_references(x, y);
+    }
+    
     pub fn set_ball_color(&mut self, ball_index: usize, color: String) {
         if ball_index < self.balls.len() {
             self.balls[ball_index].set_color(color);
         }
     }
     
+    pub fn set_ball_volume(&mut self, ball_index: usize, volume: f32) {
+        if ball_index < self.balls.len() {
+            self.balls[ball_index].set_volume(volume);
+        }
+    }
+    
+    pub fn set_ball_pitch(&mut self, ball_index: usize, pitch: f32) {
+        if ball_index < self.balls.len() {
+            self.balls[ball_index].set_pitch(pitch);
+        }
+    }
+    
+    // Change the tempo, scaling every ball's speed by the same ratio
+    pub fn set_bpm(&mut self, bpm: f32) {
+        let bpm = bpm.clamp(MIN_BPM, MAX_BPM);
+        let ratio = bpm / self.bpm;
+        for ball in &mut self.balls {
+            ball.scale_speed(ratio);
+        }
+        self.bpm = bpm;
+    }
+    
+    // Name a ball so programs can refer to it independently of creation order
+    pub fn set_ball_name(&mut self, ball_index: usize, name: Option<String>) {
+        if ball_index >= self.balls.len() {
+            return;
+        }
+        if let Some(name) = &name {
+            if let Err(e) = SimpleProgramParser::check_ball_name(name) {
+                self.log_to_console(e);
+                return;
+            }
+            let taken = self.balls.iter().enumerate()
+                .any(|(i, ball)| i != ball_index && ball.name.as_deref() == Some(name.as_str()));
+            if taken {
+                self.log_to_console(format!("Another ball is already named '{}'", name));
+                return;
+            }
+        }
+        let id = self.balls[ball_index].id.clone();
+        match &name {
+            Some(name) => self.log_to_console(format!("{} is now named '{}'", id, name)),
+            None => self.log_to_console(format!("Cleared the name of {}", id)),
+        }
+        self.balls[ball_index].set_name(name);
+    }
+    
+    // Syntax errors in just-saved source, with the line they are on; the saved program
+    // keeps the text but runs nothing until it parses
+    pub fn report_parse_errors(&mut self, source_text: &[String]) {
+        if let Err(error) = SimpleProgramParser::new().parse_multiple_programs(&source_text.join("\n")) {
+            self.log_to_console(format!("Syntax error: {}", error));
+        }
+    }
+    
+    pub fn warn_unreachable_code(&mut self, source_text: &[String]) {
+        let warnings = SimpleProgramParser::new().unreachable_code_warnings(&source_text.join("\n"));
+        for warning in warnings {
+            self.log_to_console(warning);
+        }
+    }
+    
+    // Point out library calls and return targets that don't resolve yet, after a save
+    pub fn warn_unresolved_references(&mut self, x: usize, y: usize) {
+        let programs = &self.cells[y][x].program.programs;
+        let names: Vec<String> = programs.iter().map(|program| program.name.clone()).collect();
+        let mut warnings = self.library_manager.unresolved_reference_warnings(programs, &names);
+        let balls: Vec<String> = self.balls.iter()
+            .flat_map(|ball| std::iter::once(ball.id.clone()).chain(ball.name.clone()))
+            .collect();
+        for program in programs {
+            for object in SimpleProgramParser::unknown_ball_references(&program.instructions, &balls) {
+                warnings.push(format!("Warning: {}: no ball named '{}' (yet)", program.name, object));
+            }
+        }
+        for warning in warnings {
+            self.log_to_console(warning);
+        }
+    }
+    
+    pub fn stats(&self, frame_ms: f32) -> PatchStats {
+        // Rate over the last second; if the capped history doesn't reach back that far,
+        // use the span it does cover
+        let now = std::time::Instant::now();
+        let window = std::time::Duration::from_secs(1);
+        let recent = self.collision_history.iter().filter(|event| now.duration_since(event.timestamp) <= window).count();
+        let collisions_per_second = match self.collision_history.front() {
+            Some(oldest) if recent == self.collision_history.len() && recent >= self.collision_history_limit() => {
+                recent as f32 / now.duration_since(oldest.timestamp).as_secs_f32().max(0.001)
+            },
+            _ => recent as f32,
+        };
+        
+        PatchStats {
+            active_balls: self.balls.iter().filter(|ball| ball.active).count(),
+            total_balls: self.balls.len(),
+            squares: self.cells.iter().flatten().filter(|cell| cell.content == CellContent::Square).count(),
+            voices: self.audio_engine.get_active_sample_count(),
+            max_voices: self.max_voices,
+            collisions_per_second,
+            bpm: self.bpm,
+            frame_ms,
+            update_ms: self.update_budget.update_ms,
+            lean: self.update_budget.lean,
+        }
+    }
+    
+    // Create an active ball for `create ball` and bursts
+    fn spawn_ball(&mut self, x: f32, y: f32, speed: f32, direction: Direction) {
+        let grid_x = x.round() as usize;
+        let grid_y = y.round() as usize;
+        if grid_x < grid_width() && grid_y < grid_height() {
+            self.ball_counter += 1;
+            let ball_id = format!("ball{}", self.ball_counter);
+            let mut new_ball = Ball::new(grid_x, grid_y, ball_id.clone());
+            new_ball.speed = speed;
+            new_ball.direction = direction;
+            new_ball.activate(); // Activate the newly created ball
+            let is_active = new_ball.active;
+            self.balls.push(new_ball);
+            self.log_to_console(format!("Ball {} created at ({}, {}) - Total balls: {}, Active: {}", 
+                ball_id, grid_x, grid_y, self.balls.len(), is_active));
+        } else {
+            self.log_to_console(format!("Ball creation failed - coordinates ({}, {}) out of bounds", grid_x, grid_y));
+        }
+    }
+    
+    // Fan `count` balls out of (x, y), capped per burst and by the total number of balls,
+    // so a square bursting on every hit can't flood the grid
+    fn spawn_burst(&mut self, x: f32, y: f32, count: usize, speed: f32) {
+        for direction in BURST_DIRECTIONS.iter().cycle().take(count.min(MAX_BURST_SIZE)) {
+            if self.balls.len() >= MAX_SPAWNED_BALLS {
+                self.log_to_console(format!("Burst stopped - limit of {} balls reached", MAX_SPAWNED_BALLS));
+                break;
+            }
+            self.spawn_ball(x, y, speed, *direction);
+        }
+    }
+    
+    // Count down held-back actions and hand over the ones that are due
+    fn take_due_delayed_actions(&mut self, delta_time: f32) -> Vec<DelayedActions> {
+        for batch in &mut self.delayed_actions {
+            batch.remaining -= delta_time;
+        }
+        let (due, pending) = std::mem::take(&mut self.delayed_actions).into_iter().partition(|batch| batch.remaining <= 0.0);
+        self.delayed_actions = pending;
+        due
+    }
+    
+    fn collision_history_limit(&self) -> usize {
+        if self.update_budget.lean { LEAN_COLLISION_HISTORY_LIMIT } else { COLLISION_HISTORY_LIMIT }
+    }
+    
+    // Live mix trim from the grid; the saved original is updated too so a reset keeps the balance
+    pub fn nudge_ball_trim(&mut self, ball_index: usize, delta: f32) {
+        if ball_index >= self.balls.len() {
+            return;
+        }
+        let ball = &mut self.balls[ball_index];
+        ball.set_trim(ball.trim + delta);
+        let (id, trim) = (ball.id.clone(), ball.trim);
+        for original in self.original_balls.iter_mut().filter(|original| original.id == id) {
+            original.set_trim(trim);
+        }
+    }
+    
+    // Play the sample of the ball under the cursor once, at the ball's pitch and volume
+    pub fn audition_at_cursor(&mut self) {
+        let ball = self.get_ball_at(self.cursor.x, self.cursor.y).map(|index| &self.balls[index]);
+        let Some((sample_path, pitch, volume)) = ball.and_then(|ball| {
+            ball.sample_path.clone().map(|path| (path, ball.pitch, ball.output_volume()))
+        }) else {
+            self.log_to_console("No ball with a sample under the cursor".to_string());
+            return;
+        };
+        if let Err(e) = self.audio_engine.play_on_channel_with_pitch_and_volume(0, &sample_path, pitch, volume) {
+            self.log_to_console(format!("Failed to play sample {}: {}", sample_path, e));
+        }
+    }
+    
+    // Set the glyph of the ball under the cursor, or of the square there if no ball is
+    pub fn set_glyph_at_cursor(&mut self, glyph: Option<char>) -> Result<String, String> {
+        let (x, y) = (self.cursor.x, self.cursor.y);
+        if let Some(ball_index) = self.get_ball_at(x, y) {
+            let ball = &mut self.balls[ball_index];
+            ball.glyph = glyph;
+            let id = ball.id.clone();
+            for original in self.original_balls.iter_mut().filter(|original| original.id == id) {
+                original.glyph = glyph;
+            }
+            return Ok(format!("Ball {}", id));
+        }
+        if x < grid_width() && y < grid_height() && self.cells[y][x].is_square() {
+            self.cells[y][x].glyph = glyph;
+            return Ok(format!("Square ({}, {})", x, y));
+        }
+        Err("No ball or square under the cursor".to_string())
+    }
+    
     pub fn reset_balls_to_origin(&mut self) {
         for ball in &mut self.balls {
             ball.reset_to_original();
@@ -278,6 +844,9 @@ impl SequencerGrid {
         if any_active {
             // If any balls are active, reset to original state
             self.reset_to_original_state();
+            if let Some(count) = self.automation.stop_take() {
+                self.log_to_console(format!("Recorded {} automation events - they play back on the next take", count));
+            }
         } else {
             // If no balls are active, save current state as original and start balls
             self.save_current_state_as_original();
@@ -286,14 +855,48 @@ impl SequencerGrid {
             }
         }
         
-        // Reset all hit counts and variables when toggling ball states
+        // Reset all hit counts and variables when toggling ball states; the
+        // simulated clock restarts too, so cooldowns from the last take are void
         self.program_executor.reset_all_state();
+        self.collision_cooldowns.clear();
+        
+        // Replay randomness from the same seed on every take
+        crate::rng::reseed(self.rng_seed);
+        if !any_active {
+            self.log_to_console(format!("Take started with seed {}", self.rng_seed));
+            self.automation.start_take();
+            self.ball_audio_system.start_take();
+            self.metronome.restart();
+            // Start the recording from the current mix so playback begins from the same place
+            let master_volume = self.audio_engine.master_volume();
+            self.automation.record(AutomationParam::MasterVolume, master_volume);
+        }
+    }
+    
+    // Apply an automatable parameter; `record` is false for playback so it isn't captured again
+    fn apply_automation(&mut self, param: AutomationParam, value: f32, record: bool) {
+        match param {
+            AutomationParam::MasterVolume => {
+                self.audio_engine.set_master_volume(value);
+            }
+            AutomationParam::Bpm => self.set_bpm(value),
+            AutomationParam::ChannelVolume(channel_id) => {
+                if let Err(e) = self.audio_engine.set_channel_volume(channel_id, value) {
+                    self.log_to_console(format!("Mix change failed: {}", e));
+                    return;
+                }
+            }
+        }
+        if record {
+            self.automation.record(param, value);
+        }
     }
     
     pub fn save_current_state_as_original(&mut self) {
         // Save current grid state as the original state
         self.original_cells = self.cells.clone();
         self.original_balls = self.balls.clone();
+        self.original_bpm = self.bpm;
         self.log_to_console("Current state saved as original".to_string());
     }
     
@@ -323,10 +926,33 @@ impl SequencerGrid {
         // Clear collision history and cooldowns
         self.collision_history.clear();
         self.collision_cooldowns.clear();
+        self.delayed_actions.clear();
+        
+        // Ball speeds were restored above, so restore the tempo they were saved at
+        self.bpm = self.original_bpm;
         
         self.log_to_console("Grid reset to original state".to_string());
     }
     
+    // Bring the modal stack in line with which overlays are currently open
+    pub fn sync_modals(&mut self) {
+        self.modals.sync(&[
+            (Modal::ContextMenu, self.context_menu.is_open()),
+            (Modal::SquareMenu, self.square_menu.is_open()),
+            (Modal::LibraryGui, self.library_gui.is_visible()),
+            (Modal::AudioPlayer, self.audio_player.is_visible()),
+        ]);
+    }
+    
+    // Count collisions within the last `window` of the take that match `filter`.
+    // Best-effort: only the most recent collisions are kept in the history.
+    pub fn count_collisions_since<F>(&self, window: std::time::Duration, filter: F) -> usize
+    where
+        F: Fn(&CollisionEvent) -> bool,
+    {
+        count_collisions_within(&self.collision_history, self.program_executor.state.elapsed, window, filter)
+    }
+    
     pub fn find_last_ball_collision(&self, ball_color: &str, square_x: usize, square_y: usize) -> Option<usize> {
         // Find the most recent collision of a ball with the specified color hitting the specified square
         self.collision_history
@@ -355,6 +981,7 @@ impl SequencerGrid {
                 name: "auto".to_string(),
                 samples: std::collections::HashMap::new(),
                 description: "Automatically generated samples from loaded files".to_string(),
+                enabled: true,
             };
             self.library_manager.add_sample_library(auto_library);
         }
@@ -417,6 +1044,7 @@ impl SequencerGrid {
                 name: library_name.to_string(),
                 samples: std::collections::HashMap::new(),
                 description: format!("Sample library: {}", library_name),
+                enabled: true,
             };
             self.library_manager.add_sample_library(new_library);
         }
@@ -442,6 +1070,17 @@ impl SequencerGrid {
         }
     }
     
+    // Find a sample template's behavior program: "lib.fn" names its library, a bare name
+    // is looked up in the template's own library first and then in "lib"
+    fn resolve_behavior_program(&self, library_name: &str, program_name: &str) -> Option<crate::square::Program> {
+        if let Some((library, function)) = program_name.split_once('.') {
+            return self.library_manager.get_function(library, function).cloned();
+        }
+        self.library_manager.get_function(library_name, program_name)
+            .or_else(|| self.library_manager.get_function("lib", program_name))
+            .cloned()
+    }
+
     // Automatically add program to library when created in square
     pub fn auto_add_program_to_library(&mut self, program: &crate::square::Program) {
         use crate::square::FunctionLibrary;
@@ -457,6 +1096,7 @@ impl SequencerGrid {
                 name: "auto".to_string(),
                 functions: std::collections::HashMap::new(),
                 description: "Automatically generated functions from square programs".to_string(),
+                enabled: true,
             };
             self.library_manager.add_function_library(auto_library);
         }
@@ -496,6 +1136,42 @@ impl SequencerGrid {
                                 self.list_all_samples();
                             }
                         },
+                        "enable" | "disable" => {
+                            let enabled = parts[1] == "enable";
+                            match parts.get(2) {
+                                Some(name) => self.set_library_enabled(name, enabled),
+                                None => self.log_to_console(format!("Usage: lib {} <library>", parts[1])),
+                            }
+                        },
+                        "import-folder" => {
+                            if parts.len() > 2 {
+                                // Folder names may contain spaces
+                                let folder_path = parts[2..].join(" ");
+                                self.import_sample_folder(&folder_path);
+                            } else if let Some(folder) = FileDialog::new()
+                                .set_title("Select Sample Folder to Import as Library")
+                                .pick_folder()
+                            {
+                                if let Some(folder_str) = folder.to_str() {
+                                    self.import_sample_folder(folder_str);
+                                }
+                            }
+                        },
+                        "mutate" => {
+                            if parts.len() > 3 {
+                                let seed = match parts.get(4).map(|s| s.parse::<u64>()) {
+                                    Some(Ok(seed)) => seed,
+                                    Some(Err(_)) => {
+                                        self.log_to_console(format!("Invalid seed '{}'", parts[4]));
+                                        return;
+                                    },
+                                    None => rand::random(),
+                                };
+                                self.mutate_library_function(parts[2], parts[3], seed);
+                            } else {
+                                self.log_to_console("Usage: lib mutate <library> <function> [seed]".to_string());
+                            }
+                        },
                         "clear" => {
                             if parts.len() > 2 && parts[2] == "auto" {
                                 self.clear_auto_library();
@@ -507,69 +1183,702 @@ impl SequencerGrid {
                     }
                 }
             },
-            _ => {}
-        }
-    }
-    
-    fn show_library_help(&mut self) {
-        self.log_to_console("Library Commands:".to_string());
-        self.log_to_console("  lib list - List all libraries".to_string());
-        self.log_to_console("  lib functions [library] - List functions".to_string());
-        self.log_to_console("  lib samples [library] - List samples".to_string());
-        self.log_to_console("  lib clear auto - Clear auto-generated library".to_string());
-    }
-    
-    fn list_libraries(&mut self) {
-        let mut messages = Vec::new();
-        messages.push("Function Libraries:".to_string());
-        for (name, lib) in &self.library_manager.function_libraries {
-            messages.push(format!("  {} - {} ({} functions)", name, lib.description, lib.functions.len()));
-        }
-        messages.push("Sample Libraries:".to_string());
-        for (name, lib) in &self.library_manager.sample_libraries {
-            messages.push(format!("  {} - {} ({} samples)", name, lib.description, lib.samples.len()));
-        }
-        for message in messages {
-            self.log_to_console(message);
-        }
-    }
-    
-    fn list_functions_in_library(&mut self, library_name: &str) {
-        if let Some(lib) = self.library_manager.function_libraries.get(library_name) {
-            let mut messages = Vec::new();
-            messages.push(format!("Functions in '{}' library:", library_name));
-            for (name, program) in &lib.functions {
-                messages.push(format!("  {} - {} instructions", name, program.instructions.len()));
-            }
-            for message in messages {
-                self.log_to_console(message);
-            }
-        } else {
-            self.log_to_console(format!("Function library '{}' not found", library_name));
-        }
-    }
-    
-    fn list_all_functions(&mut self) {
-        let mut messages = Vec::new();
-        for (lib_name, lib) in &self.library_manager.function_libraries {
-            messages.push(format!("Functions in '{}' library:", lib_name));
-            for (name, program) in &lib.functions {
-                messages.push(format!("  {}.{} - {} instructions", lib_name, name, program.instructions.len()));
-            }
-        }
-        for message in messages {
-            self.log_to_console(message);
-        }
-    }
-    
-    fn list_samples_in_library(&mut self, library_name: &str) {
-        if let Some(lib) = self.library_manager.sample_libraries.get(library_name) {
-            let mut messages = Vec::new();
-            messages.push(format!("Samples in '{}' library:", library_name));
-            for (name, sample) in &lib.samples {
-                messages.push(format!("  {} - speed:{}, dir:{:?}, color:{}", 
-                    name, sample.default_speed, sample.default_direction, sample.color));
-            }
+            "maxvoices" => {
+                match parts.get(1).map(|n| n.parse::<u32>()) {
+                    Some(Ok(max_voices)) if max_voices > 0 => {
+                        self.max_voices = max_voices;
+                        self.log_to_console(format!("Max voices set to {}", max_voices));
+                    },
+                    Some(_) => self.log_to_console("Usage: maxvoices <n> (n > 0)".to_string()),
+                    None => self.log_to_console(format!("Max voices: {}", self.max_voices)),
+                }
+            },
+            "glyph" => {
+                let glyph = match parts.get(1).copied() {
+                    Some("off") => None,
+                    Some(text) if text.chars().count() == 1 => {
+                        let glyph = text.chars().next().unwrap();
+                        if !crate::font::get_font().has_char(glyph) {
+                            self.log_to_console(format!("The font has no glyph for '{}'", glyph));
+                            return;
+                        }
+                        Some(glyph)
+                    },
+                    _ => {
+                        self.log_to_console("Usage: glyph <character> | glyph off".to_string());
+                        return;
+                    }
+                };
+                match self.set_glyph_at_cursor(glyph) {
+                    Ok(target) => match glyph {
+                        Some(glyph) => self.log_to_console(format!("{} now shows '{}'", target, glyph)),
+                        None => self.log_to_console(format!("{} glyph cleared", target)),
+                    },
+                    Err(e) => self.log_to_console(e),
+                }
+            },
+            "samples" => {
+                match (parts.get(1).copied(), parts.get(2).copied()) {
+                    (Some("normalize"), Some("on")) => {
+                        let target_db = match parts.get(3) {
+                            Some(value) => match value.parse::<f32>() {
+                                Ok(db) if db <= 0.0 => db,
+                                _ => {
+                                    self.log_to_console(format!("Invalid target level '{}': expected dBFS <= 0", value));
+                                    return;
+                                }
+                            },
+                            None => -1.0,
+                        };
+                        self.sample_manager.set_normalization(Some(target_db));
+                        self.log_to_console(format!("Sample normalization on: imports peak at {:.1} dBFS", target_db));
+                    },
+                    (Some("normalize"), Some("off")) => {
+                        self.sample_manager.set_normalization(None);
+                        self.log_to_console("Sample normalization off".to_string());
+                    },
+                    (Some("normalize"), None) => {
+                        let status = match self.sample_manager.normalization() {
+                            Some(db) => format!("on ({:.1} dBFS)", db),
+                            None => "off".to_string(),
+                        };
+                        self.log_to_console(format!("Sample normalization {}", status));
+                    },
+                    _ => self.log_to_console("Usage: samples normalize [on <target_db> | off]".to_string()),
+                }
+            },
+            "import" => {
+                match parts.get(1).copied() {
+                    Some("steps") => {
+                        let path = match parts.get(2) {
+                            Some(_) => Some(parts[2..].join(" ")),
+                            None => FileDialog::new()
+                                .add_filter("Step pattern", &["json"])
+                                .pick_file()
+                                .map(|p| p.to_string_lossy().to_string()),
+                        };
+                        match path {
+                            Some(path) => self.import_step_pattern(&path),
+                            None => self.log_to_console("Step pattern import cancelled".to_string()),
+                        }
+                    },
+                    Some("scene") => {
+                        let path = match parts.get(2) {
+                            Some(_) => Some(parts[2..].join(" ")),
+                            None => FileDialog::new()
+                                .add_filter("Canticle Programs", &["cant"])
+                                .pick_file()
+                                .map(|p| p.to_string_lossy().to_string()),
+                        };
+                        match path {
+                            Some(path) => self.load_scene(&path),
+                            None => self.log_to_console("Scene import cancelled".to_string()),
+                        }
+                    },
+                    _ => self.log_to_console("Usage: import steps [file.json] | import scene [file.cant]".to_string()),
+                }
+            },
+            "offset" => {
+                match parts.get(1).map(|n| n.parse::<i32>()) {
+                    Some(Ok(offset_ms)) if (-1000..=1000).contains(&offset_ms) => {
+                        self.audio_engine.set_audio_offset_ms(offset_ms);
+                        self.log_to_console(format!("Audio offset set to {} ms", offset_ms));
+                    },
+                    Some(_) => self.log_to_console("Usage: offset <ms> (-1000 to 1000, negative = earlier)".to_string()),
+                    None => self.log_to_console(format!("Audio offset: {} ms", self.audio_engine.audio_offset_ms())),
+                }
+            },
+            "seed" => {
+                match parts.get(1).copied() {
+                    Some("random") => {
+                        self.rng_seed = rand::random::<u32>() as u64;
+                        crate::rng::reseed(self.rng_seed);
+                        self.log_to_console(format!("Seed set to {}", self.rng_seed));
+                    },
+                    Some(value) => match value.parse::<u64>() {
+                        Ok(seed) => {
+                            self.rng_seed = seed;
+                            crate::rng::reseed(seed);
+                            self.log_to_console(format!("Seed set to {}", seed));
+                        },
+                        Err(_) => self.log_to_console("Usage: seed <n> | seed random".to_string()),
+                    },
+                    None => self.log_to_console(format!("Seed: {}", self.rng_seed)),
+                }
+            },
+            "bpm" => {
+                match parts.get(1).map(|n| n.parse::<f32>()) {
+                    Some(Ok(bpm)) if bpm > 0.0 => {
+                        // Only typed changes are logged; programs and automation can change tempo on every hit
+                        self.apply_automation(AutomationParam::Bpm, bpm.clamp(MIN_BPM, MAX_BPM), true);
+                        self.log_to_console(format!("Tempo set to {:.1} BPM", self.bpm));
+                    }
+                    Some(_) => self.log_to_console(format!("Usage: bpm <value> ({}-{})", MIN_BPM, MAX_BPM)),
+                    None => self.log_to_console(format!("Tempo: {:.1} BPM", self.bpm)),
+                }
+            },
+            "volume" => {
+                match parts.get(1).map(|n| n.parse::<f32>()) {
+                    Some(Ok(volume)) if volume.is_finite() => {
+                        // Out-of-range values are clamped rather than rejected
+                        let volume = volume.clamp(0.0, 1.0);
+                        self.apply_automation(AutomationParam::MasterVolume, volume, true);
+                        self.log_to_console(format!("Master volume set to {:.2}", volume));
+                    },
+                    Some(_) => self.log_to_console("Usage: volume <0.0-1.0>".to_string()),
+                    None => self.log_to_console(format!("Master volume: {:.2}", self.audio_engine.master_volume())),
+                }
+            },
+            "mix" => {
+                match (parts.get(1).map(|n| n.parse::<u32>()), parts.get(2).map(|n| n.parse::<f32>())) {
+                    (Some(Ok(channel_id)), Some(Ok(volume))) if (0.0..=2.0).contains(&volume) => {
+                        self.apply_automation(AutomationParam::ChannelVolume(channel_id), volume, true);
+                        self.log_to_console(format!("Channel {} volume set to {:.2}", channel_id, volume));
+                    },
+                    _ => self.log_to_console("Usage: mix <channel> <0-2>".to_string()),
+                }
+            },
+            "filter" => {
+                let channel_id = parts.get(1).and_then(|name| self.audio_engine.find_channel(name));
+                match (channel_id, parts.get(2).map(|n| n.parse::<f32>())) {
+                    (Some(channel_id), Some(Ok(cutoff))) if cutoff >= 0.0 => {
+                        match self.audio_engine.set_channel_filter(channel_id, cutoff) {
+                            Ok(()) if cutoff > 0.0 => self.log_to_console(format!("Channel {} lowpass at {:.0} Hz", parts[1], cutoff)),
+                            Ok(()) => self.log_to_console(format!("Channel {} lowpass off", parts[1])),
+                            Err(e) => self.log_to_console(format!("Filter failed: {}", e)),
+                        }
+                    },
+                    _ => self.log_to_console("Usage: filter <channel> <hz> (0 = off)".to_string()),
+                }
+            },
+            "mute" | "unmute" | "solo" | "unsolo" => {
+                let command = parts[0];
+                let name = parts[1..].join(" ");
+                if command == "unsolo" && name == "all" {
+                    self.audio_engine.clear_solo();
+                    self.log_to_console("Solo cleared - every channel is heard".to_string());
+                    return;
+                }
+                let Some(channel_id) = self.audio_engine.find_channel(&name) else {
+                    let mut channels = self.audio_engine.list_channels();
+                    channels.sort();
+                    let names: Vec<String> = channels.into_iter().map(|(_, name, _)| name).collect();
+                    self.log_to_console(format!("Unknown channel '{}' (usage: {} <channel>; channels: {})", name, command, names.join(", ")));
+                    return;
+                };
+                let (result, done) = match command {
+                    "mute" => (self.audio_engine.mute_channel(channel_id, true), "muted"),
+                    "unmute" => (self.audio_engine.mute_channel(channel_id, false), "unmuted"),
+                    "solo" => (self.audio_engine.solo_channel(channel_id, true), "soloed"),
+                    _ => (self.audio_engine.solo_channel(channel_id, false), "unsoloed"),
+                };
+                match result {
+                    Ok(()) => self.log_to_console(format!("Channel {} {}", name, done)),
+                    Err(e) => self.log_to_console(format!("{} failed: {}", command, e)),
+                }
+            },
+            "autostart" => {
+                match (parts.get(1).copied(), parts.get(2).map(|n| n.parse::<f32>())) {
+                    (Some("on"), None) => {
+                        self.auto_start = true;
+                        self.auto_start_delay = 0.0;
+                        self.log_to_console("Auto-start on - balls start as soon as a scene or pattern loads".to_string());
+                    },
+                    (Some("on"), Some(Ok(delay))) if delay >= 0.0 => {
+                        self.auto_start = true;
+                        self.auto_start_delay = delay;
+                        self.log_to_console(format!("Auto-start on - balls start {:.1}s after a scene or pattern loads", delay));
+                    },
+                    (Some("off"), None) => {
+                        self.auto_start = false;
+                        self.auto_start_countdown = None;
+                        self.log_to_console("Auto-start off".to_string());
+                    },
+                    _ => self.log_to_console(format!("Auto-start {} (usage: autostart on [seconds]|off)",
+                        if self.auto_start { "on" } else { "off" })),
+                }
+            },
+            "edges" => {
+                match parts.get(1).copied() {
+                    Some("precise") => {
+                        self.precise_edges = true;
+                        self.log_to_console("Edges precise - balls bounce from where they crossed the square's edge".to_string());
+                    },
+                    Some("cell") => {
+                        self.precise_edges = false;
+                        self.log_to_console("Edges per cell - balls bounce from where they were before the hit".to_string());
+                    },
+                    _ => self.log_to_console(format!("Edges {} (usage: edges precise|cell)",
+                        if self.precise_edges { "precise" } else { "cell" })),
+                }
+            },
+            "limiter" => {
+                match (parts.get(1).copied(), parts.get(2).map(|n| n.parse::<f32>())) {
+                    (Some("on"), None) => {
+                        self.audio_engine.set_limiter_enabled(true);
+                        self.log_to_console("Limiter on".to_string());
+                    },
+                    (Some("off"), None) => {
+                        self.audio_engine.set_limiter_enabled(false);
+                        self.log_to_console("Limiter off - the master output may clip".to_string());
+                    },
+                    (Some("ceiling"), Some(Ok(ceiling_db))) if (-24.0..=0.0).contains(&ceiling_db) => {
+                        self.audio_engine.set_limiter_ceiling_db(ceiling_db);
+                        self.log_to_console(format!("Limiter ceiling set to {:.1} dBFS", ceiling_db));
+                    },
+                    _ => {
+                        let limiter = self.audio_engine.limiter();
+                        self.log_to_console(format!("Limiter {} at {:.1} dBFS (usage: limiter on|off|ceiling <-24..0 dB>)",
+                            if limiter.enabled { "on" } else { "off" }, limiter.ceiling_db()));
+                    },
+                }
+            },
+            "record" => {
+                match parts.get(1).copied() {
+                    Some("start") => {
+                        let path = if parts.len() > 2 {
+                            parts[2..].join(" ")
+                        } else {
+                            let seconds = std::time::SystemTime::now()
+                                .duration_since(std::time::UNIX_EPOCH)
+                                .unwrap_or_default()
+                                .as_secs();
+                            format!("canticle-{}.wav", seconds)
+                        };
+                        match self.audio_engine.start_recording(&path) {
+                            Ok(()) => self.log_to_console(format!("Recording output to {}", path)),
+                            Err(e) => self.log_to_console(e.to_string()),
+                        }
+                    },
+                    Some("stop") => match self.audio_engine.stop_recording() {
+                        Ok(Some((path, seconds))) => self.log_to_console(format!("Recorded {:.1}s to {}", seconds, path)),
+                        Ok(None) => self.log_to_console("Not recording (start with: record start [file.wav])".to_string()),
+                        Err(e) => self.log_to_console(e.to_string()),
+                    },
+                    _ => self.log_to_console(format!("{} (usage: record start [file.wav] | record stop)",
+                        if self.audio_engine.is_recording() { "Recording" } else { "Not recording" })),
+                }
+            },
+            "automation" => {
+                match parts.get(1).copied() {
+                    Some("record") => {
+                        self.automation.mode = AutomationMode::Record;
+                        self.log_to_console("Automation armed - volume, mix and bpm changes are recorded during the next take".to_string());
+                    },
+                    Some("play") => {
+                        self.automation.mode = AutomationMode::Play;
+                        self.log_to_console(format!("Automation playback on ({} events)", self.automation.event_count()));
+                    },
+                    Some("off") => {
+                        self.automation.mode = AutomationMode::Off;
+                        self.log_to_console("Automation off".to_string());
+                    },
+                    Some("clear") => {
+                        self.automation.clear();
+                        self.log_to_console("Automation cleared".to_string());
+                    },
+                    _ => self.log_to_console(format!("Automation: {:?}, {} events (usage: automation record|play|off|clear)",
+                        self.automation.mode, self.automation.event_count())),
+                }
+            },
+            "theme" => {
+                match parts.get(1).copied() {
+                    Some("reload") => match Theme::load(Path::new(THEME_FILE)) {
+                        Ok(theme) => {
+                            self.log_to_console(format!("Theme loaded from {} ({})", THEME_FILE, theme.name));
+                            self.theme = theme;
+                        },
+                        Err(e) => self.log_to_console(format!("Theme not loaded: {}", e)),
+                    },
+                    Some(name) => match Theme::builtin(name) {
+                        Some(theme) => {
+                            self.theme = theme;
+                            self.log_to_console(format!("Theme set to {}", name));
+                        },
+                        None => self.log_to_console(format!("Unknown theme '{}'. Available: {}", name, BUILTIN_THEMES.join(", "))),
+                    },
+                    None => self.log_to_console(format!("Theme: {} (available: {}, or 'theme reload' for {})", self.theme.name, BUILTIN_THEMES.join(", "), THEME_FILE)),
+                }
+            },
+            "metronome" => {
+                match parts.get(1).copied() {
+                    Some("on") => {
+                        self.metronome.enabled = true;
+                        self.metronome.restart();
+                        self.log_to_console(format!("Metronome on at {:.1} BPM", self.bpm));
+                    },
+                    Some("off") => {
+                        self.metronome.enabled = false;
+                        self.log_to_console("Metronome off".to_string());
+                    },
+                    None => self.log_to_console(format!("Metronome: {}", if self.metronome.enabled { "on" } else { "off" })),
+                    _ => self.log_to_console("Usage: metronome on|off".to_string()),
+                }
+            },
+            "quantize" => {
+                match parts.get(1).copied() {
+                    Some("on") => {
+                        self.ball_audio_system.quantize = true;
+                        self.log_to_console(format!("Triggers quantized to 1/16th notes at {:.1} BPM", self.bpm));
+                    },
+                    Some("off") => {
+                        self.ball_audio_system.quantize = false;
+                        self.log_to_console("Trigger quantization off".to_string());
+                    },
+                    None => {
+                        let status = if self.ball_audio_system.quantize { "on (1/16th notes)" } else { "off" };
+                        self.log_to_console(format!("Trigger quantization: {}", status));
+                    },
+                    _ => self.log_to_console("Usage: quantize on|off".to_string()),
+                }
+            },
+            "autopan" => {
+                match parts.get(1).copied() {
+                    Some("on") => {
+                        self.ball_audio_system.auto_pan = true;
+                        self.log_to_console("Auto-pan on - balls without 'set pan' sound where they are, left to right".to_string());
+                    },
+                    Some("off") => {
+                        self.ball_audio_system.auto_pan = false;
+                        self.log_to_console("Auto-pan off - balls without 'set pan' play centered".to_string());
+                    },
+                    None => {
+                        let status = if self.ball_audio_system.auto_pan { "on" } else { "off" };
+                        self.log_to_console(format!("Auto-pan: {}", status));
+                    },
+                    _ => self.log_to_console("Usage: autopan on|off".to_string()),
+                }
+            },
+            "scale" => {
+                match (parts.get(1).copied(), parts.get(2).copied()) {
+                    (Some("off"), None) => {
+                        self.ball_audio_system.scale = None;
+                        self.log_to_console("Scale quantization off".to_string());
+                    },
+                    (Some(root), Some(kind)) => match crate::ball_audio::Scale::parse(root, kind) {
+                        Ok(scale) => {
+                            self.log_to_console(format!("Pitches quantized to {}", scale.name()));
+                            self.ball_audio_system.scale = Some(scale);
+                        },
+                        Err(e) => self.log_to_console(e),
+                    },
+                    (None, _) => {
+                        let status = match &self.ball_audio_system.scale {
+                            Some(scale) => scale.name(),
+                            None => "off".to_string(),
+                        };
+                        self.log_to_console(format!("Scale quantization: {}", status));
+                    },
+                    _ => self.log_to_console("Usage: scale <root> <type> | scale off".to_string()),
+                }
+            },
+            other => self.log_to_console(format!("Unknown command '{}' (press ? for the command list)", other)),
+        }
+    }
+    
+    fn show_library_help(&mut self) {
+        self.log_to_console("Library Commands:".to_string());
+        self.log_to_console("  lib list - List all libraries".to_string());
+        self.log_to_console("  lib functions [library] - List functions".to_string());
+        self.log_to_console("  lib samples [library] - List samples".to_string());
+        self.log_to_console("  lib clear auto - Clear auto-generated library".to_string());
+        self.log_to_console("  lib enable|disable <library> - Show or hide a library in listings".to_string());
+        self.log_to_console("  lib import-folder [path] - Import a folder as a sample library".to_string());
+        self.log_to_console("  lib mutate <library> <function> [seed] - Save a randomly tweaked copy".to_string());
+    }
+    
+    // Save a mutated copy of a library function next to it as <function>_v<n>
+    fn mutate_library_function(&mut self, library_name: &str, function_name: &str, seed: u64) {
+        let mut program = match self.library_manager.mutate_function(library_name, function_name, seed) {
+            Ok(program) => program,
+            Err(e) => {
+                self.log_to_console(format!("Mutation failed: {}", e));
+                return;
+            }
+        };
+        
+        let variant_name = (1..)
+            .map(|n| format!("{}_v{}", function_name, n))
+            .find(|name| self.library_manager.get_function(library_name, name).is_none())
+            .unwrap();
+        program.name = variant_name.clone();
+        match self.library_manager.add_function_to_library(library_name, program) {
+            Ok(()) => self.log_to_console(format!("Saved {}.{} (mutated from {} with seed {})",
+                library_name, variant_name, function_name, seed)),
+            Err(e) => self.log_to_console(format!("Mutation failed: {}", e)),
+        }
+    }
+    
+    // Place the squares of a scene file, replacing whatever was in those cells.
+    // Nothing changes if any part of the scene fails to parse.
+    pub fn load_scene(&mut self, path: &str) {
+        let source = match std::fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(e) => {
+                self.log_to_console(format!("Failed to read {}: {}", path, e));
+                return;
+            }
+        };
+        
+        let scene = match crate::scene::parse_scene(&source) {
+            Ok(scene) => scene,
+            Err(e) => {
+                self.log_to_console(format!("Scene import failed: {}", e));
+                return;
+            }
+        };
+        
+        // Like pasted squares, placed squares start with no hits or variables of their own
+        let positions: Vec<(usize, usize)> = scene.squares.iter().map(|square| (square.x, square.y)).collect();
+        for square in scene.squares {
+            let cell = &mut self.cells[square.y][square.x];
+            cell.place_square(None);
+            cell.program.load_programs(square.programs);
+            self.program_executor.reset_square_state(square.x, square.y);
+        }
+        self.log_to_console(format!("Loaded scene {} ({} squares)", path, positions.len()));
+        for (x, y) in positions {
+            self.warn_unresolved_references(x, y);
+        }
+        // A scene's own autostart applies to this load only, not to the session setting
+        match scene.autostart {
+            Some(delay) => self.start_auto_start_countdown(delay),
+            None => self.arm_auto_start(),
+        }
+    }
+    
+    // After a load, count down and start the balls as if P was pressed
+    fn arm_auto_start(&mut self) {
+        if self.auto_start {
+            self.start_auto_start_countdown(self.auto_start_delay);
+        }
+    }
+    
+    fn start_auto_start_countdown(&mut self, delay: f32) {
+        self.auto_start_countdown = Some(delay);
+        if delay > 0.0 {
+            self.log_to_console(format!("Auto-start in {:.1}s", delay));
+        }
+    }
+    
+    // Replace the grid with a step-sequencer pattern: one row and ball per track
+    pub fn import_step_pattern(&mut self, path: &str) {
+        let json = match std::fs::read_to_string(path) {
+            Ok(json) => json,
+            Err(e) => {
+                self.log_to_console(format!("Failed to read {}: {}", path, e));
+                return;
+            }
+        };
+        
+        let import = match crate::import::import_steps(&json) {
+            Ok(import) => import,
+            Err(e) => {
+                self.log_to_console(format!("Step pattern import failed: {}", e));
+                return;
+            }
+        };
+        
+        for warning in import.warnings {
+            self.log_to_console(format!("Warning: {}", warning));
+        }
+        
+        self.cells = import.cells;
+        self.balls = import.balls;
+        self.ball_counter = self.balls.len() as u32;
+        self.bpm = import.bpm; // Clamped by the importer, and ball speeds already match it
+        self.collision_history.clear();
+        self.collision_cooldowns.clear();
+        self.delayed_actions.clear();
+        self.program_executor.reset_all_state();
+        
+        // Sample paths in the pattern are relative to the pattern file
+        let base_dir = Path::new(path).parent().map(|dir| dir.to_path_buf()).unwrap_or_default();
+        for ball_index in 0..self.balls.len() {
+            if let Some(sample) = self.balls[ball_index].sample_path.clone() {
+                let sample_path = if Path::new(&sample).is_relative() {
+                    base_dir.join(&sample).to_string_lossy().to_string()
+                } else {
+                    sample
+                };
+                self.set_ball_sample(ball_index, sample_path);
+            }
+        }
+        
+        self.log_to_console(format!("Imported step pattern {} with {} tracks - press P to play", path, self.balls.len()));
+        self.arm_auto_start();
+    }
+    
+    // Write the whole composition to a project file. While the balls are running the
+    // grid is mid-take, so the state saved when they started is written instead.
+    pub fn save_project(&mut self, path: &str) {
+        let running = self.balls.iter().any(|ball| ball.active);
+        let (cells, balls, bpm) = if running {
+            (&self.original_cells, &self.original_balls, self.original_bpm)
+        } else {
+            (&self.cells, &self.balls, self.bpm)
+        };
+        let project = crate::project::Project::new(cells, balls, &self.library_manager, bpm, self.auto_start, self.auto_start_delay);
+        
+        match project.to_json().and_then(|json| std::fs::write(path, json).map_err(|e| e.to_string())) {
+            Ok(()) => self.log_to_console(format!("Project saved to {} ({} squares, {} balls)",
+                path, project.squares.len(), project.balls.len())),
+            Err(e) => self.log_to_console(format!("Failed to save project: {}", e)),
+        }
+    }
+    
+    // Replace the grid, balls and libraries with a project file. The loaded state
+    // becomes the saved state that P resets to; nothing changes if the file is invalid.
+    pub fn load_project(&mut self, path: &str) {
+        let json = match std::fs::read_to_string(path) {
+            Ok(json) => json,
+            Err(e) => {
+                self.log_to_console(format!("Failed to read {}: {}", path, e));
+                return;
+            }
+        };
+        
+        let project = match crate::project::parse_project(&json) {
+            Ok(project) => project,
+            Err(e) => {
+                self.log_to_console(format!("Project load failed: {}", e));
+                return;
+            }
+        };
+        
+        self.audio_engine.stop_all();
+        self.cells = project.cells();
+        self.balls = project.balls();
+        self.library_manager = project.library_manager.clone();
+        self.bpm = project.bpm.clamp(MIN_BPM, MAX_BPM);
+        self.auto_start = project.auto_start;
+        self.auto_start_delay = project.auto_start_delay;
+        // New balls continue numbering after the highest loaded ballN id
+        self.ball_counter = self.balls.iter()
+            .filter_map(|ball| ball.id.strip_prefix("ball")?.parse::<u32>().ok())
+            .max()
+            .unwrap_or(0)
+            .max(self.balls.len() as u32);
+        self.selected_ball = None;
+        self.collision_history.clear();
+        self.collision_cooldowns.clear();
+        self.delayed_actions.clear();
+        
+        for (ball_index, saved) in project.balls.iter().enumerate() {
+            if let Some(sample_path) = &saved.config.sample_path {
+                self.set_ball_sample(ball_index, sample_path.clone());
+            }
+        }
+        
+        self.original_cells = self.cells.clone();
+        self.original_balls = self.balls.clone();
+        self.original_bpm = self.bpm;
+        self.log_to_console(format!("Loaded project {} ({} squares, {} balls)", path, project.squares.len(), self.balls.len()));
+        self.arm_auto_start();
+    }
+    
+    // Import every audio file in a folder into a sample library named after the folder
+    pub fn import_sample_folder(&mut self, folder_path: &str) {
+        use crate::library::SampleLibraryBuilder;
+        use crate::ball::Direction;
+        use std::path::Path;
+        
+        let library_name = Path::new(folder_path)
+            .file_name()
+            .and_then(|s| s.to_str())
+            .unwrap_or("imported")
+            .to_string();
+        
+        let (imported, skipped) = match self.sample_manager.import_folder(folder_path) {
+            Ok(result) => result,
+            Err(e) => {
+                self.log_to_console(format!("Failed to read folder {}: {}", folder_path, e));
+                return;
+            }
+        };
+        
+        if imported.is_empty() {
+            self.log_to_console(format!("No supported audio files found in {} ({} skipped)", folder_path, skipped.len()));
+            return;
+        }
+        
+        let mut builder = SampleLibraryBuilder::new(&library_name)
+            .with_description(&format!("Imported from folder: {}", folder_path));
+        for local_path in &imported {
+            let sample_name = Path::new(local_path)
+                .file_name()
+                .and_then(|s| s.to_str())
+                .unwrap_or("unknown");
+            builder = builder.add_sample(sample_name, 2.0, Direction::Up, "white", None);
+            self.preload_queue.push(local_path.clone());
+        }
+        
+        // Merge into an existing library of the same name rather than replacing it
+        let library = builder.build();
+        match self.library_manager.sample_libraries.get_mut(&library_name) {
+            Some(existing) => existing.samples.extend(library.samples),
+            None => self.library_manager.add_sample_library(library),
+        }
+        
+        self.log_to_console(format!("Imported {} samples into library '{}'", imported.len(), library_name));
+        if !skipped.is_empty() {
+            self.log_to_console(format!("Skipped {} files: {}", skipped.len(), skipped.join(", ")));
+        }
+    }
+    
+    fn list_libraries(&mut self) {
+        let mut messages = Vec::new();
+        messages.push("Function Libraries:".to_string());
+        for (name, lib) in &self.library_manager.function_libraries {
+            let state = if lib.enabled { "" } else { " [disabled]" };
+            messages.push(format!("  {} - {} ({} functions){}", name, lib.description, lib.functions.len(), state));
+        }
+        messages.push("Sample Libraries:".to_string());
+        for (name, lib) in &self.library_manager.sample_libraries {
+            let state = if lib.enabled { "" } else { " [disabled]" };
+            messages.push(format!("  {} - {} ({} samples){}", name, lib.description, lib.samples.len(), state));
+        }
+        for message in messages {
+            self.log_to_console(message);
+        }
+    }
+    
+    fn list_functions_in_library(&mut self, library_name: &str) {
+        if let Some(lib) = self.library_manager.function_libraries.get(library_name) {
+            let mut messages = Vec::new();
+            messages.push(format!("Functions in '{}' library:", library_name));
+            for (name, program) in &lib.functions {
+                messages.push(format!("  {} - {} instructions", name, program.instructions.len()));
+            }
+            for message in messages {
+                self.log_to_console(message);
+            }
+        } else {
+            self.log_to_console(format!("Function library '{}' not found", library_name));
+        }
+    }
+    
+    fn list_all_functions(&mut self) {
+        let mut messages = Vec::new();
+        for (lib_name, lib) in self.library_manager.function_libraries.iter().filter(|(_, lib)| lib.enabled) {
+            messages.push(format!("Functions in '{}' library:", lib_name));
+            for (name, program) in &lib.functions {
+                messages.push(format!("  {}.{} - {} instructions", lib_name, name, program.instructions.len()));
+            }
+        }
+        for message in messages {
+            self.log_to_console(message);
+        }
+    }
+    
+    fn list_samples_in_library(&mut self, library_name: &str) {
+        if let Some(lib) = self.library_manager.sample_libraries.get(library_name) {
+            let mut messages = Vec::new();
+            messages.push(format!("Samples in '{}' library:", library_name));
+            for (name, sample) in &lib.samples {
+                messages.push(format!("  {} - speed:{}, dir:{:?}, color:{}", 
+                    name, sample.default_speed, sample.default_direction, sample.color));
+            }
             for message in messages {
                 self.log_to_console(message);
             }
@@ -580,7 +1889,7 @@ impl SequencerGrid {
     
     fn list_all_samples(&mut self) {
         let mut messages = Vec::new();
-        for (lib_name, lib) in &self.library_manager.sample_libraries {
+        for (lib_name, lib) in self.library_manager.sample_libraries.iter().filter(|(_, lib)| lib.enabled) {
             messages.push(format!("Samples in '{}' library:", lib_name));
             for (name, sample) in &lib.samples {
                 messages.push(format!("  {}.{} - speed:{}, dir:{:?}, color:{}", 
@@ -592,6 +1901,13 @@ impl SequencerGrid {
         }
     }
     
+    pub fn set_library_enabled(&mut self, library_name: &str, enabled: bool) {
+        match self.library_manager.set_library_enabled(library_name, enabled) {
+            Ok(()) => self.log_to_console(format!("Library '{}' {}", library_name, if enabled { "enabled" } else { "disabled" })),
+            Err(e) => self.log_to_console(e),
+        }
+    }
+    
     fn clear_auto_library(&mut self) {
         self.library_manager.function_libraries.remove("auto");
         self.library_manager.sample_libraries.remove("auto");
@@ -600,7 +1916,7 @@ impl SequencerGrid {
     
     /// Add an error comment to the program's source text to help users identify issues
     fn add_error_comment_to_program(&mut self, grid_x: usize, grid_y: usize, error_msg: &str) {
-        if grid_x < GRID_WIDTH && grid_y < GRID_HEIGHT {
+        if grid_x < grid_width() && grid_y < grid_height() {
             let square_program = &mut self.cells[grid_y][grid_x].program;
             if let Some(active_index) = square_program.active_program {
                 if let Some(program) = square_program.programs.get_mut(active_index) {
@@ -623,107 +1939,674 @@ impl SequencerGrid {
                                     }
                                 }
                             }
-                            // If we couldn't find the specific line, add at the top
-                            if !found_error_line {
-                                source_text.insert(0, error_comment);
+                            // If we couldn't find the specific line, add at the top
+                            if !found_error_line {
+                                source_text.insert(0, error_comment);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn resolve_ball_reference(&self, ball_reference: &str, current_square_x: usize, current_square_y: usize) -> Option<usize> {
+        // Parse ball reference syntax: "last.c_red.self(-10)"
+        // Format: last.<color>.self(<speed>)
+        if ball_reference.starts_with("last.") {
+            let parts: Vec<&str> = ball_reference.split('.').collect();
+            if parts.len() >= 3 && parts[0] == "last" && parts[2].starts_with("self") {
+                let ball_color = parts[1];
+                // For "self", we look for collisions with the current square
+                return self.find_last_ball_collision(ball_color, current_square_x, current_square_y);
+            }
+        }
+        None
+    }
+    
+    // A square that bounces balls: inside the grid and not pass-through
+    fn is_solid_square(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && (x as usize) < grid_width() && (y as usize) < grid_height()
+            && self.cells[y as usize][x as usize].is_square() && !self.cells[y as usize][x as usize].pass_through
+    }
+    
+    // Add this helper function to calculate edge position based on direction
+    fn calculate_edge_position(grid_x: usize, grid_y: usize, direction: Direction) -> (f32, f32) {
+        let base_x = grid_x as f32;
+        let base_y = grid_y as f32;
+        
+        match direction {
+            Direction::Up => (base_x + 0.5, base_y),              // Top edge (ball starts at top, moves up)
+            Direction::Down => (base_x + 0.5, base_y + 1.0),      // Bottom edge (ball starts at bottom, moves down)
+            Direction::Left => (base_x, base_y + 0.5),            // Left edge (ball starts at left, moves left)
+            Direction::Right => (base_x + 1.0, base_y + 0.5),     // Right edge (ball starts at right, moves right)
+            Direction::UpLeft => (base_x, base_y),                // Top-left corner
+            Direction::UpRight => (base_x + 1.0, base_y),         // Top-right corner
+            Direction::DownLeft => (base_x, base_y + 1.0),        // Bottom-left corner
+            Direction::DownRight => (base_x + 1.0, base_y + 1.0), // Bottom-right corner
+        }
+    }
+    
+    pub fn update_balls(&mut self, delta_time: f32) -> Vec<(usize, usize, usize)> { // Returns (x, y, ball_index) of each square hit; the hit has already been sounded
+        let started = std::time::Instant::now();
+        let triggered_positions = self.step_balls(delta_time);
+        match self.update_budget.record(started.elapsed()) {
+            Some(true) => self.log_to_console(format!("Ball updates over {} ms: trace logging off, collision history shortened", UPDATE_BUDGET_MS)),
+            Some(false) => self.log_to_console("Ball updates back under budget: trace logging on".to_string()),
+            None => {}
+        }
+        triggered_positions
+    }
+    
+    // Apply the actions a program produced for `ball`. `return <fn>` and `lib.fn` calls run
+    // in place, so their actions come through here as well, and a `wait` anywhere holds back
+    // everything after it.
+    fn apply_program_actions(&mut self, ball: &mut Ball, site: ActionSite, actions: Vec<ProgramAction>, outcome: &mut HitOutcome, effects: &mut StepEffects, step_log: &mut StepLog) {
+        let (grid_x, grid_y) = (site.square_x, site.square_y);
+        let (flip_x, flip_y) = (site.flip_x, site.flip_y);
+        let mut pending: VecDeque<ProgramAction> = actions.into();
+        let mut calls = 0;
+        while let Some(action) = pending.pop_front() {
+            match action {
+                ProgramAction::SetSpeed(speed) => {
+                    step_log.trace(|| format!("  → SetSpeed: {}", speed));
+                    ball.set_speed(clamp_program_value("speed", speed, MIN_SPEED, MAX_SPEED, grid_x, grid_y, step_log));
+                    outcome.should_reset_position = true;
+                }
+                ProgramAction::SetSpeedGlide { target, duration_ms } => {
+                    step_log.trace(|| format!("  → SetSpeed: {} over {}ms", target, duration_ms));
+                    ball.glide_speed(clamp_program_value("speed", target, MIN_SPEED, MAX_SPEED, grid_x, grid_y, step_log), duration_ms);
+                    outcome.should_reset_position = true;
+                }
+                ProgramAction::SetSpeedBeats(beats) => {
+                    step_log.trace(|| format!("  → SetSpeed: {} beats per cell at {:.1} BPM", beats, self.bpm));
+                    ball.set_speed(clamp_program_value("speed", beats_to_speed(beats, self.bpm), MIN_SPEED, MAX_SPEED, grid_x, grid_y, step_log));
+                    outcome.should_reset_position = true;
+                }
+                ProgramAction::SetPitch(pitch) => {
+                    step_log.trace(|| format!("  → SetPitch: {} (collision-specific)", pitch));
+                    outcome.collision_pitch = clamp_program_value("pitch", pitch, MIN_PITCH, MAX_PITCH, grid_x, grid_y, step_log); // Apply pitch only for this collision
+                }
+                ProgramAction::SetSample { library, name } => {
+                    // Leave the current sample intact unless the new one can be resolved
+                    if self.library_manager.get_sample_template(&library, &name).is_none() {
+                        step_log.trace(|| format!("  → SetSample: '{}' not found in library '{}'", name, library));
+                    } else if !self.sample_manager.sample_exists(&name) {
+                        step_log.trace(|| format!("  → SetSample: file for '{}' missing from samples folder", name));
+                    } else {
+                        let sample_path = self.sample_manager.get_local_path(&name);
+                        step_log.trace(|| format!("  → SetSample: {}.{}", library, name));
+                        if let Err(e) = self.audio_engine.preload_sample(&sample_path) {
+                            step_log.trace(|| format!("    Warning: Failed to preload sample {}: {}", sample_path, e));
+                        }
+                        ball.set_sample(sample_path);
+                    }
+                }
+                ProgramAction::SetPitchStep(semitones) => {
+                    step_log.trace(|| format!("  → SetPitchStep: {} semitones per bounce", semitones));
+                    ball.set_pitch_step(semitones);
+                }
+                ProgramAction::SetHumanize(amount) => {
+                    step_log.trace(|| format!("  → SetHumanize: {}", amount));
+                    ball.set_humanize(amount);
+                }
+                ProgramAction::SetEnvelope { attack_ms, release_ms } => {
+                    step_log.trace(|| format!("  → SetEnvelope: attack {}ms, release {}ms", attack_ms, release_ms));
+                    ball.set_envelope(
+                        clamp_program_value("attack", attack_ms, 0.0, MAX_ENVELOPE_MS, grid_x, grid_y, step_log),
+                        clamp_program_value("release", release_ms, 0.0, MAX_ENVELOPE_MS, grid_x, grid_y, step_log),
+                    );
+                }
+                ProgramAction::SetPan(pan) => {
+                    step_log.trace(|| format!("  → SetPan: {}", pan));
+                    ball.set_pan(clamp_program_value("pan", pan, -1.0, 1.0, grid_x, grid_y, step_log));
+                }
+                ProgramAction::SetVolume(volume) => {
+                    step_log.trace(|| format!("  → SetVolume: {}", volume));
+                    ball.set_volume(clamp_program_value("volume", volume, 0.0, MAX_VOLUME, grid_x, grid_y, step_log));
+                }
+                ProgramAction::SetColor(color) => {
+                    step_log.trace(|| format!("  → SetColor: {}", color));
+                    ball.set_color(color);
+                }
+                ProgramAction::SetSquareColor { x, y, color } => {
+                    if x < grid_width() && y < grid_height() && self.cells[y][x].is_square() {
+                        step_log.trace(|| format!("  → SetSquareColor at ({}, {}): {}", x, y, color));
+                        self.cells[y][x].set_color(Renderer::get_color_rgb(&color));
+                    }
+                }
+                ProgramAction::Return(Some(function_name)) => {
+                    step_log.trace(|| format!("  → Return: calling function '{}'", function_name));
+                    // Look for the named function in the current square's programs
+                    let function = self.cells[grid_y][grid_x].program.programs.iter()
+                        .find(|program| program.name == function_name)
+                        .cloned();
+                    let Some(function) = function else {
+                        step_log.trace(|| format!("    Unknown function: {}", function_name));
+                        // Collect error info to add comment later (after ball iteration)
+                        effects.error_comments.push((grid_x, grid_y, format!("Unknown function: {}", function_name)));
+                        continue;
+                    };
+                    if calls == MAX_FUNCTION_CALLS {
+                        step_log.trace(|| format!("    Not calling {}: more than {} function calls in one hit", function_name, MAX_FUNCTION_CALLS));
+                        continue;
+                    }
+                    calls += 1;
+                    let function_actions = self.call_function(&function, ball, grid_x, grid_y, Vec::new());
+                    for function_action in function_actions.into_iter().rev() {
+                        pending.push_front(function_action);
+                    }
+                }
+                ProgramAction::Return(None) => {
+                    step_log.trace(|| "  → Return: simple return".to_string());
+                }
+                ProgramAction::End => {
+                    step_log.trace(|| "  → End: natural block termination".to_string());
+                }
+                ProgramAction::SetDirection(direction) => {
+                    step_log.trace(|| format!("  → SetDirection: {:?}", direction));
+                    // Only change direction and reposition if the ball isn't already moving in that direction
+                    if ball.direction != direction {
+                        ball.direction = direction;
+                        outcome.should_snap_to_grid_center = true;
+                    } else {
+                        step_log.trace(|| "  → Ball already moving in requested direction, ignoring".to_string());
+                    }
+                }
+                ProgramAction::Bounce => {
+                    step_log.trace(|| "  → Bounce".to_string());
+                    ball.reverse_direction();
+                    outcome.should_reset_position = true;
+                    outcome.explicit_bounce = true;
+                }
+                ProgramAction::Reflect => {
+                    step_log.trace(|| "  → Reflect".to_string());
+                    ball.reflect(flip_x, flip_y);
+                    outcome.should_reset_position = true;
+                    outcome.explicit_bounce = true;
+                }
+                ProgramAction::Pass => {
+                    step_log.trace(|| "  → Pass".to_string());
+                    outcome.should_pass_through = true;
+                }
+                ProgramAction::Teleport { x, y } => {
+                    step_log.trace(|| format!("  → Teleport to ({}, {})", x, y));
+                    outcome.teleported |= teleport_ball(ball, x, y, grid_x, grid_y, step_log);
+                }
+                ProgramAction::SetBpm { value, relative } => {
+                    step_log.trace(|| format!("  → SetBpm: {}{}", if relative { "by " } else { "" }, value));
+                    effects.bpm_changes.push((value, relative));
+                }
+                ProgramAction::SetFilter(cutoff) => {
+                    step_log.trace(|| format!("  → SetFilter: {} Hz", cutoff));
+                    ball.set_filter(cutoff);
+                }
+                ProgramAction::Stop => {
+                    step_log.trace(|| "  → Stop".to_string());
+                    ball.active = false;
+                    outcome.should_reset_position = true;
+                }
+                ProgramAction::PlaySample(sample_index) => {
+                    // Use centralized audio system for PlaySample action
+                    if let Err(e) = self.ball_audio_system.play_sample_action(
+                        &self.audio_engine,
+                        ball,
+                        outcome.collision_pitch,
+                        sample_index as u32,
+                        step_log,
+                    ) {
+                        step_log.trace(|| format!("PlaySample audio error: {}", e));
+                    }
+                    // PlaySample doesn't affect ball movement, so don't reset position
+                }
+                ProgramAction::SetReverse { ball_reference, speed } => {
+                    step_log.trace(|| format!("  → SetReverse: {} at speed {}", ball_reference, speed));
+                    // Collect for later processing to avoid borrowing conflicts
+                    effects.reverse_sample_actions.push((ball_reference, speed, grid_x, grid_y));
+                    // SetReverse doesn't affect ball movement, so don't reset position
+                }
+                ProgramAction::CreateBall { x, y, speed, direction } => {
+                    step_log.trace(|| format!("  → CreateBall at ({}, {}) with speed {} and direction {:?}", x, y, speed, direction));
+                    effects.create_ball_actions.push((x, y, speed, direction));
+                }
+                ProgramAction::CreateBallRandom { speed, direction } => {
+                    step_log.trace(|| format!("  → CreateBallRandom with speed {} and direction {:?}", speed, direction));
+                    effects.create_random_ball_actions.push((speed, direction));
+                }
+                ProgramAction::SpawnBurst { x, y, count, speed } => {
+                    step_log.trace(|| format!("  → SpawnBurst: {} balls from ({}, {})", count, x, y));
+                    effects.burst_actions.push((x, y, count, speed));
+                }
+                ProgramAction::CreateSquare { x, y } => {
+                    step_log.trace(|| format!("  → CreateSquare at ({}, {})", x, y));
+                    effects.create_square_actions.push((x, y));
+                }
+                ProgramAction::CreateSquareWithProgram { x, y, program } => {
+                    step_log.trace(|| format!("  → CreateSquareWithProgram at ({}, {})", x, y));
+                    effects.create_square_with_program_actions.push((x, y, program));
+                }
+                ProgramAction::CreateBallFromSample { x, y, library_name, sample_name } => {
+                    step_log.trace(|| format!("  → CreateBallFromSample at ({}, {}) from {}.{}", x, y, library_name, sample_name));
+                    effects.create_ball_from_sample_actions.push((x, y, library_name, sample_name));
+                }
+                ProgramAction::CreateSquareFromSample { x, y, library_name, sample_name } => {
+                    step_log.trace(|| format!("  → CreateSquareFromSample at ({}, {}) from {}.{}", x, y, library_name, sample_name));
+                    effects.create_square_from_sample_actions.push((x, y, library_name, sample_name));
+                }
+                ProgramAction::CreateBallWithLibrary { x, y, library_function, audio_file } => {
+                    step_log.trace(|| format!("  → CreateBallWithLibrary at ({}, {}) with lib.{}", x, y, library_function));
+                    if let Some(ref audio) = audio_file {
+                        step_log.trace(|| format!("    and lib.{}", audio));
+                    }
+                    
+                    // Collect ball creation with library for processing after iteration
+                    effects.create_ball_with_library_actions.push((x, y, library_function.clone(), audio_file.clone()));
+                    step_log.trace(|| format!("    Ball with library queued for creation at ({}, {})", x, y));
+                }
+                ProgramAction::CreateSquareWithLibrary { x, y, library_function, audio_file } => {
+                    step_log.trace(|| format!("  → CreateSquareWithLibrary at ({}, {}) with lib.{}", x, y, library_function));
+                    if let Some(audio) = audio_file {
+                        step_log.trace(|| format!("    and lib.{}", audio));
+                    }
+                    
+                    // Create square with library function loaded
+                    let grid_x = x as usize;
+                    let grid_y = y as usize;
+                    if grid_x < grid_width() && grid_y < grid_height() {
+                        // Get the library function program
+                        if let Some(library_program) = self.library_manager.get_function("lib", &library_function) {
+                            self.cells[grid_y][grid_x].place_square(None);
+                            self.cells[grid_y][grid_x].program.add_program(library_program.clone());
+                            let program_count = self.cells[grid_y][grid_x].program.programs.len();
+                            self.cells[grid_y][grid_x].program.set_active_program(Some(program_count - 1));
+                            
+                            step_log.trace(|| format!("    Square created at ({}, {}) with lib.{} loaded", grid_x, grid_y, library_function));
+                        } else {
+                            step_log.trace(|| format!("    Failed to load library function: lib.{}", library_function));
+                        }
+                    }
+                }
+                ProgramAction::DestroyBall { x, y, ball_reference } => {
+                    if let Some(ball_ref) = ball_reference {
+                        if ball_ref == "self" {
+                            // Destroy the current ball
+                            step_log.trace(|| format!("  → DestroyBall self ({})", ball.id));
+                            effects.destroy_ball_actions.push((ball.x, ball.y));
+                        } else if let Some(target_ball_index) = self.resolve_ball_reference(&ball_ref, grid_x, grid_y) {
+                             if target_ball_index < effects.ball_positions.len() {
+                                 let (target_x, target_y) = effects.ball_positions[target_ball_index];
+                                 step_log.trace(|| format!("  → DestroyBall {} (ball {})", ball_ref, target_ball_index));
+                                 effects.destroy_ball_actions.push((target_x, target_y));
+                            }
+                        }
+                    } else {
+                        // Coordinate-based destruction
+                        step_log.trace(|| format!("  → DestroyBall at ({}, {})", x, y));
+                        effects.destroy_ball_actions.push((x, y));
+                    }
+                }
+                ProgramAction::DestroySquare { x, y, ball_reference } => {
+                    if let Some(ball_ref) = ball_reference {
+                        if ball_ref == "self" {
+                            // Destroy square at current ball position
+                            step_log.trace(|| format!("  → DestroySquare self at ({}, {})", grid_x, grid_y));
+                            effects.destroy_square_actions.push((grid_x as f32, grid_y as f32));
+                        } else if let Some(target_ball_index) = self.resolve_ball_reference(&ball_ref, grid_x, grid_y) {
+                             if target_ball_index < effects.ball_positions.len() {
+                                 let (target_x, target_y) = effects.ball_positions[target_ball_index];
+                                 let target_grid_x = target_x.round() as usize;
+                                 let target_grid_y = target_y.round() as usize;
+                                 step_log.trace(|| format!("  → DestroySquare {} at ({}, {})", ball_ref, target_grid_x, target_grid_y));
+                                 effects.destroy_square_actions.push((target_grid_x as f32, target_grid_y as f32));
+                            }
+                        }
+                    } else {
+                        // Coordinate-based destruction
+                        step_log.trace(|| format!("  → DestroySquare at ({}, {})", x, y));
+                        effects.destroy_square_actions.push((x, y));
+                    }
+                }
+                ProgramAction::DestroyBalls { color } => {
+                    step_log.trace(|| format!("  → DestroyBalls {}", color.as_deref().unwrap_or("all")));
+                    effects.destroy_ball_colors.push(color);
+                }
+                ProgramAction::DestroySquares { color } => {
+                    step_log.trace(|| format!("  → DestroySquares {}", color.as_deref().unwrap_or("all")));
+                    effects.destroy_square_colors.push(color);
+                }
+                ProgramAction::Log(text) => {
+                    step_log.notice(format!("[log ({}, {})] {}", grid_x, grid_y, text));
+                }
+                ProgramAction::Print(text) => {
+                    step_log.trace(|| format!("  → Print: {}", text));
+                    
+                    // Store the printed text on the current square for visual display
+                    if grid_x < grid_width() && grid_y < grid_height() {
+                        if self.cells[grid_y][grid_x].content == CellContent::Square {
+                            // Truncate text to fit in square (max ~10 characters per line)
+                            let truncated_text = if text.len() > 10 {
+                                format!("{}...", &text[..7])
+                            } else {
+                                text.clone()
+                            };
+                            
+                            // Get existing text and split into lines
+                            let existing_text = self.cells[grid_y][grid_x].display_text
+                                .as_ref()
+                                .cloned()
+                                .unwrap_or_default();
+                            
+                            let mut lines: Vec<String> = existing_text.split('\n')
+                                .map(|s| s.to_string())
+                                .collect();
+                            
+                            // Ensure we have at least 3 lines
+                            while lines.len() < 3 {
+                                lines.push(String::new());
+                            }
+                            
+                            // Set the third line to the print output
+                            lines[2] = truncated_text;
+                            
+                            // Join back into display text
+                            let formatted_text = lines.join("\n");
+                            self.cells[grid_y][grid_x].display_text = Some(formatted_text);
+                        }
+                    }
+                }
+                ProgramAction::ExecuteLibraryFunction { library_function, args } => {
+                    step_log.trace(|| format!("  → ExecuteLibraryFunction: {} {:?}", library_function, args));
+                    
+                    // Parse the library function call (e.g., "lib.function_name" or "auto.test")
+                    let Some((library_name, function_name)) = library_function.split_once('.') else {
+                        step_log.trace(|| format!("    Invalid library function format: {} (expected library.function)", library_function));
+                        continue;
+                    };
+                    let Some(library_program) = self.library_manager.get_function(library_name, function_name).cloned() else {
+                        step_log.trace(|| format!("    Failed to find library function: {}.{}", library_name, function_name));
+                        continue;
+                    };
+                    if calls == MAX_FUNCTION_CALLS {
+                        step_log.trace(|| format!("    Not calling {}: more than {} function calls in one hit", library_function, MAX_FUNCTION_CALLS));
+                        continue;
+                    }
+                    if args.len() != library_program.parameters.len() {
+                        step_log.trace(|| format!("    Warning: {} expects {} arguments, got {}",
+                            function_name, library_program.parameters.len(), args.len()));
+                    }
+                    calls += 1;
+                    let library_actions = self.call_function(&library_program, ball, grid_x, grid_y, args);
+                    for library_action in library_actions.into_iter().rev() {
+                        pending.push_front(library_action);
+                    }
+                }
+                ProgramAction::Wait(delay_ms) => {
+                    // Everything after this is held back, including what called functions still had to do
+                    step_log.trace(|| format!("  → Wait: {}ms before {} more actions", delay_ms, pending.len()));
+                    effects.delayed_actions.push(DelayedActions {
+                        remaining: delay_ms / 1000.0,
+                        ball_id: ball.id.clone(),
+                        square_x: grid_x,
+                        square_y: grid_y,
+                        actions: pending.drain(..).collect(),
+                    });
+                }
+                ProgramAction::SetSliceArray { x, y, markers } => {
+                    step_log.trace(|| format!("  → SetSliceArray at ({}, {}) with {} markers", x, y, markers.len()));
+                    
+                    // Only set up the slice array if it doesn't already exist
+                    if !self.program_executor.state.slice_arrays.contains_key(&(x, y)) {
+                        // Store the slice array in the program executor state
+                        self.program_executor.state.slice_arrays.insert((x, y), markers.clone());
+                        // Initialize the hit index to 0 for first time setup
+                        self.program_executor.state.slice_hit_indices.insert((x, y), 0);
+                        step_log.trace(|| "    Slice array initialized".to_string());
+                    } else {
+                        step_log.trace(|| "    Slice array already exists, skipping setup".to_string());
+                    }
+                }
+                ProgramAction::ResetHits { x, y } => {
+                    if x < grid_width() && y < grid_height() && self.cells[y][x].is_square() {
+                        step_log.trace(|| format!("  → ResetHits at ({}, {})", x, y));
+                        self.program_executor.reset_square_hit_counts(x, y);
+                        self.cells[y][x].program.reset_hits();
+                    } else {
+                        step_log.trace(|| format!("  → ResetHits: no square at ({}, {})", x, y));
+                    }
+                }
+                ProgramAction::SetSolid { x, y, solid } => {
+                    if x < grid_width() && y < grid_height() && self.cells[y][x].is_square() {
+                        step_log.trace(|| format!("  → SetSolid at ({}, {}): {}", x, y, if solid { "on" } else { "off" }));
+                        self.cells[y][x].pass_through = !solid;
+                    }
+                }
+                ProgramAction::SetActiveProgram { x, y, program } => {
+                    if x >= grid_width() || y >= grid_height() || !self.cells[y][x].is_square() {
+                        step_log.trace(|| format!("  → SetActiveProgram: no square at ({}, {})", x, y));
+                    } else {
+                        let square_program = &mut self.cells[y][x].program;
+                        match square_program.find_program(&program) {
+                            Some(index) => {
+                                square_program.set_active_program(Some(index));
+                                step_log.trace(|| format!("  → SetActiveProgram at ({}, {}): {} ({})", x, y, index, square_program.programs[index].name));
+                            }
+                            None => {
+                                step_log.trace(|| format!("  → SetActiveProgram at ({}, {}): no program matching {:?}", x, y, program));
+                            }
+                        }
+                    }
+                }
+                ProgramAction::SetDirectionToCoordinate { target_x, target_y } => {
+                    step_log.trace(|| format!("  → SetDirectionToCoordinate: target ({}, {})", target_x, target_y));
+                    
+                    // Calculate direction vector to target
+                    let current_x = ball.x;
+                    let current_y = ball.y;
+                    let target_center_x = target_x + 0.5;
+                    let target_center_y = target_y + 0.5;
+                    
+                    let dx = target_center_x - current_x;
+                    let dy = target_center_y - current_y;
+                    let distance = (dx * dx + dy * dy).sqrt();
+                    
+                    if distance > 0.001 { // Avoid division by zero
+                        // Normalize direction vector
+                        let norm_dx = dx / distance;
+                        let norm_dy = dy / distance;
+                        
+                        // Find closest direction
+                        let direction = if norm_dx.abs() > norm_dy.abs() {
+                            if norm_dx > 0.0 { Direction::Right } else { Direction::Left }
+                        } else {
+                            if norm_dy > 0.0 { Direction::Down } else { Direction::Up }
+                        };
+                        
+                        // Check for diagonal movement
+                        let direction = if (norm_dx.abs() - norm_dy.abs()).abs() < 0.3 { // Close to diagonal
+                            match (norm_dx > 0.0, norm_dy > 0.0) {
+                                (true, true) => Direction::DownRight,
+                                (true, false) => Direction::UpRight,
+                                (false, true) => Direction::DownLeft,
+                                (false, false) => Direction::UpLeft,
+                            }
+                        } else {
+                            direction
+                        };
+                        
+                        ball.direction = direction;
+                        
+                        // Calculate time to reach target and adjust position for smooth movement
+                        let time_to_target = distance / ball.speed;
+                        let (dir_dx, dir_dy): (f32, f32) = match ball.direction {
+                            Direction::Up => (0.0, -1.0),
+                            Direction::Down => (0.0, 1.0),
+                            Direction::Left => (-1.0, 0.0),
+                            Direction::Right => (1.0, 0.0),
+                            Direction::UpLeft => (-0.707, -0.707),
+                            Direction::UpRight => (0.707, -0.707),
+                            Direction::DownLeft => (-0.707, 0.707),
+                            Direction::DownRight => (0.707, 0.707),
+                        };
+                        
+                        // Move ball back along the direction vector to ensure smooth arrival
+                        ball.x = target_center_x - (dir_dx * ball.speed * time_to_target);
+                        ball.y = target_center_y - (dir_dy * ball.speed * time_to_target);
+                        
+                        // Ensure ball stays within bounds
+                        ball.x = ball.x.max(0.0).min(grid_width() as f32);
+                        ball.y = ball.y.max(0.0).min(grid_height() as f32);
+                        
+                        outcome.should_snap_to_grid_center = true;
+                    }
+                }
+                ProgramAction::PlaySliceMarker { x, y, marker_index } => {
+                    step_log.trace(|| format!("  → PlaySliceMarker at ({}, {}) marker {}", x, y, marker_index));
+                    // Get the current slice array for this square
+                    if let Some(slice_array) = self.program_executor.state.slice_arrays.get(&(x, y)) {
+                        let current_index = self.program_executor.state.slice_hit_indices.get(&(x, y)).unwrap_or(&0);
+                        if *current_index < slice_array.len() {
+                            let marker_to_play = slice_array[*current_index];
+                            step_log.trace(|| format!("    Playing marker {} from slice array (index {})", marker_to_play, current_index));
+                            
+                            // Try to get markers from audio player first, then from saved markers
+                            let mut marker_found = false;
+                            if let Some(markers) = self.audio_player.get_markers() {
+                                // Look for marker by extracting number from "Marker_X" format or by position index
+                                let marker = markers.iter().find(|m| {
+                                    // Try to extract number from "Marker_X" format
+                                    if m.name.starts_with("Marker_") {
+                                        if let Ok(marker_num) = m.name[7..].parse::<u32>() {
+                                            return marker_num == marker_to_play;
+                                        }
+                                    }
+                                    // Fallback: try parsing the entire name as a number
+                                    m.name.parse::<u32>().unwrap_or(0) == marker_to_play
+                                });
+                                
+                                if let Some(marker) = marker {
+                                    // Play the marker using the audio engine
+                                    if let Some(sample_path) = self.audio_player.get_sample_info().map(|(path, _, _, _)| path) {
+                                        if let Err(e) = self.ball_audio_system.play_slice(&self.audio_engine, 0, sample_path, marker.position, None) {
+                                            step_log.trace(|| format!("    Error playing marker: {}", e));
+                                        }
+                                        marker_found = true;
+                                    }
+                                }
+                            }
+                            
+                            // If not found in current markers, search saved markers
+                            if !marker_found {
+                                for (sample_path, saved_markers) in self.audio_player.get_all_saved_markers() {
+                                    // Look for marker by extracting number from "Marker_X" format or by position index
+                                    let marker = saved_markers.iter().find(|m| {
+                                        // Try to extract number from "Marker_X" format
+                                        if m.name.starts_with("Marker_") {
+                                            if let Ok(marker_num) = m.name[7..].parse::<u32>() {
+                                                return marker_num == marker_to_play;
+                                            }
+                                        }
+                                        // Fallback: try parsing the entire name as a number
+                                        m.name.parse::<u32>().unwrap_or(0) == marker_to_play
+                                    });
+                                    
+                                    if let Some(marker) = marker {
+                                        if let Err(e) = self.ball_audio_system.play_slice(&self.audio_engine, 0, &sample_path, marker.position, None) {
+                                            step_log.trace(|| format!("    Error playing saved marker: {}", e));
+                                        }
+                                        marker_found = true;
+                                        break;
+                                    }
+                                }
+                            }
+                            
+                            if !marker_found {
+                                step_log.trace(|| format!("    Marker {} not found in current or saved markers", marker_to_play));
                             }
+                            
+                            // Increment the hit index for next time
+                            let next_index = (*current_index + 1) % slice_array.len();
+                            self.program_executor.state.slice_hit_indices.insert((x, y), next_index);
                         }
                     }
                 }
+                _ => {
+                    step_log.trace(|| "  → Unknown action".to_string());
+                } // Handle other actions as needed
             }
         }
     }
-
-    pub fn resolve_ball_reference(&self, ball_reference: &str, current_square_x: usize, current_square_y: usize) -> Option<usize> {
-        // Parse ball reference syntax: "last.c_red.self(-10)"
-        // Format: last.<color>.self(<speed>)
-        if ball_reference.starts_with("last.") {
-            let parts: Vec<&str> = ball_reference.split('.').collect();
-            if parts.len() >= 3 && parts[0] == "last" && parts[2].starts_with("self") {
-                let ball_color = parts[1];
-                // For "self", we look for collisions with the current square
-                return self.find_last_ball_collision(ball_color, current_square_x, current_square_y);
-            }
-        }
-        None
-    }
     
-    // Add this helper function to calculate edge position based on direction
-    fn calculate_edge_position(grid_x: usize, grid_y: usize, direction: Direction) -> (f32, f32) {
-        let base_x = grid_x as f32;
-        let base_y = grid_y as f32;
-        
-        match direction {
-            Direction::Up => (base_x + 0.5, base_y),              // Top edge (ball starts at top, moves up)
-            Direction::Down => (base_x + 0.5, base_y + 1.0),      // Bottom edge (ball starts at bottom, moves down)
-            Direction::Left => (base_x, base_y + 0.5),            // Left edge (ball starts at left, moves left)
-            Direction::Right => (base_x + 1.0, base_y + 0.5),     // Right edge (ball starts at right, moves right)
-            Direction::UpLeft => (base_x, base_y),                // Top-left corner
-            Direction::UpRight => (base_x + 1.0, base_y),         // Top-right corner
-            Direction::DownLeft => (base_x, base_y + 1.0),        // Bottom-left corner
-            Direction::DownRight => (base_x + 1.0, base_y + 1.0), // Bottom-right corner
+    // Run a called function (`return <fn>` or `lib.fn`) against the ball as it is now,
+    // with `args` bound to its parameters
+    fn call_function(&self, function: &Program, ball: &Ball, square_x: usize, square_y: usize, args: Vec<Value>) -> Vec<ProgramAction> {
+        // The calling square's own variables are visible to the function, as in the square's program
+        let mut variables = self.program_executor.state.square_variables.get(&(square_x, square_y)).cloned().unwrap_or_default();
+        variables.extend(self.program_executor.hit_count_variables(square_x, square_y, &function.instructions, &self.collision_history));
+        let mut context = crate::square::ExecutionContext {
+            variables,
+            ball_hit_count: 0,
+            square_hit_count: 0,
+            ball_x: ball.x,
+            ball_y: ball.y,
+            ball_speed: ball.speed,
+            ball_direction: ball.direction,
+            ball_pitch: ball.pitch,
+            ball_volume: ball.volume,
+            square_x,
+            square_y,
+            time: self.program_executor.state.elapsed,
+        };
+        for (parameter, value) in function.parameters.iter().zip(args) {
+            context.variables.insert(parameter.clone(), value);
         }
+        crate::square::SquareProgram::new().execute_instructions(&function.instructions, &mut context)
     }
     
-    pub fn update_balls(&mut self, delta_time: f32) -> Vec<(usize, usize, usize)> { // Returns (x, y, ball_index) where samples should be triggered
+    fn step_balls(&mut self, delta_time: f32) -> Vec<(usize, usize, usize)> {
         let mut triggered_positions = Vec::new();
         
         // Clean up finished audio samples for better performance
         self.audio_engine.cleanup_finished_samples();
-        
-        // Collect reverse sample actions to process after the mutable iteration
-        let mut reverse_sample_actions = Vec::new();
+        self.ball_audio_system.advance_clock(delta_time, self.bpm);
+        self.program_executor.state.elapsed += delta_time;
         
         // Collect all log messages to avoid borrowing conflicts
-        let mut all_log_messages = Vec::new();
-        
-        // Collect create/destroy actions to process after ball iteration
-        let mut create_ball_actions = Vec::new();
-        let mut create_ball_with_library_actions = Vec::new();
-        let mut destroy_ball_actions = Vec::new();
-        let mut create_square_actions = Vec::new();
-        let mut create_square_with_program_actions = Vec::new();
-        let mut create_ball_from_sample_actions = Vec::new();
-        let mut create_square_from_sample_actions = Vec::new();
-        let mut destroy_square_actions = Vec::new();
+        let mut step_log = StepLog::new(!self.update_budget.lean);
         
         // Performance monitoring
         let active_samples = self.audio_engine.get_active_sample_count();
-        if active_samples > 15 {
+        if active_samples > self.max_voices {
             // Skip audio processing if too many samples are playing to prevent audio engine overload
+            self.last_overload = Some(std::time::Instant::now());
             self.log_to_console(format!("Audio engine overloaded ({} samples), skipping new triggers", active_samples));
             return triggered_positions;
         }
+        self.program_executor.state.active_voices = active_samples;
         
-        // Collect ball information for reference resolution before mutable iteration
-        let ball_positions: Vec<(f32, f32)> = self.balls.iter().map(|b| (b.x, b.y)).collect();
-        let collision_history = self.collision_history.clone();
-        
-        // Helper function to resolve ball references without borrowing self
-        let resolve_ball_ref = |ball_reference: &str, current_square_x: usize, current_square_y: usize| -> Option<usize> {
-            if ball_reference.starts_with("last.") {
-                let parts: Vec<&str> = ball_reference.split('.').collect();
-                if parts.len() >= 3 && parts[0] == "last" && parts[2].starts_with("self") {
-                    let ball_color = parts[1];
-                    // Find the most recent collision of a ball with the specified color hitting the specified square
-                    return collision_history
-                        .iter()
-                        .rev() // Start from most recent
-                        .find(|event| {
-                            event.ball_color == ball_color && 
-                            event.square_x == current_square_x && 
-                            event.square_y == current_square_y
-                        })
-                        .map(|event| event.ball_index);
-                }
-            }
-            None
+        // Collect create/destroy actions to process after ball iteration
+        let mut effects = StepEffects {
+            // Ball positions for reference resolution, as they were before anything moved
+            ball_positions: self.balls.iter().map(|b| (b.x, b.y)).collect(),
+            ..StepEffects::default()
         };
+        // Taken out while they move so programs can change the rest of the grid
+        let mut balls = std::mem::take(&mut self.balls);
         
-        // Collect error comments to add after ball iteration (to avoid borrowing conflicts)
-        let mut error_comments: Vec<(usize, usize, String)> = Vec::new();
+        // Apply actions whose `wait` has run out. The ball has moved on since, so the bounce
+        // and reset the actions ask for are ignored; there's no face left to reflect off either.
+        for batch in self.take_due_delayed_actions(delta_time) {
+            let Some(ball) = balls.iter_mut().find(|ball| ball.id == batch.ball_id) else {
+                step_log.trace(|| format!("  → Dropped {} delayed actions: ball {} is gone", batch.actions.len(), batch.ball_id));
+                continue;
+            };
+            let mut outcome = HitOutcome::new(ball.pitch);
+            let site = ActionSite { square_x: batch.square_x, square_y: batch.square_y, flip_x: false, flip_y: false };
+            self.apply_program_actions(ball, site, batch.actions, &mut outcome, &mut effects, &mut step_log);
+        }
         
-        for (ball_index, ball) in self.balls.iter_mut().enumerate() {
+        let history_limit = self.collision_history_limit();
+        let precise_edges = self.precise_edges;
+        for (ball_index, ball) in balls.iter_mut().enumerate() {
             if !ball.active {
                 continue;
             }
@@ -733,12 +2616,36 @@ impl SequencerGrid {
             let old_y = ball.y;
             
             // Update ball position and get newly entered grid cells
-            let entered_cells = ball.update_position(delta_time);
+            let mut entered_cells = ball.update_position(delta_time);
+            let (new_x, new_y) = (ball.x, ball.y);
+            
+            // Only the first square hit per update is processed (see `break` below)
+            sort_nearest_first(&mut entered_cells, (old_x, old_y));
             
             // Check for collisions with squares in newly entered cells
-            for (grid_x, grid_y) in entered_cells {
-                if grid_x < GRID_WIDTH && grid_y < GRID_HEIGHT {
+            for entry in entered_cells {
+                let (grid_x, grid_y) = (entry.x, entry.y);
+                // Where a bounce puts the ball back: with precise edges, just outside the edge it
+                // crossed (plus the overshoot), otherwise wherever it was before this update
+                let (reset_x, reset_y) = if precise_edges {
+                    rebound_point((old_x, old_y), (entry.entry_x, entry.entry_y), (new_x, new_y))
+                } else {
+                    (old_x, old_y)
+                };
+                // A cell only grazed at a corner stops the ball only when the cell across that
+                // corner is solid too; a lone square leaves a gap to slip through
+                if let Some((across_x, across_y)) = entry.across_corner {
+                    if !self.is_solid_square(grid_x as i32, grid_y as i32) || !self.is_solid_square(across_x, across_y) {
+                        continue;
+                    }
+                }
+                // Faces the ball came in through, for `reflect`
+                let (flip_x, flip_y) = entry.faces;
+                if grid_x < grid_width() && grid_y < grid_height() {
                     if self.cells[grid_y][grid_x].is_square() {
+                        // Pass-through squares run their program but never bounce or sound the ball
+                        let pass_through = self.cells[grid_y][grid_x].pass_through;
+                        
                         // Record collision event
                         let collision_event = CollisionEvent {
                             ball_index,
@@ -746,11 +2653,12 @@ impl SequencerGrid {
                             square_x: grid_x,
                             square_y: grid_y,
                             timestamp: std::time::Instant::now(),
+                            time: self.program_executor.state.elapsed,
                         };
                         self.collision_history.push_back(collision_event);
                         
-                        // Keep only recent collisions (last 100)
-                        if self.collision_history.len() > 100 {
+                        // Keep only recent collisions
+                        while self.collision_history.len() > history_limit {
                             self.collision_history.pop_front();
                         }
                         
@@ -758,14 +2666,14 @@ impl SequencerGrid {
                         
                         // Check cooldown before executing program
                         let can_execute = {
-                            const COOLDOWN_MS: u128 = 100; // 100ms cooldown between executions
-                            let now = std::time::Instant::now();
+                            const COOLDOWN_SECS: f32 = 0.1; // 100ms of simulated time between executions
+                            let now = self.program_executor.state.elapsed;
                             
                             // Check if there's an existing cooldown for this combination
                             if let Some(cooldown) = self.collision_cooldowns.iter().find(|c| 
                                 c.ball_index == ball_index && c.square_x == grid_x && c.square_y == grid_y
                             ) {
-                                now.duration_since(cooldown.last_collision).as_millis() >= COOLDOWN_MS
+                                now - cooldown.last_collision >= COOLDOWN_SECS
                             } else {
                                 true // No existing cooldown
                             }
@@ -774,657 +2682,73 @@ impl SequencerGrid {
                         if can_execute {
                             let square_program = &self.cells[grid_y][grid_x].program;
                             // Debug: Show what programs are available on this square
-                            all_log_messages.push(format!("Square ({},{}) has {} programs, active: {:?}", 
+                            step_log.trace(|| format!("Square ({},{}) has {} programs, active: {:?}", 
                                 grid_x, grid_y, square_program.programs.len(), square_program.active_program));
                             for (i, prog) in square_program.programs.iter().enumerate() {
-                                all_log_messages.push(format!("  Program {}: '{}' with {} instructions", i, prog.name, prog.instructions.len()));
+                                step_log.trace(|| format!("  Program {}: '{}' with {} instructions", i, prog.name, prog.instructions.len()));
                             }
                             
-                            if !square_program.programs.is_empty() {
-                                if let Some(active_program_index) = square_program.active_program {
-                                    if let Some(program) = square_program.programs.get(active_program_index) {
-                                        let actions = self.program_executor.execute_on_collision(
-                                            program, ball, grid_x, grid_y
-                                        );
-                                        
-                                        // Collect log messages to avoid borrowing conflicts
-                                        if !actions.is_empty() {
-                                            all_log_messages.push(format!(
-                                                "Executing program at ({},{}) for {} ball: {} actions",
-                                                grid_x, grid_y, ball.color, actions.len()
-                                            ));
-                                        }
-                                        
-                                        // Check if any action requires ball position reset
-                        let mut should_reset_position = false;
-                        let mut should_snap_to_grid_center = false;
-                        let mut explicit_bounce = false;
-                        let mut collision_pitch = ball.pitch; // Start with ball's base pitch
-                        
-                        // Apply program actions to the ball
-                        for action in actions {
-                                            match action {
-                                                ProgramAction::SetSpeed(speed) => {
-                                                    all_log_messages.push(format!("  → SetSpeed: {}", speed));
-                                                    ball.speed = speed.max(0.1); // Ensure minimum speed
-                                                    should_reset_position = true;
-                                                }
-                                                ProgramAction::SetPitch(pitch) => {
-                                                    all_log_messages.push(format!("  → SetPitch: {} (collision-specific)", pitch));
-                                                    collision_pitch = pitch; // Apply pitch only for this collision
-                                                }
-                                                ProgramAction::SetVolume(volume) => {
-                                                    all_log_messages.push(format!("  → SetVolume: {}", volume));
-                                                    ball.set_volume(volume);
-                                                }
-                                                ProgramAction::SetColor(color) => {
-                                                    all_log_messages.push(format!("  → SetColor: {}", color));
-                                                    ball.set_color(color);
-                                                }
-                                                ProgramAction::Return(function_name) => {
-                                                    if let Some(ref func_name) = function_name {
-                                                        all_log_messages.push(format!("  → Return: calling function '{}'", func_name));
-                                                        
-                                                        // Look for the named function in the current square's programs
-                                                        let square_program = &self.cells[grid_y][grid_x].program;
-                                                        let mut found_function = None;
-                                                        
-                                                        for program in &square_program.programs {
-                                                            if program.name == *func_name {
-                                                                found_function = Some(program.clone());
-                                                                break;
-                                                            }
-                                                        }
-                                                        
-                                                        if let Some(target_program) = found_function {
-                                                            all_log_messages.push(format!("    Executing function: {}", func_name));
-                                                            
-                                                            // Execute the target function's instructions
-                                                            let mut context = crate::square::ExecutionContext {
-                                                                variables: std::collections::HashMap::new(),
-                                                                ball_hit_count: 0,
-                                                                square_hit_count: 0,
-                                                                ball_x: ball.x,
-                                                                ball_y: ball.y,
-                                                                ball_speed: ball.speed,
-                                                                ball_direction: ball.direction,
-                                                                ball_pitch: ball.pitch,
-                                                                ball_volume: ball.volume,
-                                                                square_x: grid_x,
-                                                                square_y: grid_y,
-                                                            };
-                                                            
-                                                            // Create a temporary SquareProgram to execute the function
-                                                            let mut temp_square_program = crate::square::SquareProgram::new();
-                                                            let function_actions = temp_square_program.execute_instructions(&target_program.instructions, &mut context);
-                                                            
-                                                            // Apply the actions from the function
-                                                            for function_action in function_actions {
-                                                                match function_action {
-                                                                    ProgramAction::CreateBall { x, y, speed, direction } => {
-                                                                        all_log_messages.push(format!("    Function creating ball at ({}, {})", x, y));
-                                                                        create_ball_actions.push((x, y, speed, direction));
-                                                                    }
-                                                                    ProgramAction::CreateSquare { x, y } => {
-                                                                        all_log_messages.push(format!("    Function creating square at ({}, {})", x, y));
-                                                                        create_square_actions.push((x, y));
-                                                                    }
-                                                                    ProgramAction::SetSpeed(speed) => {
-                                                                        all_log_messages.push(format!("    Function setting speed: {}", speed));
-                                                                        ball.speed = speed.max(0.1);
-                                                                        should_reset_position = true;
-                                                                    }
-                                                                    ProgramAction::SetPitch(pitch) => {
-                                                                        all_log_messages.push(format!("    Function setting pitch: {}", pitch));
-                                                                        ball.set_pitch(pitch);
-                                                                    }
-                                                                    ProgramAction::SetVolume(volume) => {
-                                                                        all_log_messages.push(format!("    Function setting volume: {}", volume));
-                                                                        ball.set_volume(volume);
-                                                                    }
-                                                                    ProgramAction::SetDirection(direction) => {
-                                                        all_log_messages.push(format!("    Function setting direction: {:?}", direction));
-                                                        // Only change direction and reposition if the ball isn't already moving in that direction
-                                                        if ball.direction != direction {
-                                                            ball.direction = direction;
-                                                            should_snap_to_grid_center = true;
-                                                        } else {
-                                                            all_log_messages.push("    Ball already moving in requested direction, ignoring".to_string());
-                                                        }
-                                                    }
-                                                                    ProgramAction::Bounce => {
-                                                                            all_log_messages.push("    Function bouncing".to_string());
-                                                                            ball.reverse_direction();
-                                                                            should_reset_position = true;
-                                                                            explicit_bounce = true;
-                                                                        }
-                                                                        ProgramAction::SetSliceArray { x, y, markers } => {
-                                                                            all_log_messages.push(format!("    Function setting slice array at ({}, {}) with {} markers", x, y, markers.len()));
-                                                                            
-                                                                            // Only set up the slice array if it doesn't already exist
-                                                                            if !self.program_executor.state.slice_arrays.contains_key(&(x, y)) {
-                                                                                // Store the slice array in the program executor state
-                                                                                self.program_executor.state.slice_arrays.insert((x, y), markers.clone());
-                                                                                // Initialize the hit index to 0 for first time setup
-                                                                                self.program_executor.state.slice_hit_indices.insert((x, y), 0);
-                                                                                all_log_messages.push("    Slice array initialized".to_string());
-                                                                            } else {
-                                                                                all_log_messages.push("    Slice array already exists, skipping setup".to_string());
-                                                                            }
-                                                                        }
-                                                                        // Handle other actions as needed
-                                                                        _ => {
-                                                                            all_log_messages.push(format!("    Function action: {:?}", function_action));
-                                                                        }
-                                                                }
-                                                            }
-                                                        } else {
-                                                            all_log_messages.push(format!("    Unknown function: {}", func_name));
-                                                            // Collect error info to add comment later (after ball iteration)
-                                                            error_comments.push((grid_x, grid_y, format!("Unknown function: {}", func_name)));
-                                                        }
-                                                    } else {
-                                                        all_log_messages.push("  → Return: simple return".to_string());
-                                                    }
-                                                }
-                                                ProgramAction::End => {
-                                                    all_log_messages.push("  → End: natural block termination".to_string());
-                                                }
-                                                ProgramAction::SetDirection(direction) => {
-                                                    all_log_messages.push(format!("  → SetDirection: {:?}", direction));
-                                                    // Only change direction and reposition if the ball isn't already moving in that direction
-                                                    if ball.direction != direction {
-                                                        ball.direction = direction;
-                                                        should_snap_to_grid_center = true;
-                                                    } else {
-                                                        all_log_messages.push("  → Ball already moving in requested direction, ignoring".to_string());
-                                                    }
-                                                }
-                                                ProgramAction::Bounce => {
-                                                    all_log_messages.push("  → Bounce".to_string());
-                                                    ball.reverse_direction();
-                                                    should_reset_position = true;
-                                                    explicit_bounce = true;
-                                                }
-                                                ProgramAction::Stop => {
-                                                    all_log_messages.push("  → Stop".to_string());
-                                                    ball.active = false;
-                                                    should_reset_position = true;
-                                                }
-                                                ProgramAction::PlaySample(sample_index) => {
-                                                    // Use centralized audio system for PlaySample action
-                                                    if let Err(e) = self.ball_audio_system.play_sample_action(
-                                                        &self.audio_engine,
-                                                        ball,
-                                                        collision_pitch,
-                                                        sample_index as u32,
-                                                        &mut all_log_messages,
-                                                    ) {
-                                                        all_log_messages.push(format!("PlaySample audio error: {}", e));
-                                                    }
-                                                    // PlaySample doesn't affect ball movement, so don't reset position
-                                                }
-                                                ProgramAction::SetReverse { ball_reference, speed } => {
-                                                    all_log_messages.push(format!("  → SetReverse: {} at speed {}", ball_reference, speed));
-                                                    // Collect for later processing to avoid borrowing conflicts
-                                                    reverse_sample_actions.push((ball_reference, speed, grid_x, grid_y));
-                                                    // SetReverse doesn't affect ball movement, so don't reset position
-                                                }
-                                                ProgramAction::CreateBall { x, y, speed, direction } => {
-                                                    all_log_messages.push(format!("  → CreateBall at ({}, {}) with speed {} and direction {:?}", x, y, speed, direction));
-                                                    create_ball_actions.push((x, y, speed, direction));
-                                                }
-                                                ProgramAction::CreateSquare { x, y } => {
-                                                    all_log_messages.push(format!("  → CreateSquare at ({}, {})", x, y));
-                                                    create_square_actions.push((x, y));
-                                                }
-                                                ProgramAction::CreateSquareWithProgram { x, y, program } => {
-                                                    all_log_messages.push(format!("  → CreateSquareWithProgram at ({}, {})", x, y));
-                                                    create_square_with_program_actions.push((x, y, program));
-                                                }
-                                                ProgramAction::CreateBallFromSample { x, y, library_name, sample_name } => {
-                                                    all_log_messages.push(format!("  → CreateBallFromSample at ({}, {}) from {}.{}", x, y, library_name, sample_name));
-                                                    create_ball_from_sample_actions.push((x, y, library_name, sample_name));
-                                                }
-                                                ProgramAction::CreateSquareFromSample { x, y, library_name, sample_name } => {
-                                                    all_log_messages.push(format!("  → CreateSquareFromSample at ({}, {}) from {}.{}", x, y, library_name, sample_name));
-                                                    create_square_from_sample_actions.push((x, y, library_name, sample_name));
-                                                }
-                                                ProgramAction::CreateBallWithLibrary { x, y, library_function, audio_file } => {
-                                                    all_log_messages.push(format!("  → CreateBallWithLibrary at ({}, {}) with lib.{}", x, y, library_function));
-                                                    if let Some(ref audio) = audio_file {
-                                                        all_log_messages.push(format!("    and lib.{}", audio));
-                                                    }
-                                                    
-                                                    // Collect ball creation with library for processing after iteration
-                                                    create_ball_with_library_actions.push((x, y, library_function.clone(), audio_file.clone()));
-                                                    all_log_messages.push(format!("    Ball with library queued for creation at ({}, {})", x, y));
-                                                }
-                                                ProgramAction::CreateSquareWithLibrary { x, y, library_function, audio_file } => {
-                                                    all_log_messages.push(format!("  → CreateSquareWithLibrary at ({}, {}) with lib.{}", x, y, library_function));
-                                                    if let Some(audio) = audio_file {
-                                                        all_log_messages.push(format!("    and lib.{}", audio));
-                                                    }
-                                                    
-                                                    // Create square with library function loaded
-                                                    let grid_x = x as usize;
-                                                    let grid_y = y as usize;
-                                                    if grid_x < GRID_WIDTH && grid_y < GRID_HEIGHT {
-                                                        // Get the library function program
-                                                        if let Some(library_program) = self.library_manager.get_function("lib", &library_function) {
-                                                            self.cells[grid_y][grid_x].place_square(None);
-                                                            self.cells[grid_y][grid_x].program.add_program(library_program.clone());
-                                                            let program_count = self.cells[grid_y][grid_x].program.programs.len();
-                                                            self.cells[grid_y][grid_x].program.set_active_program(Some(program_count - 1));
-                                                            
-                                                            all_log_messages.push(format!("    Square created at ({}, {}) with lib.{} loaded", grid_x, grid_y, library_function));
-                                                        } else {
-                                                            all_log_messages.push(format!("    Failed to load library function: lib.{}", library_function));
-                                                        }
-                                                    }
-                                                }
-                                                ProgramAction::DestroyBall { x, y, ball_reference } => {
-                                                    if let Some(ball_ref) = ball_reference {
-                                                        if ball_ref == "self" {
-                                                            // Destroy the current ball
-                                                            all_log_messages.push(format!("  → DestroyBall self (ball {})", ball_index));
-                                                            destroy_ball_actions.push((ball.x, ball.y));
-                                                        } else if let Some(target_ball_index) = resolve_ball_ref(&ball_ref, grid_x, grid_y) {
-                                                             if target_ball_index < ball_positions.len() {
-                                                                 let (target_x, target_y) = ball_positions[target_ball_index];
-                                                                 all_log_messages.push(format!("  → DestroyBall {} (ball {})", ball_ref, target_ball_index));
-                                                                 destroy_ball_actions.push((target_x, target_y));
-                                                            }
-                                                        }
-                                                    } else {
-                                                        // Coordinate-based destruction
-                                                        all_log_messages.push(format!("  → DestroyBall at ({}, {})", x, y));
-                                                        destroy_ball_actions.push((x, y));
-                                                    }
-                                                }
-                                                ProgramAction::DestroySquare { x, y, ball_reference } => {
-                                                    if let Some(ball_ref) = ball_reference {
-                                                        if ball_ref == "self" {
-                                                            // Destroy square at current ball position
-                                                            all_log_messages.push(format!("  → DestroySquare self at ({}, {})", grid_x, grid_y));
-                                                            destroy_square_actions.push((grid_x as f32, grid_y as f32));
-                                                        } else if let Some(target_ball_index) = resolve_ball_ref(&ball_ref, grid_x, grid_y) {
-                                                             if target_ball_index < ball_positions.len() {
-                                                                 let (target_x, target_y) = ball_positions[target_ball_index];
-                                                                 let target_grid_x = target_x.round() as usize;
-                                                                 let target_grid_y = target_y.round() as usize;
-                                                                 all_log_messages.push(format!("  → DestroySquare {} at ({}, {})", ball_ref, target_grid_x, target_grid_y));
-                                                                 destroy_square_actions.push((target_grid_x as f32, target_grid_y as f32));
-                                                            }
-                                                        }
-                                                    } else {
-                                                        // Coordinate-based destruction
-                                                        all_log_messages.push(format!("  → DestroySquare at ({}, {})", x, y));
-                                                        destroy_square_actions.push((x, y));
-                                                    }
-                                                }
-                                                ProgramAction::Print(text) => {
-                                                    all_log_messages.push(format!("  → Print: {}", text));
-                                                    
-                                                    // Store the printed text on the current square for visual display
-                                                    if grid_x < GRID_WIDTH && grid_y < GRID_HEIGHT {
-                                                        if self.cells[grid_y][grid_x].content == CellContent::Square {
-                                                            // Truncate text to fit in square (max ~10 characters per line)
-                                                            let truncated_text = if text.len() > 10 {
-                                                                format!("{}...", &text[..7])
-                                                            } else {
-                                                                text.clone()
-                                                            };
-                                                            
-                                                            // Get existing text and split into lines
-                                                            let existing_text = self.cells[grid_y][grid_x].display_text
-                                                                .as_ref()
-                                                                .cloned()
-                                                                .unwrap_or_default();
-                                                            
-                                                            let mut lines: Vec<String> = existing_text.split('\n')
-                                                                .map(|s| s.to_string())
-                                                                .collect();
-                                                            
-                                                            // Ensure we have at least 3 lines
-                                                            while lines.len() < 3 {
-                                                                lines.push(String::new());
-                                                            }
-                                                            
-                                                            // Set the third line to the print output
-                                                            lines[2] = truncated_text;
-                                                            
-                                                            // Join back into display text
-                                                            let formatted_text = lines.join("\n");
-                                                            self.cells[grid_y][grid_x].display_text = Some(formatted_text);
-                                                        }
-                                                    }
-                                                }
-                                                ProgramAction::ExecuteLibraryFunction { library_function } => {
-                                                    all_log_messages.push(format!("  → ExecuteLibraryFunction: {}", library_function));
-                                                    
-                                                    // Parse the library function call (e.g., "lib.function_name" or "auto.test")
-                                                    if let Some(dot_pos) = library_function.find('.') {
-                                                        let library_name = &library_function[..dot_pos];
-                                                        let function_name = &library_function[dot_pos + 1..];
-                                                        
-                                                        // Get the library function program and execute it
-                                                        if let Some(library_program) = self.library_manager.get_function(library_name, function_name) {
-                                                            all_log_messages.push(format!("    Executing library function: {}", function_name));
-                                                            
-                                                            // Execute the library function's instructions
-                                                            let mut context = crate::square::ExecutionContext {
-                                                                variables: std::collections::HashMap::new(),
-                                                                ball_hit_count: 0,
-                                                                square_hit_count: 0,
-                                                                ball_x: ball.x,
-                                                                ball_y: ball.y,
-                                                                ball_speed: ball.speed,
-                                                                ball_direction: ball.direction,
-                                                                ball_pitch: ball.pitch,
-                                                                ball_volume: ball.volume,
-                                                                square_x: grid_x,
-                                                                square_y: grid_y,
-                                                            };
-                                                            // Create a temporary SquareProgram to execute the library function
-                                                            let mut temp_square_program = crate::square::SquareProgram::new();
-                                                            let library_actions = temp_square_program.execute_instructions(&library_program.instructions, &mut context);
-                                                            
-                                                            // Apply the actions from the library function
-                                            for library_action in library_actions {
-                                                match library_action {
-                                                    ProgramAction::CreateBall { x, y, speed, direction } => {
-                                                        all_log_messages.push(format!("    Library function creating ball at ({}, {})", x, y));
-                                                        create_ball_actions.push((x, y, speed, direction));
-                                                    }
-                                                    ProgramAction::CreateSquare { x, y } => {
-                                                        all_log_messages.push(format!("    Library function creating square at ({}, {})", x, y));
-                                                        create_square_actions.push((x, y));
-                                                    }
-                                                    ProgramAction::Return(function_name) => {
-                                                        if let Some(ref func_name) = function_name {
-                                                            all_log_messages.push(format!("    Library function return: calling function '{}'", func_name));
-                                                            
-                                                            // Look for the named function in the current square's programs
-                                                            let square_program = &self.cells[grid_y][grid_x].program;
-                                                            let mut found_function = None;
-                                                            
-                                                            for program in &square_program.programs {
-                                                                if program.name == *func_name {
-                                                                    found_function = Some(program.clone());
-                                                                    break;
-                                                                }
-                                                            }
-                                                            
-                                                            if let Some(target_program) = found_function {
-                                                                all_log_messages.push(format!("      Executing function: {}", func_name));
-                                                                
-                                                                // Execute the target function's instructions
-                                                                let mut context = crate::square::ExecutionContext {
-                                                                    variables: std::collections::HashMap::new(),
-                                                                    ball_hit_count: 0,
-                                                                    square_hit_count: 0,
-                                                                    ball_x: ball.x,
-                                                                    ball_y: ball.y,
-                                                                    ball_speed: ball.speed,
-                                                                    ball_direction: ball.direction,
-                                                                    ball_pitch: ball.pitch,
-                                                                    ball_volume: ball.volume,
-                                                                    square_x: grid_x,
-                                                                    square_y: grid_y,
-                                                                };
-                                                                
-                                                                // Create a temporary SquareProgram to execute the function
-                                                                let mut temp_square_program = crate::square::SquareProgram::new();
-                                                                let function_actions = temp_square_program.execute_instructions(&target_program.instructions, &mut context);
-                                                                
-                                                                // Apply the actions from the function
-                                                                for function_action in function_actions {
-                                                                    match function_action {
-                                                                        ProgramAction::CreateBall { x, y, speed, direction } => {
-                                                                            all_log_messages.push(format!("      Function creating ball at ({}, {})", x, y));
-                                                                            create_ball_actions.push((x, y, speed, direction));
-                                                                        }
-                                                                        ProgramAction::CreateSquare { x, y } => {
-                                                                            all_log_messages.push(format!("      Function creating square at ({}, {})", x, y));
-                                                                            create_square_actions.push((x, y));
-                                                                        }
-                                                                        ProgramAction::SetSpeed(speed) => {
-                                                                            all_log_messages.push(format!("      Function setting speed: {}", speed));
-                                                                            ball.speed = speed.max(0.1);
-                                                                            should_reset_position = true;
-                                                                        }
-                                                                        ProgramAction::SetPitch(pitch) => {
-                                                                            all_log_messages.push(format!("      Function setting pitch: {}", pitch));
-                                                                            ball.set_pitch(pitch);
-                                                                        }
-                                                                        ProgramAction::SetVolume(volume) => {
-                                                                            all_log_messages.push(format!("      Function setting volume: {}", volume));
-                                                                            ball.set_volume(volume);
-                                                                        }
-                                                                        ProgramAction::SetDirection(direction) => {
-                                                                            all_log_messages.push(format!("      Function setting direction: {:?}", direction));
-                                                                            // Only change direction and reposition if the ball isn't already moving in that direction
-                                                                            if ball.direction != direction {
-                                                                                ball.direction = direction;
-                                                                                should_snap_to_grid_center = true;
-                                                                            } else {
-                                                                                all_log_messages.push("      Ball already moving in requested direction, ignoring".to_string());
-                                                                            }
-                                                                        }
-                                                                        ProgramAction::Bounce => {
-                                                                            all_log_messages.push("      Function bouncing".to_string());
-                                                                            ball.reverse_direction();
-                                                                            should_reset_position = true;
-                                                                            explicit_bounce = true;
-                                                                        }
-                                                                        // Handle other actions as needed
-                                                                        _ => {
-                                                                            all_log_messages.push(format!("      Function action: {:?}", function_action));
-                                                                        }
-                                                                    }
-                                                                }
-                                                            } else {
-                                                                all_log_messages.push(format!("      Unknown function: {}", func_name));
-                                                                // Collect error info to add comment later (after ball iteration)
-                                                                error_comments.push((grid_x, grid_y, format!("Unknown function: {}", func_name)));
-                                                            }
-                                                        } else {
-                                                            all_log_messages.push("    Library function return: simple return".to_string());
-                                                        }
-                                                    }
-                                                    ProgramAction::End => {
-                                                        all_log_messages.push("    Library function end: natural block termination".to_string());
-                                                    }
-                                                    // Handle other actions as needed
-                                                    _ => {
-                                                        all_log_messages.push(format!("    Library function action: {:?}", library_action));
-                                                    }
-                                                }
-                                            }
-                                                        } else {
-                                                            all_log_messages.push(format!("    Failed to find library function: {}.{}", library_name, function_name));
-                                                        }
-                                                    } else {
-                                                        all_log_messages.push(format!("    Invalid library function format: {} (expected library.function)", library_function));
-                                                    }
-                                                }
-                                                ProgramAction::SetSliceArray { x, y, markers } => {
-                                                    all_log_messages.push(format!("  → SetSliceArray at ({}, {}) with {} markers", x, y, markers.len()));
-                                                    
-                                                    // Only set up the slice array if it doesn't already exist
-                                                    if !self.program_executor.state.slice_arrays.contains_key(&(x, y)) {
-                                                        // Store the slice array in the program executor state
-                                                        self.program_executor.state.slice_arrays.insert((x, y), markers.clone());
-                                                        // Initialize the hit index to 0 for first time setup
-                                                        self.program_executor.state.slice_hit_indices.insert((x, y), 0);
-                                                        all_log_messages.push("    Slice array initialized".to_string());
-                                                    } else {
-                                                        all_log_messages.push("    Slice array already exists, skipping setup".to_string());
-                                                    }
-                                                }
-                                                ProgramAction::SetDirectionToCoordinate { target_x, target_y } => {
-                                                    all_log_messages.push(format!("  → SetDirectionToCoordinate: target ({}, {})", target_x, target_y));
-                                                    
-                                                    // Calculate direction vector to target
-                                                    let current_x = ball.x;
-                                                    let current_y = ball.y;
-                                                    let target_center_x = target_x + 0.5;
-                                                    let target_center_y = target_y + 0.5;
-                                                    
-                                                    let dx = target_center_x - current_x;
-                                                    let dy = target_center_y - current_y;
-                                                    let distance = (dx * dx + dy * dy).sqrt();
-                                                    
-                                                    if distance > 0.001 { // Avoid division by zero
-                                                        // Normalize direction vector
-                                                        let norm_dx = dx / distance;
-                                                        let norm_dy = dy / distance;
-                                                        
-                                                        // Find closest direction
-                                                        let direction = if norm_dx.abs() > norm_dy.abs() {
-                                                            if norm_dx > 0.0 { Direction::Right } else { Direction::Left }
-                                                        } else {
-                                                            if norm_dy > 0.0 { Direction::Down } else { Direction::Up }
-                                                        };
-                                                        
-                                                        // Check for diagonal movement
-                                                        let direction = if (norm_dx.abs() - norm_dy.abs()).abs() < 0.3 { // Close to diagonal
-                                                            match (norm_dx > 0.0, norm_dy > 0.0) {
-                                                                (true, true) => Direction::DownRight,
-                                                                (true, false) => Direction::UpRight,
-                                                                (false, true) => Direction::DownLeft,
-                                                                (false, false) => Direction::UpLeft,
-                                                            }
-                                                        } else {
-                                                            direction
-                                                        };
-                                                        
-                                                        ball.direction = direction;
-                                                        
-                                                        // Calculate time to reach target and adjust position for smooth movement
-                                                        let time_to_target = distance / ball.speed;
-                                                        let (dir_dx, dir_dy): (f32, f32) = match ball.direction {
-                                                            Direction::Up => (0.0, -1.0),
-                                                            Direction::Down => (0.0, 1.0),
-                                                            Direction::Left => (-1.0, 0.0),
-                                                            Direction::Right => (1.0, 0.0),
-                                                            Direction::UpLeft => (-0.707, -0.707),
-                                                            Direction::UpRight => (0.707, -0.707),
-                                                            Direction::DownLeft => (-0.707, 0.707),
-                                                            Direction::DownRight => (0.707, 0.707),
-                                                        };
-                                                        
-                                                        // Move ball back along the direction vector to ensure smooth arrival
-                                                        ball.x = target_center_x - (dir_dx * ball.speed * time_to_target);
-                                                        ball.y = target_center_y - (dir_dy * ball.speed * time_to_target);
-                                                        
-                                                        // Ensure ball stays within bounds
-                                                        ball.x = ball.x.max(0.0).min(GRID_WIDTH as f32);
-                                                        ball.y = ball.y.max(0.0).min(GRID_HEIGHT as f32);
-                                                        
-                                                        should_snap_to_grid_center = true;
-                                                    }
-                                                }
-                                                ProgramAction::PlaySliceMarker { x, y, marker_index } => {
-                                                    all_log_messages.push(format!("  → PlaySliceMarker at ({}, {}) marker {}", x, y, marker_index));
-                                                    // Get the current slice array for this square
-                                                    if let Some(slice_array) = self.program_executor.state.slice_arrays.get(&(x, y)) {
-                                                        let current_index = self.program_executor.state.slice_hit_indices.get(&(x, y)).unwrap_or(&0);
-                                                        if *current_index < slice_array.len() {
-                                                            let marker_to_play = slice_array[*current_index];
-                                                            all_log_messages.push(format!("    Playing marker {} from slice array (index {})", marker_to_play, current_index));
-                                                            
-                                                            // Try to get markers from audio player first, then from saved markers
-                                                            let mut marker_found = false;
-                                                            if let Some(markers) = self.audio_player.get_markers() {
-                                                                // Look for marker by extracting number from "Marker_X" format or by position index
-                                                                let marker = markers.iter().find(|m| {
-                                                                    // Try to extract number from "Marker_X" format
-                                                                    if m.name.starts_with("Marker_") {
-                                                                        if let Ok(marker_num) = m.name[7..].parse::<u32>() {
-                                                                            return marker_num == marker_to_play;
-                                                                        }
-                                                                    }
-                                                                    // Fallback: try parsing the entire name as a number
-                                                                    m.name.parse::<u32>().unwrap_or(0) == marker_to_play
-                                                                });
-                                                                
-                                                                if let Some(marker) = marker {
-                                                                    // Play the marker using the audio engine
-                                                                    if let Some(sample_path) = self.audio_player.get_sample_info().map(|(path, _, _, _)| path) {
-                                                                        if let Err(e) = self.audio_engine.play_on_channel_with_position(0, sample_path, 1.0, 1.0, marker.position) {
-                                                                            all_log_messages.push(format!("    Error playing marker: {}", e));
-                                                                        }
-                                                                        marker_found = true;
-                                                                    }
-                                                                }
-                                                            }
-                                                            
-                                                            // If not found in current markers, search saved markers
-                                                            if !marker_found {
-                                                                for (sample_path, saved_markers) in self.audio_player.get_all_saved_markers() {
-                                                                    // Look for marker by extracting number from "Marker_X" format or by position index
-                                                                    let marker = saved_markers.iter().find(|m| {
-                                                                        // Try to extract number from "Marker_X" format
-                                                                        if m.name.starts_with("Marker_") {
-                                                                            if let Ok(marker_num) = m.name[7..].parse::<u32>() {
-                                                                                return marker_num == marker_to_play;
-                                                                            }
-                                                                        }
-                                                                        // Fallback: try parsing the entire name as a number
-                                                                        m.name.parse::<u32>().unwrap_or(0) == marker_to_play
-                                                                    });
-                                                                    
-                                                                    if let Some(marker) = marker {
-                                                                        if let Err(e) = self.audio_engine.play_on_channel_with_position(0, &sample_path, 1.0, 1.0, marker.position) {
-                                                                            all_log_messages.push(format!("    Error playing saved marker: {}", e));
-                                                                        }
-                                                                        marker_found = true;
-                                                                        break;
-                                                                    }
-                                                                }
-                                                            }
-                                                            
-                                                            if !marker_found {
-                                                                all_log_messages.push(format!("    Marker {} not found in current or saved markers", marker_to_play));
-                                                            }
-                                                            
-                                                            // Increment the hit index for next time
-                                            let next_index = (*current_index + 1) % slice_array.len();
-                                            self.program_executor.state.slice_hit_indices.insert((x, y), next_index);
-                                                        }
-                                                    }
-                                                }
-                                                _ => {
-                                                    all_log_messages.push("  → Unknown action".to_string());
-                                                } // Handle other actions as needed
-                                            }
-                                        }
-                                        
-                                        // Only play ball's audio if there's no slice array active for this square
+                            // The ball's instrument behavior stands in for a square with no program of its own:
+                            // none at all, or only the built-in default bounce
+                            let square_active = square_program.active_program.and_then(|index| square_program.programs.get(index));
+                            let stands_in = square_active.is_none_or(|program| program.is_builtin_default() || program.instructions.is_empty());
+                            let behavior = if stands_in { ball.behavior.clone() } else { None };
+                            if !square_program.programs.is_empty() || behavior.is_some() {
+                                if let Some(program) = behavior.as_ref().or(square_active) {
+                                    let actions = self.program_executor.execute_on_collision(
+                                        program, ball, grid_x, grid_y, &self.collision_history
+                                    );
+                                    
+                                    // Collect log messages to avoid borrowing conflicts
+                                    if !actions.is_empty() {
+                                        step_log.trace(|| format!(
+                                            "Executing program at ({},{}) for {} ball: {} actions",
+                                            grid_x, grid_y, ball.color, actions.len()
+                                        ));
+                                    }
+                                    
+                                    if !pass_through {
+                                        ball.apply_pitch_step(); // Climb/descend by the ball's per-bounce step before playing
+                                    }
+                                    let mut outcome = HitOutcome::new(ball.pitch); // Start with ball's base pitch
+                                    let site = ActionSite { square_x: grid_x, square_y: grid_y, flip_x, flip_y };
+                                    self.apply_program_actions(ball, site, actions, &mut outcome, &mut effects, &mut step_log);
+                                    let HitOutcome {
+                                        collision_pitch,
+                                        mut should_reset_position,
+                                        should_snap_to_grid_center,
+                                        explicit_bounce,
+                                        should_pass_through,
+                                        teleported,
+                                    } = outcome;
+                                    
+                                    // Only play ball's audio if there's no slice array active for this square
                         let has_slice_array = self.program_executor.state.slice_arrays.contains_key(&(grid_x, grid_y));
-                        if !has_slice_array {
+                        if pass_through {
+                            step_log.trace(|| "Skipping regular ball audio - pass-through square".to_string());
+                        } else if !has_slice_array {
                             if let Err(e) = self.ball_audio_system.play_collision_audio(
                                 &self.audio_engine,
                                 ball,
                                 collision_pitch,
-                                &mut all_log_messages,
+                                &mut step_log,
                             ) {
-                                all_log_messages.push(format!("Ball audio system error: {}", e));
+                                step_log.trace(|| format!("Ball audio system error: {}", e));
                             }
                         } else {
-                            all_log_messages.push("Skipping regular ball audio - slice array active".to_string());
+                            step_log.trace(|| "Skipping regular ball audio - slice array active".to_string());
                         }
-                                        
-                                        // Always bounce off squares unless an explicit bounce was already performed
-                                        if !explicit_bounce {
-                                            ball.reverse_direction();
-                                            should_reset_position = true;
-                                        }
-                                        
-                                        // Reset position based on action type
-                        if should_snap_to_grid_center {
+                                    
+                                    // Always bounce off squares unless an explicit bounce was already performed
+                                    if !explicit_bounce && !pass_through && !should_pass_through && !teleported {
+                                        ball.reverse_direction();
+                                        should_reset_position = true;
+                                    }
+                                    
+                                    // Reset position based on action type
+                        if teleported {
+                            // Already at the destination's center; the next update moves on from there
+                        } else if should_snap_to_grid_center {
                             // Position ball at the edge it should start from, based on its direction
                             let (edge_x, edge_y) = Self::calculate_edge_position(grid_x, grid_y, ball.direction);
                             ball.x = edge_x;
@@ -1434,151 +2758,153 @@ impl SequencerGrid {
                             ball.last_grid_x = grid_x;
                             ball.last_grid_y = grid_y;
                         } else if should_reset_position {
-                                            // Move ball back to previous position for other actions
-                                            ball.x = old_x;
-                                            ball.y = old_y;
-                                            ball.last_grid_x = old_x.floor() as usize;
-                                            ball.last_grid_y = old_y.floor() as usize;
-                                        }
-                                        
-                                        // Check for slice arrays and play next marker in sequence
-                                        if let Some(slice_array) = self.program_executor.state.slice_arrays.get(&(grid_x, grid_y)) {
-                                            let current_index = self.program_executor.state.slice_hit_indices.get(&(grid_x, grid_y)).unwrap_or(&0);
-                                            if *current_index < slice_array.len() {
-                                                let marker_to_play = slice_array[*current_index];
-                                                all_log_messages.push(format!("  → Slice Array: Playing marker {} (index {} of {})", marker_to_play, current_index, slice_array.len()));
+                                        // Move ball back to previous position for other actions
+                                        ball.x = reset_x;
+                                        ball.y = reset_y;
+                                        ball.last_grid_x = reset_x.floor() as usize;
+                                        ball.last_grid_y = reset_y.floor() as usize;
+                                    }
+                                    
+                                    // Check for slice arrays and play next marker in sequence
+                                    if let Some(slice_array) = self.program_executor.state.slice_arrays.get(&(grid_x, grid_y)) {
+                                        let current_index = self.program_executor.state.slice_hit_indices.get(&(grid_x, grid_y)).unwrap_or(&0);
+                                        if *current_index < slice_array.len() {
+                                            let marker_to_play = slice_array[*current_index];
+                                            step_log.trace(|| format!("  → Slice Array: Playing marker {} (index {} of {})", marker_to_play, current_index, slice_array.len()));
+                                            
+                                            // Use the ball's sample path for slice array playback
+                                            if let Some(ball_sample_path) = &ball.sample_path {
+                                                step_log.trace(|| format!("    Looking for markers for ball sample: {}", ball_sample_path));
                                                 
-                                                // Use the ball's sample path for slice array playback
-                                                if let Some(ball_sample_path) = &ball.sample_path {
-                                                    all_log_messages.push(format!("    Looking for markers for ball sample: {}", ball_sample_path));
-                                                    
-                                                    // First check if the ball's sample has markers in the audio player (if it's currently open)
-                                                    let markers_and_path = if let Some(markers) = self.audio_player.get_markers() {
-                                                        if let Some(current_sample_path) = self.audio_player.get_sample_info().map(|(path, _, _, _)| path) {
-                                                            all_log_messages.push(format!("    Audio player has sample: {} (looking for: {})", current_sample_path, ball_sample_path));
-                                                            if current_sample_path == ball_sample_path {
-                                                                all_log_messages.push(format!("    Found {} markers in audio player", markers.len()));
-                                                                Some((markers, ball_sample_path.clone()))
-                                                            } else {
-                                                                all_log_messages.push("    Audio player sample doesn't match ball sample".to_string());
-                                                                None
-                                                            }
+                                                // First check if the ball's sample has markers in the audio player (if it's currently open)
+                                                let markers_and_path = if let Some(markers) = self.audio_player.get_markers() {
+                                                    if let Some(current_sample_path) = self.audio_player.get_sample_info().map(|(path, _, _, _)| path) {
+                                                        step_log.trace(|| format!("    Audio player has sample: {} (looking for: {})", current_sample_path, ball_sample_path));
+                                                        if current_sample_path == ball_sample_path {
+                                                            step_log.trace(|| format!("    Found {} markers in audio player", markers.len()));
+                                                            Some((markers, ball_sample_path.clone()))
                                                         } else {
-                                                            all_log_messages.push("    No sample info in audio player".to_string());
+                                                            step_log.trace(|| "    Audio player sample doesn't match ball sample".to_string());
                                                             None
                                                         }
                                                     } else {
-                                                        all_log_messages.push("    No markers in audio player".to_string());
+                                                        step_log.trace(|| "    No sample info in audio player".to_string());
                                                         None
-                                                    };
-                                                    
-                                                    // If not found in current audio player, check saved markers for the ball's sample
-                                                    let markers_and_path = markers_and_path.or_else(|| {
-                                                        if let Some(saved_markers) = self.audio_player.get_saved_markers(ball_sample_path) {
-                                                            all_log_messages.push(format!("    Found {} saved markers for ball sample", saved_markers.len()));
-                                                            Some((saved_markers, ball_sample_path.clone()))
-                                                        } else {
-                                                            all_log_messages.push("    No saved markers for ball sample".to_string());
-                                                            None
+                                                    }
+                                                } else {
+                                                    step_log.trace(|| "    No markers in audio player".to_string());
+                                                    None
+                                                };
+                                                
+                                                // If not found in current audio player, check saved markers for the ball's sample
+                                                let markers_and_path = markers_and_path.or_else(|| {
+                                                    if let Some(saved_markers) = self.audio_player.get_saved_markers(ball_sample_path) {
+                                                        step_log.trace(|| format!("    Found {} saved markers for ball sample", saved_markers.len()));
+                                                        Some((saved_markers, ball_sample_path.clone()))
+                                                    } else {
+                                                        step_log.trace(|| "    No saved markers for ball sample".to_string());
+                                                        None
+                                                    }
+                                                });
+                                                
+                                                if let Some((markers, sample_path)) = markers_and_path {
+                                                    // Look for marker by extracting number from "Marker_X" format or by position index
+                                                    let marker = markers.iter().find(|m| {
+                                                        // Try to extract number from "Marker_X" format
+                                                        if m.name.starts_with("Marker_") {
+                                                            if let Ok(marker_num) = m.name[7..].parse::<u32>() {
+                                                                return marker_num == marker_to_play;
+                                                            }
                                                         }
+                                                        // Fallback: try parsing the entire name as a number
+                                                        m.name.parse::<u32>().unwrap_or(0) == marker_to_play
                                                     });
                                                     
-                                                    if let Some((markers, sample_path)) = markers_and_path {
-                                                        // Look for marker by extracting number from "Marker_X" format or by position index
-                                                        let marker = markers.iter().find(|m| {
-                                                            // Try to extract number from "Marker_X" format
-                                                            if m.name.starts_with("Marker_") {
-                                                                if let Ok(marker_num) = m.name[7..].parse::<u32>() {
-                                                                    return marker_num == marker_to_play;
-                                                                }
-                                                            }
-                                                            // Fallback: try parsing the entire name as a number
-                                                            m.name.parse::<u32>().unwrap_or(0) == marker_to_play
-                                                        });
-                                                        
-                                                        if let Some(marker) = marker {
-                                            all_log_messages.push(format!("    Found marker '{}' at position {}", marker.name, marker.position));
-                                            
-                                            // Find the next marker chronologically for end position
+                                                    if let Some(marker) = marker {
+                                        step_log.trace(|| format!("    Found marker '{}' at position {}", marker.name, marker.position));
+                                        
+                                        // Find the next marker chronologically for end position
                             let end_position = {
                                 // Find the next marker chronologically after the current marker
                                 let mut sorted_markers: Vec<_> = markers.iter().collect();
                                 sorted_markers.sort_by(|a, b| a.position.partial_cmp(&b.position).unwrap());
                                 
                                 let next_chronological = sorted_markers.iter()
-                                    .find(|m| m.position > marker.position)
-                                    .map(|m| m.position);
-                                    
+                                .find(|m| m.position > marker.position)
+                                .map(|m| m.position);
+                                
                                 next_chronological.unwrap_or(1.0) // Play to end if no next marker
                             };
-                                            
-                                            all_log_messages.push(format!("    Playing segment from {} to {:?}", marker.position, end_position));
+                                        
+                                        step_log.trace(|| format!("    Playing segment from {} to {:?}", marker.position, end_position));
                             
                             // Create a dedicated channel for this segment to avoid conflicts
                             let segment_channel = self.audio_engine.create_channel(format!("Segment_{}_{}", grid_x, grid_y));
                             
                             // Play the marker segment using the dedicated channel
-                            if let Err(e) = self.audio_engine.play_on_channel_with_segment(segment_channel, &sample_path, 1.0, 1.0, marker.position, Some(end_position)) {
-                                all_log_messages.push(format!("    Error playing slice marker: {}", e));
+                            if let Err(e) = self.ball_audio_system.play_slice(&self.audio_engine, segment_channel, &sample_path, marker.position, Some(end_position)) {
+                                step_log.trace(|| format!("    Error playing slice marker: {}", e));
                             } else {
-                                all_log_messages.push("    Successfully started segment playback".to_string());
+                                step_log.trace(|| "    Successfully started segment playback".to_string());
                             }
-                                            
-                                            // Increment the hit index for next time
-                                            let next_index = (*current_index + 1) % slice_array.len();
-                                            self.program_executor.state.slice_hit_indices.insert((grid_x, grid_y), next_index);
-                                        } else {
-                                            all_log_messages.push(format!("    Marker {} not found in ball sample markers", marker_to_play));
-                                        }
-                                                    } else {
-                                                        all_log_messages.push(format!("    No markers available for ball sample: {}", ball_sample_path));
-                                                    }
+                                        
+                                        // Increment the hit index for next time
+                                        let next_index = (*current_index + 1) % slice_array.len();
+                                        self.program_executor.state.slice_hit_indices.insert((grid_x, grid_y), next_index);
+                                    } else {
+                                        step_log.trace(|| format!("    Marker {} not found in ball sample markers", marker_to_play));
+                                    }
                                                 } else {
-                                                    all_log_messages.push("    Ball has no sample path for slice array playback".to_string());
+                                                    step_log.trace(|| format!("    No markers available for ball sample: {}", ball_sample_path));
                                                 }
+                                            } else {
+                                                step_log.trace(|| "    Ball has no sample path for slice array playback".to_string());
                                             }
                                         }
-                                        
-                                        // Update cooldown tracking
-                                        let now = std::time::Instant::now();
-                                        if let Some(cooldown) = self.collision_cooldowns.iter_mut().find(|c| 
-                                            c.ball_index == ball_index && c.square_x == grid_x && c.square_y == grid_y
-                                        ) {
-                                            cooldown.last_collision = now;
-                                        } else {
-                                            self.collision_cooldowns.push(CollisionCooldown {
-                                                ball_index,
-                                                square_x: grid_x,
-                                                square_y: grid_y,
-                                                last_collision: now,
-                                            });
-                                            
-                                            // Clean up old cooldowns (keep only last 50)
-                                            if self.collision_cooldowns.len() > 50 {
-                                                self.collision_cooldowns.remove(0);
-                                            }
+                                    }
+                                    
+                                    // Update cooldown tracking
+                                    let now = self.program_executor.state.elapsed;
+                                    if let Some(cooldown) = self.collision_cooldowns.iter_mut().find(|c| 
+                                        c.ball_index == ball_index && c.square_x == grid_x && c.square_y == grid_y
+                                    ) {
+                                        cooldown.last_collision = now;
+                                    } else {
+                                        self.collision_cooldowns.push(CollisionCooldown {
+                                            ball_index,
+                                            square_x: grid_x,
+                                            square_y: grid_y,
+                                            last_collision: now,
+                                        });
+                                        
+                                        // Clean up old cooldowns (keep only last 50)
+                                        if self.collision_cooldowns.len() > 50 {
+                                            self.collision_cooldowns.remove(0);
                                         }
                                     }
                                 }
-                            } else {
+                            } else if !pass_through {
                                 // Default behavior: reverse direction
                                 ball.reverse_direction();
                                 // Move ball back to previous position to prevent overlap
-                                ball.x = old_x;
-                                ball.y = old_y;
-                                ball.last_grid_x = old_x.floor() as usize;
-                                ball.last_grid_y = old_y.floor() as usize;
+                                ball.x = reset_x;
+                                ball.y = reset_y;
+                                ball.last_grid_x = reset_x.floor() as usize;
+                                ball.last_grid_y = reset_y.floor() as usize;
                             }
-                        } else {
+                        } else if !pass_through {
                             // Cooldown active, just reverse direction without executing program
                             ball.reverse_direction();
                             // Move ball back to previous position to prevent overlap
-                            ball.x = old_x;
-                            ball.y = old_y;
-                            ball.last_grid_x = old_x.floor() as usize;
-                            ball.last_grid_y = old_y.floor() as usize;
+                            ball.x = reset_x;
+                            ball.y = reset_y;
+                            ball.last_grid_x = reset_x.floor() as usize;
+                            ball.last_grid_y = reset_y.floor() as usize;
                         }
                         
+                        if pass_through {
+                            continue; // Ball keeps moving, so later cells in this update still count
+                        }
                         triggered_positions.push((grid_x, grid_y, ball_index));
                         break; // Only trigger once per update
                     }
@@ -1586,6 +2912,28 @@ impl SequencerGrid {
             }
         }
         
+        self.balls = balls;
+        let StepEffects {
+            reverse_sample_actions,
+            create_ball_actions,
+            create_random_ball_actions,
+            burst_actions,
+            bpm_changes,
+            create_ball_with_library_actions,
+            destroy_ball_actions,
+            create_square_actions,
+            create_square_with_program_actions,
+            create_ball_from_sample_actions,
+            create_square_from_sample_actions,
+            destroy_square_actions,
+            destroy_ball_colors,
+            destroy_square_colors,
+            error_comments,
+            delayed_actions,
+            ..
+        } = effects;
+        self.delayed_actions.extend(delayed_actions);
+        
         // Process reverse sample actions after the mutable iteration
         for (ball_reference, speed, grid_x, grid_y) in reverse_sample_actions {
             if let Some(referenced_ball_index) = self.resolve_ball_reference(&ball_reference, grid_x, grid_y) {
@@ -1612,28 +2960,47 @@ impl SequencerGrid {
         
         // Process create/destroy actions after the mutable iteration
         for (x, y, speed, direction) in create_ball_actions {
-            let grid_x = x.round() as usize;
-            let grid_y = y.round() as usize;
-            if grid_x < GRID_WIDTH && grid_y < GRID_HEIGHT {
-                self.ball_counter += 1;
-                let ball_id = format!("ball{}", self.ball_counter);
-                let mut new_ball = Ball::new(grid_x, grid_y, ball_id.clone());
-                new_ball.speed = speed;
-                new_ball.direction = direction;
-                new_ball.activate(); // Activate the newly created ball
-                let is_active = new_ball.active;
-                self.balls.push(new_ball);
-                self.log_to_console(format!("Ball {} created at ({}, {}) - Total balls: {}, Active: {}", 
-                    ball_id, grid_x, grid_y, self.balls.len(), is_active));
-            } else {
-                self.log_to_console(format!("Ball creation failed - coordinates ({}, {}) out of bounds", grid_x, grid_y));
+            self.spawn_ball(x, y, speed, direction);
+        }
+        
+        for (x, y, count, speed) in burst_actions {
+            self.spawn_burst(x, y, count, speed);
+        }
+        
+        for (value, relative) in bpm_changes {
+            let bpm = if relative { self.bpm + value } else { value };
+            self.set_bpm(bpm);
+        }
+        
+        for (speed, direction) in create_random_ball_actions {
+            // Empty means no square and no ball currently in the cell
+            let empty_cells: Vec<(usize, usize)> = (0..grid_height())
+                .flat_map(|y| (0..grid_width()).map(move |x| (x, y)))
+                .filter(|&(x, y)| self.cells[y][x].content == CellContent::Empty && self.get_ball_at(x, y).is_none())
+                .collect();
+            
+            if empty_cells.is_empty() {
+                self.log_to_console("Random ball creation failed - no empty cells left".to_string());
+                continue;
             }
+            
+            use rand::Rng;
+            let (grid_x, grid_y) = empty_cells[crate::rng::with_rng(|rng| rng.gen_range(0..empty_cells.len()))];
+            self.ball_counter += 1;
+            let ball_id = format!("ball{}", self.ball_counter);
+            let mut new_ball = Ball::new(grid_x, grid_y, ball_id.clone());
+            new_ball.speed = speed;
+            new_ball.direction = direction;
+            new_ball.activate();
+            self.balls.push(new_ball);
+            self.log_to_console(format!("Ball {} created at random cell ({}, {}) - Total balls: {}", 
+                ball_id, grid_x, grid_y, self.balls.len()));
         }
         
         for (x, y) in create_square_actions {
             let grid_x = x as usize;
             let grid_y = y as usize;
-            if grid_x < GRID_WIDTH && grid_y < GRID_HEIGHT {
+            if grid_x < grid_width() && grid_y < grid_height() {
                 self.cells[grid_y][grid_x].place_square(Some([255, 100, 100])); // Red square
             }
         }
@@ -1641,7 +3008,7 @@ impl SequencerGrid {
         for (x, y, program) in create_square_with_program_actions {
             let grid_x = x as usize;
             let grid_y = y as usize;
-            if grid_x < GRID_WIDTH && grid_y < GRID_HEIGHT {
+            if grid_x < grid_width() && grid_y < grid_height() {
                 self.cells[grid_y][grid_x].place_square(Some([255, 100, 100])); // Red square
                 self.cells[grid_y][grid_x].program.add_program(program.clone());
                 // Set the newly added program as active
@@ -1657,7 +3024,7 @@ impl SequencerGrid {
         for (x, y, library_name, sample_name) in create_ball_from_sample_actions {
             let grid_x = x as usize;
             let grid_y = y as usize;
-            if grid_x < GRID_WIDTH && grid_y < GRID_HEIGHT {
+            if grid_x < grid_width() && grid_y < grid_height() {
                 if let Some(sample_template) = self.library_manager.get_ball_sample(&library_name, &sample_name) {
                     let template_clone = sample_template.clone();
                     self.ball_counter += 1;
@@ -1666,6 +3033,12 @@ impl SequencerGrid {
                     new_ball.speed = template_clone.default_speed;
                     new_ball.direction = template_clone.default_direction;
                     new_ball.color = template_clone.color.clone();
+                    if let Some(program_name) = &template_clone.behavior_program {
+                        match self.resolve_behavior_program(&library_name, program_name) {
+                            Some(program) => new_ball.behavior = Some(program),
+                            None => self.log_to_console(format!("Sample {}.{}: behavior program '{}' not found", library_name, sample_name, program_name)),
+                        }
+                    }
                     
                     // Set sample path based on sample name (assuming .wav extension)
                     let sample_path = format!("{}.wav", sample_name);
@@ -1688,7 +3061,7 @@ impl SequencerGrid {
         for (x, y, library_name, sample_name) in create_square_from_sample_actions {
             let grid_x = x as usize;
             let grid_y = y as usize;
-            if grid_x < GRID_WIDTH && grid_y < GRID_HEIGHT {
+            if grid_x < grid_width() && grid_y < grid_height() {
                 if let Some(sample_template) = self.library_manager.get_square_sample(&library_name, &sample_name) {
                     // Parse color string to RGB array
                     let color_rgb = if sample_template.color == "red" {
@@ -1701,15 +3074,16 @@ impl SequencerGrid {
                         [200, 200, 200] // Default gray
                     };
                     self.cells[grid_y][grid_x].place_square(Some(color_rgb));
-                    if let Some(program_name) = &sample_template.behavior_program {
+                    if let Some(program_name) = sample_template.behavior_program.clone() {
                         // Look up the actual program from the library
-                        if let Some(library_program) = self.library_manager.get_function("lib", program_name) {
-                            let program_clone = library_program.clone();
+                        if let Some(program_clone) = self.resolve_behavior_program(&library_name, &program_name) {
                             self.cells[grid_y][grid_x].program.add_program(program_clone.clone());
                             let program_count = self.cells[grid_y][grid_x].program.programs.len();
                             self.cells[grid_y][grid_x].program.set_active_program(Some(program_count - 1));
                             // Automatically add program to library
                             self.auto_add_program_to_library(&program_clone);
+                        } else {
+                            self.log_to_console(format!("Sample {}.{}: behavior program '{}' not found", library_name, sample_name, program_name));
                         }
                     }
                     self.log_to_console(format!("Square created from sample {}.{} at ({}, {})", library_name, sample_name, grid_x, grid_y));
@@ -1725,7 +3099,7 @@ impl SequencerGrid {
         for (x, y, library_function, audio_file) in create_ball_with_library_actions {
             let grid_x = x.round() as usize;
             let grid_y = y.round() as usize;
-            if grid_x < GRID_WIDTH && grid_y < GRID_HEIGHT {
+            if grid_x < grid_width() && grid_y < grid_height() {
                 self.ball_counter += 1;
                 let ball_id = format!("ball{}", self.ball_counter);
                 let mut new_ball = Ball::new(grid_x, grid_y, ball_id.clone());
@@ -1788,16 +3162,34 @@ impl SequencerGrid {
             });
         }
         
+        // Colors are compared as RGB so "c_red", "Red" and "#ff0000" all match
+        let color_matches = |filter: &Option<String>, rgb: [u8; 3]| {
+            filter.as_ref().map_or(true, |color| Renderer::get_color_rgb(color) == rgb)
+        };
+        if !destroy_ball_colors.is_empty() {
+            self.balls.retain(|ball| {
+                let rgb = Renderer::get_color_rgb(&ball.color);
+                !destroy_ball_colors.iter().any(|filter| color_matches(filter, rgb))
+            });
+        }
+        
         for (x, y) in destroy_square_actions {
             let grid_x = x.round() as usize;
             let grid_y = y.round() as usize;
-            if grid_x < GRID_WIDTH && grid_y < GRID_HEIGHT {
+            if grid_x < grid_width() && grid_y < grid_height() {
                 self.cells[grid_y][grid_x].clear();
             }
         }
         
-        // Log all collected messages after ball processing is complete
-        for message in all_log_messages {
+        for cell in self.cells.iter_mut().flatten() {
+            if cell.is_square() && destroy_square_colors.iter().any(|filter| color_matches(filter, cell.color)) {
+                cell.clear();
+            }
+        }
+        
+        // Log all collected messages after ball processing is complete; each console line
+        // is also a write to the log file
+        for message in step_log.lines {
             self.log_to_console(message);
         }
         
@@ -1818,6 +3210,28 @@ impl SequencerGrid {
     pub fn update(&mut self, delta_time: f32) {
         // Update audio player
         self.audio_player.update(delta_time, &self.audio_engine);
+        
+        for event in self.automation.advance(delta_time) {
+            self.apply_automation(event.param, event.value, false);
+        }
+        
+        for message in self.preload_queue.process(&self.audio_engine, PRELOAD_FRAME_BUDGET) {
+            self.log_to_console(message);
+        }
+        
+        if let Some(remaining) = self.auto_start_countdown {
+            let remaining = remaining - delta_time;
+            if remaining > 0.0 {
+                self.auto_start_countdown = Some(remaining);
+            } else {
+                self.auto_start_countdown = None;
+                // Someone may have pressed P during the countdown
+                if !self.balls.iter().any(|ball| ball.active) {
+                    self.log_to_console("Auto-start".to_string());
+                    self.toggle_all_balls();
+                }
+            }
+        }
     }
 }
 
@@ -1826,7 +3240,6 @@ pub struct SequencerUI {
     pixels: Pixels,
     input: WinitInputHelper,
     last_update: std::time::Instant,
-    audio_engine: AudioEngine,
     // Label editing state
     label_editing_mode: bool,
     label_editing_x: usize,
@@ -1836,25 +3249,48 @@ pub struct SequencerUI {
     // Track last cursor position for console logging
     last_cursor_x: usize,
     last_cursor_y: usize,
+    // Smooth ball and square edges; off by default so the crisp look is unchanged (F4 toggles)
+    antialiasing: bool,
+    // Ball whose live values are shown in the status bar (follows it after it leaves the cursor)
+    hovered_ball_id: Option<String>,
+    // Repeats cursor movement while an arrow key is held
+    cursor_repeat: KeyRepeat,
+    // Master output level for the peak meter, decays between audio peaks
+    output_level: f32,
+    // Smoothed frame time shown in the stats panel (F7)
+    frame_ms: f32,
+    // Last pointer position over the window, in physical pixels
+    mouse_position: Option<(f32, f32)>,
+    // Console lines scrolled up from the newest (0 = following new output), and the
+    // grid's console_logged count when the scroll position was last adjusted
+    console_scroll: usize,
+    console_logged_seen: usize,
+    // Console prompt text (the prompt itself is Modal::ConsolePrompt, opened with a
+    // backtick), the commands entered so far (oldest first), and which of them Up/Down is showing
+    console_input: String,
+    console_history: Vec<String>,
+    console_history_index: Option<usize>,
 }
 
 impl SequencerUI {
     pub fn new(window: &winit::window::Window, audio_engine: AudioEngine) -> Result<Self, Error> {
         let window_size = window.inner_size();
         let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, window);
-        let pixels = Pixels::new(WINDOW_WIDTH as u32, WINDOW_HEIGHT as u32, surface_texture)?;
+        let pixels = Pixels::new(window_width() as u32, window_height() as u32, surface_texture)?;
         
         let mut grid = SequencerGrid::new(audio_engine);
         
         // Add startup message to console
         grid.log_to_console("Quadracollision Canticle v0.001".to_string());
+        if Path::new(THEME_FILE).exists() {
+            grid.handle_console_command("theme reload");
+        }
         
         Ok(Self {
             grid,
             pixels,
             input: WinitInputHelper::new(),
             last_update: std::time::Instant::now(),
-            audio_engine: AudioEngine::new().map_err(|e| Error::UserDefined(Box::new(e)))?,
             label_editing_mode: false,
             label_editing_x: 0,
             label_editing_y: 0,
@@ -1862,6 +3298,17 @@ impl SequencerUI {
             label_editing_line: 0,
             last_cursor_x: 0,
             last_cursor_y: 0,
+            antialiasing: false,
+            hovered_ball_id: None,
+            cursor_repeat: KeyRepeat::new(std::time::Duration::from_millis(300), std::time::Duration::from_millis(60)),
+            output_level: 0.0,
+            frame_ms: 0.0,
+            mouse_position: None,
+            console_scroll: 0,
+            console_logged_seen: 0,
+            console_input: String::new(),
+            console_history: Vec::new(),
+            console_history_index: None,
         })
     }
     
@@ -1874,6 +3321,11 @@ impl SequencerUI {
             self.last_cursor_x = current_x;
             self.last_cursor_y = current_y;
             
+            // Follow the ball under the cursor with the live readout, or stop following
+            self.hovered_ball_id = self.grid.get_ball_at(current_x, current_y)
+                .and_then(|ball_index| self.grid.balls.get(ball_index))
+                .map(|ball| ball.id.clone());
+            
             // Get ball information at cursor position
             let ball_info = if let Some(ball_index) = self.grid.get_ball_at(current_x, current_y) {
                 if let Some(ball) = self.grid.balls.get(ball_index) {
@@ -1881,7 +3333,8 @@ impl SequencerUI {
                         .and_then(|path| std::path::Path::new(path).file_stem())
                         .and_then(|stem| stem.to_str())
                         .unwrap_or("no_sample");
-                    format!("Ball: {} ({})", ball.id, sample_name)
+                    let pan = ball.output_pan(self.grid.ball_audio_system.auto_pan);
+                    format!("Ball: {} ({}, pan {:+.2})", ball.id, sample_name, pan)
                 } else {
                     "Ball: unknown".to_string()
                 }
@@ -1906,6 +3359,13 @@ impl SequencerUI {
         }
     }
     
+    // Scrolled all the way up, the oldest message is on the top row and the bottom
+    // row holds the "newer lines" note (above the prompt, when it is open)
+    fn max_console_scroll(&self) -> usize {
+        let rows = Renderer::console_visible_lines() - 1 - self.grid.modals.is_open(Modal::ConsolePrompt) as usize;
+        self.grid.console_messages.len().saturating_sub(rows)
+    }
+    
     pub fn handle_input(&mut self, event: &Event<()>) {
         if self.input.update(event) {
             // Handle label editing mode first
@@ -1914,53 +3374,99 @@ impl SequencerUI {
                 return;
             }
             
-            // Handle context menu input first
-            if let Some(action) = self.grid.context_menu.handle_input(&self.input, &self.grid.balls) {
-                 match action {
-                     ContextMenuAction::SetDirection { ball_index, direction } => {
-                         self.grid.set_ball_direction(ball_index, direction);
-                     }
-                     ContextMenuAction::SetSpeed { ball_index, speed } => {
-                         self.grid.set_ball_speed(ball_index, speed);
-                     }
-                     ContextMenuAction::SetSample { ball_index, sample } => {
-                         self.grid.set_ball_sample(ball_index, sample);
-                     }
-                     ContextMenuAction::SetColor { ball_index, color } => {
-                         self.grid.set_ball_color(ball_index, color);
-                     }
-                     ContextMenuAction::OpenFileDialog { ball_index } => {
-                         self.open_file_dialog_for_ball(ball_index);
-                     }
-                     ContextMenuAction::AddSampleToLibrary { ball_index } => {
-                         self.add_sample_to_library_for_ball(ball_index);
-                     }
-                     ContextMenuAction::OpenAudioPlayer { ball_index } => {
-                         if let Some(ball) = self.grid.balls.get(ball_index) {
-                             if let Some(ref sample_path) = ball.sample_path {
-                                 // Open the audio player with the ball's sample
-                                 if let Err(e) = self.grid.audio_player.open_sample(sample_path.clone(), &mut self.grid.audio_engine) {
-                                     self.grid.log_to_console(format!("Failed to open audio player: {}", e));
-                                 } else {
-                                     self.grid.log_to_console(format!("Opened audio player for ball sample: {}", sample_path));
-                                 }
-                             }
-                         }
-                     }
-                 }
-                 return;
-             }
+            // Input goes to the topmost open overlay only
+            self.grid.sync_modals();
+            let active_modal = self.grid.modals.top();
+            
+            // The console prompt takes every key while it is open
+            if active_modal == Some(Modal::ConsolePrompt) {
+                self.handle_console_input();
+                return;
+            }
+            
+            // Help overlay toggle; while shown it swallows all other input
+            let help_pressed = self.input.key_pressed(VirtualKeyCode::F12) ||
+                (self.input.key_pressed(VirtualKeyCode::Slash) && self.input.held_shift());
+            if active_modal == Some(Modal::Help) {
+                if help_pressed || self.input.key_pressed(VirtualKeyCode::Escape) {
+                    self.grid.modals.close(Modal::Help);
+                }
+                return;
+            }
+            if help_pressed && active_modal.is_none() {
+                self.grid.modals.open(Modal::Help);
+                return;
+            }
+            if self.input.key_pressed(VirtualKeyCode::Grave) && active_modal.is_none() {
+                self.grid.modals.open(Modal::ConsolePrompt);
+                self.console_input.clear();
+                self.console_history_index = None;
+                return;
+            }
             
-            if self.grid.context_menu.is_open() {
+            // Context menu
+            if active_modal == Some(Modal::ContextMenu) {
+                if let Some(action) = self.grid.context_menu.handle_input(&self.input, &self.grid.balls) {
+                    match action {
+                        ContextMenuAction::SetDirection { ball_index, direction } => {
+                            self.grid.set_ball_direction(ball_index, direction);
+                        }
+                        ContextMenuAction::SetSpeed { ball_index, speed } => {
+                            self.grid.set_ball_speed(ball_index, speed);
+                        }
+                        ContextMenuAction::SetSample { ball_index, sample } => {
+                            self.grid.set_ball_sample(ball_index, sample);
+                        }
+                        ContextMenuAction::SetColor { ball_index, color } => {
+                            self.grid.set_ball_color(ball_index, color);
+                        }
+                        ContextMenuAction::SetName { ball_index, name } => {
+                            self.grid.set_ball_name(ball_index, name);
+                        }
+                        ContextMenuAction::SetVolume { ball_index, volume } => {
+                            self.grid.set_ball_volume(ball_index, volume);
+                        }
+                        ContextMenuAction::SetPitch { ball_index, pitch } => {
+                            self.grid.set_ball_pitch(ball_index, pitch);
+                        }
+                        ContextMenuAction::OpenFileDialog { ball_index } => {
+                            self.open_file_dialog_for_ball(ball_index);
+                        }
+                        ContextMenuAction::AddSampleToLibrary { ball_index } => {
+                            self.add_sample_to_library_for_ball(ball_index);
+                        }
+                        ContextMenuAction::CopyBallConfig { ball_index } => {
+                            self.grid.copy_ball_config(ball_index);
+                        }
+                        ContextMenuAction::PasteBallConfig { ball_index } => {
+                            self.grid.paste_ball_config(ball_index);
+                        }
+                        ContextMenuAction::OpenAudioPlayer { ball_index } => {
+                            if let Some(ball) = self.grid.balls.get(ball_index) {
+                                if let Some(ref sample_path) = ball.sample_path {
+                                    // Open the audio player with the ball's sample
+                                    if let Err(e) = self.grid.audio_player.open_sample(sample_path.clone(), &mut self.grid.audio_engine) {
+                                        self.grid.log_to_console(format!("Failed to open audio player: {}", e));
+                                    } else {
+                                        self.grid.log_to_console(format!("Opened audio player for ball sample: {}", sample_path));
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
                 return;
             }
 
             // Handle square menu input
-            if self.grid.square_menu.is_open() {
+            if active_modal == Some(Modal::SquareMenu) {
                 if let Some(action) = self.grid.square_menu.handle_input(&self.input, &self.grid.cells) {
                     match action {
                         SquareMenuAction::SaveProgram { square_x, square_y, program, program_index } => {
-                            if square_x < GRID_WIDTH && square_y < GRID_HEIGHT {
+                            let source_text = self.grid.square_menu.program_editor.get_program_text();
+                            self.grid.report_parse_errors(&source_text);
+                            self.grid.warn_unreachable_code(&source_text);
+                            if square_x < grid_width() && square_y < grid_height() {
                                 let square_program = &mut self.grid.cells[square_y][square_x].program;
                                 
                                 if let Some(index) = program_index {
@@ -1976,10 +3482,14 @@ impl SequencerUI {
                                 
                                 // Automatically add program to library
                                 self.grid.auto_add_program_to_library(&program);
+                                self.grid.warn_unresolved_references(square_x, square_y);
                             }
                         }
                         SquareMenuAction::SaveMultiplePrograms { square_x, square_y, programs, program_index } => {
-                            if square_x < GRID_WIDTH && square_y < GRID_HEIGHT {
+                            let source_text = self.grid.square_menu.program_editor.get_program_text();
+                            self.grid.report_parse_errors(&source_text);
+                            self.grid.warn_unreachable_code(&source_text);
+                            if square_x < grid_width() && square_y < grid_height() {
                                 // First, handle the square program operations
                                 {
                                     let square_program = &mut self.grid.cells[square_y][square_x].program;
@@ -2013,15 +3523,24 @@ impl SequencerUI {
                                 for program in &programs {
                                     self.grid.auto_add_program_to_library(program);
                                 }
+                                self.grid.warn_unresolved_references(square_x, square_y);
                             }
                         }
 
                         SquareMenuAction::ClearPrograms { square_x, square_y } => {
-                            if square_x < GRID_WIDTH && square_y < GRID_HEIGHT {
+                            if square_x < grid_width() && square_y < grid_height() {
                                 self.grid.cells[square_y][square_x].program.programs.clear();
                                 self.grid.cells[square_y][square_x].program.set_active_program(None);
                             }
                         }
+                        SquareMenuAction::TogglePassThrough { square_x, square_y } => {
+                            if square_x < grid_width() && square_y < grid_height() {
+                                let cell = &mut self.grid.cells[square_y][square_x];
+                                cell.pass_through = !cell.pass_through;
+                                let mode = if cell.pass_through { "pass-through" } else { "solid" };
+                                self.grid.log_to_console(format!("Square ({}, {}) is now {}", square_x, square_y, mode));
+                            }
+                        }
                         SquareMenuAction::SaveProgramToFile => {
                             let program_name = self.grid.square_menu.program_editor.get_program_name();
                             let program_text = self.grid.square_menu.program_editor.get_program_text();
@@ -2040,26 +3559,42 @@ impl SequencerUI {
             }
 
             // Library GUI open (G key) - only opens, never closes
-            if self.input.key_pressed(VirtualKeyCode::G) {
-                // Only open if hidden
-                if !self.grid.library_gui.is_visible() {
-                    self.grid.library_gui.toggle();
-                }
+            if active_modal.is_none() && self.input.key_pressed(VirtualKeyCode::G) {
+                self.grid.library_gui.toggle();
             }
             
-            // Handle library GUI input if visible and audio player is not open
-            if self.grid.library_gui.is_visible() && !self.grid.audio_player.is_visible() {
+            // Handle library GUI input
+            if active_modal == Some(Modal::LibraryGui) {
                 if let Some(action) = self.grid.library_gui.handle_input(&self.input, &self.grid.library_manager, &self.grid.cells) {
                     match action {
+                        LibraryGuiAction::ToggleLibrary { library_name } => {
+                            let enabled = !self.grid.library_manager.is_library_enabled(&library_name);
+                            self.grid.set_library_enabled(&library_name, enabled);
+                        }
                         LibraryGuiAction::RenameItem { library_name, old_name, new_name, is_sample } => {
-                            // TODO: Implement rename functionality
-                            self.grid.log_to_console(format!("Rename {} from {} to {} in library {}", 
-                                if is_sample { "sample" } else { "program" }, old_name, new_name, library_name));
+                            let kind = if is_sample { "sample" } else { "program" };
+                            let result = if is_sample {
+                                self.grid.library_manager.rename_sample(&library_name, &old_name, &new_name)
+                            } else {
+                                self.grid.library_manager.rename_function(&library_name, &old_name, &new_name)
+                            };
+                            match result {
+                                Ok(()) => self.grid.log_to_console(format!("Renamed {} {}.{} to {}", kind, library_name, old_name, new_name)),
+                                Err(e) => self.grid.log_to_console(format!("Rename failed: {}", e)),
+                            }
                         }
                         LibraryGuiAction::DeleteItem { library_name, item_name, is_sample } => {
-                            // TODO: Implement delete functionality
-                            self.grid.log_to_console(format!("Delete {} {} from library {}", 
-                                if is_sample { "sample" } else { "program" }, item_name, library_name));
+                            let kind = if is_sample { "sample" } else { "program" };
+                            let result = if is_sample {
+                                self.grid.library_manager.delete_sample(&library_name, &item_name)
+                            } else {
+                                self.grid.library_manager.delete_function(&library_name, &item_name)
+                            };
+                            match result {
+                                Ok(()) => self.grid.log_to_console(format!("Deleted {} {}.{}", kind, library_name, item_name)),
+                                Err(e) => self.grid.log_to_console(format!("Delete failed: {}", e)),
+                            }
+                            self.grid.library_gui.clamp_selection(&self.grid.library_manager, &self.grid.cells);
                         }
                         LibraryGuiAction::CreateProgram { library_name, name, program } => {
                             // Add program to the specified library
@@ -2072,6 +3607,7 @@ impl SequencerUI {
                                     name: library_name.clone(),
                                     functions: std::collections::HashMap::new(),
                                     description: format!("User created library: {}", library_name),
+                                    enabled: true,
                                 };
                                 new_lib.functions.insert(name.clone(), program);
                                 self.grid.library_manager.function_libraries.insert(library_name.clone(), new_lib);
@@ -2112,7 +3648,7 @@ impl SequencerUI {
                                         // For squares, we don't remove the program entirely, just log a warning
                                         // since squares need to maintain their program structure
                                         self.grid.log_to_console(format!("Warning: Program '{}' in square ({}, {}) has no valid def statement", name, x, y));
-                                        if x < crate::sequencer::GRID_WIDTH && y < crate::sequencer::GRID_HEIGHT {
+                                        if x < crate::sequencer::grid_width() && y < crate::sequencer::grid_height() {
                                             if let Some(square_program) = self.grid.cells[y][x].program.programs.get_mut(program_index) {
                                                 *square_program = updated_program;
                                             }
@@ -2141,7 +3677,7 @@ impl SequencerUI {
                                 },
                                 crate::library_gui::ProgramSource::Square { x, y, program_index } => {
                                     // Update program in square
-                                    if x < crate::sequencer::GRID_WIDTH && y < crate::sequencer::GRID_HEIGHT {
+                                    if x < crate::sequencer::grid_width() && y < crate::sequencer::grid_height() {
                                         if let Some(square_program) = self.grid.cells[y][x].program.programs.get_mut(program_index) {
                                             *square_program = updated_program;
                                             self.grid.log_to_console(format!("Updated program '{}' in square ({}, {})", updated_program_name, x, y));
@@ -2152,7 +3688,7 @@ impl SequencerUI {
                         }
                         LibraryGuiAction::OpenSquareScript { x, y, program_index } => {
                             // Open the square menu in program editor mode for the specific square
-                            if x < crate::sequencer::GRID_WIDTH && y < crate::sequencer::GRID_HEIGHT {
+                            if x < crate::sequencer::grid_width() && y < crate::sequencer::grid_height() {
                                 let cell = &self.grid.cells[y][x];
                                 
                                 // Get the program at the specified index
@@ -2245,7 +3781,7 @@ impl SequencerUI {
                         }
                         LibraryGuiAction::LoadProgramToSquare { program, square_x, square_y } => {
                             // Load the selected program into the target square
-                            if square_x < GRID_WIDTH && square_y < GRID_HEIGHT {
+                            if square_x < grid_width() && square_y < grid_height() {
                                 self.grid.cells[square_y][square_x].program.add_program(program);
                                 let program_count = self.grid.cells[square_y][square_x].program.programs.len();
                                 self.grid.cells[square_y][square_x].program.set_active_program(Some(program_count - 1));
@@ -2256,8 +3792,8 @@ impl SequencerUI {
                 return; // Don't process other input while library GUI is open
             }
             
-            // Handle audio player input if visible
-            if self.grid.audio_player.is_visible() {
+            // Handle audio player input
+            if active_modal == Some(Modal::AudioPlayer) {
                 if let Some(action) = self.grid.audio_player.handle_input(&self.input, &mut self.grid.audio_engine) {
                     match action {
                         AudioPlayerAction::Close => {
@@ -2277,31 +3813,50 @@ impl SequencerUI {
             
             // Normal grid navigation (only when library GUI, audio player, and square menu are not open)
             if !self.grid.square_menu.is_open() {
-                if self.input.key_pressed(VirtualKeyCode::Up) {
+                if self.cursor_repeat.fired(&self.input, VirtualKeyCode::Up) {
                     self.grid.cursor.move_up();
                     self.log_cursor_position_if_changed();
                 }
-                if self.input.key_pressed(VirtualKeyCode::Down) {
+                if self.cursor_repeat.fired(&self.input, VirtualKeyCode::Down) {
                     self.grid.cursor.move_down();
                     self.log_cursor_position_if_changed();
                 }
-                if self.input.key_pressed(VirtualKeyCode::Left) {
+                if self.cursor_repeat.fired(&self.input, VirtualKeyCode::Left) {
                     self.grid.cursor.move_left();
                     self.log_cursor_position_if_changed();
                 }
-                if self.input.key_pressed(VirtualKeyCode::Right) {
+                if self.cursor_repeat.fired(&self.input, VirtualKeyCode::Right) {
                     self.grid.cursor.move_right();
                     self.log_cursor_position_if_changed();
                 }
             }
             
-            // Shape placement / Label editing
-            if self.input.key_pressed(VirtualKeyCode::S) {
+            // Project save/load
+            if self.input.held_control() {
+                if self.input.key_pressed(VirtualKeyCode::S) {
+                    self.save_project_with_dialog();
+                }
+                if self.input.key_pressed(VirtualKeyCode::O) {
+                    self.load_project_with_dialog();
+                }
+                if self.input.key_pressed(VirtualKeyCode::C) {
+                    self.grid.copy_cell(self.grid.cursor.x, self.grid.cursor.y);
+                }
+                if self.input.key_pressed(VirtualKeyCode::V) {
+                    self.grid.paste_cell(self.grid.cursor.x, self.grid.cursor.y);
+                }
+            }
+            
+            // Shape placement / Label editing; Shift+S starts a rectangle that the next S fills
+            if self.input.key_pressed(VirtualKeyCode::S) && !self.input.held_control() {
                 let cursor_x = self.grid.cursor.x;
                 let cursor_y = self.grid.cursor.y;
                 
-                // Check if there's already a square at cursor position
-                if cursor_x < GRID_WIDTH && cursor_y < GRID_HEIGHT && 
+                if self.input.held_shift() {
+                    self.grid.set_selection_anchor(cursor_x, cursor_y);
+                } else if self.grid.selection_anchor.is_some() {
+                    self.grid.fill_selection(cursor_x, cursor_y);
+                } else if cursor_x < grid_width() && cursor_y < grid_height() && 
                    self.grid.cells[cursor_y][cursor_x].content == CellContent::Square {
                     // Enter label editing mode
                     self.label_editing_mode = true;
@@ -2313,13 +3868,13 @@ impl SequencerUI {
                     self.grid.place_square(cursor_x, cursor_y);
                 }
             }
-            if self.input.key_pressed(VirtualKeyCode::C) {
+            if self.input.key_pressed(VirtualKeyCode::C) && !self.input.held_control() {
                  self.grid.place_ball(self.grid.cursor.x, self.grid.cursor.y);
              }
             
             // Stop all sounds and toggle ball movement (P key)
             if self.input.key_pressed(VirtualKeyCode::P) {
-                self.audio_engine.stop_all();
+                self.grid.audio_engine.stop_all();
                 self.grid.toggle_all_balls();
                 let any_active = self.grid.balls.iter().any(|ball| ball.active);
                 if any_active {
@@ -2329,9 +3884,13 @@ impl SequencerUI {
                 }
             }
             
-            // Cell clearing
+            // Cell clearing; Shift+Delete clears the whole selection when one is started
             if self.input.key_pressed(VirtualKeyCode::Delete) || self.input.key_pressed(VirtualKeyCode::Back) {
-                self.grid.clear_cell(self.grid.cursor.x, self.grid.cursor.y);
+                if self.input.held_shift() && self.grid.selection_anchor.is_some() {
+                    self.grid.clear_selection(self.grid.cursor.x, self.grid.cursor.y);
+                } else {
+                    self.grid.clear_cell(self.grid.cursor.x, self.grid.cursor.y);
+                }
             }
             
             // Context menu for balls or library for empty tiles
@@ -2343,7 +3902,7 @@ impl SequencerUI {
                 let has_ball = self.grid.get_ball_at(cursor_x, cursor_y).is_some();
                 
                 // Check if there's a square at cursor position
-                let has_square = cursor_x < GRID_WIDTH && cursor_y < GRID_HEIGHT && 
+                let has_square = cursor_x < grid_width() && cursor_y < grid_height() && 
                                 self.grid.cells[cursor_y][cursor_x].content == CellContent::Square;
                 
                 if has_ball || has_square {
@@ -2379,6 +3938,50 @@ impl SequencerUI {
             if self.input.key_pressed(VirtualKeyCode::F3) {
                 self.grid.handle_console_command("lib clear auto");
             }
+            
+            // Toggle antialiased rendering
+            if self.input.key_pressed(VirtualKeyCode::F4) {
+                self.antialiasing = !self.antialiasing;
+                self.grid.log_to_console(format!("Antialiasing {}", if self.antialiasing { "on" } else { "off" }));
+            }
+            
+            // Trim the volume of the ball under the cursor (PageUp/PageDown); elsewhere they scroll the console
+            if let Some(ball_index) = self.grid.get_ball_at(self.grid.cursor.x, self.grid.cursor.y) {
+                if self.input.key_pressed(VirtualKeyCode::PageUp) {
+                    self.grid.nudge_ball_trim(ball_index, BALL_TRIM_STEP);
+                }
+                if self.input.key_pressed(VirtualKeyCode::PageDown) {
+                    self.grid.nudge_ball_trim(ball_index, -BALL_TRIM_STEP);
+                }
+            } else {
+                let page = Renderer::console_visible_lines() - 1;
+                if self.input.key_pressed(VirtualKeyCode::PageUp) {
+                    self.console_scroll = (self.console_scroll + page).min(self.max_console_scroll());
+                }
+                if self.input.key_pressed(VirtualKeyCode::PageDown) {
+                    self.console_scroll = self.console_scroll.saturating_sub(page);
+                }
+            }
+            
+            // Audition the sample of the ball under the cursor
+            if self.input.key_pressed(VirtualKeyCode::A) {
+                self.grid.audition_at_cursor();
+            }
+            
+            // Toggle grid overview in the console area (F5)
+            if self.input.key_pressed(VirtualKeyCode::F5) {
+                self.grid.modals.toggle(Modal::Minimap);
+            }
+            
+            // Toggle the trigger timeline in place of the console messages (F6)
+            if self.input.key_pressed(VirtualKeyCode::F6) {
+                self.grid.modals.toggle(Modal::Timeline);
+            }
+            
+            // Toggle the stats panel (F7)
+            if self.input.key_pressed(VirtualKeyCode::F7) {
+                self.grid.modals.toggle(Modal::Stats);
+            }
         }
     }
     
@@ -2397,7 +4000,7 @@ impl SequencerUI {
                 self.current_label.push('\n'); // Add newline separator
             } else {
                 // Save and exit from second line
-                if self.label_editing_x < GRID_WIDTH && self.label_editing_y < GRID_HEIGHT {
+                if self.label_editing_x < grid_width() && self.label_editing_y < grid_height() {
                     let label = if self.current_label.trim().is_empty() {
                         None
                     } else {
@@ -2483,50 +4086,103 @@ impl SequencerUI {
         self.label_editing_line = 0;
     }
     
+    // Typed characters arrive through ReceivedCharacter; this handles the editing keys
+    fn handle_console_input(&mut self) {
+        if self.input.key_pressed(VirtualKeyCode::Escape) || self.input.key_pressed(VirtualKeyCode::Grave) {
+            self.grid.modals.close(Modal::ConsolePrompt);
+            self.console_history_index = None;
+            return;
+        }
+        
+        if self.input.key_pressed(VirtualKeyCode::Return) {
+            let command = std::mem::take(&mut self.console_input);
+            let command = command.trim();
+            self.console_history_index = None;
+            if command.is_empty() {
+                return;
+            }
+            if self.console_history.last().map(String::as_str) != Some(command) {
+                self.console_history.push(command.to_string());
+                if self.console_history.len() > CONSOLE_HISTORY_LIMIT {
+                    self.console_history.remove(0);
+                }
+            }
+            self.console_scroll = 0;
+            self.grid.log_to_console(format!("> {}", command));
+            self.grid.handle_console_command(command);
+            return;
+        }
+        
+        if self.input.key_pressed(VirtualKeyCode::Back) {
+            self.console_input.pop();
+            return;
+        }
+        
+        // Up walks back through earlier commands, Down forward to an empty prompt
+        if self.input.key_pressed(VirtualKeyCode::Up) && !self.console_history.is_empty() {
+            let index = match self.console_history_index {
+                Some(index) => index.saturating_sub(1),
+                None => self.console_history.len() - 1,
+            };
+            self.console_history_index = Some(index);
+            self.console_input = self.console_history[index].clone();
+        } else if self.input.key_pressed(VirtualKeyCode::Down) {
+            if let Some(index) = self.console_history_index {
+                if index + 1 < self.console_history.len() {
+                    self.console_history_index = Some(index + 1);
+                    self.console_input = self.console_history[index + 1].clone();
+                } else {
+                    self.console_history_index = None;
+                    self.console_input.clear();
+                }
+            }
+        }
+    }
+    
 
     pub fn render(&mut self) -> Result<(), Error> {
         // Calculate delta time for smooth movement
         let now = std::time::Instant::now();
         let delta_time = now.duration_since(self.last_update).as_secs_f32();
         self.last_update = now;
+        self.frame_ms = self.frame_ms * 0.9 + delta_time * 1000.0 * 0.1;
         
         // Update context menu timing
         self.grid.context_menu.update(delta_time);
         
-        // Update balls with delta time
-        let triggered_positions = self.grid.update_balls(delta_time);
+        // Update balls with delta time; hits are sounded by the grid's BallAudioSystem as they happen
+        self.grid.update_balls(delta_time);
         
         // Update grid (including audio player)
         self.grid.update(delta_time);
         
-        // Play audio samples for triggered positions
-        for (_x, _y, ball_index) in triggered_positions {
-            if let Some(ball) = self.grid.balls.get(ball_index) {
-                if let Some(sample_path) = &ball.sample_path {
-                    // Use the first channel (channel 0) for ball samples
-                    if let Err(e) = self.audio_engine.play_on_channel(0, sample_path) {
-                        log::warn!("Failed to play sample {}: {}", sample_path, e);
-                    }
-                }
-            }
+        if let Err(e) = self.grid.metronome.tick(delta_time, self.grid.bpm, &self.grid.audio_engine) {
+            log::warn!("{}", e);
+        }
+        
+        // A scrolled-up console stays on the same lines while new messages arrive below
+        let new_lines = self.grid.console_logged - self.console_logged_seen;
+        if self.console_scroll > 0 {
+            self.console_scroll = (self.console_scroll + new_lines).min(self.max_console_scroll());
         }
+        self.console_logged_seen = self.grid.console_logged;
         
         let frame = self.pixels.frame_mut();
         
         // Clear the frame
         for pixel in frame.chunks_exact_mut(4) {
-            pixel[0] = 20;  // R
-            pixel[1] = 20;  // G
-            pixel[2] = 20;  // B
+            pixel[0] = self.grid.theme.background[0];
+            pixel[1] = self.grid.theme.background[1];
+            pixel[2] = self.grid.theme.background[2];
             pixel[3] = 255; // A
         }
         
         // Draw grid lines using renderer
-        Renderer::draw_grid_lines(frame);
+        Renderer::draw_grid_lines(frame, &self.grid.theme);
         
         // Draw cells
-        for y in 0..GRID_HEIGHT {
-            for x in 0..GRID_WIDTH {
+        for y in 0..grid_height() {
+            for x in 0..grid_width() {
                 let cell = &self.grid.cells[y][x];
                 match cell.content {
                     CellContent::Square => {
@@ -2550,7 +4206,13 @@ impl SequencerUI {
                         } else {
                             cell.display_text.clone()
                         };
-                        Renderer::draw_square(frame, x, y, cell.color, &display_text);
+                        // Pass-through squares are drawn at half brightness
+                        let color = if cell.pass_through {
+                            [cell.color[0] / 2, cell.color[1] / 2, cell.color[2] / 2]
+                        } else {
+                            cell.color
+                        };
+                        Renderer::draw_square(frame, x, y, color, &display_text, cell.glyph, self.antialiasing);
                     }
                     CellContent::Empty => {}
                 }
@@ -2560,33 +4222,140 @@ impl SequencerUI {
         // Draw balls using renderer
         for ball in &self.grid.balls {
             let ball_color = Renderer::get_color_rgb(&ball.color);
-            Renderer::draw_ball(frame, ball.x, ball.y, ball_color);
+            Renderer::draw_ball(frame, ball.x, ball.y, ball_color, ball.glyph, self.antialiasing);
         }
         
-        // Draw context menu if open
-        self.grid.context_menu.render(frame, &self.grid.balls);
+        // Draw open overlays bottom to top so the active one is drawn last
+        self.grid.sync_modals();
+        for modal in self.grid.modals.iter() {
+            match modal {
+                Modal::ContextMenu => self.grid.context_menu.render(frame, &self.grid.balls),
+                Modal::SquareMenu => self.grid.square_menu.render(frame, &self.grid.cells),
+                Modal::LibraryGui => self.grid.library_gui.render(frame, &self.grid.library_manager, &self.grid.cells, window_width(), window_height()),
+                Modal::AudioPlayer => self.grid.audio_player.render(frame, window_width(), window_height()),
+                // Drawn below with the console area, or above everything for help
+                Modal::Help | Modal::ConsolePrompt | Modal::Stats | Modal::Timeline | Modal::Minimap => {}
+            }
+        }
         
-        // Draw square menu if open
-        self.grid.square_menu.render(frame, &self.grid.cells);
+        // Live readout for the hovered ball; a ball passing under the cursor becomes hovered.
+        // Stopped balls only show it while under the cursor (e.g. for trimming).
+        let ball_under_cursor = self.grid.get_ball_at(self.grid.cursor.x, self.grid.cursor.y);
+        if let Some(ball_index) = ball_under_cursor {
+            self.hovered_ball_id = Some(self.grid.balls[ball_index].id.clone());
+        }
+        if let Some(ref hovered_id) = self.hovered_ball_id {
+            if let Some(ball) = self.grid.balls.iter().find(|ball| &ball.id == hovered_id) {
+                if (ball.active || ball_under_cursor.is_some()) && matches!(self.grid.modals.top(), None | Some(Modal::ConsolePrompt)) {
+                    Renderer::draw_ball_readout(frame, ball, &self.grid.theme);
+                }
+            }
+        }
         
-        // Draw library GUI if visible
-        self.grid.library_gui.render(frame, &self.grid.library_manager, &self.grid.cells, WINDOW_WIDTH, WINDOW_HEIGHT);
+        // Draw cursor unless a full-screen overlay is active (the context menu leaves the grid visible)
+        if self.grid.modals.top().map_or(true, Modal::shows_grid) {
+            if let Some(anchor) = self.grid.selection_anchor {
+                Renderer::draw_selection(frame, anchor, (self.grid.cursor.x, self.grid.cursor.y), &self.grid.theme);
+            }
+            Renderer::draw_cursor(frame, self.grid.cursor.x, self.grid.cursor.y, &self.grid.theme);
+        }
         
-        // Draw audio player if visible
-        self.grid.audio_player.render(frame, WINDOW_WIDTH, WINDOW_HEIGHT);
+        // Draw console area using renderer
+        if self.grid.modals.is_open(Modal::Timeline) {
+            Renderer::draw_timeline(frame, &self.grid.collision_history, &self.grid.theme);
+        } else {
+            let prompt = self.grid.modals.is_open(Modal::ConsolePrompt).then_some(self.console_input.as_str());
+            Renderer::draw_console(frame, &self.grid.console_messages, self.console_scroll, prompt, &self.grid.theme);
+        }
+        Renderer::draw_transport_indicator(frame, self.grid.bpm, self.grid.rng_seed, &self.grid.theme);
+        self.output_level = (self.output_level * 0.9).max(self.grid.audio_engine.take_output_peak());
+        Renderer::draw_peak_meter(frame, self.output_level, self.grid.audio_engine.limiter());
+        if self.grid.modals.is_open(Modal::Minimap) {
+            // The whole grid currently fits in the window, so the viewport is the full grid
+            Renderer::draw_minimap(frame, &self.grid.cells, &self.grid.balls, (0, 0, grid_width(), grid_height()), &self.grid.theme);
+        }
         
-        // Draw cursor only when library GUI, audio player, and square menu are not visible
-        if !self.grid.library_gui.is_visible() && !self.grid.audio_player.is_visible() && !self.grid.square_menu.is_open() {
-            Renderer::draw_cursor(frame, self.grid.cursor.x, self.grid.cursor.y);
+        if self.grid.modals.is_open(Modal::Stats) {
+            Renderer::draw_stats_overlay(frame, &self.grid.stats(self.frame_ms), &self.grid.theme);
         }
         
-        // Draw console area using renderer
-        Renderer::draw_console(frame, &self.grid.console_messages);
+        if let Some((loaded, total)) = self.grid.preload_queue.progress() {
+            Renderer::draw_loading_bar(frame, loaded, total, &self.grid.theme);
+        }
+        
+        // Flash an overload warning for a moment after triggers were dropped
+        if let Some(last_overload) = self.grid.last_overload {
+            if last_overload.elapsed().as_millis() < 500 {
+                Renderer::draw_overload_indicator(frame);
+            }
+        }
+        
+        // Draw help overlay on top of everything
+        if self.grid.modals.is_open(Modal::Help) {
+            Renderer::draw_help_overlay(frame, &self.grid.theme);
+        }
         
         self.pixels.render()
     }
     
     
+    pub fn handle_mouse_moved(&mut self, position: winit::dpi::PhysicalPosition<f64>) {
+        self.mouse_position = Some((position.x as f32, position.y as f32));
+    }
+    
+    pub fn handle_mouse_left(&mut self) {
+        self.mouse_position = None;
+    }
+    
+    // Left click moves the cursor to the cell under the pointer (a second click on an
+    // empty cell places a square there); right click opens that cell's ball or square menu.
+    // Only the grid and the audio player's waveform take clicks.
+    pub fn handle_mouse_click(&mut self, button: winit::event::MouseButton) {
+        self.grid.sync_modals();
+        if self.grid.modals.top() == Some(Modal::AudioPlayer) && button == winit::event::MouseButton::Left {
+            if let Some((pixel_x, pixel_y)) = self.mouse_position.and_then(|position| self.pixels.window_pos_to_pixel(position).ok()) {
+                self.grid.audio_player.handle_click(pixel_x, pixel_y, window_width(), window_height());
+            }
+            return;
+        }
+        if self.grid.modals.top().is_some() || self.label_editing_mode {
+            return;
+        }
+        let Some((cell_x, cell_y)) = self.cell_under_mouse() else {
+            return;
+        };
+        
+        match button {
+            winit::event::MouseButton::Left => {
+                let already_there = (self.grid.cursor.x, self.grid.cursor.y) == (cell_x, cell_y);
+                self.grid.cursor.x = cell_x;
+                self.grid.cursor.y = cell_y;
+                if already_there && !self.grid.cells[cell_y][cell_x].is_square() && self.grid.get_ball_at(cell_x, cell_y).is_none() {
+                    self.grid.place_square(cell_x, cell_y);
+                }
+                self.log_cursor_position_if_changed();
+            }
+            winit::event::MouseButton::Right => {
+                self.grid.cursor.x = cell_x;
+                self.grid.cursor.y = cell_y;
+                self.log_cursor_position_if_changed();
+                self.grid.open_context_menu(cell_x, cell_y);
+            }
+            _ => {}
+        }
+    }
+    
+    // Grid cell under the pointer; None outside the grid, including the console area
+    fn cell_under_mouse(&self) -> Option<(usize, usize)> {
+        // Accounts for the surface scale factor and any letterboxing around the frame
+        let (pixel_x, pixel_y) = self.pixels.window_pos_to_pixel(self.mouse_position?).ok()?;
+        if pixel_y >= grid_area_height() {
+            return None;
+        }
+        let (cell_x, cell_y) = (pixel_x / CELL_SIZE, pixel_y / CELL_SIZE);
+        (cell_x < grid_width() && cell_y < grid_height()).then_some((cell_x, cell_y))
+    }
+    
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
         if let Err(err) = self.pixels.resize_surface(new_size.width, new_size.height) {
             log::error!("Failed to resize surface: {}", err);
@@ -2620,6 +4389,27 @@ impl SequencerUI {
         }
     }
 
+    fn save_project_with_dialog(&mut self) {
+        if let Some(file_path) = FileDialog::new()
+            .add_filter("Canticle Projects", &["json"])
+            .set_title("Save Project")
+            .set_file_name("project.json")
+            .save_file()
+        {
+            self.grid.save_project(&file_path.to_string_lossy());
+        }
+    }
+
+    fn load_project_with_dialog(&mut self) {
+        if let Some(file_path) = FileDialog::new()
+            .add_filter("Canticle Projects", &["json"])
+            .set_title("Load Project")
+            .pick_file()
+        {
+            self.grid.load_project(&file_path.to_string_lossy());
+        }
+    }
+
     fn save_program_to_file_with_data(&mut self, program_name: String, program_text: Vec<String>) {
         let default_filename = format!("{}.cant", program_name);
         
@@ -2654,15 +4444,17 @@ impl SequencerUI {
                     Ok(content) => {
                         let lines: Vec<String> = content.lines().map(|s| s.to_string()).collect();
                         
-                        // Update the appropriate editor based on current context
-                        if self.grid.library_gui.is_visible() {
-                            // Update library GUI editor
-                            if let Some(editor) = self.grid.library_gui.get_current_editor_mut() {
-                                *editor = crate::program_editor::ProgramEditor::new_with_text(lines);
+                        // Update the editor of whichever overlay is active
+                        match self.grid.modals.top() {
+                            Some(Modal::LibraryGui) => {
+                                if let Some(editor) = self.grid.library_gui.get_current_editor_mut() {
+                                    *editor = crate::program_editor::ProgramEditor::new_with_text(lines);
+                                }
+                            }
+                            Some(Modal::SquareMenu) => {
+                                self.grid.square_menu.program_editor = crate::program_editor::ProgramEditor::new_with_text(lines);
                             }
-                        } else if self.grid.square_menu.is_open() {
-                            // Update square menu editor
-                            self.grid.square_menu.program_editor = crate::program_editor::ProgramEditor::new_with_text(lines);
+                            _ => {}
                         }
                         
                         self.grid.log_to_console(format!("Program loaded from: {}", path_str));
@@ -2676,20 +4468,57 @@ impl SequencerUI {
     }
 }
 
-pub async fn run_sequencer(audio_engine: AudioEngine) -> Result<(), Error> {
+// Clamp a value a program set, logging it so an odd-sounding patch has an explanation
+fn clamp_program_value(name: &str, value: f32, min: f32, max: f32, x: usize, y: usize, log: &mut StepLog) -> f32 {
+    let clamped = if value.is_nan() { min } else { value.clamp(min, max) };
+    if clamped != value {
+        log.notice(format!("clamped {} {} → {} at ({},{})", name, value, clamped, x, y));
+    }
+    clamped
+}
+
+// Put a ball at the center of cell (x, y) for a program at (square_x, square_y). The
+// ball counts as already inside that cell, so arriving there isn't a hit of its own.
+// Returns false (and logs why) when the cell is off the grid.
+fn teleport_ball(ball: &mut Ball, x: i32, y: i32, square_x: usize, square_y: usize, log: &mut StepLog) -> bool {
+    if x < 0 || y < 0 || x as usize >= grid_width() || y as usize >= grid_height() {
+        log.trace(|| format!("teleport({}, {}) at ({},{}) is outside the {}x{} grid", x, y, square_x, square_y, grid_width(), grid_height()));
+        return false;
+    }
+    ball.x = x as f32 + 0.5;
+    ball.y = y as f32 + 0.5;
+    ball.last_grid_x = x as usize;
+    ball.last_grid_y = y as usize;
+    true
+}
+
+// winit panics instead of returning an error when no display backend is available,
+// so turn that panic into an error (without the panic message and backtrace)
+fn create_event_loop() -> Result<EventLoop<()>, String> {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(|_| {}));
+    let event_loop = std::panic::catch_unwind(EventLoop::new);
+    std::panic::set_hook(default_hook);
+    event_loop.map_err(|_| "Could not connect to a display (no X11 or Wayland session found)".to_string())
+}
+
+const DISPLAY_HINT: &str = "Check that a display is available (e.g. DISPLAY or WAYLAND_DISPLAY is set) and that graphics drivers are installed.";
+
+pub async fn run_sequencer(audio_engine: AudioEngine) -> Result<(), String> {
     
-    let event_loop = EventLoop::new();
+    let event_loop = create_event_loop().map_err(|e| format!("{}. {}", e, DISPLAY_HINT))?;
     let window = {
-        let size = LogicalSize::new(WINDOW_WIDTH as f64, WINDOW_HEIGHT as f64);
+        let size = LogicalSize::new(window_width() as f64, window_height() as f64);
         WindowBuilder::new()
             .with_title("Canticle")
             .with_inner_size(size)
             .with_min_inner_size(size)
             .build(&event_loop)
-            .unwrap()
+            .map_err(|e| format!("Could not create the window: {}. {}", e, DISPLAY_HINT))?
     };
     
-    let mut sequencer_ui = SequencerUI::new(&window, audio_engine)?;
+    let mut sequencer_ui = SequencerUI::new(&window, audio_engine)
+        .map_err(|e| format!("Could not initialize the graphics surface: {}. {}", e, DISPLAY_HINT))?;
     
     event_loop.run(move |event, _, control_flow| {
         match event {
@@ -2708,21 +4537,48 @@ pub async fn run_sequencer(audio_engine: AudioEngine) -> Result<(), Error> {
                     winit::event::WindowEvent::Resized(new_size) => {
                         sequencer_ui.resize(*new_size);
                     }
+                    winit::event::WindowEvent::CursorMoved { position, .. } => {
+                        sequencer_ui.handle_mouse_moved(*position);
+                    }
+                    winit::event::WindowEvent::CursorLeft { .. } => {
+                        sequencer_ui.handle_mouse_left();
+                    }
+                    winit::event::WindowEvent::MouseInput { state: winit::event::ElementState::Pressed, button, .. } => {
+                        sequencer_ui.handle_mouse_click(*button);
+                    }
                     winit::event::WindowEvent::ReceivedCharacter(ch) => {
                         // Filter out control characters (backspace, delete, etc.)
                         if ch.is_control() {
                             return;
                         }
                         
-                        // Pass character directly to program editor
-                        if sequencer_ui.grid.square_menu.is_open() {
-                            if let crate::square_menu::SquareMenuState::ProgramEditor { .. } = sequencer_ui.grid.square_menu.state {
-                                sequencer_ui.grid.square_menu.program_editor.insert_character(*ch);
+                        // An open console prompt gets the text; the backtick only toggles it
+                        sequencer_ui.grid.sync_modals();
+                        if sequencer_ui.grid.modals.top() == Some(Modal::ConsolePrompt) {
+                            if *ch != '`' {
+                                sequencer_ui.console_input.push(*ch);
+                            }
+                            return;
+                        }
+                        
+                        // Pass character directly to the active overlay's program editor
+                        match sequencer_ui.grid.modals.top() {
+                            Some(Modal::SquareMenu) => {
+                                if let crate::square_menu::SquareMenuState::ProgramEditor { .. } = sequencer_ui.grid.square_menu.state {
+                                    sequencer_ui.grid.square_menu.program_editor.insert_character(*ch);
+                                }
+                            }
+                            Some(Modal::LibraryGui) => {
+                                if let Some(editor) = sequencer_ui.grid.library_gui.get_current_editor_mut() {
+                                    editor.insert_character(*ch);
+                                } else {
+                                    sequencer_ui.grid.library_gui.insert_name_character(*ch);
+                                }
                             }
-                        } else if sequencer_ui.grid.library_gui.is_visible() {
-                            if let Some(editor) = sequencer_ui.grid.library_gui.get_current_editor_mut() {
-                                editor.insert_character(*ch);
+                            Some(Modal::ContextMenu) => {
+                                sequencer_ui.grid.context_menu.insert_name_character(*ch);
                             }
+                            _ => {}
                         }
                     }
                     _ => {}
@@ -2738,4 +4594,153 @@ pub async fn run_sequencer(audio_engine: AudioEngine) -> Result<(), Error> {
         
         sequencer_ui.handle_input(&event);
     });
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid() -> SequencerGrid {
+        SequencerGrid::new(AudioEngine::new_null())
+    }
+
+    #[test]
+    fn lean_step_log_keeps_notices_without_formatting_traces() {
+        let mut lean = StepLog::new(false);
+        lean.trace(|| unreachable!("trace formatted in lean mode"));
+        lean.notice("[log (1, 1)] hi".to_string());
+        assert_eq!(lean.lines, vec!["[log (1, 1)] hi"]);
+
+        let mut full = StepLog::new(true);
+        full.trace(|| "  → Bounce".to_string());
+        full.notice("[log (1, 1)] hi".to_string());
+        assert_eq!(full.lines.len(), 2);
+    }
+
+    #[test]
+    fn collisions_are_counted_over_the_take_clock() {
+        let mut grid = grid();
+        for (time, color) in [(0.5, "Red"), (1.5, "Blue"), (2.5, "Red")] {
+            grid.collision_history.push_back(CollisionEvent {
+                ball_index: 0,
+                ball_color: color.to_string(),
+                square_x: 1,
+                square_y: 1,
+                timestamp: std::time::Instant::now(),
+                time,
+            });
+        }
+        grid.program_executor.state.elapsed = 3.0;
+        let second = std::time::Duration::from_secs(1);
+        assert_eq!(grid.count_collisions_since(second, |_| true), 1);
+        assert_eq!(grid.count_collisions_since(second * 2, |_| true), 2);
+        assert_eq!(grid.count_collisions_since(second * 3, |event| event.ball_color == "Red"), 2);
+    }
+
+    #[test]
+    fn pasted_squares_own_their_programs_and_start_uncounted() {
+        let mut grid = grid();
+        grid.place_square(1, 1);
+        let programs = SimpleProgramParser::new().parse_multiple_programs("def a\nset speed 2\nend\ndef b\nset speed 3\nend").unwrap();
+        grid.cells[1][1].program.load_programs(programs);
+        let instructions = grid.cells[1][1].program.programs[0].instructions.clone();
+        assert!(!instructions.is_empty());
+        grid.copy_cell(1, 1);
+
+        let state = &mut grid.program_executor.state;
+        state.square_hit_counts.insert((3, 3), 5);
+        state.square_variables.insert((3, 3), std::collections::HashMap::from([("n".to_string(), Value::Number(4.0))]));
+        grid.paste_cell(3, 3);
+        assert_eq!(grid.cells[3][3].program.programs.len(), 2);
+        assert!(!grid.program_executor.state.square_hit_counts.contains_key(&(3, 3)));
+        assert!(!grid.program_executor.state.square_variables.contains_key(&(3, 3)));
+
+        let pasted = &mut grid.cells[3][3].program.programs;
+        pasted[0].instructions.clear();
+        pasted[1].name = "renamed".to_string();
+        let original = &grid.cells[1][1].program.programs;
+        assert_eq!(original[0].instructions, instructions);
+        assert_eq!(original[1].name, "b");
+
+        // Later pastes come from the copy, not from the edited square
+        grid.paste_cell(5, 5);
+        assert_eq!(grid.cells[5][5].program.programs[1].name, "b");
+    }
+
+    #[test]
+    fn a_quarter_beat_per_cell_at_120_bpm_is_eight_cells_a_second() {
+        assert_eq!(beats_to_speed(0.25, 120.0), 8.0);
+        assert_eq!(beats_to_speed(1.0, 60.0), 1.0);
+    }
+
+    #[test]
+    fn bursts_stop_at_the_ball_limit_but_create_ball_does_not() {
+        let mut grid = grid();
+        for _ in 0..MAX_SPAWNED_BALLS - 3 {
+            grid.spawn_ball(1.0, 1.0, 1.0, Direction::Right);
+        }
+        grid.spawn_burst(5.0, 5.0, 8, 1.0);
+        assert_eq!(grid.balls.len(), MAX_SPAWNED_BALLS);
+
+        grid.spawn_ball(2.0, 2.0, 1.0, Direction::Left);
+        assert_eq!(grid.balls.len(), MAX_SPAWNED_BALLS + 1);
+    }
+
+    #[test]
+    fn delayed_actions_find_their_ball_after_earlier_balls_are_destroyed() {
+        let mut grid = grid();
+        grid.place_ball(1, 1);
+        grid.place_ball(5, 5);
+        let ball_id = grid.balls[1].id.clone();
+        grid.delayed_actions.push(DelayedActions {
+            remaining: 0.0,
+            ball_id,
+            square_x: 0,
+            square_y: 0,
+            actions: vec![ProgramAction::SetVolume(0.5)],
+        });
+        grid.balls.remove(0);
+
+        grid.update_balls(0.01);
+        assert_eq!(grid.balls[0].volume, 0.5);
+        assert!(grid.delayed_actions.is_empty());
+    }
+
+    #[test]
+    fn loaded_scene_squares_run_the_first_scene_function() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), "square (3, 4): def kick\npass\nend\ndef back\nset direction down\n").unwrap();
+        let mut grid = grid();
+        grid.load_scene(file.path().to_str().unwrap());
+
+        let program = &grid.cells[4][3].program;
+        let names: Vec<&str> = program.programs.iter().map(|program| program.name.as_str()).collect();
+        assert_eq!(names, vec!["kick", "back"]);
+        assert_eq!(program.active_program, Some(0));
+    }
+
+    #[test]
+    fn loaded_scene_squares_start_uncounted() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), "square (3, 4): def kick\npass\nend\n").unwrap();
+        let mut grid = grid();
+        let state = &mut grid.program_executor.state;
+        state.square_hit_counts.insert((3, 4), 5);
+        state.square_variables.insert((3, 4), std::collections::HashMap::from([("n".to_string(), Value::Number(4.0))]));
+        grid.load_scene(file.path().to_str().unwrap());
+
+        assert!(!grid.program_executor.state.square_hit_counts.contains_key(&(3, 4)));
+        assert!(!grid.program_executor.state.square_variables.contains_key(&(3, 4)));
+    }
+
+    #[test]
+    fn scene_autostart_does_not_change_the_session_setting() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), "autostart 2\nsquare (3, 4): def kick\npass\nend\n").unwrap();
+        let mut grid = grid();
+        grid.load_scene(file.path().to_str().unwrap());
+
+        assert_eq!(grid.auto_start_countdown, Some(2.0));
+        assert!(!grid.auto_start);
+        assert_eq!(grid.auto_start_delay, 0.0);
+    }
+}
@@ -0,0 +1,252 @@
+// Headless simulation runner: drives a SequencerGrid without a window or sound
+// device so ball/square behaviour can be scripted and checked step by step.
+// Only built for tests.
+
+use crate::audio_engine::AudioEngine;
+use crate::ball::{Ball, Direction};
+use crate::programmer::SimpleProgramParser;
+use crate::sequencer::{CollisionEvent, SequencerGrid, grid_height, grid_width};
+
+#[derive(Clone, Debug)]
+pub struct SimulationTrigger {
+    pub step: usize,
+    pub x: usize,
+    pub y: usize,
+    pub ball_index: usize,
+}
+
+pub struct SimulationResult {
+    pub triggers: Vec<SimulationTrigger>,
+    pub collisions: Vec<CollisionEvent>, // Most recent collisions, as kept by the grid
+    pub balls: Vec<Ball>,
+}
+
+pub struct Simulation {
+    pub grid: SequencerGrid,
+    steps_run: usize,
+}
+
+impl Simulation {
+    pub fn new() -> Self {
+        Self {
+            grid: SequencerGrid::new(AudioEngine::new_null()),
+            steps_run: 0,
+        }
+    }
+
+    pub fn place_square(&mut self, x: usize, y: usize) -> &mut Self {
+        self.grid.place_square(x, y);
+        self
+    }
+
+    // Place a ball and return its index into `grid.balls`
+    pub fn place_ball(&mut self, x: usize, y: usize, direction: Direction, speed: f32) -> Result<usize, String> {
+        if x >= grid_width() || y >= grid_height() {
+            return Err(format!("Ball position ({}, {}) is outside the grid", x, y));
+        }
+        self.grid.place_ball(x, y);
+        let index = self.grid.balls.len() - 1;
+        self.grid.set_ball_direction(index, direction);
+        self.grid.set_ball_speed(index, speed);
+        Ok(index)
+    }
+
+    // Parse `source` and attach every program in it to the square at (x, y),
+    // making the first one active. Places the square if the cell is empty.
+    pub fn program_square(&mut self, x: usize, y: usize, source: &str) -> Result<(), String> {
+        if x >= grid_width() || y >= grid_height() {
+            return Err(format!("Square position ({}, {}) is outside the grid", x, y));
+        }
+        let programs = SimpleProgramParser::new().parse_multiple_programs(source)?;
+        if programs.is_empty() {
+            return Err("No programs found".to_string());
+        }
+        if !self.grid.cells[y][x].is_square() {
+            self.grid.place_square(x, y);
+        }
+        self.grid.cells[y][x].program.load_programs(programs);
+        Ok(())
+    }
+
+    // Same as pressing P: saves the layout as the reset point and starts all balls
+    pub fn start(&mut self) -> &mut Self {
+        if !self.grid.balls.iter().any(|ball| ball.active) {
+            self.grid.toggle_all_balls();
+        }
+        self
+    }
+
+    // Advance `steps` updates of `dt` seconds each. Collision cooldowns run on the
+    // simulated clock, so the same steps always give the same triggers.
+    pub fn run(&mut self, steps: usize, dt: f32) -> SimulationResult {
+        let mut triggers = Vec::new();
+        for _ in 0..steps {
+            for (x, y, ball_index) in self.grid.update_balls(dt) {
+                triggers.push(SimulationTrigger { step: self.steps_run, x, y, ball_index });
+            }
+            self.grid.update(dt);
+            self.steps_run += 1;
+        }
+
+        SimulationResult {
+            triggers,
+            collisions: self.grid.collision_history.iter().cloned().collect(),
+            balls: self.grid.balls.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sequencer::beats_to_speed;
+
+    // Ball at (1, 2) heading right into a square at (4, 2)
+    fn rightward_hit(source: &str) -> Simulation {
+        let mut sim = Simulation::new();
+        sim.program_square(4, 2, source).unwrap();
+        sim.place_ball(1, 2, Direction::Right, 4.0).unwrap();
+        sim.start();
+        sim
+    }
+
+    #[test]
+    fn loaded_program_replaces_the_default_bounce() {
+        let mut sim = rightward_hit("def slow\nset speed 2\nreflect");
+        let square = &sim.grid.cells[2][4].program;
+        assert_eq!(square.programs.len(), 1);
+        assert_eq!(square.active_program, Some(0));
+        assert_eq!(square.programs[0].name, "slow");
+
+        let result = sim.run(60, 1.0 / 60.0);
+        assert!(result.triggers.iter().any(|t| (t.x, t.y) == (4, 2)));
+        assert!(result.collisions.iter().any(|c| (c.square_x, c.square_y) == (4, 2)));
+        assert_eq!(result.balls[0].speed, 2.0);
+    }
+
+    #[test]
+    fn ball_behavior_runs_on_a_square_with_only_the_default_bounce() {
+        let mut sim = Simulation::new();
+        sim.place_square(4, 2);
+        let index = sim.place_ball(1, 2, Direction::Right, 4.0).unwrap();
+        let behavior = SimpleProgramParser::new().parse_program("def slow\nset speed 2\nreflect").unwrap();
+        sim.grid.balls[index].behavior = Some(behavior);
+        sim.start();
+
+        let result = sim.run(60, 1.0 / 60.0);
+        assert!(result.triggers.iter().any(|t| (t.x, t.y) == (4, 2)));
+        assert_eq!(result.balls[0].speed, 2.0);
+    }
+
+    #[test]
+    fn wait_inside_a_called_function_holds_back_the_rest() {
+        let mut sim = rightward_hit("def main\nreflect\nreturn later\ndef later\nwait 100\nset speed 2\nend");
+        let mut steps = 0;
+        while sim.run(1, 1.0 / 60.0).triggers.is_empty() {
+            steps += 1;
+            assert!(steps < 120, "ball never reached the square");
+        }
+        assert_eq!(sim.grid.balls[0].speed, 4.0);
+        assert_eq!(sim.grid.delayed_actions.len(), 1);
+
+        sim.run(3, 1.0 / 60.0);
+        assert_eq!(sim.grid.balls[0].speed, 4.0);
+        sim.run(4, 1.0 / 60.0);
+        assert_eq!(sim.grid.balls[0].speed, 2.0);
+        assert!(sim.grid.delayed_actions.is_empty());
+    }
+
+    // A fast ball heading down-right from (1, 1) passes exactly through the corner at (2, 2)
+    fn diagonal_past(squares: &[(usize, usize)]) -> Vec<(usize, usize)> {
+        let mut sim = Simulation::new();
+        for &(x, y) in squares {
+            sim.place_square(x, y);
+        }
+        sim.place_ball(1, 1, Direction::DownRight, 40.0).unwrap();
+        sim.start();
+        sim.run(6, 1.0 / 60.0).triggers.iter().map(|t| (t.x, t.y)).collect()
+    }
+
+    #[test]
+    fn fast_diagonal_slips_past_a_lone_square_at_a_corner() {
+        assert!(diagonal_past(&[(2, 1)]).is_empty());
+        assert!(diagonal_past(&[(1, 2)]).is_empty());
+    }
+
+    #[test]
+    fn fast_diagonal_stops_between_squares_meeting_at_a_corner() {
+        let triggers = diagonal_past(&[(2, 1), (1, 2)]);
+        assert!(matches!(triggers.first(), Some((2, 1)) | Some((1, 2))), "got {:?}", triggers);
+    }
+
+    #[test]
+    fn set_filter_belongs_to_the_ball_not_the_hit_channel() {
+        let mut sim = rightward_hit("def dark\nset filter 800\nreflect");
+        sim.place_ball(1, 4, Direction::Up, 1.0).unwrap();
+        sim.run(60, 1.0 / 60.0);
+        assert_eq!(sim.grid.balls[0].filter_cutoff, 800.0);
+        assert_eq!(sim.grid.balls[1].filter_cutoff, 0.0);
+    }
+
+    #[test]
+    fn beat_speed_follows_tempo_changes() {
+        let mut sim = rightward_hit("def sixteenths\nset speed beats 1/4\nreflect");
+        sim.run(60, 1.0 / 60.0);
+        assert_eq!(sim.grid.bpm, 120.0);
+        assert_eq!(sim.grid.balls[0].speed, beats_to_speed(0.25, 120.0));
+
+        sim.grid.set_bpm(90.0);
+        assert!((sim.grid.balls[0].speed - beats_to_speed(0.25, 90.0)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn returned_function_sees_the_square_variables() {
+        let mut sim = rightward_hit("def main\nvar n = 3\nreturn slow\ndef slow\nset speed n\nreflect\nend");
+        sim.run(60, 1.0 / 60.0);
+        assert_eq!(sim.grid.balls[0].speed, 3.0);
+    }
+
+    // A red ball hits a square running `source` while a blue and a second red ball sit
+    // clear of everything; returns the colors of the balls left afterwards
+    fn destroy_on_hit(source: &str) -> Vec<String> {
+        let mut sim = rightward_hit(source);
+        for (y, color) in [(6, "Blue"), (8, "Red")] {
+            let index = sim.place_ball(7, y, Direction::Up, 0.1).unwrap();
+            sim.grid.set_ball_color(index, color.to_string());
+        }
+        sim.grid.set_ball_color(0, "Red".to_string());
+        sim.run(60, 1.0 / 60.0);
+        sim.grid.balls.iter().map(|ball| ball.color.clone()).collect()
+    }
+
+    #[test]
+    fn destroy_all_balls_clears_every_ball() {
+        assert!(destroy_on_hit("def wipe\ndestroy ball(all)").is_empty());
+    }
+
+    #[test]
+    fn destroy_balls_by_color_leaves_other_colors() {
+        assert_eq!(destroy_on_hit("def wipe\ndestroy ball(c_red)"), vec!["Blue"]);
+    }
+
+    #[test]
+    fn hitting_ball_can_destroy_itself() {
+        assert_eq!(destroy_on_hit("def gone\ndestroy ball(self)"), vec!["Blue", "Red"]);
+    }
+
+    #[test]
+    fn identical_runs_give_identical_triggers() {
+        let run = || {
+            let mut sim = rightward_hit("def slow\nset speed 2\nreflect");
+            sim.place_square(0, 2);
+            sim.run(240, 1.0 / 60.0)
+                .triggers
+                .iter()
+                .map(|t| (t.step, t.x, t.y, t.ball_index))
+                .collect::<Vec<_>>()
+        };
+        let first = run();
+        assert!(first.len() > 2, "expected the ball to hit repeatedly, got {:?}", first);
+        assert_eq!(first, run());
+    }
+}
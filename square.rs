@@ -1,10 +1,12 @@
-#[derive(Clone, Copy, PartialEq, Debug)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
 pub enum CellContent {
     Empty,
     Square,
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub enum Value {
     Number(f32),
     Direction(crate::ball::Direction),
@@ -13,7 +15,20 @@ pub enum Value {
     Coordinate(f32, f32), // Add coordinate support
 }
 
-#[derive(Clone, PartialEq, Debug)]
+impl Value {
+    // Text shown by print and log
+    pub fn display_text(&self) -> String {
+        match self {
+            Value::Number(n) => n.to_string(),
+            Value::Boolean(b) => b.to_string(),
+            Value::Direction(d) => format!("{:?}", d),
+            Value::String(s) => s.clone(),
+            Value::Coordinate(x, y) => format!("({}, {})", x, y),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub enum Expression {
     Literal(Value),
     Variable(String),
@@ -21,16 +36,53 @@ pub enum Expression {
     BinaryOp { left: Box<Expression>, op: BinaryOperator, right: Box<Expression> },
     BallProperty(BallProperty),
     Random { min: f32, max: f32 },
+    Call { name: String, args: Vec<Expression> }, // Built-in function, see EXPRESSION_FUNCTIONS
+    Time, // Seconds since the take started
+}
+
+// Functions callable in expressions, with how many arguments each takes
+pub const EXPRESSION_FUNCTIONS: &[(&str, usize)] = &[
+    ("min", 2), ("max", 2), ("clamp", 3),
+    ("abs", 1), ("floor", 1), ("ceil", 1), ("round", 1), ("sqrt", 1),
+    ("sin", 1), ("cos", 1),
+];
+
+// Apply a built-in function to evaluated arguments; anything but numbers gives 0
+pub fn call_function(name: &str, args: &[Value]) -> Value {
+    let numbers: Option<Vec<f32>> = args.iter()
+        .map(|arg| if let Value::Number(n) = arg { Some(*n) } else { None })
+        .collect();
+    let Some(numbers) = numbers else {
+        return Value::Number(0.0);
+    };
+    Value::Number(match (name, numbers.as_slice()) {
+        ("min", [a, b]) => a.min(*b),
+        ("max", [a, b]) => a.max(*b),
+        ("clamp", [value, low, high]) => value.max(*low).min(*high), // f32::clamp panics when low > high
+        ("abs", [value]) => value.abs(),
+        ("floor", [value]) => value.floor(),
+        ("ceil", [value]) => value.ceil(),
+        ("round", [value]) => value.round(),
+        // A NaN would carry on into pitch and speed, so a negative root is 0
+        ("sqrt", [value]) if *value < 0.0 => {
+            log::warn!("sqrt({}) of a negative number, using 0", value);
+            0.0
+        }
+        ("sqrt", [value]) => value.sqrt(),
+        ("sin", [radians]) => radians.sin(),
+        ("cos", [radians]) => radians.cos(),
+        _ => 0.0,
+    })
 }
 
-#[derive(Clone, Copy, PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
 pub enum BinaryOperator {
     Add, Sub, Mul, Div, Mod,
     Equal, NotEqual, Less, Greater, LessEqual, GreaterEqual,
     And, Or,
 }
 
-#[derive(Clone, Copy, PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
 pub enum BallProperty {
     Speed,
     Direction,
@@ -41,21 +93,34 @@ pub enum BallProperty {
     Volume,
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub enum DestroyTarget {
     Coordinates { x: Expression, y: Expression },
     BallReference(String), // "self", "last.c_red.self", etc.
+    All,
+    Color(String), // Every ball or square of this color ("c_red", "#ff8800")
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub enum Instruction {
     // Ball manipulation
     SetSpeed(Expression),
+    SetSpeedGlide { target: Expression, duration_ms: Expression }, // Ramp speed to target over duration_ms
+    SetSpeedBeats(Expression), // Beats per cell at the grid tempo ("set speed beats 1/4")
     SetDirection(Expression),
     SetPitch(Expression),
+    SetPitchStep(Expression), // Semitones added to the ball's pitch on each bounce
+    SetHumanize(Expression), // Amount of random pitch/timing deviation (0.0-1.0)
+    SetEnvelope { attack_ms: Expression, release_ms: Expression }, // Fade in/out on every sample the ball plays
+    SetPan(Expression), // Stereo position of the ball's hits (-1.0 = left, 1.0 = right)
+    SetFilter(Expression), // Lowpass cutoff in Hz for the ball's own hits; 0 = off
+    SetBpm { value: Expression, relative: bool }, // Global tempo; relative adds to the current BPM
     SetVolume(Expression),
     SetColor(Expression),
+    SetSquareColor(Expression), // Fill color of this square (name or #rrggbb)
+    SetSample { library: String, name: String }, // Swap the ball's sample for one from a sample library
     Bounce,
+    Reflect, // Bounce like a mirror: flip only the direction component across the face that was hit
     Stop,
     
     // Variables
@@ -64,11 +129,11 @@ pub enum Instruction {
     
     // Control flow
     If { condition: Expression, then_block: Vec<Instruction>, else_block: Option<Vec<Instruction>> },
-    Loop { count: Expression, body: Vec<Instruction> },
+    Loop { count: Expression, body: Vec<Instruction> }, // 'loop N' ... 'end', at most MAX_LOOP_ITERATIONS times
     RepeatAnd { count: Expression, body: Vec<Instruction> }, // Repeat instructions N times with 'and N'
     RepeatThen { count: Expression, body: Vec<Instruction> }, // Repeat instructions N times with 'then N'
     ExecuteProgram(Program),
-    ExecuteLibraryFunction { library_function: String },
+    ExecuteLibraryFunction { library_function: String, args: Vec<Expression> },
     ContinueToNext, // Continue to next function in sequence
     Return(Option<String>), // None = simple return, Some(name) = call function and return
     End, // Natural end of block
@@ -81,7 +146,10 @@ pub enum Instruction {
     // Grid interaction
     SpawnBall { x: Expression, y: Expression, speed: Expression, direction: Expression },
     CreateBall { x: Expression, y: Expression, speed: Expression, direction: Expression },
+    CreateBallRandom { speed: Expression, direction: Expression }, // Cell is picked at runtime
+    SpawnBurst { count: Expression }, // Fan of balls leaving this square at the hitting ball's speed
     CreateSquare { x: Expression, y: Expression },
+    CreateWall { start: (Expression, Expression), end: (Expression, Expression), filled: bool }, // Line or rectangle of squares
     CreateSquareWithProgram { x: Expression, y: Expression, program: Program },
     CreateBallFromSample { x: Expression, y: Expression, library_name: String, sample_name: String },
     CreateSquareFromSample { x: Expression, y: Expression, library_name: String, sample_name: String },
@@ -90,11 +158,28 @@ pub enum Instruction {
     DestroyBall { target: DestroyTarget },
     DestroySquare { target: DestroyTarget },
     
+    // Hit counting
+    ResetHits, // Reset this square's hit counters
+    
+    // Motion control
+    Pass, // Don't bounce: the ball continues straight through this square
+    Teleport { x: Expression, y: Expression }, // Move the ball to the center of another cell, keeping its heading
+    Wait(Expression), // Hold back the actions after this by N milliseconds; the bounce still happens now
+    SetSolid(bool), // Whether this square bounces balls (false = pass-through) from the next hit on
+    
+    // Program switching
+    SetActiveProgram(Expression), // Switch this square to another stored program (index or name)
+    
+    // Controlling other squares
+    ResetSquare { x: Expression, y: Expression }, // Reset another square's hit counters
+    ArmSquare { x: Expression, y: Expression, program: Expression }, // Switch another square's active program
+    
     // Debugging
     Print(Expression),
+    Log(Vec<Expression>), // Console message: the values are concatenated, e.g. log "speed is " speed
 }
 
-#[derive(Clone, Copy, PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
 pub enum SquareEffect {
     None,               // No effect, ball passes through
     Bounce,             // Reverse ball direction (default)
@@ -107,30 +192,146 @@ pub enum SquareEffect {
 
 use std::collections::{HashMap, VecDeque};
 use crate::ball::Ball;
+use crate::sequencer::{grid_height, grid_width};
 
-#[derive(Clone, PartialEq, Debug)]
+pub const MAX_LOOP_ITERATIONS: usize = 1000; // A mistyped loop count can't stall the ball update
+
+// Cells of the rectangle spanned by two corners (a line when they share a row or
+// column): just the outline unless `filled`, clipped to the grid
+pub fn wall_cells(start: (i32, i32), end: (i32, i32), filled: bool) -> Vec<(i32, i32)> {
+    let (min_x, max_x) = (start.0.min(end.0), start.0.max(end.0));
+    let (min_y, max_y) = (start.1.min(end.1), start.1.max(end.1));
+    let mut cells = Vec::new();
+    for y in min_y.max(0)..=max_y.min(grid_height() as i32 - 1) {
+        for x in min_x.max(0)..=max_x.min(grid_width() as i32 - 1) {
+            if filled || x == min_x || x == max_x || y == min_y || y == max_y {
+                cells.push((x, y));
+            }
+        }
+    }
+    cells
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Program {
     pub instructions: Vec<Instruction>,
     pub name: String,
     pub source_text: Option<Vec<String>>, // Preserve original source text for editing
+    pub parameters: Vec<String>, // Named parameters bound from call-site arguments, e.g. def spawner(x, y)
+}
+
+impl Program {
+    // The "Default" bounce every new square starts with
+    pub fn is_builtin_default(&self) -> bool {
+        self.name == "Default" && matches!(self.instructions.as_slice(), [Instruction::Bounce])
+    }
+}
+
+impl Instruction {
+    // Call `f` with every variable name read by this instruction, including nested blocks
+    pub fn for_each_variable<'a>(&'a self, f: &mut impl FnMut(&'a str)) {
+        let mut expression = |expression: &'a Expression| expression.for_each_variable(f);
+        match self {
+            Instruction::SetSpeed(value) | Instruction::SetSpeedBeats(value) | Instruction::SetDirection(value)
+            | Instruction::SetPitch(value) | Instruction::SetPitchStep(value) | Instruction::SetHumanize(value)
+            | Instruction::SetPan(value) | Instruction::SetFilter(value) | Instruction::SetBpm { value, .. }
+            | Instruction::SetVolume(value) | Instruction::SetColor(value) | Instruction::SetSquareColor(value)
+            | Instruction::SetVariable { value, .. } | Instruction::SetGlobalVariable { value, .. }
+            | Instruction::PlaySample(value) | Instruction::SetReverse { speed: value, .. }
+            | Instruction::SpawnBurst { count: value } | Instruction::Wait(value)
+            | Instruction::SetActiveProgram(value) | Instruction::Print(value) => expression(value),
+            Instruction::SetSpeedGlide { target: a, duration_ms: b } | Instruction::SetEnvelope { attack_ms: a, release_ms: b }
+            | Instruction::CreateBallRandom { speed: a, direction: b } | Instruction::CreateSquare { x: a, y: b }
+            | Instruction::CreateBallFromSample { x: a, y: b, .. } | Instruction::CreateSquareFromSample { x: a, y: b, .. }
+            | Instruction::CreateBallWithLibrary { x: a, y: b, .. } | Instruction::CreateSquareWithLibrary { x: a, y: b, .. }
+            | Instruction::Teleport { x: a, y: b } | Instruction::ResetSquare { x: a, y: b } => {
+                expression(a);
+                expression(b);
+            }
+            Instruction::SpawnBall { x, y, speed, direction } | Instruction::CreateBall { x, y, speed, direction } => {
+                for value in [x, y, speed, direction] {
+                    expression(value);
+                }
+            }
+            Instruction::CreateWall { start, end, .. } => {
+                for value in [&start.0, &start.1, &end.0, &end.1] {
+                    expression(value);
+                }
+            }
+            Instruction::ArmSquare { x, y, program } => {
+                for value in [x, y, program] {
+                    expression(value);
+                }
+            }
+            Instruction::ExecuteLibraryFunction { args: values, .. } | Instruction::Log(values) => {
+                values.iter().for_each(expression);
+            }
+            Instruction::If { condition, then_block, else_block } => {
+                expression(condition);
+                for instruction in then_block.iter().chain(else_block.iter().flatten()) {
+                    instruction.for_each_variable(f);
+                }
+            }
+            Instruction::Loop { count, body } | Instruction::RepeatAnd { count, body } | Instruction::RepeatThen { count, body } => {
+                expression(count);
+                for instruction in body {
+                    instruction.for_each_variable(f);
+                }
+            }
+            Instruction::CreateSquareWithProgram { x, y, program } => {
+                expression(x);
+                expression(y);
+                for instruction in &program.instructions {
+                    instruction.for_each_variable(f);
+                }
+            }
+            Instruction::ExecuteProgram(program) => {
+                for instruction in &program.instructions {
+                    instruction.for_each_variable(f);
+                }
+            }
+            Instruction::SetSample { .. } | Instruction::Bounce | Instruction::Reflect | Instruction::Stop
+            | Instruction::ContinueToNext | Instruction::Return(_) | Instruction::End | Instruction::SetSliceArray { .. }
+            | Instruction::DestroyBall { .. } | Instruction::DestroySquare { .. } | Instruction::ResetHits
+            | Instruction::Pass | Instruction::SetSolid(_) => {}
+        }
+    }
+}
+
+impl Expression {
+    // Call `f` with every variable name this expression reads
+    pub fn for_each_variable<'a>(&'a self, f: &mut impl FnMut(&'a str)) {
+        match self {
+            Expression::Variable(name) => f(name),
+            Expression::BinaryOp { left, right, .. } => {
+                left.for_each_variable(f);
+                right.for_each_variable(f);
+            }
+            Expression::Call { args, .. } => args.iter().for_each(|arg| arg.for_each_variable(f)),
+            Expression::Literal(_) | Expression::GlobalVariable(_) | Expression::BallProperty(_)
+            | Expression::Random { .. } | Expression::Time => {}
+        }
+    }
 }
 
 // Library system for reusable components
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct FunctionLibrary {
     pub name: String,
     pub functions: HashMap<String, Program>,
     pub description: String,
+    pub enabled: bool, // Disabled libraries stay loaded but are left out of listings and searches
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct SampleLibrary {
     pub name: String,
     pub samples: HashMap<String, SampleTemplate>,
     pub description: String,
+    pub enabled: bool,
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct SampleTemplate {
     pub name: String,
     pub default_speed: f32,
@@ -139,7 +340,7 @@ pub struct SampleTemplate {
     pub behavior_program: Option<String>, // Reference to function in library
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct LibraryManager {
     pub function_libraries: HashMap<String, FunctionLibrary>,
     pub sample_libraries: HashMap<String, SampleLibrary>,
@@ -167,6 +368,29 @@ impl LibraryManager {
         self.sample_libraries.insert(library.name.clone(), library);
     }
     
+    // Enable or disable every library (function and sample) with this name
+    pub fn set_library_enabled(&mut self, library_name: &str, enabled: bool) -> Result<(), String> {
+        let mut found = false;
+        if let Some(library) = self.function_libraries.get_mut(library_name) {
+            library.enabled = enabled;
+            found = true;
+        }
+        if let Some(library) = self.sample_libraries.get_mut(library_name) {
+            library.enabled = enabled;
+            found = true;
+        }
+        if found {
+            Ok(())
+        } else {
+            Err(format!("Library '{}' not found", library_name))
+        }
+    }
+    
+    pub fn is_library_enabled(&self, library_name: &str) -> bool {
+        self.function_libraries.get(library_name).map_or(true, |library| library.enabled)
+            && self.sample_libraries.get(library_name).map_or(true, |library| library.enabled)
+    }
+    
     pub fn get_function(&self, library_name: &str, function_name: &str) -> Option<&Program> {
         self.function_libraries.get(library_name)?
             .functions.get(function_name)
@@ -201,6 +425,7 @@ impl LibraryManager {
             name: "default".to_string(),
             functions: HashMap::new(),
             description: format!("Library loaded from {}", file_path),
+            enabled: true,
         };
         
         while i < lines.len() {
@@ -218,6 +443,7 @@ impl LibraryManager {
                     name: library_name,
                     functions: HashMap::new(),
                     description: format!("Library loaded from {}", file_path),
+                    enabled: true,
                 };
                 i += 1;
             } else if line.starts_with("def ") {
@@ -251,6 +477,7 @@ impl LibraryManager {
             name: function_name,
             instructions,
             source_text: None,
+            parameters: Vec::new(),
         }, next_i))
     }
     
@@ -274,6 +501,8 @@ impl LibraryManager {
                 instructions.push(self.parse_create_square_instruction(line)?);
             } else if line == "bounce" {
                 instructions.push(Instruction::Bounce);
+            } else if line == "reflect" {
+                instructions.push(Instruction::Reflect);
             } else if line.starts_with("set speed") {
                 instructions.push(self.parse_set_speed_instruction(line)?);
             }
@@ -370,6 +599,7 @@ impl LibraryManager {
             name: "lib".to_string(),
             functions: HashMap::new(),
             description: "Default function library with common behaviors".to_string(),
+            enabled: true,
         };
         
         // Add common function templates
@@ -384,12 +614,14 @@ impl LibraryManager {
                 }
             ],
             source_text: None,
+            parameters: Vec::new(),
         });
         
         default_functions.functions.insert("bounce".to_string(), Program {
             name: "bounce".to_string(),
             instructions: vec![Instruction::Bounce],
             source_text: None,
+            parameters: Vec::new(),
         });
         
         default_functions.functions.insert("speed_boost".to_string(), Program {
@@ -402,6 +634,7 @@ impl LibraryManager {
                 }),
             ],
             source_text: None,
+            parameters: Vec::new(),
         });
         
         default_functions.functions.insert("direction_cycle".to_string(), Program {
@@ -438,6 +671,7 @@ impl LibraryManager {
                 },
             ],
             source_text: None,
+            parameters: Vec::new(),
         });
         
         self.add_function_library(default_functions);
@@ -447,6 +681,7 @@ impl LibraryManager {
             name: "default".to_string(),
             samples: HashMap::new(),
             description: "Default sample library with common ball types".to_string(),
+            enabled: true,
         };
         
         default_samples.samples.insert("red_bouncer".to_string(), SampleTemplate {
@@ -490,15 +725,16 @@ pub struct ExecutionContext {
     pub ball_volume: f32,
     pub square_x: usize,
     pub square_y: usize,
+    pub time: f32, // Seconds since the take started, for `time`
 }
 
-#[derive(Clone, Copy, PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
 pub struct ProgramStep {
     pub trigger_hits: u32,     // Number of hits required to trigger this step
     pub effect: SquareEffect,  // Effect to apply when triggered
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SquareProgram {
     pub steps: Vec<ProgramStep>, // Legacy: Sequence of programmed effects
     pub programs: Vec<Program>,  // New: Full programs with instructions
@@ -516,6 +752,7 @@ impl Default for SquareProgram {
                     name: "Default".to_string(),
                     instructions: vec![Instruction::Bounce],
                     source_text: None,
+                    parameters: Vec::new(),
                 }
             ],
             hit_count: 0,
@@ -580,18 +817,21 @@ impl SquareProgram {
         self.active_program = index;
     }
     
+    // Resolve a program reference (index or name) to an index into `programs`
+    pub fn find_program(&self, reference: &Value) -> Option<usize> {
+        match reference {
+            Value::Number(index) if *index >= 0.0 && (*index as usize) < self.programs.len() => Some(*index as usize),
+            Value::String(name) => self.programs.iter().position(|program| program.name == *name),
+            _ => None,
+        }
+    }
+    
     pub fn replace_or_add_program(&mut self, program: Program) -> usize {
         // If there's an active program and it's the default, replace it
         if let Some(active_index) = self.active_program {
-            if let Some(existing_program) = self.programs.get(active_index) {
-                let is_default_program = existing_program.name == "Default" && 
-                    existing_program.instructions.len() == 1 &&
-                    matches!(existing_program.instructions[0], crate::square::Instruction::Bounce);
-                
-                if is_default_program {
-                    self.programs[active_index] = program;
-                    return active_index;
-                }
+            if self.programs.get(active_index).is_some_and(Program::is_builtin_default) {
+                self.programs[active_index] = program;
+                return active_index;
             }
         }
         
@@ -600,6 +840,17 @@ impl SquareProgram {
         self.programs.len() - 1
     }
     
+    // Attach loaded programs (from a file, scene or pattern) in place of the built-in
+    // default and make the first of them active
+    pub fn load_programs(&mut self, programs: Vec<Program>) {
+        if programs.is_empty() {
+            return;
+        }
+        self.programs.retain(|program| !program.is_builtin_default());
+        self.active_program = Some(self.programs.len());
+        self.programs.extend(programs);
+    }
+    
     pub fn execute_program(&self, context: &mut ExecutionContext) -> Vec<ProgramAction> {
         if let Some(program_index) = self.active_program {
             if let Some(program) = self.programs.get(program_index) {
@@ -635,6 +886,17 @@ impl SquareProgram {
                         actions.push(ProgramAction::SetSpeed(speed));
                     }
                 }
+                Instruction::SetSpeedGlide { target, duration_ms } => {
+                    if let (Value::Number(target), Value::Number(duration_ms)) =
+                        (self.evaluate_expression(target, context), self.evaluate_expression(duration_ms, context)) {
+                        actions.push(ProgramAction::SetSpeedGlide { target, duration_ms });
+                    }
+                }
+                Instruction::SetSpeedBeats(expr) => {
+                    if let Value::Number(beats) = self.evaluate_expression(expr, context) {
+                        actions.push(ProgramAction::SetSpeedBeats(beats));
+                    }
+                }
                 Instruction::SetDirection(expr) => {
                     if let Value::Direction(dir) = self.evaluate_expression(expr, context) {
                         actions.push(ProgramAction::SetDirection(dir));
@@ -645,6 +907,36 @@ impl SquareProgram {
                         actions.push(ProgramAction::SetPitch(pitch));
                     }
                 }
+                Instruction::SetPitchStep(expr) => {
+                    if let Value::Number(semitones) = self.evaluate_expression(expr, context) {
+                        actions.push(ProgramAction::SetPitchStep(semitones));
+                    }
+                }
+                Instruction::SetHumanize(expr) => {
+                    if let Value::Number(amount) = self.evaluate_expression(expr, context) {
+                        actions.push(ProgramAction::SetHumanize(amount));
+                    }
+                }
+                Instruction::SetEnvelope { attack_ms, release_ms } => {
+                    if let (Value::Number(attack_ms), Value::Number(release_ms)) = (self.evaluate_expression(attack_ms, context), self.evaluate_expression(release_ms, context)) {
+                        actions.push(ProgramAction::SetEnvelope { attack_ms, release_ms });
+                    }
+                }
+                Instruction::SetPan(expr) => {
+                    if let Value::Number(pan) = self.evaluate_expression(expr, context) {
+                        actions.push(ProgramAction::SetPan(pan));
+                    }
+                }
+                Instruction::SetFilter(expr) => {
+                    if let Value::Number(cutoff) = self.evaluate_expression(expr, context) {
+                        actions.push(ProgramAction::SetFilter(cutoff));
+                    }
+                }
+                Instruction::SetBpm { value, relative } => {
+                    if let Value::Number(bpm) = self.evaluate_expression(value, context) {
+                        actions.push(ProgramAction::SetBpm { value: bpm, relative: *relative });
+                    }
+                }
                 Instruction::SetVolume(expr) => {
                     if let Value::Number(volume) = self.evaluate_expression(expr, context) {
                         actions.push(ProgramAction::SetVolume(volume));
@@ -655,9 +947,23 @@ impl SquareProgram {
                         actions.push(ProgramAction::SetColor(color));
                     }
                 }
+                Instruction::SetSquareColor(expr) => {
+                    if let Value::String(color) = self.evaluate_expression(expr, context) {
+                        actions.push(ProgramAction::SetSquareColor { x: context.square_x, y: context.square_y, color });
+                    }
+                }
+                Instruction::SetSample { library, name } => {
+                    actions.push(ProgramAction::SetSample { library: library.clone(), name: name.clone() });
+                }
                 Instruction::Bounce => {
                     actions.push(ProgramAction::Bounce);
                 }
+                Instruction::Reflect => {
+                    actions.push(ProgramAction::Reflect);
+                }
+                Instruction::Pass => {
+                    actions.push(ProgramAction::Pass);
+                }
                 Instruction::Stop => {
                     actions.push(ProgramAction::Stop);
                 }
@@ -686,7 +992,7 @@ impl SquareProgram {
                 }
                 Instruction::Loop { count, body } => {
                     if let Value::Number(n) = self.evaluate_expression(count, context) {
-                        for _ in 0..(n as i32).max(0) {
+                        for _ in 0..(n.max(0.0) as usize).min(MAX_LOOP_ITERATIONS) {
                             actions.extend(self.execute_instructions(body, context));
                         }
                     }
@@ -740,6 +1046,24 @@ impl SquareProgram {
                         actions.push(ProgramAction::CreateBall { x, y, speed: s, direction: d });
                     }
                 }
+                Instruction::CreateBallRandom { speed, direction } => {
+                    let speed_val = self.evaluate_expression(speed, context);
+                    let dir_val = self.evaluate_expression(direction, context);
+                    
+                    if let (Value::Number(s), Value::Direction(d)) = (speed_val, dir_val) {
+                        actions.push(ProgramAction::CreateBallRandom { speed: s, direction: d });
+                    }
+                }
+                Instruction::SpawnBurst { count } => {
+                    if let Value::Number(n) = self.evaluate_expression(count, context) {
+                        actions.push(ProgramAction::SpawnBurst {
+                            x: context.square_x as f32,
+                            y: context.square_y as f32,
+                            count: n.max(0.0) as usize,
+                            speed: context.ball_speed,
+                        });
+                    }
+                }
                 Instruction::CreateSquare { x, y } => {
                     let x_val = self.evaluate_expression(x, context);
                     let y_val = self.evaluate_expression(y, context);
@@ -748,6 +1072,17 @@ impl SquareProgram {
                         actions.push(ProgramAction::CreateSquare { x: x as i32, y: y as i32 });
                     }
                 }
+                Instruction::CreateWall { start, end, filled } => {
+                    let corners = (
+                        self.evaluate_expression(&start.0, context), self.evaluate_expression(&start.1, context),
+                        self.evaluate_expression(&end.0, context), self.evaluate_expression(&end.1, context),
+                    );
+                    if let (Value::Number(x1), Value::Number(y1), Value::Number(x2), Value::Number(y2)) = corners {
+                        for (x, y) in wall_cells((x1 as i32, y1 as i32), (x2 as i32, y2 as i32), *filled) {
+                            actions.push(ProgramAction::CreateSquare { x, y });
+                        }
+                    }
+                }
                 Instruction::CreateSquareWithProgram { x, y, program } => {
                     let x_val = self.evaluate_expression(x, context);
                     let y_val = self.evaluate_expression(y, context);
@@ -820,6 +1155,8 @@ impl SquareProgram {
                         DestroyTarget::BallReference(ball_ref) => {
                             actions.push(ProgramAction::DestroyBall { x: 0.0, y: 0.0, ball_reference: Some(ball_ref.clone()) });
                         }
+                        DestroyTarget::All => actions.push(ProgramAction::DestroyBalls { color: None }),
+                        DestroyTarget::Color(color) => actions.push(ProgramAction::DestroyBalls { color: Some(color.clone()) }),
                     }
                 }
                 Instruction::DestroySquare { target } => {
@@ -834,31 +1171,79 @@ impl SquareProgram {
                         DestroyTarget::BallReference(ball_ref) => {
                             actions.push(ProgramAction::DestroySquare { x: 0.0, y: 0.0, ball_reference: Some(ball_ref.clone()) });
                         }
+                        DestroyTarget::All => actions.push(ProgramAction::DestroySquares { color: None }),
+                        DestroyTarget::Color(color) => actions.push(ProgramAction::DestroySquares { color: Some(color.clone()) }),
                     }
                 }
+                Instruction::Log(parts) => {
+                    let text: String = parts.iter().map(|part| self.evaluate_expression(part, context).display_text()).collect();
+                    actions.push(ProgramAction::Log(text));
+                }
                 Instruction::Print(expr) => {
-                    println!("DEBUG SQUARE: Print instruction with expression: {:?}", expr);
+                    log::debug!("Square: Print instruction with expression: {:?}", expr);
                     let val = self.evaluate_expression(expr, context);
-                    println!("DEBUG SQUARE: Evaluated expression to value: {:?}", val);
-                    let display_text = match val {
-                        Value::Number(n) => n.to_string(),
-                        Value::Boolean(b) => b.to_string(),
-                        Value::Direction(d) => format!("{:?}", d),
-                        Value::String(s) => s,
-                        Value::Coordinate(x, y) => format!("({}, {})", x, y),
-                    };
-                    println!("DEBUG SQUARE: Final display text: {}", display_text);
+                    log::debug!("Square: Evaluated expression to value: {:?}", val);
+                    let display_text = val.display_text();
+                    log::debug!("Square: Final display text: {}", display_text);
                     actions.push(ProgramAction::Print(display_text));
                 }
+                Instruction::ResetHits => {
+                    actions.push(ProgramAction::ResetHits {
+                        x: context.square_x,
+                        y: context.square_y,
+                    });
+                }
+                Instruction::SetSolid(solid) => {
+                    actions.push(ProgramAction::SetSolid {
+                        x: context.square_x,
+                        y: context.square_y,
+                        solid: *solid,
+                    });
+                }
+                Instruction::Wait(expr) => {
+                    if let Value::Number(ms) = self.evaluate_expression(expr, context) {
+                        actions.push(ProgramAction::Wait(ms.max(0.0)));
+                    }
+                }
+                Instruction::SetActiveProgram(expr) => {
+                    let program = self.evaluate_expression(expr, context);
+                    actions.push(ProgramAction::SetActiveProgram {
+                        x: context.square_x,
+                        y: context.square_y,
+                        program,
+                    });
+                }
+                Instruction::Teleport { x, y } => {
+                    if let (Value::Number(x), Value::Number(y)) = (self.evaluate_expression(x, context), self.evaluate_expression(y, context)) {
+                        actions.push(ProgramAction::Teleport { x: x.round() as i32, y: y.round() as i32 });
+                    }
+                }
+                Instruction::ResetSquare { x, y } => {
+                    if let (Value::Number(x), Value::Number(y)) = (self.evaluate_expression(x, context), self.evaluate_expression(y, context)) {
+                        if x >= 0.0 && y >= 0.0 {
+                            actions.push(ProgramAction::ResetHits { x: x.round() as usize, y: y.round() as usize });
+                        }
+                    }
+                }
+                Instruction::ArmSquare { x, y, program } => {
+                    if let (Value::Number(x), Value::Number(y)) = (self.evaluate_expression(x, context), self.evaluate_expression(y, context)) {
+                        if x >= 0.0 && y >= 0.0 {
+                            let program = self.evaluate_expression(program, context);
+                            actions.push(ProgramAction::SetActiveProgram { x: x.round() as usize, y: y.round() as usize, program });
+                        }
+                    }
+                }
                 Instruction::ExecuteProgram(program) => {
                     actions.push(ProgramAction::ExecuteProgram(program.clone()));
                 }
                 Instruction::ContinueToNext => {
                     actions.push(ProgramAction::ContinueToNext);
                 }
-                Instruction::ExecuteLibraryFunction { library_function } => {
+                Instruction::ExecuteLibraryFunction { library_function, args } => {
+                    let args = args.iter().map(|arg| self.evaluate_expression(arg, context)).collect();
                     actions.push(ProgramAction::ExecuteLibraryFunction { 
-                        library_function: library_function.clone() 
+                        library_function: library_function.clone(),
+                        args,
                     });
                 }
                 Instruction::Return(function_name) => {
@@ -905,9 +1290,17 @@ impl SquareProgram {
             }
             Expression::Random { min, max } => {
                 use rand::Rng;
-                let mut rng = rand::thread_rng();
-                Value::Number(rng.gen_range(*min..*max))
+                if min < max {
+                    Value::Number(crate::rng::with_rng(|rng| rng.gen_range(*min..*max)))
+                } else {
+                    Value::Number(*min) // random(3, 3) is just 3; an empty range would panic
+                }
             }
+            Expression::Call { name, args } => {
+                let args: Vec<Value> = args.iter().map(|arg| self.evaluate_expression(arg, context)).collect();
+                call_function(name, &args)
+            }
+            Expression::Time => Value::Number(context.time),
         }
     }
     
@@ -938,6 +1331,13 @@ impl SquareProgram {
                     _ => Value::Boolean(false),
                 }
             }
+            (Value::Direction(a), Value::Direction(b)) => {
+                match op {
+                    BinaryOperator::Equal => Value::Boolean(a == b),
+                    BinaryOperator::NotEqual => Value::Boolean(a != b),
+                    _ => Value::Boolean(false),
+                }
+            }
             _ => Value::Boolean(false),
         }
     }
@@ -946,19 +1346,38 @@ impl SquareProgram {
 #[derive(Clone, PartialEq, Debug)]
 pub enum ProgramAction {
     SetSpeed(f32),
+    SetSpeedGlide { target: f32, duration_ms: f32 },
+    SetSpeedBeats(f32), // Beats per cell; the sequencer converts it with the current BPM
     SetDirection(crate::ball::Direction),
     SetDirectionToCoordinate { target_x: f32, target_y: f32 },
     SetPitch(f32),
+    SetPitchStep(f32),
+    SetHumanize(f32),
+    SetPan(f32),
+    SetEnvelope { attack_ms: f32, release_ms: f32 },
+    SetFilter(f32),
+    SetBpm { value: f32, relative: bool },
     SetVolume(f32),
     SetColor(String),
+    SetSquareColor { x: usize, y: usize, color: String },
+    SetSample { library: String, name: String },
     Bounce,
+    Reflect,
+    Pass,
+    Teleport { x: i32, y: i32 }, // Unchecked; the sequencer rejects cells outside the grid
     Stop,
     PlaySample(usize),
     SetReverse { ball_reference: String, speed: f32 },
     SetSliceArray { x: usize, y: usize, markers: Vec<u32> },
+    ResetHits { x: usize, y: usize },
+    SetSolid { x: usize, y: usize, solid: bool },
+    Wait(f32), // Milliseconds before the actions that follow are applied
+    SetActiveProgram { x: usize, y: usize, program: Value },
     PlaySliceMarker { x: usize, y: usize, marker_index: u32 },
     SpawnBall { x: f32, y: f32, speed: f32, direction: crate::ball::Direction },
     CreateBall { x: f32, y: f32, speed: f32, direction: crate::ball::Direction },
+    CreateBallRandom { speed: f32, direction: crate::ball::Direction },
+    SpawnBurst { x: f32, y: f32, count: usize, speed: f32 },
     CreateSquare { x: i32, y: i32 },
     CreateSquareWithProgram { x: i32, y: i32, program: Program },
     CreateBallFromSample { x: i32, y: i32, library_name: String, sample_name: String },
@@ -967,9 +1386,12 @@ pub enum ProgramAction {
     CreateSquareWithLibrary { x: f32, y: f32, library_function: String, audio_file: Option<String> },
     DestroyBall { x: f32, y: f32, ball_reference: Option<String> },
     DestroySquare { x: f32, y: f32, ball_reference: Option<String> },
+    DestroyBalls { color: Option<String> }, // None destroys every ball
+    DestroySquares { color: Option<String> },
     Print(String),
+    Log(String),
     ExecuteProgram(Program),
-    ExecuteLibraryFunction { library_function: String },
+    ExecuteLibraryFunction { library_function: String, args: Vec<Value> },
     SetGlobalVariable { name: String, value: Value },
     ContinueToNext,
     Return(Option<String>), // None = simple return, Some(name) = call function and return
@@ -979,12 +1401,14 @@ pub enum ProgramAction {
 
 
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Cell {
     pub content: CellContent,
     pub color: [u8; 3], // RGB color
     pub program: SquareProgram, // Programming for square effects
     pub display_text: Option<String>, // Text to display on the square
+    pub pass_through: bool, // Balls run the program but don't bounce or play their sample
+    pub glyph: Option<char>, // Symbol drawn in the middle of the square
 }
 
 impl Default for Cell {
@@ -994,6 +1418,8 @@ impl Default for Cell {
             color: [100, 100, 100], // Default gray color
             program: SquareProgram::default(),
             display_text: None,
+            pass_through: false,
+            glyph: None,
         }
     }
 }
@@ -1005,6 +1431,8 @@ impl Cell {
             color,
             program: SquareProgram::default(),
             display_text: None,
+            pass_through: false,
+            glyph: None,
         }
     }
     
@@ -1029,6 +1457,8 @@ impl Cell {
         self.color = [100, 100, 100];
         self.program = SquareProgram::default();
         self.display_text = None;
+        self.pass_through = false;
+        self.glyph = None;
     }
     
     pub fn place_square(&mut self, color: Option<[u8; 3]>) {
@@ -1039,6 +1469,8 @@ impl Cell {
             self.color = [255, 255, 255]; // Default white square
         }
         self.program = SquareProgram::default();
+        self.pass_through = false;
+        self.glyph = None;
     }
     
     pub fn set_program(&mut self, program: SquareProgram) {
@@ -3,6 +3,7 @@ use crate::square::{Cell, Program};
 use crate::program_editor::{ProgramEditor, ProgramEditorAction};
 use std::time::{Duration, Instant};
 use crate::font;
+use crate::sequencer::{grid_area_height, window_width};
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum SquareMenuState {
@@ -21,7 +22,7 @@ pub struct SquareContextMenu {
     key_repeat_rate: Duration,
 }
 
-const SQUARE_MENU_OPTIONS: &[&str] = &["Edit Program", "Clear Programs"];
+const SQUARE_MENU_OPTIONS: &[&str] = &["Edit Program", "Clear Programs", "Toggle Pass-Through"];
 
 impl SquareContextMenu {
     pub fn new() -> Self {
@@ -66,6 +67,9 @@ impl SquareContextMenu {
             Instruction::Bounce => {
                 lines.push("bounce".to_string());
             },
+            Instruction::Reflect => {
+                lines.push("reflect".to_string());
+            },
             Instruction::Stop => {
                 lines.push("stop".to_string());
             },
@@ -80,12 +84,27 @@ impl SquareContextMenu {
                 let y_val = self.expression_to_number(y).unwrap_or(0.0);
                 lines.push(format!("create square({}, {})", x_val as i32, y_val as i32));
             },
-            Instruction::If { condition, then_block, else_block: _ } => {
+            Instruction::If { condition, then_block, else_block } => {
                 if let Some(condition_line) = self.condition_to_source(condition) {
                     lines.push(format!("if {}", condition_line));
                     for then_instruction in then_block {
                         self.instruction_to_source_lines(then_instruction, lines);
                     }
+                    if let Some(else_block) = else_block {
+                        lines.push("else".to_string());
+                        for else_instruction in else_block {
+                            self.instruction_to_source_lines(else_instruction, lines);
+                        }
+                    }
+                }
+            },
+            Instruction::Loop { count, body } => {
+                if let Some(count) = self.expression_to_number(count) {
+                    lines.push(format!("loop {}", count));
+                    for body_instruction in body {
+                        self.instruction_to_source_lines(body_instruction, lines);
+                    }
+                    lines.push("end".to_string());
                 }
             },
             _ => {
@@ -138,7 +157,7 @@ impl SquareContextMenu {
         !matches!(self.state, SquareMenuState::None)
     }
 
-    pub fn handle_input(&mut self, input: &winit_input_helper::WinitInputHelper, cells: &[[Cell; crate::sequencer::GRID_WIDTH]; crate::sequencer::GRID_HEIGHT]) -> Option<SquareMenuAction> {
+    pub fn handle_input(&mut self, input: &winit_input_helper::WinitInputHelper, cells: &[Vec<Cell>]) -> Option<SquareMenuAction> {
         match self.state {
             SquareMenuState::SquareMenu { square_x, square_y, selected_option } => {
                 if input.key_pressed(VirtualKeyCode::Escape) {
@@ -159,7 +178,7 @@ impl SquareContextMenu {
                     match selected_option {
                         0 => {
                             // Edit Program - Initialize with square's current program
-                            if square_x < crate::sequencer::GRID_WIDTH && square_y < crate::sequencer::GRID_HEIGHT {
+                            if square_x < crate::sequencer::grid_width() && square_y < crate::sequencer::grid_height() {
                                 let cell = &cells[square_y][square_x];
                                 
                                 // Get the active program, or the first program if no active program is set
@@ -199,6 +218,9 @@ impl SquareContextMenu {
                             // Clear Programs
                             return Some(SquareMenuAction::ClearPrograms { square_x, square_y });
                         },
+                        2 => {
+                            return Some(SquareMenuAction::TogglePassThrough { square_x, square_y });
+                        },
                         _ => {}
                     }
                     return None;
@@ -287,7 +309,7 @@ impl SquareContextMenu {
 
 
 
-    pub fn render(&self, frame: &mut [u8], cells: &[[Cell; crate::sequencer::GRID_WIDTH]; crate::sequencer::GRID_HEIGHT]) {
+    pub fn render(&self, frame: &mut [u8], cells: &[Vec<Cell>]) {
         match self.state {
             SquareMenuState::SquareMenu { square_x, square_y, selected_option } => {
                 self.draw_square_menu(frame, square_x, square_y, selected_option);
@@ -311,13 +333,13 @@ impl SquareContextMenu {
         draw_menu_border(frame, menu_x, menu_y, menu_width, menu_height);
 
         // Draw title
-        font::draw_text(frame, "Square Programming", menu_x + 10, menu_y + 5, [255, 255, 255], false, 640);
+        font::draw_text(frame, "Square Programming", menu_x + 10, menu_y + 5, [255, 255, 255], false, window_width());
 
         // Draw menu options
         for (i, option) in SQUARE_MENU_OPTIONS.iter().enumerate() {
             let y_pos = menu_y + 25 + i * 20;
             let selected = i == selected_option;
-            font::draw_text(frame, option, menu_x + 10, y_pos, [255, 255, 255], selected, 640);
+            font::draw_text(frame, option, menu_x + 10, y_pos, [255, 255, 255], selected, window_width());
         }
     }
 
@@ -331,6 +353,7 @@ pub enum SquareMenuAction {
     SaveProgram { square_x: usize, square_y: usize, program: Program, program_index: Option<usize> },
     SaveMultiplePrograms { square_x: usize, square_y: usize, programs: Vec<Program>, program_index: Option<usize> },
     ClearPrograms { square_x: usize, square_y: usize },
+    TogglePassThrough { square_x: usize, square_y: usize },
     SaveProgramToFile,
     LoadProgramFromFile,
     OpenLibrary { square_x: usize, square_y: usize }, // Add this new variant
@@ -338,8 +361,8 @@ pub enum SquareMenuAction {
 
 // Helper functions for drawing (similar to context_menu.rs)
 fn draw_menu_background(frame: &mut [u8], x: usize, y: usize, width: usize, height: usize) {
-    let window_width = 640;
-    let window_height = 480;
+    let window_width = window_width();
+    let window_height = grid_area_height();
     
     for py in y..y + height {
         for px in x..x + width {
@@ -357,8 +380,8 @@ fn draw_menu_background(frame: &mut [u8], x: usize, y: usize, width: usize, heig
 }
 
 fn draw_menu_border(frame: &mut [u8], x: usize, y: usize, width: usize, height: usize) {
-    let window_width = 640;
-    let window_height = 480;
+    let window_width = window_width();
+    let window_height = grid_area_height();
     let border_color = [100, 100, 100];
     
     // Top and bottom borders
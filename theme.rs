@@ -0,0 +1,181 @@
+// UI colors for the grid, cursor and console, with a few built-in themes.
+//
+// A theme.toml in the working directory replaces the default at startup.
+// Top-level keys name colors, given as "#rrggbb" or [r, g, b]:
+//
+//   base = "light"             # start from a built-in theme
+//   cursor = "#ff0080"
+//   grid_lines = [180, 180, 180]
+
+use crate::renderer::Renderer;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+pub const THEME_FILE: &str = "theme.toml";
+pub const BUILTIN_THEMES: &[&str] = &["dark", "light", "high-contrast"];
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Theme {
+    #[serde(skip)]
+    pub name: String,
+    pub background: [u8; 3],
+    pub grid_lines: [u8; 3],
+    pub cursor: [u8; 3],
+    pub console_background: [u8; 3],
+    pub console_border: [u8; 3],
+    pub console_text: [u8; 3],
+    pub panel_background: [u8; 3], // Help, loading and other overlays drawn over the grid
+    pub panel_border: [u8; 3],
+    pub panel_text: [u8; 3],
+    pub muted_text: [u8; 3], // Hints and placeholders
+    pub heading_text: [u8; 3],
+    pub key_text: [u8; 3], // Key names in the help overlay
+    pub readout_text: [u8; 3], // Hovered ball's live values
+    pub transport_background: [u8; 3],
+    pub transport_text: [u8; 3],
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Self {
+            name: "dark".to_string(),
+            background: [20, 20, 20],
+            grid_lines: [60, 60, 60],
+            cursor: [255, 255, 0],
+            console_background: [30, 30, 30],
+            console_border: [100, 100, 100],
+            console_text: [200, 200, 200],
+            panel_background: [25, 25, 25],
+            panel_border: [100, 100, 100],
+            panel_text: [220, 220, 220],
+            muted_text: [150, 150, 150],
+            heading_text: [255, 255, 100],
+            key_text: [150, 200, 255],
+            readout_text: [150, 255, 150],
+            transport_background: [45, 45, 60],
+            transport_text: [180, 180, 220],
+        }
+    }
+
+    pub fn light() -> Self {
+        Self {
+            name: "light".to_string(),
+            background: [235, 235, 230],
+            grid_lines: [190, 190, 185],
+            cursor: [220, 60, 0],
+            console_background: [215, 215, 210],
+            console_border: [150, 150, 150],
+            console_text: [40, 40, 40],
+            panel_background: [245, 245, 240],
+            panel_border: [150, 150, 150],
+            panel_text: [30, 30, 30],
+            muted_text: [120, 120, 120],
+            heading_text: [170, 80, 0],
+            key_text: [20, 80, 170],
+            readout_text: [20, 120, 40],
+            transport_background: [200, 200, 215],
+            transport_text: [40, 40, 90],
+        }
+    }
+
+    pub fn high_contrast() -> Self {
+        Self {
+            name: "high-contrast".to_string(),
+            background: [0, 0, 0],
+            grid_lines: [160, 160, 160],
+            cursor: [0, 255, 255],
+            console_background: [0, 0, 0],
+            console_border: [255, 255, 255],
+            console_text: [255, 255, 255],
+            panel_background: [0, 0, 0],
+            panel_border: [255, 255, 255],
+            panel_text: [255, 255, 255],
+            muted_text: [200, 200, 200],
+            heading_text: [255, 255, 0],
+            key_text: [0, 255, 255],
+            readout_text: [0, 255, 0],
+            transport_background: [0, 0, 0],
+            transport_text: [255, 255, 255],
+        }
+    }
+
+    pub fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::dark()),
+            "light" => Some(Self::light()),
+            "high-contrast" => Some(Self::high_contrast()),
+            _ => None,
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut overrides: toml::Table = toml::from_str(text).map_err(|e| e.to_string())?;
+
+        let base = match overrides.remove("base") {
+            Some(toml::Value::String(base)) => Self::builtin(&base)
+                .ok_or_else(|| format!("unknown base theme '{}' (use {})", base, BUILTIN_THEMES.join(", ")))?,
+            Some(other) => return Err(format!("base must be a theme name, not {}", other)),
+            None => Self::dark(),
+        };
+        if overrides.is_empty() {
+            return Ok(base);
+        }
+
+        // Overrides apply on top of the base; unknown keys are rejected when deserializing
+        let mut table = toml::Table::try_from(&base).map_err(|e| e.to_string())?;
+        for (key, value) in overrides {
+            let color = match value {
+                toml::Value::String(hex) => Renderer::parse_hex_color(&hex)
+                    .map(|[r, g, b]| toml::Value::Array(vec![r.into(), g.into(), b.into()]))
+                    .ok_or_else(|| format!("{}: invalid color \"{}\"", key, hex))?,
+                other => other,
+            };
+            table.insert(key, color);
+        }
+        let mut theme: Theme = table.try_into().map_err(|e: toml::de::Error| e.message().to_string())?;
+        theme.name = "custom".to_string();
+        Ok(theme)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+        Self::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overrides_apply_on_top_of_the_base() {
+        let theme = Theme::parse("cursor = \"#ff0080\" # pink\ngrid_lines = [180, 180, 180]\nbase = \"light\"\n").unwrap();
+        assert_eq!(theme.name, "custom");
+        assert_eq!(theme.cursor, [255, 0, 128]);
+        assert_eq!(theme.grid_lines, [180, 180, 180]);
+        assert_eq!(theme.background, Theme::light().background);
+    }
+
+    #[test]
+    fn a_bare_base_is_the_builtin_theme() {
+        assert_eq!(Theme::parse("base = \"high-contrast\"").unwrap(), Theme::high_contrast());
+        assert_eq!(Theme::parse("").unwrap(), Theme::dark());
+    }
+
+    #[test]
+    fn bad_files_are_rejected() {
+        assert!(Theme::parse("base = \"sepia\"").unwrap_err().contains("sepia"));
+        assert!(Theme::parse("cursr = \"#ffffff\"").unwrap_err().contains("cursr"));
+        assert!(Theme::parse("cursor = \"#fffff\"").is_err());
+        assert!(Theme::parse("cursor = [256, 0, 0]").is_err());
+        assert!(Theme::parse("cursor = ").is_err());
+    }
+}